@@ -0,0 +1,44 @@
+fn main() {
+    #[cfg(feature = "ffi")]
+    generate_header();
+
+    #[cfg(feature = "grpc")]
+    compile_protos();
+}
+
+#[cfg(feature = "grpc")]
+fn compile_protos() {
+    // Use the bundled protoc, so building doesn't need one installed
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("Couldn't find bundled protoc");
+    std::env::set_var("PROTOC", protoc);
+
+    // Only the server is used here, and the generated client code needs the
+    // 2021 edition prelude. Maps are sorted so requests are always handled
+    // in the same order.
+    tonic_prost_build::configure()
+        .build_client(false)
+        .btree_map(".")
+        .compile_protos(&["proto/party_recogniser.proto"], &["proto"])
+        .expect("Couldn't compile protos");
+}
+
+// Writes the C header for the ffi module to include/
+#[cfg(feature = "ffi")]
+fn generate_header() {
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+
+    println!("cargo:rerun-if-changed=src/ffi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(format!("{}/cbindgen.toml", crate_dir))
+        .expect("Couldn't read cbindgen.toml");
+
+    // Only the ffi module is parsed, so nothing else from the crate leaks
+    // into the header
+    cbindgen::Builder::new()
+        .with_config(config)
+        .with_src(format!("{}/src/ffi.rs", crate_dir))
+        .generate()
+        .expect("Couldn't generate C header")
+        .write_to_file(format!("{}/include/party_recogniser.h", crate_dir));
+}
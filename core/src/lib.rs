@@ -0,0 +1,494 @@
+// Prediction from trained log probability tables. This crate doesn't depend
+// on std, so trained models can be evaluated in firmware and wasm. Every
+// table is indexed by plain integers, so it knows nothing about votes or
+// parties - that mapping lives in the std crate.
+#![no_std]
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::fmt;
+
+const SCORE_LANES: usize = 8;
+const LOG_TABLES_MAGIC: &[u8; 4] = b"NBL1";
+const QUANTIZED_MAGIC: &[u8; 4] = b"NBQ1";
+const HEADER_LEN: usize = 4 + 3 * 2;
+
+// Attribute, choice and class counts of a table
+type Dimensions = (usize, usize, usize);
+
+// Precision of the log probability tables and scores. Memory-constrained
+// deployments can halve the table size with the f32 feature.
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+#[cfg(feature = "f32")]
+pub type Float = f32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    BadMagic,
+    BadLength,
+    BadValueSize,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::BadMagic => write!(f, "Not a serialized model table"),
+            DecodeError::BadLength => write!(f, "Model table has unexpected length"),
+            DecodeError::BadValueSize => write!(f, "Model table has unsupported value size"),
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+// Index of the class whose score is the highest. Ties go to the earlier
+// class, like everywhere a class is picked.
+pub fn argmax<T: PartialOrd>(scores: &[T]) -> usize {
+    let mut res = 0;
+
+    for i in 1..scores.len() {
+        if scores[i] > scores[res] {
+            res = i;
+        }
+    }
+
+    res
+}
+
+fn attr_idx(attribute: usize, choice: usize, choices_count: usize, classes_count: usize) -> usize {
+    (attribute * choices_count + choice) * classes_count
+}
+
+// Little-endian layout shared by all tables: magic, then the attribute,
+// choice and class counts as u16
+fn write_header(res: &mut Vec<u8>, magic: &[u8; 4], dims: Dimensions) {
+    res.extend_from_slice(magic);
+    res.extend_from_slice(&(dims.0 as u16).to_le_bytes());
+    res.extend_from_slice(&(dims.1 as u16).to_le_bytes());
+    res.extend_from_slice(&(dims.2 as u16).to_le_bytes());
+}
+
+fn read_header<'a>(
+    bytes: &'a [u8],
+    magic: &[u8; 4],
+) -> Result<(Dimensions, &'a [u8]), DecodeError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(DecodeError::BadLength);
+    }
+
+    if &bytes[..4] != magic {
+        return Err(DecodeError::BadMagic);
+    }
+
+    let read_u16 = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]) as usize;
+    Ok((
+        (read_u16(4), read_u16(6), read_u16(8)),
+        &bytes[HEADER_LEN..],
+    ))
+}
+
+// Log probabilities of the classes and of every attribute value given a class
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogTables {
+    attributes_count: usize,
+    choices_count: usize,
+    class_weights: Vec<Float>,
+    // Flat [attribute][choice][class] table. The classes are innermost, so
+    // one lookup yields the weights of all classes.
+    attr_weights: Vec<Float>,
+}
+
+impl LogTables {
+    pub fn new(
+        attributes_count: usize,
+        choices_count: usize,
+        class_weights: Vec<Float>,
+        attr_weights: Vec<Float>,
+    ) -> Self {
+        assert_eq!(
+            attr_weights.len(),
+            attributes_count * choices_count * class_weights.len(),
+            "Attribute table doesn't match the dimensions"
+        );
+
+        LogTables {
+            attributes_count,
+            choices_count,
+            class_weights,
+            attr_weights,
+        }
+    }
+
+    pub fn attributes_count(&self) -> usize {
+        self.attributes_count
+    }
+
+    pub fn choices_count(&self) -> usize {
+        self.choices_count
+    }
+
+    pub fn classes_count(&self) -> usize {
+        self.class_weights.len()
+    }
+
+    pub fn class_weights(&self) -> &[Float] {
+        &self.class_weights
+    }
+
+    pub fn attr_weights(&self) -> &[Float] {
+        &self.attr_weights
+    }
+
+    // Weights of all classes for the given attribute value
+    pub fn weights(&self, attribute: usize, choice: usize) -> &[Float] {
+        let classes_count = self.classes_count();
+        let start = attr_idx(attribute, choice, self.choices_count, classes_count);
+        &self.attr_weights[start..start + classes_count]
+    }
+
+    pub fn score_class<I: IntoIterator<Item = usize>>(&self, choices: I, class: usize) -> Float {
+        let mut res = self.class_weights[class];
+
+        for (i, choice) in choices.into_iter().enumerate() {
+            res += self.weights(i, choice)[class];
+        }
+
+        res
+    }
+
+    pub fn score<I: IntoIterator<Item = usize>>(&self, choices: I) -> Vec<Float> {
+        let mut res = self.class_weights.clone();
+
+        for (i, choice) in choices.into_iter().enumerate() {
+            for (score, weight) in res.iter_mut().zip(self.weights(i, choice)) {
+                *score += weight;
+            }
+        }
+
+        res
+    }
+
+    pub fn predict<I: IntoIterator<Item = usize>>(&self, choices: I) -> usize {
+        argmax(&self.score(choices))
+    }
+
+    // Scores the rows SCORE_LANES at a time and returns a flat [row][class]
+    // table. The per-class sums of a chunk live in one contiguous
+    // accumulator and the class weights of a value are adjacent in the
+    // table, so the inner loops compile down to packed additions. Sums are
+    // accumulated in the same order as in score_class, so both paths produce
+    // identical scores. `choice` gives the choice of a row for an attribute.
+    pub fn score_batch<R, F>(&self, rows: &[R], choice: F) -> Vec<Float>
+    where
+        F: Fn(&R, usize) -> usize,
+    {
+        let classes_count = self.classes_count();
+        let mut res = Vec::with_capacity(rows.len() * classes_count);
+        let mut acc = vec![0.0; SCORE_LANES * classes_count];
+
+        for chunk in rows.chunks(SCORE_LANES) {
+            for scores in acc.chunks_exact_mut(classes_count) {
+                scores.copy_from_slice(&self.class_weights);
+            }
+
+            for i in 0..self.attributes_count {
+                for (scores, row) in acc.chunks_exact_mut(classes_count).zip(chunk) {
+                    for (score, weight) in scores.iter_mut().zip(self.weights(i, choice(row, i))) {
+                        *score += weight;
+                    }
+                }
+            }
+
+            res.extend_from_slice(&acc[..chunk.len() * classes_count]);
+        }
+
+        res
+    }
+
+    // Header, the size of a value in bytes as u16, then the class and the
+    // attribute weights in the precision of Float
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let value_size = core::mem::size_of::<Float>();
+        let mut res = Vec::with_capacity(
+            HEADER_LEN + 2 + (self.class_weights.len() + self.attr_weights.len()) * value_size,
+        );
+
+        write_header(
+            &mut res,
+            LOG_TABLES_MAGIC,
+            (
+                self.attributes_count,
+                self.choices_count,
+                self.classes_count(),
+            ),
+        );
+        res.extend_from_slice(&(value_size as u16).to_le_bytes());
+
+        for weight in self.class_weights.iter().chain(&self.attr_weights) {
+            res.extend_from_slice(&weight.to_le_bytes());
+        }
+
+        res
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let ((attributes_count, choices_count, classes_count), rest) =
+            read_header(bytes, LOG_TABLES_MAGIC)?;
+
+        if rest.len() < 2 {
+            return Err(DecodeError::BadLength);
+        }
+
+        let value_size = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+        let values = &rest[2..];
+        let values_count = classes_count + attributes_count * choices_count * classes_count;
+
+        if value_size != 4 && value_size != 8 {
+            return Err(DecodeError::BadValueSize);
+        }
+
+        if values.len() != values_count * value_size {
+            return Err(DecodeError::BadLength);
+        }
+
+        let mut weights: Vec<Float> = values
+            .chunks_exact(value_size)
+            .map(|x| {
+                if value_size == 4 {
+                    f32::from_le_bytes(x.try_into().unwrap()) as Float
+                } else {
+                    f64::from_le_bytes(x.try_into().unwrap()) as Float
+                }
+            })
+            .collect();
+
+        let attr_weights = weights.split_off(classes_count);
+        Ok(LogTables::new(
+            attributes_count,
+            choices_count,
+            weights,
+            attr_weights,
+        ))
+    }
+}
+
+// Log probabilities stored as i16 fixed-point numbers (value * scale), so a
+// trained model can be evaluated with integer arithmetic only, e.g. on
+// microcontrollers without an FPU. Scores are summed in i32.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantizedTables {
+    scale: f32,
+    attributes_count: usize,
+    choices_count: usize,
+    class_weights: Vec<i16>,
+    // Same layout as the attribute table of LogTables
+    attr_weights: Vec<i16>,
+}
+
+impl QuantizedTables {
+    pub fn new(
+        scale: f32,
+        attributes_count: usize,
+        choices_count: usize,
+        class_weights: Vec<i16>,
+        attr_weights: Vec<i16>,
+    ) -> Self {
+        assert_eq!(
+            attr_weights.len(),
+            attributes_count * choices_count * class_weights.len(),
+            "Attribute table doesn't match the dimensions"
+        );
+
+        QuantizedTables {
+            scale,
+            attributes_count,
+            choices_count,
+            class_weights,
+            attr_weights,
+        }
+    }
+
+    // Dividing a score by the scale gives back an approximate log10
+    // probability
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    pub fn attributes_count(&self) -> usize {
+        self.attributes_count
+    }
+
+    pub fn choices_count(&self) -> usize {
+        self.choices_count
+    }
+
+    pub fn classes_count(&self) -> usize {
+        self.class_weights.len()
+    }
+
+    pub fn score_class<I: IntoIterator<Item = usize>>(&self, choices: I, class: usize) -> i32 {
+        let classes_count = self.classes_count();
+        let mut res = self.class_weights[class] as i32;
+
+        for (i, choice) in choices.into_iter().enumerate() {
+            let idx = attr_idx(i, choice, self.choices_count, classes_count) + class;
+            res += self.attr_weights[idx] as i32;
+        }
+
+        res
+    }
+
+    pub fn predict<I: IntoIterator<Item = usize>>(&self, choices: I) -> usize {
+        let classes_count = self.classes_count();
+        let mut scores: Vec<i32> = self.class_weights.iter().map(|&x| x as i32).collect();
+
+        for (i, choice) in choices.into_iter().enumerate() {
+            let start = attr_idx(i, choice, self.choices_count, classes_count);
+
+            for (score, &weight) in scores.iter_mut().zip(&self.attr_weights[start..]) {
+                *score += weight as i32;
+            }
+        }
+
+        argmax(&scores)
+    }
+
+    // Header, the scale as f32, then the class and the attribute weights
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut res = Vec::with_capacity(
+            HEADER_LEN + 4 + (self.class_weights.len() + self.attr_weights.len()) * 2,
+        );
+
+        write_header(
+            &mut res,
+            QUANTIZED_MAGIC,
+            (
+                self.attributes_count,
+                self.choices_count,
+                self.classes_count(),
+            ),
+        );
+        res.extend_from_slice(&self.scale.to_le_bytes());
+
+        for weight in self.class_weights.iter().chain(&self.attr_weights) {
+            res.extend_from_slice(&weight.to_le_bytes());
+        }
+
+        res
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let ((attributes_count, choices_count, classes_count), rest) =
+            read_header(bytes, QUANTIZED_MAGIC)?;
+        let values_count = classes_count + attributes_count * choices_count * classes_count;
+
+        if rest.len() != 4 + values_count * 2 {
+            return Err(DecodeError::BadLength);
+        }
+
+        let scale = f32::from_le_bytes(rest[..4].try_into().unwrap());
+        let mut weights: Vec<i16> = rest[4..]
+            .chunks_exact(2)
+            .map(|x| i16::from_le_bytes([x[0], x[1]]))
+            .collect();
+
+        let attr_weights = weights.split_off(classes_count);
+        Ok(QuantizedTables::new(
+            scale,
+            attributes_count,
+            choices_count,
+            weights,
+            attr_weights,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ties_go_to_the_earlier_class() {
+        assert_eq!(argmax(&[1.0, 2.0, 0.5]), 1);
+        assert_eq!(argmax(&[2.0, 2.0]), 0);
+        assert_eq!(argmax(&[1, 3, 3]), 1);
+    }
+
+    // Two attributes of three choices and two classes
+    fn log_tables() -> LogTables {
+        LogTables::new(
+            2,
+            3,
+            vec![-0.25, -0.5],
+            (1..=12).map(|x| -(x as Float) / 16.0).collect(),
+        )
+    }
+
+    fn quantized_tables() -> QuantizedTables {
+        QuantizedTables::new(
+            100.0,
+            2,
+            3,
+            vec![-25, -50],
+            (1..=12).map(|x| -x * 100).collect(),
+        )
+    }
+
+    #[test]
+    fn reads_the_tables_it_writes() {
+        let tables = log_tables();
+        assert_eq!(LogTables::from_bytes(&tables.to_bytes()), Ok(tables));
+
+        let tables = quantized_tables();
+        assert_eq!(QuantizedTables::from_bytes(&tables.to_bytes()), Ok(tables));
+    }
+
+    #[test]
+    fn rejects_bytes_that_arent_a_table() {
+        let bytes = log_tables().to_bytes();
+        assert_eq!(LogTables::from_bytes(&[]), Err(DecodeError::BadLength));
+        assert_eq!(
+            LogTables::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(DecodeError::BadLength)
+        );
+        assert_eq!(
+            QuantizedTables::from_bytes(&bytes),
+            Err(DecodeError::BadMagic)
+        );
+
+        // Values of 2 bytes
+        let mut bad_size = bytes.clone();
+        bad_size[HEADER_LEN..HEADER_LEN + 2].copy_from_slice(&2u16.to_le_bytes());
+        assert_eq!(
+            LogTables::from_bytes(&bad_size),
+            Err(DecodeError::BadValueSize)
+        );
+
+        let bytes = quantized_tables().to_bytes();
+        assert_eq!(
+            QuantizedTables::from_bytes(&bytes[..bytes.len() - 2]),
+            Err(DecodeError::BadLength)
+        );
+        assert_eq!(LogTables::from_bytes(&bytes), Err(DecodeError::BadMagic));
+    }
+
+    #[test]
+    fn scores_by_the_tables() {
+        let tables = log_tables();
+        // The first choice of the first attribute and the last of the second
+        let expected = [
+            -0.25 - 1.0 / 16.0 - 11.0 / 16.0,
+            -0.5 - 2.0 / 16.0 - 12.0 / 16.0,
+        ];
+
+        assert_eq!(tables.score([0, 2]), expected);
+        assert_eq!(tables.score_class([0, 2], 1), expected[1]);
+        assert_eq!(tables.predict([0, 2]), 0);
+        assert_eq!(quantized_tables().predict([0, 2]), 0);
+        assert_eq!(quantized_tables().score_class([0, 2], 1), -50 - 200 - 1200);
+    }
+}
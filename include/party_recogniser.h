@@ -0,0 +1,64 @@
+#ifndef PARTY_RECOGNISER_H
+#define PARTY_RECOGNISER_H
+
+/* Generated by cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#define NB_ATTRIBUTES_COUNT 16
+
+#define NB_CLASSES_COUNT 2
+
+#define NB_YES 0
+
+#define NB_NO 1
+
+#define NB_UNKNOWN 2
+
+#define NB_REPUBLICAN 0
+
+#define NB_DEMOCRAT 1
+
+typedef struct NbModel NbModel;
+
+/*
+ Loads a model saved with --save-model. Returns NULL if the path isn't
+ valid UTF-8 or the model can't be read. The model must be released with
+ nb_free.
+
+ # Safety
+
+ `path` must be NULL or a valid NUL-terminated string.
+ */
+struct NbModel *nb_model_load(const char *path);
+
+/*
+ Predicts the class of NB_ATTRIBUTES_COUNT votes, each one of NB_YES,
+ NB_NO or NB_UNKNOWN. Returns NB_REPUBLICAN or NB_DEMOCRAT, or -1 if an
+ argument is invalid. When `probabilities` isn't NULL, the posterior
+ probability of each class is written to it, indexed by class.
+
+ # Safety
+
+ `model` must come from nb_model_load, `votes` must point to `votes_len`
+ bytes and `probabilities` must be NULL or point to NB_CLASSES_COUNT
+ doubles.
+ */
+int nb_predict(const struct NbModel *model,
+               const uint8_t *votes,
+               uintptr_t votes_len,
+               double *probabilities);
+
+/*
+ Releases a model returned by nb_model_load. Passing NULL does nothing.
+
+ # Safety
+
+ `model` must be NULL or come from nb_model_load and not be used again.
+ */
+void nb_free(struct NbModel *model);
+
+#endif  /* PARTY_RECOGNISER_H */
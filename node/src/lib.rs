@@ -0,0 +1,105 @@
+use napi::{Error, Result};
+use napi_derive::napi;
+
+use party_recogniser_naive_bayes::data::{parse_attributes, try_parse_row, Row, CLASSES};
+use party_recogniser_naive_bayes::model::Model;
+
+// Node.js bindings. Build with `cargo build --release -p party_recogniser_node`
+// and copy target/release/libparty_recogniser_node.so (.dylib on macOS, .dll
+// on Windows) to party_recogniser.node to require() it.
+#[napi(js_name = "Model")]
+pub struct NodeModel {
+    model: Model,
+}
+
+#[napi(object)]
+pub struct Prediction {
+    pub class: String,
+    // Posterior probability of each class, in the order of `classes()`
+    pub probabilities: Vec<f64>,
+}
+
+#[napi]
+pub fn classes() -> Vec<String> {
+    CLASSES.iter().map(|x| x.name().to_string()).collect()
+}
+
+// These two return plain errors instead of napi ones, which need Node.js,
+// so they can be tested without it
+fn parse_rows(data: &str) -> std::result::Result<Vec<Row>, String> {
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| try_parse_row(line).map_err(|e| format!("Line {}: {}", i + 1, e)))
+        .collect()
+}
+
+fn prediction(model: &Model, votes: &str) -> std::result::Result<Prediction, String> {
+    let attributes = parse_attributes(votes)?;
+
+    Ok(Prediction {
+        class: model.classify(&attributes).name().to_string(),
+        probabilities: model.probabilities(&attributes).to_vec(),
+    })
+}
+
+#[napi]
+impl NodeModel {
+    // Trains on the contents of a dataset file in the house-votes format,
+    // throwing with the line of the first row that can't be parsed
+    #[napi(factory)]
+    pub fn train(data: String) -> Result<NodeModel> {
+        let rows = parse_rows(&data).map_err(Error::from_reason)?;
+
+        Ok(NodeModel {
+            model: Model::from_rows(rows),
+        })
+    }
+
+    #[napi(factory)]
+    pub fn load(path: String) -> Result<NodeModel> {
+        let model = Model::load(&path).map_err(|e| Error::from_reason(e.to_string()))?;
+        Ok(NodeModel { model })
+    }
+
+    #[napi]
+    pub fn save(&self, path: String) -> Result<()> {
+        self.model
+            .save(&path)
+            .map_err(|e| Error::from_reason(e.to_string()))
+    }
+
+    // Takes comma-separated votes, e.g. "y,n,?,..."
+    #[napi]
+    pub fn predict(&self, votes: String) -> Result<Prediction> {
+        prediction(&self.model, &votes).map_err(Error::from_reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &str = include_str!("../../house-votes-84.data");
+    const VOTES: &str = "n,y,n,y,y,y,n,n,n,y,?,y,y,y,n,y";
+
+    #[test]
+    fn predicts_like_the_model() {
+        let model = Model::from_rows(parse_rows(&format!("{}\n\n", DATA)).unwrap());
+        let expected = Model::from_rows(DATA.lines().map(|line| try_parse_row(line).unwrap()));
+        let attributes = parse_attributes(VOTES).unwrap();
+
+        let prediction = prediction(&model, VOTES).unwrap();
+        assert_eq!(prediction.class, expected.classify(&attributes).name());
+        assert_eq!(
+            prediction.probabilities,
+            expected.probabilities(&attributes).to_vec()
+        );
+    }
+
+    #[test]
+    fn reports_the_line_of_bad_rows() {
+        assert!(parse_rows("\ngreen,y").unwrap_err().starts_with("Line 2: "));
+        assert!(prediction(&Model::from_rows(vec![]), "y,n").is_err());
+    }
+}
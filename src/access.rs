@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use sha2::{Digest, Sha256};
+
+// Past this many tracked clients, the ones whose buckets have refilled are
+// forgotten so the map can't grow without bound
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denied {
+    Unauthorized,
+    RateLimited,
+}
+
+impl fmt::Display for Denied {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Denied::Unauthorized => write!(f, "Missing or invalid API key"),
+            Denied::RateLimited => write!(f, "Too many requests"),
+        }
+    }
+}
+
+// Decides which requests get through to the prediction endpoints. Without
+// API keys every client is allowed, and without a rate limit there is no
+// limit on how often.
+pub struct AccessControl {
+    api_keys: Vec<String>,
+    limiter: Option<RateLimiter>,
+}
+
+impl AccessControl {
+    pub fn new(api_keys: Vec<String>, requests_per_second: Option<u32>) -> Self {
+        AccessControl {
+            api_keys,
+            limiter: requests_per_second.map(RateLimiter::new),
+        }
+    }
+
+    // Clients with a valid API key are limited by their key, since several
+    // clients can share an address behind a proxy, and other clients by
+    // their address. The address is checked before the key and charged for
+    // every missing or invalid one, so keys can't be guessed faster than
+    // the limit.
+    pub fn check(&self, api_key: Option<&str>, addr: Option<IpAddr>) -> Result<(), Denied> {
+        let valid = self.is_valid(api_key);
+        let Some(limiter) = &self.limiter else {
+            return if valid {
+                Ok(())
+            } else {
+                Err(Denied::Unauthorized)
+            };
+        };
+        let address = format!(
+            "addr:{}",
+            addr.map_or_else(|| "unknown".to_string(), |addr| addr.to_string())
+        );
+        let limited = |allowed: bool| {
+            if allowed {
+                Ok(())
+            } else {
+                Err(Denied::RateLimited)
+            }
+        };
+
+        match api_key {
+            _ if self.api_keys.is_empty() => limited(limiter.allow(&address)),
+            _ if !limiter.has_capacity(&address) => Err(Denied::RateLimited),
+            Some(key) if valid => limited(limiter.allow(&format!("key:{}", key))),
+            _ => {
+                limiter.allow(&address);
+                Err(Denied::Unauthorized)
+            }
+        }
+    }
+
+    // Any key is valid when none are configured
+    fn is_valid(&self, api_key: Option<&str>) -> bool {
+        self.api_keys.is_empty()
+            || api_key.is_some_and(|key| self.api_keys.iter().any(|x| constant_time_eq(x, key)))
+    }
+}
+
+// Compares digests of the keys, which always have the same length, and
+// doesn't return early on the first difference, so the time taken tells
+// neither the length of a key nor how much of a guess was right
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    Sha256::digest(a.as_bytes())
+        .iter()
+        .zip(Sha256::digest(b.as_bytes()).iter())
+        .fold(0, |acc, (x, y)| acc | (x ^ y))
+        == 0
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+// Token bucket per client, which allows bursts of up to a second's worth of
+// requests
+struct RateLimiter {
+    rate: f64,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    fn new(requests_per_second: u32) -> Self {
+        RateLimiter {
+            rate: requests_per_second as f64,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Uses up a request of the client if it has one left
+    fn allow(&self, client: &str) -> bool {
+        self.update(client, |tokens| {
+            if *tokens < 1.0 {
+                return false;
+            }
+
+            *tokens -= 1.0;
+            true
+        })
+    }
+
+    // Whether the client has a request left, without using it up
+    fn has_capacity(&self, client: &str) -> bool {
+        self.update(client, |tokens| *tokens >= 1.0)
+    }
+
+    // Refills the bucket of the client for the time since it was last used
+    // before handing its tokens to f
+    fn update(&self, client: &str, f: impl FnOnce(&mut f64) -> bool) -> bool {
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= MAX_TRACKED_CLIENTS {
+            let rate = self.rate;
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated).as_secs_f64() * rate < rate
+            });
+        }
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: self.rate,
+            updated: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * self.rate).min(self.rate);
+        bucket.updated = now;
+
+        f(&mut bucket.tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(last: u8) -> Option<IpAddr> {
+        Some(IpAddr::from([10, 0, 0, last]))
+    }
+
+    #[test]
+    fn checks_keys() {
+        let open = AccessControl::new(vec![], None);
+        assert_eq!(open.check(None, addr(1)), Ok(()));
+
+        let access = AccessControl::new(vec!["secret".to_string()], None);
+        assert_eq!(access.check(Some("secret"), addr(1)), Ok(()));
+        assert_eq!(
+            access.check(Some("secre"), addr(1)),
+            Err(Denied::Unauthorized)
+        );
+        assert_eq!(access.check(None, addr(1)), Err(Denied::Unauthorized));
+    }
+
+    #[test]
+    fn limits_every_address() {
+        let access = AccessControl::new(vec![], Some(2));
+
+        assert_eq!(access.check(None, addr(1)), Ok(()));
+        assert_eq!(access.check(None, addr(1)), Ok(()));
+        assert_eq!(access.check(None, addr(1)), Err(Denied::RateLimited));
+        assert_eq!(access.check(None, addr(2)), Ok(()));
+        assert_eq!(access.check(None, None), Ok(()));
+    }
+
+    #[test]
+    fn limits_valid_keys_by_key() {
+        let access = AccessControl::new(vec!["secret".to_string()], Some(2));
+
+        assert_eq!(access.check(Some("secret"), addr(1)), Ok(()));
+        assert_eq!(access.check(Some("secret"), addr(2)), Ok(()));
+        assert_eq!(
+            access.check(Some("secret"), addr(3)),
+            Err(Denied::RateLimited)
+        );
+        // The address wasn't charged for the key's requests
+        assert_eq!(
+            access.check(Some("other"), addr(1)),
+            Err(Denied::Unauthorized)
+        );
+    }
+
+    #[test]
+    fn charges_guesses_to_the_address() {
+        let access = AccessControl::new(vec!["secret".to_string()], Some(2));
+
+        assert_eq!(
+            access.check(Some("guess"), addr(1)),
+            Err(Denied::Unauthorized)
+        );
+        assert_eq!(access.check(None, addr(1)), Err(Denied::Unauthorized));
+        assert_eq!(
+            access.check(Some("guess"), addr(1)),
+            Err(Denied::RateLimited)
+        );
+        assert_eq!(
+            access.check(Some("secret"), addr(1)),
+            Err(Denied::RateLimited)
+        );
+        assert_eq!(access.check(Some("secret"), addr(2)), Ok(()));
+    }
+}
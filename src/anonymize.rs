@@ -0,0 +1,194 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+
+use sha2::{Digest, Sha256};
+
+use crate::data::{Choice, Row};
+
+// Columns of the input lines that identify a member instead of being part
+// of the row, e.g. a name before the class. They're split off before the
+// rest of the line is parsed, so they never reach training.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Identifiers {
+    // 0-based and sorted
+    columns: Vec<usize>,
+}
+
+impl Identifiers {
+    // The columns are numbered from 1, like in AttributeGroup
+    pub fn new(columns: &[usize]) -> Result<Self, String> {
+        if columns.contains(&0) {
+            return Err("Identifier columns are numbered from 1".to_string());
+        }
+
+        let mut columns: Vec<usize> = columns.iter().map(|i| i - 1).collect();
+        columns.sort_unstable();
+        columns.dedup();
+        Ok(Identifiers { columns })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    // The line without the identifier columns, and their values in order
+    pub fn split<'a>(&self, line: &'a [u8]) -> (Vec<u8>, Vec<&'a [u8]>) {
+        let mut rest = Vec::with_capacity(line.len());
+        let mut values = vec![];
+
+        for (i, field) in line.split(|&b| b == b',').enumerate() {
+            if self.columns.binary_search(&i).is_ok() {
+                values.push(field);
+                continue;
+            }
+            if i > values.len() {
+                rest.push(b',');
+            }
+            rest.extend_from_slice(field);
+        }
+
+        (rest, values)
+    }
+
+    // The other way around from split, with the values back in their
+    // columns
+    pub fn join(&self, rest: &str, values: &[String]) -> String {
+        let mut fields: Vec<&str> = rest.split(',').collect();
+        for (&i, value) in self.columns.iter().zip(values) {
+            fields.insert(i.min(fields.len()), value);
+        }
+
+        fields.join(",")
+    }
+}
+
+// Stands in for an identifier: the first 16 hex digits of the SHA-256 of the
+// salt and it, so rows of the same member can still be linked without
+// naming them. The salt has to be kept secret, or the names could be found
+// by hashing every name.
+pub fn pseudonym(salt: &str, identifier: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(identifier);
+    hasher.finalize()[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+// Makes every combination of votes on the quasi-identifiers, the attributes
+// that could single a member out, shared by at least k rows like in
+// k-anonymity. Rows of rarer combinations have their votes on them
+// suppressed as unknown, so they hide among each other, or are dropped if
+// even together there are fewer than k of them.
+#[derive(Debug, Clone)]
+pub struct Anonymizer {
+    // 0-based
+    quasi_identifiers: Vec<usize>,
+    rare: Vec<Vec<Choice>>,
+    // Rows that are suppressed, including the ones that only had unknown
+    // votes on the quasi-identifiers to begin with
+    pub suppressed: usize,
+    // When the suppressed rows are too few to be kept
+    pub drops_suppressed: bool,
+}
+
+impl Anonymizer {
+    // The quasi-identifiers are numbered from 1 in dataset order, like in
+    // AttributeGroup. The rows are the ones anonymize will be given.
+    pub fn new<I>(k: usize, quasi_identifiers: &[usize], rows: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<Row>,
+    {
+        let quasi_identifiers: Vec<usize> = quasi_identifiers.iter().map(|i| i - 1).collect();
+        let suppressed_key = vec![Choice::Unknown; quasi_identifiers.len()];
+
+        let mut counts: HashMap<Vec<Choice>, usize> = HashMap::new();
+        for row in rows {
+            *counts
+                .entry(key(&quasi_identifiers, row.borrow()))
+                .or_default() += 1;
+        }
+
+        let rare: Vec<Vec<Choice>> = counts
+            .iter()
+            .filter(|&(key, &count)| count < k && *key != suppressed_key)
+            .map(|(key, _)| key.clone())
+            .collect();
+        let suppressed = rare.iter().map(|key| counts[key]).sum::<usize>()
+            + counts.get(&suppressed_key).copied().unwrap_or(0);
+
+        Anonymizer {
+            quasi_identifiers,
+            rare,
+            suppressed,
+            drops_suppressed: suppressed < k,
+        }
+    }
+
+    // None when the row is dropped
+    pub fn anonymize(&self, mut row: Row) -> Option<Row> {
+        let key = key(&self.quasi_identifiers, &row);
+        let suppressed = key.iter().all(|&choice| choice == Choice::Unknown);
+        if !suppressed && !self.rare.contains(&key) {
+            return Some(row);
+        }
+        if self.drops_suppressed {
+            return None;
+        }
+
+        for &i in &self.quasi_identifiers {
+            row.attributes[i] = Choice::Unknown;
+        }
+        Some(row)
+    }
+}
+
+fn key(quasi_identifiers: &[usize], row: &Row) -> Vec<Choice> {
+    quasi_identifiers
+        .iter()
+        .map(|&i| row.attributes[i])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::try_parse_row;
+
+    #[test]
+    fn splits_off_and_joins_back_identifiers() {
+        let identifiers = Identifiers::new(&[3, 1]).unwrap();
+        let (rest, values) = identifiers.split(b"Jane,democrat,42,y,n");
+
+        assert_eq!(rest, b"democrat,y,n");
+        assert_eq!(values, [&b"Jane"[..], b"42"]);
+        assert_eq!(
+            identifiers.join("democrat,y,n", &["a".to_string(), "b".to_string()]),
+            "a,democrat,b,y,n"
+        );
+        assert!(Identifiers::new(&[0]).is_err());
+    }
+
+    #[test]
+    fn pseudonyms_depend_on_the_salt() {
+        assert_eq!(pseudonym("salt", b"Jane"), pseudonym("salt", b"Jane"));
+        assert_ne!(pseudonym("salt", b"Jane"), pseudonym("salt", b"John"));
+        assert_ne!(pseudonym("salt", b"Jane"), pseudonym("pepper", b"Jane"));
+        assert_eq!(pseudonym("salt", b"Jane").len(), 16);
+    }
+
+    #[test]
+    fn suppresses_rare_combinations() {
+        let common = try_parse_row("democrat,y,y,y,y,y,y,y,y,y,y,y,y,y,y,y,y").unwrap();
+        let rare = try_parse_row("republican,n,y,y,y,y,y,y,y,y,y,y,y,y,y,y,y").unwrap();
+        let rows = [common.clone(), common.clone(), rare.clone()];
+
+        let anonymizer = Anonymizer::new(2, &[1], &rows);
+        assert_eq!(anonymizer.suppressed, 1);
+        assert!(anonymizer.drops_suppressed);
+        assert_eq!(anonymizer.anonymize(common.clone()), Some(common));
+        assert_eq!(anonymizer.anonymize(rare), None);
+    }
+}
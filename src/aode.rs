@@ -0,0 +1,221 @@
+use crate::classifier::{normalize_log_probabilities, Classifier};
+use crate::counts::JointCounts;
+use crate::data::{Choice, ATTRIBUTES_COUNT, CHOICES, CLASSES, CLASSES_COUNT};
+
+// A choice has to be seen this many times to be a parent, so that estimates
+// conditioned on it are reliable enough
+pub const MIN_PARENT_COUNT: u32 = 30;
+
+// Averaged one-dependence estimators (Webb, Boughton and Wang, 2005). Every
+// attribute takes a turn as the parent of all the others, which gives a
+// model like naive Bayes where each vote also depends on the parent's, and
+// the probabilities of those models are averaged. Parents whose choice is
+// too rare are left out, and without any the model falls back to naive
+// Bayes.
+#[derive(Debug, Clone)]
+pub struct AodeModel {
+    // Rows of any class that voted each choice on each attribute, packed
+    // [attribute][choice]
+    choice_counts: Vec<u32>,
+    // Natural log of P(class, choice of the parent), packed
+    // [class][parent][choice]
+    log_joints: Vec<f64>,
+    // Natural log of P(choice | class, choice of the parent), packed
+    // [class][parent][parent choice][attribute][choice]
+    log_conditionals: Vec<f64>,
+    // For the fallback
+    log_priors: [f64; CLASSES_COUNT],
+    // Natural log of P(choice | class), packed [class][attribute][choice]
+    log_naive_conditionals: Vec<f64>,
+}
+
+impl AodeModel {
+    // Every choice starts with the smoothing count, like in Trainer
+    pub fn new(counts: &JointCounts, smoothing: f64) -> Self {
+        let choices = CHOICES.len() as f64;
+        let total = counts.rows_count() as f64;
+        let mut choice_counts = vec![0; ATTRIBUTES_COUNT * CHOICES.len()];
+        let mut log_joints = vec![0.0; CLASSES_COUNT * ATTRIBUTES_COUNT * CHOICES.len()];
+        let mut log_conditionals =
+            vec![0.0; CLASSES_COUNT * (ATTRIBUTES_COUNT * CHOICES.len()).pow(2)];
+        let mut log_naive_conditionals =
+            vec![0.0; CLASSES_COUNT * ATTRIBUTES_COUNT * CHOICES.len()];
+
+        for &class in CLASSES.iter() {
+            let class_count = counts.class_count(class) as f64;
+
+            for parent in 0..ATTRIBUTES_COUNT {
+                for &parent_choice in CHOICES.iter() {
+                    let parent_count = counts.count(class, parent, parent_choice);
+                    choice_counts[parent * CHOICES.len() + parent_choice.index()] += parent_count;
+
+                    let joint = Self::joint_idx(class.index(), parent, parent_choice);
+                    log_joints[joint] = ((parent_count as f64 + smoothing)
+                        / (total + smoothing * CLASSES_COUNT as f64 * choices))
+                        .ln();
+                    log_naive_conditionals[joint] = ((parent_count as f64 + smoothing)
+                        / (class_count + smoothing * choices))
+                        .ln();
+
+                    for attribute in 0..ATTRIBUTES_COUNT {
+                        for &choice in CHOICES.iter() {
+                            let count =
+                                counts.pair_count(class, parent, parent_choice, attribute, choice);
+                            log_conditionals[Self::conditional_idx(
+                                class.index(),
+                                parent,
+                                parent_choice,
+                                attribute,
+                                choice,
+                            )] = ((count as f64 + smoothing)
+                                / (parent_count as f64 + smoothing * choices))
+                                .ln();
+                        }
+                    }
+                }
+            }
+        }
+
+        AodeModel {
+            choice_counts,
+            log_joints,
+            log_conditionals,
+            log_priors: CLASSES.map(|class| (counts.class_count(class) as f64 / total).ln()),
+            log_naive_conditionals,
+        }
+    }
+
+    fn joint_idx(class: usize, attribute: usize, choice: Choice) -> usize {
+        (class * ATTRIBUTES_COUNT + attribute) * CHOICES.len() + choice.index()
+    }
+
+    fn conditional_idx(
+        class: usize,
+        parent: usize,
+        parent_choice: Choice,
+        attribute: usize,
+        choice: Choice,
+    ) -> usize {
+        Self::joint_idx(class, parent, parent_choice) * ATTRIBUTES_COUNT * CHOICES.len()
+            + attribute * CHOICES.len()
+            + choice.index()
+    }
+
+    // Attributes whose vote in the row is common enough to be a parent
+    pub fn parents<'a>(&'a self, attributes: &'a [Choice]) -> impl Iterator<Item = usize> + 'a {
+        attributes
+            .iter()
+            .enumerate()
+            .filter(move |&(parent, &choice)| {
+                self.choice_counts[parent * CHOICES.len() + choice.index()] >= MIN_PARENT_COUNT
+            })
+            .map(|(parent, _)| parent)
+    }
+}
+
+impl Classifier for AodeModel {
+    fn log_probabilities(&self, attributes: &[Choice]) -> [f64; CLASSES_COUNT] {
+        let parents: Vec<usize> = self.parents(attributes).collect();
+        let mut res = [0.0; CLASSES_COUNT];
+
+        for (class, log_probability) in res.iter_mut().enumerate() {
+            if parents.is_empty() {
+                *log_probability = self.log_priors[class]
+                    + attributes
+                        .iter()
+                        .enumerate()
+                        .map(|(attribute, &choice)| {
+                            self.log_naive_conditionals[Self::joint_idx(class, attribute, choice)]
+                        })
+                        .sum::<f64>();
+                continue;
+            }
+
+            // log of the sum over the parents, shifted by the highest term
+            let terms: Vec<f64> = parents
+                .iter()
+                .map(|&parent| {
+                    let parent_choice = attributes[parent];
+                    self.log_joints[Self::joint_idx(class, parent, parent_choice)]
+                        + attributes
+                            .iter()
+                            .enumerate()
+                            .filter(|&(attribute, _)| attribute != parent)
+                            .map(|(attribute, &choice)| {
+                                self.log_conditionals[Self::conditional_idx(
+                                    class,
+                                    parent,
+                                    parent_choice,
+                                    attribute,
+                                    choice,
+                                )]
+                            })
+                            .sum::<f64>()
+                })
+                .collect();
+            let max = terms.iter().fold(f64::NEG_INFINITY, |acc, &x| acc.max(x));
+            // Averaging only scales every class by the same amount
+            *log_probability = terms
+                .iter()
+                .map(|term| (term - max).exp())
+                .sum::<f64>()
+                .ln()
+                + max;
+        }
+
+        normalize_log_probabilities(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{try_parse_row, Row};
+    use crate::model::{Model, DEFAULT_SMOOTHING};
+
+    // Democrats voted the same on the first two bills and republicans didn't,
+    // each pattern this many times
+    fn agreeing_rows(copies: usize) -> Vec<Row> {
+        let rest = ",n".repeat(ATTRIBUTES_COUNT - 2);
+        [
+            "democrat,y,y",
+            "democrat,n,n",
+            "republican,y,n",
+            "republican,n,y",
+        ]
+        .iter()
+        .flat_map(|votes| std::iter::repeat_n(format!("{}{}", votes, rest), copies))
+        .map(|line| try_parse_row(&line).unwrap())
+        .collect()
+    }
+
+    #[test]
+    fn averages_the_common_parents() {
+        let rows = agreeing_rows(15);
+        let model = AodeModel::new(&rows.iter().collect(), DEFAULT_SMOOTHING);
+
+        // Each vote on the first two bills was seen 30 times, and the votes on
+        // the rest 60 times
+        let row = &rows[0];
+        assert_eq!(model.parents(&row.attributes).count(), ATTRIBUTES_COUNT);
+        for row in &rows {
+            assert_eq!(model.classify(&row.attributes), row.class);
+        }
+    }
+
+    #[test]
+    fn falls_back_to_naive_bayes_without_common_parents() {
+        let rows = agreeing_rows(1);
+        let model = AodeModel::new(&rows.iter().collect(), DEFAULT_SMOOTHING);
+        let naive_bayes = Model::from_rows(rows.iter().cloned());
+
+        for row in &rows {
+            assert_eq!(model.parents(&row.attributes).count(), 0);
+            let (aode, expected) = (
+                model.probabilities(&row.attributes),
+                naive_bayes.probabilities(&row.attributes),
+            );
+            assert!((aode[0] - expected[0]).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,150 @@
+use serde::Deserialize;
+
+use std::fs;
+use std::io;
+
+use crate::data::{ATTRIBUTES_COUNT, ATTRIBUTE_NAMES};
+
+// Human-readable names and descriptions of the attributes, shown in reports
+// instead of the short names from the dataset description. Read from a TOML
+// file like
+//
+//     [[attribute]]
+//     attribute = 4
+//     name = "Physician fee freeze"
+//     description = "Freezing the fees doctors get from Medicare"
+//
+// Attributes are numbered from 1 in dataset order. The ones that aren't
+// listed keep their short names.
+#[derive(Debug, Clone)]
+pub struct AttributeMetadata {
+    names: Vec<String>,
+    descriptions: Vec<Option<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct MetadataFile {
+    #[serde(default)]
+    attribute: Vec<AttributeEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AttributeEntry {
+    attribute: usize,
+    name: Option<String>,
+    description: Option<String>,
+}
+
+impl Default for AttributeMetadata {
+    fn default() -> Self {
+        AttributeMetadata {
+            names: ATTRIBUTE_NAMES
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            descriptions: vec![None; ATTRIBUTES_COUNT],
+        }
+    }
+}
+
+impl AttributeMetadata {
+    pub fn load(filename: &str) -> io::Result<Self> {
+        let contents = fs::read_to_string(filename)?;
+        let file: MetadataFile =
+            toml::from_str(&contents).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidData, message);
+        let mut metadata = Self::default();
+        let mut seen = [false; ATTRIBUTES_COUNT];
+
+        for entry in file.attribute {
+            if !(1..=ATTRIBUTES_COUNT).contains(&entry.attribute) {
+                return Err(invalid(format!(
+                    "Attribute {} isn't between 1 and {}",
+                    entry.attribute, ATTRIBUTES_COUNT
+                )));
+            }
+
+            let i = entry.attribute - 1;
+            if seen[i] {
+                return Err(invalid(format!(
+                    "Attribute {} is listed more than once",
+                    entry.attribute
+                )));
+            }
+            seen[i] = true;
+
+            if let Some(name) = entry.name {
+                metadata.names[i] = name;
+            }
+            metadata.descriptions[i] = entry.description;
+        }
+
+        Ok(metadata)
+    }
+
+    // attribute is the 0-based index, like everywhere besides the file
+    pub fn name(&self, attribute: usize) -> &str {
+        &self.names[attribute]
+    }
+
+    pub fn description(&self, attribute: usize) -> Option<&str> {
+        self.descriptions[attribute].as_deref()
+    }
+
+    pub fn has_descriptions(&self) -> bool {
+        self.descriptions.iter().any(Option::is_some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(name: &str, contents: &str) -> io::Result<AttributeMetadata> {
+        let path = std::env::temp_dir().join(format!("{}-{}.toml", name, std::process::id()));
+        let filename = path.to_str().unwrap();
+        fs::write(filename, contents).unwrap();
+        let res = AttributeMetadata::load(filename);
+        fs::remove_file(filename).unwrap();
+        res
+    }
+
+    #[test]
+    fn names_the_listed_attributes() {
+        let metadata = load(
+            "metadata",
+            "[[attribute]]\nattribute = 4\nname = \"Physician fee freeze\"\n\
+             description = \"Freezing the fees doctors get from Medicare\"\n\n\
+             [[attribute]]\nattribute = 1\ndescription = \"Aid for disabled infants\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(metadata.name(3), "Physician fee freeze");
+        assert_eq!(
+            metadata.description(3),
+            Some("Freezing the fees doctors get from Medicare")
+        );
+        // Without a name the short one is kept
+        assert_eq!(metadata.name(0), ATTRIBUTE_NAMES[0]);
+        assert_eq!(metadata.description(0), Some("Aid for disabled infants"));
+        assert_eq!(metadata.name(1), ATTRIBUTE_NAMES[1]);
+        assert_eq!(metadata.description(1), None);
+        assert!(metadata.has_descriptions());
+        assert!(!AttributeMetadata::default().has_descriptions());
+    }
+
+    #[test]
+    fn rejects_attributes_out_of_range_or_listed_twice() {
+        let error = load("out-of-range", "[[attribute]]\nattribute = 17\n").unwrap_err();
+        assert_eq!(error.to_string(), "Attribute 17 isn't between 1 and 16");
+
+        let twice = "[[attribute]]\nattribute = 2\n\n[[attribute]]\nattribute = 2\n";
+        let error = load("twice", twice).unwrap_err();
+        assert_eq!(error.to_string(), "Attribute 2 is listed more than once");
+
+        assert!(load("unknown", "[[attribute]]\nattribute = 2\nlabel = \"x\"\n").is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::data::{Choice, Class, CLASSES, CLASSES_COUNT};
+use crate::output::ClassProbability;
+
+// Appends every prediction to a JSON Lines file for reviewing automated
+// decisions later. Each record is written as soon as it's made, so nothing
+// is lost if the process is killed.
+pub struct AuditLog {
+    file: Mutex<File>,
+    // Log the votes themselves rather than only their hash
+    include_values: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditRecord {
+    // RFC 3339 in UTC
+    pub timestamp: String,
+    // See Model::fingerprint
+    pub model_version: String,
+    // Hex SHA-256 of the votes as in the dataset, e.g. y,n,?,... It tells
+    // which input a prediction was made for, but with only 3^16 possible
+    // inputs it doesn't hide them.
+    pub input_sha256: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub votes: Option<String>,
+    pub class: &'static str,
+    pub probabilities: Vec<ClassProbability>,
+}
+
+fn votes_string(attributes: &[Choice]) -> String {
+    attributes
+        .iter()
+        .map(|choice| choice.name())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+impl AuditLog {
+    pub fn open(filename: &str, include_values: bool) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(filename)?;
+
+        Ok(AuditLog {
+            file: Mutex::new(file),
+            include_values,
+        })
+    }
+
+    pub fn record(
+        &self,
+        model_version: &str,
+        attributes: &[Choice],
+        class: Class,
+        probabilities: &[f64; CLASSES_COUNT],
+    ) -> io::Result<()> {
+        let votes = votes_string(attributes);
+
+        let record = AuditRecord {
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            model_version: model_version.to_string(),
+            input_sha256: Sha256::digest(votes.as_bytes())
+                .iter()
+                .map(|byte| format!("{:02x}", byte))
+                .collect(),
+            votes: self.include_values.then_some(votes),
+            class: class.name(),
+            probabilities: CLASSES
+                .iter()
+                .map(|class| ClassProbability {
+                    class: class.name(),
+                    probability: probabilities[class.index()],
+                })
+                .collect(),
+        };
+
+        let mut line = serde_json::to_string(&record)?;
+        line.push('\n');
+        // A single write per record, so concurrent records don't interleave
+        self.file.lock().unwrap().write_all(line.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::Value;
+
+    use super::*;
+    use crate::data::{try_parse_row, ATTRIBUTES_COUNT};
+    use crate::manifest::hash_bytes;
+
+    // Every record of a log the votes were predicted into
+    fn records(name: &str, include_values: bool, attributes: &[Choice]) -> Vec<Value> {
+        let filename = std::env::temp_dir().join(format!("{}-{}.jsonl", name, std::process::id()));
+        let filename = filename.to_str().unwrap();
+        let _ = fs::remove_file(filename);
+
+        // Reopened, so the second record is appended to the first
+        for _ in 0..2 {
+            AuditLog::open(filename, include_values)
+                .unwrap()
+                .record("v1", attributes, Class::Democrat, &[0.25, 0.75])
+                .unwrap();
+        }
+        let contents = fs::read_to_string(filename).unwrap();
+        fs::remove_file(filename).unwrap();
+
+        contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn appends_a_line_per_prediction() {
+        let row = try_parse_row("democrat,y,n,?,y,n,?,y,n,?,y,n,?,y,n,?,y").unwrap();
+        let records = records("audit-hashed", false, &row.attributes);
+
+        assert_eq!(records.len(), 2);
+        let record = &records[0];
+        assert_eq!(record["model_version"], "v1");
+        assert_eq!(record["class"], "democrat");
+        assert_eq!(record["probabilities"][1]["probability"], 0.75);
+        assert_eq!(
+            record["input_sha256"],
+            hash_bytes(b"y,n,?,y,n,?,y,n,?,y,n,?,y,n,?,y")
+        );
+        // Only the hash of the votes, unless they're asked for
+        assert!(record.get("votes").is_none());
+        assert!(record["timestamp"].as_str().unwrap().ends_with('Z'));
+    }
+
+    #[test]
+    fn includes_the_votes_when_asked() {
+        let records = records("audit-values", true, &[Choice::Yes; ATTRIBUTES_COUNT]);
+
+        assert_eq!(records[0]["votes"], vec!["y"; ATTRIBUTES_COUNT].join(","));
+    }
+}
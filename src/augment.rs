@@ -0,0 +1,109 @@
+use rand::Rng;
+
+use crate::data::{Choice, Row};
+
+// Extra noisy copies of every training row, to see how robust a model is
+// to vote changes and to regularise it on tiny datasets. Only training rows
+// are augmented, never the ones a model is evaluated on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Augmentation {
+    // Copies added per row, next to the row itself
+    pub copies: usize,
+    // Chance of each yes vote of a copy becoming a no and the other way
+    // round. Unknown votes are kept.
+    pub flip_probability: f64,
+}
+
+pub const DEFAULT_FLIP_PROBABILITY: f64 = 0.1;
+
+impl Augmentation {
+    pub fn new(copies: usize) -> Self {
+        Augmentation {
+            copies,
+            flip_probability: DEFAULT_FLIP_PROBABILITY,
+        }
+    }
+
+    fn flip<R: Rng>(&self, choice: Choice, rng: &mut R) -> Choice {
+        match choice {
+            Choice::Yes if rng.gen_bool(self.flip_probability) => Choice::No,
+            Choice::No if rng.gen_bool(self.flip_probability) => Choice::Yes,
+            _ => choice,
+        }
+    }
+
+    // The row followed by its noisy copies
+    pub fn augment<R: Rng>(&self, row: &Row, rng: &mut R) -> Vec<Row> {
+        let mut res = Vec::with_capacity(self.copies + 1);
+        res.push(row.clone());
+
+        for _ in 0..self.copies {
+            res.push(Row {
+                class: row.class,
+                attributes: row
+                    .attributes
+                    .iter()
+                    .map(|&choice| self.flip(choice, rng))
+                    .collect(),
+            });
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::data::{try_parse_row, ATTRIBUTES_COUNT};
+
+    #[test]
+    fn flips_about_the_flip_probability_of_the_votes() {
+        let row = try_parse_row("democrat,y,n,?,y,n,?,y,n,?,y,n,?,y,n,?,y").unwrap();
+        let augmentation = Augmentation {
+            copies: 10_000,
+            flip_probability: 0.2,
+        };
+        let rows = augmentation.augment(&row, &mut StdRng::seed_from_u64(1));
+
+        assert_eq!(rows.len(), 10_001);
+        assert_eq!(rows[0], row);
+        assert!(rows.iter().all(|copy| copy.class == row.class));
+
+        let mut flipped = 0;
+        let mut votes = 0;
+        for copy in &rows[1..] {
+            for (&choice, &original) in copy.attributes.iter().zip(&row.attributes) {
+                if original == Choice::Unknown {
+                    assert_eq!(choice, Choice::Unknown);
+                    continue;
+                }
+
+                votes += 1;
+                if choice != original {
+                    flipped += 1;
+                }
+            }
+        }
+        assert_eq!(votes, 10_000 * (ATTRIBUTES_COUNT - 5));
+        let share = flipped as f64 / votes as f64;
+        assert!((share - 0.2).abs() < 0.01);
+    }
+
+    #[test]
+    fn keeps_the_copies_without_a_flip_probability() {
+        let row = try_parse_row("republican,y,n,?,y,n,?,y,n,?,y,n,?,y,n,?,y").unwrap();
+        let augmentation = Augmentation {
+            copies: 3,
+            flip_probability: 0.0,
+        };
+
+        assert_eq!(
+            augmentation.augment(&row, &mut StdRng::seed_from_u64(1)),
+            vec![row; 4]
+        );
+    }
+}
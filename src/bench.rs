@@ -0,0 +1,258 @@
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::Serialize;
+
+use crate::data::{split_for_crossvalidation_with_rng, try_parse_row_bytes, Row};
+use crate::generate::generate;
+use crate::model::{Model, Trainer};
+
+// Rows are sampled from the model and cycled through, so the benchmark
+// doesn't need the dataset and always predicts the same inputs
+const SAMPLE_ROWS: usize = 1024;
+
+#[derive(Debug, Serialize)]
+pub struct LatencyStats {
+    // Timed calls and the rows each of them predicted
+    pub calls: usize,
+    pub rows_per_call: usize,
+    #[serde(serialize_with = "as_nanos")]
+    pub total: Duration,
+    #[serde(serialize_with = "as_nanos")]
+    pub p50: Duration,
+    #[serde(serialize_with = "as_nanos")]
+    pub p90: Duration,
+    #[serde(serialize_with = "as_nanos")]
+    pub p99: Duration,
+    #[serde(serialize_with = "as_nanos")]
+    pub p999: Duration,
+    #[serde(serialize_with = "as_nanos")]
+    pub max: Duration,
+}
+
+fn as_nanos<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_nanos() as u64)
+}
+
+impl LatencyStats {
+    fn new(mut latencies: Vec<Duration>, rows_per_call: usize) -> Self {
+        latencies.sort_unstable();
+        let percentile = |p: f64| latencies[((latencies.len() - 1) as f64 * p) as usize];
+
+        LatencyStats {
+            calls: latencies.len(),
+            rows_per_call,
+            total: latencies.iter().sum(),
+            p50: percentile(0.5),
+            p90: percentile(0.9),
+            p99: percentile(0.99),
+            p999: percentile(0.999),
+            max: *latencies.last().unwrap(),
+        }
+    }
+
+    pub fn rows_per_second(&self) -> f64 {
+        (self.calls * self.rows_per_call) as f64 / self.total.as_secs_f64()
+    }
+}
+
+// Fastest of the runs of bench_train at a dataset size
+#[derive(Debug, Serialize)]
+pub struct TrainTimings {
+    pub rows: usize,
+    // Parsing the rows from memory, so the disk cache doesn't skew it
+    #[serde(serialize_with = "as_nanos")]
+    pub load: Duration,
+    #[serde(serialize_with = "as_nanos")]
+    pub split: Duration,
+    #[serde(serialize_with = "as_nanos")]
+    pub fit: Duration,
+}
+
+impl TrainTimings {
+    pub fn total(&self) -> Duration {
+        self.load + self.split + self.fit
+    }
+
+    pub fn rows_per_second(&self) -> f64 {
+        self.rows as f64 / self.total().as_secs_f64()
+    }
+}
+
+// A dataset of the given size in the format of the data file. It repeats
+// the rows of the data, or samples new ones from a model trained on them if
+// synthetic is set.
+pub fn training_input(data: &[Row], rows: usize, synthetic: bool, seed: u64) -> Vec<u8> {
+    let rows: Vec<Row> = if synthetic {
+        let model = Model::new(&data.iter().collect::<Vec<_>>());
+        generate(&model, rows, &mut StdRng::seed_from_u64(seed)).collect()
+    } else {
+        data.iter().cycle().take(rows).cloned().collect()
+    };
+
+    rows.iter()
+        .map(|row| format!("{}\n", row))
+        .collect::<String>()
+        .into_bytes()
+}
+
+// Times parsing the input, splitting it for cross-validation and fitting a
+// model on all of it, keeping the fastest time of every phase over the runs.
+// Fails if the rows can't be split into the folds.
+pub fn bench_train(
+    input: &[u8],
+    splits: usize,
+    seed: u64,
+    runs: usize,
+) -> Result<TrainTimings, String> {
+    let mut res: Option<TrainTimings> = None;
+
+    for _ in 0..runs.max(1) {
+        let start = Instant::now();
+        let rows: Vec<Row> = input
+            .strip_suffix(b"\n")
+            .unwrap_or(input)
+            .split(|&b| b == b'\n')
+            .map(|line| try_parse_row_bytes(line).expect("Benchmark rows are written to parse"))
+            .collect();
+        let load = start.elapsed();
+
+        let start = Instant::now();
+        let split_data =
+            split_for_crossvalidation_with_rng(rows, splits, &mut StdRng::seed_from_u64(seed))?;
+        let split = start.elapsed();
+
+        let start = Instant::now();
+        let mut trainer = Trainer::new();
+        split_data.iter().flatten().for_each(|row| trainer.add(row));
+        black_box(trainer.build());
+        let fit = start.elapsed();
+
+        let timings = TrainTimings {
+            rows: split_data.iter().map(Vec::len).sum(),
+            load,
+            split,
+            fit,
+        };
+        res = Some(match res {
+            Some(best) => TrainTimings {
+                load: best.load.min(timings.load),
+                split: best.split.min(timings.split),
+                fit: best.fit.min(timings.fit),
+                ..best
+            },
+            None => timings,
+        });
+    }
+
+    Ok(res.unwrap())
+}
+
+pub fn sample_rows(model: &Model, seed: u64) -> Vec<Row> {
+    generate(model, SAMPLE_ROWS, &mut StdRng::seed_from_u64(seed)).collect()
+}
+
+// Times Model::classify one row at a time
+pub fn bench_single(model: &Model, rows: &[Row], iterations: usize) -> LatencyStats {
+    let latencies = rows
+        .iter()
+        .cycle()
+        .take(iterations.max(1))
+        .map(|row| {
+            let start = Instant::now();
+            black_box(model.classify(black_box(&row.attributes)));
+            start.elapsed()
+        })
+        .collect();
+
+    LatencyStats::new(latencies, 1)
+}
+
+// Times Model::predict_batch, predicting about as many rows in total as
+// bench_single
+pub fn bench_batch(
+    model: &Model,
+    rows: &[Row],
+    batch_size: usize,
+    iterations: usize,
+) -> LatencyStats {
+    let batch: Vec<Row> = rows.iter().cycle().take(batch_size).cloned().collect();
+    let latencies = (0..(iterations / batch_size).max(1))
+        .map(|_| {
+            let start = Instant::now();
+            black_box(model.predict_batch(black_box(&batch)));
+            start.elapsed()
+        })
+        .collect();
+
+    LatencyStats::new(latencies, batch_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn house_votes() -> Vec<Row> {
+        include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| crate::data::try_parse_row(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn takes_the_percentiles_of_the_latencies() {
+        let latencies = (1..=1000).rev().map(Duration::from_micros).collect();
+        let stats = LatencyStats::new(latencies, 10);
+
+        assert_eq!(stats.calls, 1000);
+        assert_eq!(stats.p50, Duration::from_micros(500));
+        assert_eq!(stats.p90, Duration::from_micros(900));
+        assert_eq!(stats.p99, Duration::from_micros(990));
+        assert_eq!(stats.p999, Duration::from_micros(999));
+        assert_eq!(stats.max, Duration::from_micros(1000));
+        assert_eq!(stats.total, Duration::from_micros(500 * 1001));
+        assert_eq!(stats.rows_per_second(), 10_000.0 / 0.5005);
+    }
+
+    #[test]
+    fn times_single_rows_and_batches() {
+        let model = Model::from_rows(house_votes());
+        let rows = sample_rows(&model, 1);
+        assert_eq!(rows.len(), SAMPLE_ROWS);
+        assert_eq!(rows, sample_rows(&model, 1));
+
+        let single = bench_single(&model, &rows, 100);
+        assert_eq!((single.calls, single.rows_per_call), (100, 1));
+        let batch = bench_batch(&model, &rows, 32, 100);
+        assert_eq!((batch.calls, batch.rows_per_call), (3, 32));
+        assert!(single.p50 <= single.max);
+    }
+
+    #[test]
+    fn writes_training_input_of_the_size() {
+        let data = house_votes();
+
+        let repeated = training_input(&data, 1000, false, 1);
+        let lines: Vec<_> = std::str::from_utf8(&repeated).unwrap().lines().collect();
+        assert_eq!(lines.len(), 1000);
+        assert_eq!(lines[435], data[0].to_string());
+
+        let synthetic = training_input(&data, 1000, true, 1);
+        assert_eq!(synthetic.iter().filter(|&&b| b == b'\n').count(), 1000);
+        assert_eq!(synthetic, training_input(&data, 1000, true, 1));
+        assert_ne!(synthetic, repeated);
+    }
+
+    #[test]
+    fn times_training_on_every_row() {
+        let input = training_input(&house_votes(), 1000, false, 1);
+        let timings = bench_train(&input, 10, 1, 2).unwrap();
+
+        assert_eq!(timings.rows, 1000);
+        assert_eq!(timings.total(), timings.load + timings.split + timings.fit);
+        let too_few = training_input(&house_votes(), 5, false, 1);
+        assert!(bench_train(&too_few, 10, 1, 1).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::classifier::{normalize_log_probabilities, Classifier};
+use crate::counts::JointCounts;
+use crate::data::{Choice, ATTRIBUTES_COUNT, CLASSES, CLASSES_COUNT};
+
+// Bernoulli naive Bayes, where every attribute is only whether the member
+// voted yes. No and unknown votes are the same absence of a yes, so the
+// model can't tell a member who voted against a bill from one who didn't
+// vote.
+#[derive(Debug, Clone)]
+pub struct BernoulliModel {
+    log_priors: [f64; CLASSES_COUNT],
+    // Natural log of P(yes | class) and of P(no yes | class), packed
+    // [class][attribute][voted yes]
+    log_conditionals: Vec<f64>,
+}
+
+impl BernoulliModel {
+    // Both outcomes start with the smoothing count, like choices in Trainer
+    pub fn new(counts: &JointCounts, smoothing: f64) -> Self {
+        let total = counts.rows_count() as f64;
+        let log_priors = CLASSES.map(|class| (counts.class_count(class) as f64 / total).ln());
+        let mut log_conditionals = vec![0.0; CLASSES_COUNT * ATTRIBUTES_COUNT * 2];
+
+        for &class in CLASSES.iter() {
+            let class_count = counts.class_count(class) as f64;
+
+            for attribute in 0..ATTRIBUTES_COUNT {
+                let yes = (counts.count(class, attribute, Choice::Yes) as f64 + smoothing)
+                    / (class_count + 2.0 * smoothing);
+                log_conditionals[Self::idx(class.index(), attribute, true)] = yes.ln();
+                log_conditionals[Self::idx(class.index(), attribute, false)] = (1.0 - yes).ln();
+            }
+        }
+
+        BernoulliModel {
+            log_priors,
+            log_conditionals,
+        }
+    }
+
+    fn idx(class: usize, attribute: usize, yes: bool) -> usize {
+        (class * ATTRIBUTES_COUNT + attribute) * 2 + yes as usize
+    }
+}
+
+impl Classifier for BernoulliModel {
+    fn log_probabilities(&self, attributes: &[Choice]) -> [f64; CLASSES_COUNT] {
+        let mut res = self.log_priors;
+
+        for (class, log_probability) in res.iter_mut().enumerate() {
+            for (attribute, &choice) in attributes.iter().enumerate() {
+                *log_probability +=
+                    self.log_conditionals[Self::idx(class, attribute, choice == Choice::Yes)];
+            }
+        }
+
+        normalize_log_probabilities(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{try_parse_row, Row};
+    use crate::model::DEFAULT_SMOOTHING;
+
+    #[test]
+    fn only_tells_yes_votes_apart() {
+        let rows: Vec<Row> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let model = BernoulliModel::new(&rows.iter().collect(), DEFAULT_SMOOTHING);
+
+        let mut row = rows[0].attributes.clone();
+        row[3] = Choice::No;
+        let no = model.probabilities(&row);
+        row[3] = Choice::Unknown;
+        assert_eq!(model.probabilities(&row), no);
+        row[3] = Choice::Yes;
+        assert_ne!(model.probabilities(&row), no);
+        assert!((no.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,207 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use serde::{Deserialize, Serialize};
+
+use crate::manifest::{hash_bytes, timestamp};
+
+const DATA_FILE: &str = "data";
+const METADATA_FILE: &str = "metadata.json";
+
+// Where downloads are cached instead of the platform's cache directory
+pub const DIR_VARIABLE: &str = "PARTY_RECOGNISER_CACHE";
+
+// Datasets downloaded from s3:// and gs:// URIs, laid out as
+// <root>/<SHA-256 of the URI>/{data,metadata.json}, so repeated runs don't
+// download them again
+pub struct Cache {
+    root: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Metadata {
+    pub uri: String,
+    // Of the object when it was downloaded. The cached copy is only used
+    // while the object still has it.
+    pub e_tag: String,
+    // Of the cached copy, so a damaged one is downloaded again
+    pub sha256: String,
+    pub size: u64,
+    // RFC 3339 in UTC
+    pub downloaded_at: String,
+}
+
+// $XDG_CACHE_HOME or ~/.cache on Linux, ~/Library/Caches on macOS and
+// %LOCALAPPDATA% on Windows, or None without a home directory
+pub fn platform_dir() -> Option<PathBuf> {
+    let var = |name| {
+        env::var_os(name)
+            .filter(|x| !x.is_empty())
+            .map(PathBuf::from)
+    };
+
+    let base = if cfg!(windows) {
+        var("LOCALAPPDATA")
+    } else if cfg!(target_os = "macos") {
+        var("HOME").map(|home| home.join("Library").join("Caches"))
+    } else {
+        var("XDG_CACHE_HOME").or_else(|| var("HOME").map(|home| home.join(".cache")))
+    };
+
+    base.map(|base| base.join("party-recogniser"))
+}
+
+impl Cache {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Cache { root: root.into() }
+    }
+
+    // Of PARTY_RECOGNISER_CACHE, or else the platform's cache directory
+    pub fn from_env() -> Option<Self> {
+        env::var_os(DIR_VARIABLE)
+            .filter(|x| !x.is_empty())
+            .map(PathBuf::from)
+            .or_else(platform_dir)
+            .map(Self::new)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn entry_dir(&self, uri: &str) -> PathBuf {
+        self.root.join(hash_bytes(uri.as_bytes()))
+    }
+
+    // The cached copy of the object at the URI, if it was downloaded while it
+    // had the ETag and hasn't been damaged since
+    pub fn get(&self, uri: &str, e_tag: &str) -> io::Result<Option<Vec<u8>>> {
+        let dir = self.entry_dir(uri);
+        let metadata = match File::open(dir.join(METADATA_FILE)) {
+            Ok(file) => Self::read_metadata(file)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if metadata.uri != uri || metadata.e_tag != e_tag {
+            return Ok(None);
+        }
+
+        let contents = match fs::read(dir.join(DATA_FILE)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(Some(contents).filter(|contents| hash_bytes(contents) == metadata.sha256))
+    }
+
+    // Replaces whatever was cached for the URI
+    pub fn put(&self, uri: &str, e_tag: &str, contents: &[u8]) -> io::Result<Metadata> {
+        let dir = self.entry_dir(uri);
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join(DATA_FILE), contents)?;
+
+        let metadata = Metadata {
+            uri: uri.to_string(),
+            e_tag: e_tag.to_string(),
+            sha256: hash_bytes(contents),
+            size: contents.len() as u64,
+            downloaded_at: timestamp(SystemTime::now()),
+        };
+        let mut writer = BufWriter::new(File::create(dir.join(METADATA_FILE))?);
+        serde_json::to_writer_pretty(&mut writer, &metadata)?;
+        writer.flush()?;
+
+        Ok(metadata)
+    }
+
+    fn read_metadata(file: File) -> io::Result<Metadata> {
+        Ok(serde_json::from_reader(BufReader::new(file))?)
+    }
+
+    // Every cached dataset, sorted by URI. An empty or missing cache has no
+    // entries.
+    pub fn list(&self) -> io::Result<Vec<Metadata>> {
+        let mut res = vec![];
+
+        if !self.root.exists() {
+            return Ok(res);
+        }
+
+        for dir in fs::read_dir(&self.root)? {
+            let path = dir?.path().join(METADATA_FILE);
+            if path.exists() {
+                res.push(Self::read_metadata(File::open(path)?)?);
+            }
+        }
+
+        res.sort_by(|a, b| a.uri.cmp(&b.uri));
+        Ok(res)
+    }
+
+    // Removes every cached dataset, returning how many there were
+    pub fn clear(&self) -> io::Result<usize> {
+        let count = self.list()?.len();
+
+        if self.root.exists() {
+            fs::remove_dir_all(&self.root)?;
+        }
+
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URI: &str = "s3://bucket/house-votes-84.data";
+
+    fn cache(name: &str) -> Cache {
+        let root = env::temp_dir().join(format!("cache-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        Cache::new(root)
+    }
+
+    #[test]
+    fn gets_what_it_puts_until_the_e_tag_changes() {
+        let cache = cache("e-tag");
+        assert_eq!(cache.get(URI, "1").unwrap(), None);
+
+        cache.put(URI, "1", b"republican,y").unwrap();
+        assert_eq!(cache.get(URI, "1").unwrap(), Some(b"republican,y".to_vec()));
+        assert_eq!(cache.get(URI, "2").unwrap(), None);
+        assert_eq!(cache.get("gs://bucket/other.data", "1").unwrap(), None);
+
+        cache.put(URI, "2", b"democrat,n").unwrap();
+        assert_eq!(cache.get(URI, "2").unwrap(), Some(b"democrat,n".to_vec()));
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn downloads_damaged_copies_again() {
+        let cache = cache("damaged");
+        cache.put(URI, "1", b"republican,y").unwrap();
+
+        fs::write(cache.entry_dir(URI).join(DATA_FILE), b"republican,n").unwrap();
+        assert_eq!(cache.get(URI, "1").unwrap(), None);
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn lists_and_clears_the_datasets() {
+        let cache = cache("list");
+        assert!(cache.list().unwrap().is_empty());
+
+        cache.put(URI, "1", b"republican,y").unwrap();
+        cache.put("gs://bucket/a.data", "2", b"democrat,n").unwrap();
+        let uris: Vec<_> = cache.list().unwrap().into_iter().map(|x| x.uri).collect();
+        assert_eq!(uris, ["gs://bucket/a.data", URI]);
+
+        assert_eq!(cache.clear().unwrap(), 2);
+        assert!(cache.list().unwrap().is_empty());
+        assert_eq!(cache.get(URI, "1").unwrap(), None);
+    }
+}
@@ -0,0 +1,101 @@
+use std::iter;
+
+use textplots::{Chart, Plot, Shape};
+
+use crate::curves;
+use crate::data::Class;
+use crate::evaluation::FoldResult;
+
+// In braille dots, two to a character across and four down
+const WIDTH: u32 = 120;
+const HEIGHT: u32 = 40;
+const PROBABILITY_BINS: usize = 20;
+
+// The accuracy of every fold, the ROC curve and how the rows spread over
+// P(positive) as terminal charts, for a quick look where --plots can't be
+// opened. The probabilities are of the positive class for every tested row,
+// like for crate::curves.
+pub fn charts(folds: &[FoldResult], probabilities: &[(bool, f64)], positive: Class) -> String {
+    let accuracies: Vec<(f32, f32)> = folds
+        .iter()
+        .map(|fold| (fold.fold as f32, fold.accuracy as f32))
+        .collect();
+    let last_fold = folds.len().saturating_sub(1).max(1) as f32;
+
+    let roc = curves::roc_curve(probabilities);
+    let roc_points: Vec<(f32, f32)> = roc
+        .iter()
+        .map(|point| {
+            (
+                point.false_positive_rate as f32,
+                point.true_positive_rate as f32,
+            )
+        })
+        .collect();
+
+    let mut counts = [0usize; PROBABILITY_BINS];
+    for &(_, probability) in probabilities {
+        counts[((probability * PROBABILITY_BINS as f64) as usize).min(PROBABILITY_BINS - 1)] += 1;
+    }
+    // Every bar is drawn from the point before it, at the height of its own
+    let spread: Vec<(f32, f32)> = iter::once((0.0, 0.0))
+        .chain(
+            counts
+                .iter()
+                .enumerate()
+                .map(|(i, &count)| ((i + 1) as f32 / PROBABILITY_BINS as f32, count as f32)),
+        )
+        .collect();
+
+    format!(
+        "Accuracy per fold\n{}\nROC of finding {}s, area {:.4}\n{}\nRows by P({})\n{}",
+        render(Chart::new(WIDTH, HEIGHT, 0.0, last_fold).lineplot(&Shape::Lines(&accuracies))),
+        positive.name(),
+        curves::roc_area(&roc),
+        render(
+            Chart::new_with_y_range(WIDTH, HEIGHT, 0.0, 1.0, 0.0, 1.0)
+                .lineplot(&Shape::Lines(&roc_points))
+        ),
+        positive.name(),
+        render(Chart::new(WIDTH, HEIGHT, 0.0, 1.0).lineplot(&Shape::Bars(&spread))),
+    )
+}
+
+fn render(chart: &mut Chart) -> String {
+    chart.axis();
+    chart.figures();
+    chart.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{try_parse_row, Row};
+    use crate::evaluation::CrossValidation;
+
+    #[test]
+    fn charts_the_folds_the_roc_curve_and_the_spread() {
+        let rows: Vec<Row> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let crossvalidation = CrossValidation {
+            seed: Some(1),
+            ..CrossValidation::new(5)
+        };
+        let folds: Vec<FoldResult> = crossvalidation.run(rows).unwrap().collect();
+        let probabilities = [(true, 0.9), (true, 0.6), (false, 0.4), (false, 0.1)];
+        let charts = charts(&folds, &probabilities, Class::Democrat);
+
+        let lines: Vec<&str> = charts.lines().collect();
+        for title in [
+            "Accuracy per fold",
+            "ROC of finding democrats, area 1.0000",
+            "Rows by P(democrat)",
+        ] {
+            assert!(lines.contains(&title), "{}", title);
+        }
+        // The charts are drawn in braille dots
+        assert!(charts.contains(|c: char| ('\u{2801}'..='\u{28ff}').contains(&c)));
+    }
+}
@@ -0,0 +1,167 @@
+use std::fs;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::config::RunConfig;
+use crate::model::Trainer;
+
+// Counts of a streaming pass over a dataset, saved every so often so that an
+// interrupted pass can carry on from the line it had reached instead of
+// starting over
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub data: String,
+    // Augmented copies and privacy noise depend on it, so a pass with a
+    // different seed can't be resumed
+    pub seed: u64,
+    // See settings_hash. Missing from checkpoints saved before it was
+    // stored, which aren't resumed.
+    #[serde(default)]
+    pub settings: String,
+    // 1-based line of the last row that was counted
+    pub line: usize,
+    pub trainer: Trainer,
+}
+
+impl Checkpoint {
+    // None when there isn't one yet
+    pub fn load(filename: &str) -> io::Result<Option<Self>> {
+        let contents = match fs::read(filename) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        serde_json::from_slice(&contents)
+            .map(Some)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // Renamed over the old one, so an interruption while saving leaves the
+    // previous checkpoint
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let temporary = format!("{}.tmp", filename);
+        fs::write(&temporary, serde_json::to_vec(self)?)?;
+        fs::rename(&temporary, filename)
+    }
+}
+
+// Hex SHA-256 of the settings of a run that decide which rows are counted
+// and how, from reading them to dedup, sampling, anonymization,
+// augmentation and the training settings, so a pass is only resumed with the same ones
+pub fn settings_hash(config: &RunConfig) -> String {
+    let settings = RunConfig {
+        data: config.data.clone(),
+        on_error: config.on_error,
+        encoding: config.encoding.clone(),
+        identifiers: config.identifiers.clone(),
+        sheet: config.sheet.clone(),
+        dedup: config.dedup,
+        sample: config.sample,
+        k_anonymity: config.k_anonymity,
+        quasi_identifiers: config.quasi_identifiers.clone(),
+        smoothing: config.smoothing,
+        missing_votes: config.missing_votes,
+        prior_mode: config.prior_mode,
+        feature_selection: config.feature_selection,
+        augment: config.augment,
+        flip_probability: config.flip_probability,
+        ..RunConfig::default()
+    };
+    let json = serde_json::to_vec(&settings).unwrap();
+    Sha256::digest(&json)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::OutputConfig;
+    use crate::data::try_parse_row;
+    use crate::model::MissingVotes;
+
+    #[test]
+    fn resumes_where_it_left_off() {
+        let rows: Vec<_> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let filename = std::env::temp_dir().join(format!("checkpoint-{}.json", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        let config = RunConfig::default();
+
+        assert!(Checkpoint::load(filename).unwrap().is_none());
+
+        let mut trainer = Trainer::new().with_missing_votes(MissingVotes::Ignore);
+        rows[..100].iter().for_each(|row| trainer.add(row));
+        Checkpoint {
+            data: "house-votes-84.data".to_string(),
+            seed: 1,
+            settings: settings_hash(&config),
+            line: 100,
+            trainer,
+        }
+        .save(filename)
+        .unwrap();
+
+        let checkpoint = Checkpoint::load(filename).unwrap().unwrap();
+        fs::remove_file(filename).unwrap();
+        assert_eq!(checkpoint.line, 100);
+        assert_eq!(checkpoint.settings, settings_hash(&config));
+
+        let mut resumed = checkpoint.trainer;
+        rows[checkpoint.line..]
+            .iter()
+            .for_each(|row| resumed.add(row));
+        let mut whole = Trainer::new().with_missing_votes(MissingVotes::Ignore);
+        rows.iter().for_each(|row| whole.add(row));
+        assert_eq!(resumed.build().fingerprint(), whole.build().fingerprint());
+    }
+
+    #[test]
+    fn hashes_the_settings_of_the_counts() {
+        let config = RunConfig {
+            data: Some("house-votes-84.data".to_string()),
+            smoothing: Some(1.0),
+            ..RunConfig::default()
+        };
+        let hash = settings_hash(&config);
+
+        // Not how the rows are counted
+        let reported = RunConfig {
+            folds: Some(5),
+            top_attributes: Some(3),
+            output: OutputConfig {
+                checkpoint_every: Some(10),
+                ..OutputConfig::default()
+            },
+            ..config.clone()
+        };
+        assert_eq!(settings_hash(&reported), hash);
+
+        for changed in [
+            RunConfig {
+                smoothing: Some(0.5),
+                ..config.clone()
+            },
+            RunConfig {
+                dedup: Some(true),
+                ..config.clone()
+            },
+            RunConfig {
+                identifiers: Some(vec![1]),
+                ..config.clone()
+            },
+            RunConfig {
+                feature_selection: Some(4),
+                ..config.clone()
+            },
+        ] {
+            assert_ne!(settings_hash(&changed), hash);
+        }
+    }
+}
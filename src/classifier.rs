@@ -0,0 +1,111 @@
+use serde::{Deserialize, Serialize};
+
+use crate::data::{Choice, Class, Row, CLASSES, CLASSES_COUNT};
+use crate::model::Model;
+use crate::summation;
+
+// Which model cross-validation trains on every fold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ModelKind {
+    // Naive Bayes, the only kind that can be saved
+    #[value(name = "nb")]
+    #[serde(rename = "nb")]
+    NaiveBayes,
+    // Tree-augmented naive Bayes, see crate::tan
+    Tan,
+    // Averaged one-dependence estimators, see crate::aode
+    Aode,
+    // Only whether each vote is yes, see crate::bernoulli
+    Bernoulli,
+    // See crate::complement
+    Complement,
+    // Whichever of the others cross-validates best on the same folds
+    Auto,
+}
+
+// What --model auto picks from
+pub const AUTO_CANDIDATES: [ModelKind; 5] = [
+    ModelKind::NaiveBayes,
+    ModelKind::Bernoulli,
+    ModelKind::Complement,
+    ModelKind::Tan,
+    ModelKind::Aode,
+];
+
+impl ModelKind {
+    // As given to --model
+    pub fn name(self) -> &'static str {
+        match self {
+            ModelKind::NaiveBayes => "nb",
+            ModelKind::Tan => "tan",
+            ModelKind::Aode => "aode",
+            ModelKind::Bernoulli => "bernoulli",
+            ModelKind::Complement => "complement",
+            ModelKind::Auto => "auto",
+        }
+    }
+}
+
+// What cross-validation and its metrics need from a model, so that the
+// variants of naive Bayes share them
+pub trait Classifier {
+    // Natural log of P(class | votes), in the order of CLASSES
+    fn log_probabilities(&self, attributes: &[Choice]) -> [f64; CLASSES_COUNT];
+
+    fn probabilities(&self, attributes: &[Choice]) -> [f64; CLASSES_COUNT] {
+        self.log_probabilities(attributes).map(f64::exp)
+    }
+
+    // The likeliest class, ties going to the first
+    fn classify(&self, attributes: &[Choice]) -> Class {
+        let probabilities = self.log_probabilities(attributes);
+        CLASSES.iter().copied().fold(CLASSES[0], |best, class| {
+            if probabilities[class.index()] > probabilities[best.index()] {
+                class
+            } else {
+                best
+            }
+        })
+    }
+
+    // Average log probability of the class of each row, see Model::score
+    fn score(&self, rows: &[Row]) -> f64 {
+        summation::mean(
+            rows.iter()
+                .map(|row| self.log_probabilities(&row.attributes)[row.class.index()]),
+        )
+    }
+}
+
+// Normalizes unnormalized natural log probabilities, shifting by the
+// highest so the powers can't all underflow
+pub fn normalize_log_probabilities(scores: [f64; CLASSES_COUNT]) -> [f64; CLASSES_COUNT] {
+    let max = scores.iter().fold(f64::NEG_INFINITY, |acc, &x| acc.max(x));
+    let total = scores
+        .iter()
+        .map(|&score| (score - max).exp())
+        .sum::<f64>()
+        .ln()
+        + max;
+    scores.map(|score| score - total)
+}
+
+impl Classifier for Model {
+    fn log_probabilities(&self, attributes: &[Choice]) -> [f64; CLASSES_COUNT] {
+        Model::log_probabilities(self, attributes)
+    }
+
+    fn probabilities(&self, attributes: &[Choice]) -> [f64; CLASSES_COUNT] {
+        Model::probabilities(self, attributes)
+    }
+
+    // Follows the threshold of the model
+    fn classify(&self, attributes: &[Choice]) -> Class {
+        Model::classify(self, attributes)
+    }
+
+    fn score(&self, rows: &[Row]) -> f64 {
+        Model::score(self, rows)
+    }
+}
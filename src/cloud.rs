@@ -0,0 +1,162 @@
+use object_store::aws::AmazonS3Builder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::path::Path;
+use object_store::{ObjectStore, ObjectStoreExt};
+
+use crate::cache::Cache;
+
+// Downloads the object at an s3://bucket/key or gs://bucket/key URI. The
+// credentials and region are found in the environment like the official
+// SDKs do, e.g. AWS_ACCESS_KEY_ID and AWS_REGION or
+// GOOGLE_APPLICATION_CREDENTIALS.
+//
+// With a cache, the object is only downloaded when its ETag changed since it
+// was cached. Objects without one are always downloaded.
+pub fn download(uri: &str, cache: Option<&Cache>) -> Result<Vec<u8>, String> {
+    let invalid = || format!("Expected a URI like s3://bucket/key, got {}", uri);
+    let (scheme, rest) = uri.split_once("://").ok_or_else(invalid)?;
+    let (bucket, key) = rest
+        .split_once('/')
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+        .ok_or_else(invalid)?;
+
+    let store: Box<dyn ObjectStore> = match scheme {
+        "s3" => Box::new(
+            AmazonS3Builder::from_env()
+                .with_bucket_name(bucket)
+                .build()
+                .map_err(|e| e.to_string())?,
+        ),
+        "gs" => Box::new(
+            GoogleCloudStorageBuilder::from_env()
+                .with_bucket_name(bucket)
+                .build()
+                .map_err(|e| e.to_string())?,
+        ),
+        _ => return Err(invalid()),
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let path = Path::from(key);
+    let failed = |e: object_store::Error| format!("Couldn't download {}: {}", uri, e);
+    let cached = |e: std::io::Error| format!("Couldn't cache {}: {}", uri, e);
+
+    let e_tag = match cache {
+        Some(_) => runtime.block_on(store.head(&path)).map_err(failed)?.e_tag,
+        None => None,
+    };
+    if let (Some(cache), Some(e_tag)) = (cache, &e_tag) {
+        if let Some(contents) = cache.get(uri, e_tag).map_err(cached)? {
+            return Ok(contents);
+        }
+    }
+
+    let contents = runtime
+        .block_on(async { store.get(&path).await?.bytes().await })
+        .map(|bytes| bytes.to_vec())
+        .map_err(failed)?;
+    if let (Some(cache), Some(e_tag)) = (cache, &e_tag) {
+        cache.put(uri, e_tag, &contents).map_err(cached)?;
+    }
+
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::{env, fs, thread};
+
+    use super::*;
+
+    // The ETag and contents of the one object of a fake S3, and how many
+    // times they were downloaded
+    struct Object {
+        e_tag: String,
+        contents: String,
+        downloads: usize,
+    }
+
+    // Answers HEAD and GET requests for any key like S3 would, over plain
+    // HTTP on the endpoint it returns
+    fn serve(object: Arc<Mutex<Object>>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+
+                let mut object = object.lock().unwrap();
+                let head = request.starts_with("HEAD");
+                if !head {
+                    object.downloads += 1;
+                }
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nETag: \"{}\"\r\nLast-Modified: Wed, 14 Oct 2026 12:00:00 GMT\r\nConnection: close\r\n\r\n{}",
+                    object.contents.len(),
+                    object.e_tag,
+                    if head { "" } else { &object.contents }
+                )
+                .unwrap();
+            }
+        });
+
+        endpoint
+    }
+
+    #[test]
+    fn downloads_objects_again_only_when_their_e_tag_changes() {
+        let object = Arc::new(Mutex::new(Object {
+            e_tag: "1".to_string(),
+            contents: "republican,y".to_string(),
+            downloads: 0,
+        }));
+        env::set_var("AWS_ENDPOINT", serve(object.clone()));
+        env::set_var("AWS_ALLOW_HTTP", "true");
+        env::set_var("AWS_REGION", "us-east-1");
+        env::set_var("AWS_SKIP_SIGNATURE", "true");
+        let root = env::temp_dir().join(format!("cloud-{}", std::process::id()));
+        let cache = Cache::new(&root);
+        let uri = "s3://bucket/house-votes-84.data";
+        let downloads = || object.lock().unwrap().downloads;
+
+        assert_eq!(download(uri, Some(&cache)).unwrap(), b"republican,y");
+        assert_eq!(download(uri, Some(&cache)).unwrap(), b"republican,y");
+        assert_eq!(downloads(), 1);
+
+        *object.lock().unwrap() = Object {
+            e_tag: "2".to_string(),
+            contents: "democrat,n".to_string(),
+            downloads: 1,
+        };
+        assert_eq!(download(uri, Some(&cache)).unwrap(), b"democrat,n");
+        assert_eq!(downloads(), 2);
+        assert_eq!(download(uri, None).unwrap(), b"democrat,n");
+        assert_eq!(downloads(), 3);
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn rejects_uris_without_a_bucket_and_key() {
+        for uri in ["s3://bucket", "s3:///key", "ftp://bucket/key", "bucket/key"] {
+            assert_eq!(
+                download(uri, None).unwrap_err(),
+                format!("Expected a URI like s3://bucket/key, got {}", uri)
+            );
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::classifier::{normalize_log_probabilities, Classifier};
+use crate::counts::JointCounts;
+use crate::data::{Choice, ATTRIBUTES_COUNT, CHOICES, CLASSES, CLASSES_COUNT};
+
+// Complement naive Bayes (Rennie et al., 2003). The votes of each class are
+// estimated from the rows of every other class, which has more rows to go
+// on when the classes are imbalanced, and a class scores higher the less
+// its member's votes look like the other classes'. The scores are
+// normalized like probabilities, but aren't calibrated ones.
+#[derive(Debug, Clone)]
+pub struct ComplementModel {
+    log_priors: [f64; CLASSES_COUNT],
+    // Natural log of P(choice | any other class), packed
+    // [class][attribute][choice]
+    log_complements: Vec<f64>,
+}
+
+impl ComplementModel {
+    // Every choice starts with the smoothing count, like in Trainer
+    pub fn new(counts: &JointCounts, smoothing: f64) -> Self {
+        let total = counts.rows_count() as f64;
+        let log_priors = CLASSES.map(|class| (counts.class_count(class) as f64 / total).ln());
+        let mut log_complements = vec![0.0; CLASSES_COUNT * ATTRIBUTES_COUNT * CHOICES.len()];
+
+        for &class in CLASSES.iter() {
+            let others = CLASSES.iter().filter(|&&other| other != class);
+            let complement_count = total - counts.class_count(class) as f64;
+
+            for attribute in 0..ATTRIBUTES_COUNT {
+                for &choice in CHOICES.iter() {
+                    let count: u32 = others
+                        .clone()
+                        .map(|&other| counts.count(other, attribute, choice))
+                        .sum();
+                    log_complements[Self::idx(class.index(), attribute, choice)] = ((count as f64
+                        + smoothing)
+                        / (complement_count + smoothing * CHOICES.len() as f64))
+                        .ln();
+                }
+            }
+        }
+
+        ComplementModel {
+            log_priors,
+            log_complements,
+        }
+    }
+
+    fn idx(class: usize, attribute: usize, choice: Choice) -> usize {
+        (class * ATTRIBUTES_COUNT + attribute) * CHOICES.len() + choice.index()
+    }
+}
+
+impl Classifier for ComplementModel {
+    fn log_probabilities(&self, attributes: &[Choice]) -> [f64; CLASSES_COUNT] {
+        let mut res = self.log_priors;
+
+        for (class, score) in res.iter_mut().enumerate() {
+            for (attribute, &choice) in attributes.iter().enumerate() {
+                *score -= self.log_complements[Self::idx(class, attribute, choice)];
+            }
+        }
+
+        normalize_log_probabilities(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{try_parse_row, Class, Row};
+    use crate::model::DEFAULT_SMOOTHING;
+
+    #[test]
+    fn favours_the_class_whose_complement_the_votes_are_unlike() {
+        // Republicans voted yes on the first bill and democrats no
+        let rest = ",n".repeat(ATTRIBUTES_COUNT - 1);
+        let rows: Vec<Row> = ["republican,y", "republican,y", "democrat,n", "democrat,n"]
+            .iter()
+            .map(|votes| try_parse_row(&format!("{}{}", votes, rest)).unwrap())
+            .collect();
+        let model = ComplementModel::new(&rows.iter().collect(), DEFAULT_SMOOTHING);
+
+        assert_eq!(model.classify(&rows[0].attributes), Class::Republican);
+        assert_eq!(model.classify(&rows[2].attributes), Class::Democrat);
+        let probabilities = model.probabilities(&rows[0].attributes);
+        assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,392 @@
+use std::env;
+use std::fs;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::classifier::ModelKind;
+use crate::data::{Class, OnError};
+use crate::joint::AttributeGroup;
+use crate::model::{MissingVotes, PriorMode};
+
+// What the cross-validation run reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Metric {
+    // Accuracy of every fold and their average
+    Accuracy,
+    // Confusion matrix over all folds
+    Confusion,
+    // Attributes that point to each class most strongly, from a model
+    // trained on the whole dataset
+    Attributes,
+    // Average precision of finding the positive class over all folds, see
+    // crate::curves
+    PrecisionRecall,
+    // How well the likeliest rows of the positive class are found, by
+    // decile of rows
+    Lift,
+    // How well the probabilities match how often rows turn out to be of the
+    // positive class
+    Calibration,
+}
+
+pub const DEFAULT_METRICS: [Metric; 6] = [
+    Metric::Accuracy,
+    Metric::Confusion,
+    Metric::Attributes,
+    Metric::PrecisionRecall,
+    Metric::Lift,
+    Metric::Calibration,
+];
+pub const DEFAULT_TOP_ATTRIBUTES: usize = 5;
+pub const DEFAULT_POSITIVE_CLASS: Class = Class::Republican;
+pub const DEFAULT_CHECKPOINT_EVERY: usize = 10_000;
+
+// Settings of a run, e.g. from a file like
+//
+//     data = "house-votes-84.data"
+//     on-error = "skip"
+//     encoding = "latin1"
+//     identifiers = [1]
+//     sheet = "Votes"
+//     dedup = true
+//     sample = 0.1
+//     k-anonymity = 5
+//     quasi-identifiers = [1, 2, 3]
+//     folds = 10
+//     seed = 42
+//     smoothing = 1.0
+//     missing-votes = "category"
+//     prior-mode = "learned"
+//     feature-selection = 8
+//     augment = 2
+//     flip-probability = 0.1
+//     epsilon = 1.0
+//     model = "nb"
+//     joint = [[3, 4], [7, 8]]
+//     metrics = [
+//         "accuracy",
+//         "confusion",
+//         "attributes",
+//         "precision-recall",
+//         "lift",
+//         "calibration",
+//     ]
+//     top-attributes = 5
+//     positive-class = "republican"
+//
+//     [output]
+//     color = false
+//     mem-report = true
+//     error-analysis = true
+//     save-model = "model.json"
+//     save-fold-models = "runs/folds"
+//     train-final = "final.json"
+//     best-fold = false
+//     min-accuracy = 0.9
+//     checkpoint = "runs/train.checkpoint"
+//     checkpoint-every = 10000
+//     manifest = "runs/latest.json"
+//     pr-curve = "runs/pr.csv"
+//     gain-curve = "runs/gain.csv"
+//     calibration-curve = "runs/calibration.csv"
+//     plots = "runs/plots"
+//     charts = true
+//     history = "runs/history.csv"
+//     mlflow = "http://localhost:5000"
+//     mlflow-experiment = "party-recogniser"
+//     model-card = "runs/model-card.md"
+//
+// or the same keys in YAML. A file with every setting describes a whole
+// experiment that can be versioned and rerun with run --spec.
+//
+// Everything is optional. Settings that are left out come from the next
+// layer, see RunConfig::or. The lowest layer is the environment, see
+// RunConfig::from_env.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct RunConfig {
+    pub data: Option<String>,
+    // What to do with rows that can't be parsed [default: fail]
+    pub on_error: Option<OnError>,
+    // WHATWG label of the encoding of the data [default: utf-8]
+    pub encoding: Option<String>,
+    // Columns, numbered from 1, that identify a member and are dropped
+    // before parsing, see crate::anonymize::Identifiers
+    pub identifiers: Option<Vec<usize>>,
+    // Sheet of an .xlsx dataset [default: the first one]
+    pub sheet: Option<String>,
+    // Drop rows identical to an earlier one before training
+    pub dedup: Option<bool>,
+    // Only train and test on about this share of the rows, picked with the
+    // seed
+    pub sample: Option<f64>,
+    // Suppress the votes on the quasi-identifiers of rows that fewer rows
+    // share them with, see crate::anonymize
+    pub k_anonymity: Option<usize>,
+    // Attributes, numbered from 1, that could single a member out [default:
+    // all]
+    pub quasi_identifiers: Option<Vec<usize>>,
+    pub folds: Option<usize>,
+    pub seed: Option<u64>,
+    pub smoothing: Option<f64>,
+    // Unknown votes are counted as a category [default: category]
+    pub missing_votes: Option<MissingVotes>,
+    // Priors are the shares of the classes [default: learned]
+    pub prior_mode: Option<PriorMode>,
+    // Only predict from this many of the attributes, only with naive Bayes
+    // [default: all]
+    pub feature_selection: Option<usize>,
+    // Noisy copies added per training row, see crate::augment
+    pub augment: Option<usize>,
+    pub flip_probability: Option<f64>,
+    // Differential privacy budget, see crate::privacy
+    pub epsilon: Option<f64>,
+    // Only naive Bayes models can be saved, auto picks the kind that
+    // cross-validates best [default: nb]
+    pub model: Option<ModelKind>,
+    // Attributes modelled together, only with naive Bayes
+    pub joint: Option<Vec<AttributeGroup>>,
+    pub metrics: Option<Vec<Metric>>,
+    // Attributes reported per class for Metric::Attributes
+    pub top_attributes: Option<usize>,
+    // The class precision, recall, lift and calibration are about
+    pub positive_class: Option<Class>,
+    pub output: OutputConfig,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct OutputConfig {
+    pub color: Option<bool>,
+    pub mem_report: Option<bool>,
+    // List the misclassified rows, see crate::error_analysis
+    pub error_analysis: Option<bool>,
+    pub save_model: Option<String>,
+    // Directory for the model and metrics of every fold
+    pub save_fold_models: Option<String>,
+    // Save and export the model of the most accurate fold instead of one
+    // trained on the whole dataset
+    pub best_fold: Option<bool>,
+    // Models aren't saved when the average accuracy is below this
+    pub min_accuracy: Option<f64>,
+    // Where the counts of training on the whole dataset are saved so the
+    // pass can be resumed, see crate::checkpoint
+    pub checkpoint: Option<String>,
+    // Lines between checkpoints
+    pub checkpoint_every: Option<usize>,
+    // Always trained on the whole dataset, even with best_fold
+    pub train_final: Option<String>,
+    pub export_quantized: Option<String>,
+    // Where to write the manifest of the run, see crate::manifest
+    pub manifest: Option<String>,
+    // Where to write the points of the precision-recall curve as CSV
+    pub pr_curve: Option<String>,
+    // Where to write the points of the cumulative gain curve as CSV
+    pub gain_curve: Option<String>,
+    // Where to write the bins of the calibration curve as CSV
+    pub calibration_curve: Option<String>,
+    // Directory for SVG plots of the folds and curves, see crate::plots
+    pub plots: Option<String>,
+    // Print terminal charts of the run, see crate::charts
+    pub charts: Option<bool>,
+    // CSV every run appends a line of its metrics to, see crate::history
+    pub history: Option<String>,
+    // Tracking server every run is logged to, see crate::mlflow
+    pub mlflow: Option<String>,
+    // [default: party-recogniser]
+    pub mlflow_experiment: Option<String>,
+    // Where to write the card of the saved model as Markdown, see
+    // crate::model_card
+    pub model_card: Option<String>,
+    // Where to write the run as an HTML page, see crate::report
+    pub report: Option<String>,
+}
+
+impl RunConfig {
+    // YAML files have the same keys, told apart by their extension
+    pub fn load(filename: &str) -> io::Result<Self> {
+        let contents = fs::read_to_string(filename)?;
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+
+        if filename.ends_with(".yaml") || filename.ends_with(".yml") {
+            serde_yaml::from_str(&contents).map_err(|e| invalid(e.to_string()))
+        } else {
+            toml::from_str(&contents).map_err(|e| invalid(e.to_string()))
+        }
+    }
+
+    // The settings that can be given as PARTY_RECOGNISER_* variables, for
+    // deployments where writing a config file is awkward
+    pub fn from_env() -> Self {
+        RunConfig {
+            data: env::var("PARTY_RECOGNISER_DATA").ok(),
+            ..Self::default()
+        }
+    }
+
+    // Every setting that's set, without the output, as the key and value
+    // they'd have in a config file
+    pub fn settings(&self) -> Vec<(String, String)> {
+        let settings = RunConfig {
+            output: OutputConfig::default(),
+            ..self.clone()
+        };
+        let Value::Object(settings) = serde_json::to_value(settings).unwrap() else {
+            unreachable!("RunConfig is a struct");
+        };
+
+        settings
+            .into_iter()
+            .filter_map(|(key, value)| match value {
+                Value::Null | Value::Object(_) => None,
+                Value::String(value) => Some((key, value)),
+                value => Some((key, value.to_string())),
+            })
+            .collect()
+    }
+
+    // Fills in whatever isn't set here from the lower layer, so e.g. CLI
+    // flags can override a config file with cli.or(file)
+    pub fn or(self, lower: RunConfig) -> RunConfig {
+        RunConfig {
+            data: self.data.or(lower.data),
+            on_error: self.on_error.or(lower.on_error),
+            encoding: self.encoding.or(lower.encoding),
+            identifiers: self.identifiers.or(lower.identifiers),
+            sheet: self.sheet.or(lower.sheet),
+            dedup: self.dedup.or(lower.dedup),
+            sample: self.sample.or(lower.sample),
+            k_anonymity: self.k_anonymity.or(lower.k_anonymity),
+            quasi_identifiers: self.quasi_identifiers.or(lower.quasi_identifiers),
+            folds: self.folds.or(lower.folds),
+            seed: self.seed.or(lower.seed),
+            smoothing: self.smoothing.or(lower.smoothing),
+            missing_votes: self.missing_votes.or(lower.missing_votes),
+            prior_mode: self.prior_mode.or(lower.prior_mode),
+            feature_selection: self.feature_selection.or(lower.feature_selection),
+            augment: self.augment.or(lower.augment),
+            flip_probability: self.flip_probability.or(lower.flip_probability),
+            epsilon: self.epsilon.or(lower.epsilon),
+            model: self.model.or(lower.model),
+            joint: self.joint.or(lower.joint),
+            metrics: self.metrics.or(lower.metrics),
+            top_attributes: self.top_attributes.or(lower.top_attributes),
+            positive_class: self.positive_class.or(lower.positive_class),
+            output: OutputConfig {
+                color: self.output.color.or(lower.output.color),
+                mem_report: self.output.mem_report.or(lower.output.mem_report),
+                error_analysis: self.output.error_analysis.or(lower.output.error_analysis),
+                save_model: self.output.save_model.or(lower.output.save_model),
+                save_fold_models: self
+                    .output
+                    .save_fold_models
+                    .or(lower.output.save_fold_models),
+                best_fold: self.output.best_fold.or(lower.output.best_fold),
+                min_accuracy: self.output.min_accuracy.or(lower.output.min_accuracy),
+                checkpoint: self.output.checkpoint.or(lower.output.checkpoint),
+                checkpoint_every: self
+                    .output
+                    .checkpoint_every
+                    .or(lower.output.checkpoint_every),
+                train_final: self.output.train_final.or(lower.output.train_final),
+                export_quantized: self
+                    .output
+                    .export_quantized
+                    .or(lower.output.export_quantized),
+                manifest: self.output.manifest.or(lower.output.manifest),
+                pr_curve: self.output.pr_curve.or(lower.output.pr_curve),
+                gain_curve: self.output.gain_curve.or(lower.output.gain_curve),
+                calibration_curve: self
+                    .output
+                    .calibration_curve
+                    .or(lower.output.calibration_curve),
+                plots: self.output.plots.or(lower.output.plots),
+                charts: self.output.charts.or(lower.output.charts),
+                history: self.output.history.or(lower.output.history),
+                mlflow: self.output.mlflow.or(lower.output.mlflow),
+                mlflow_experiment: self
+                    .output
+                    .mlflow_experiment
+                    .or(lower.output.mlflow_experiment),
+                model_card: self.output.model_card.or(lower.output.model_card),
+                report: self.output.report.or(lower.output.report),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(data: &str) -> RunConfig {
+        RunConfig {
+            data: Some(data.to_string()),
+            ..RunConfig::default()
+        }
+    }
+
+    #[test]
+    fn reads_the_environment_under_the_other_layers() {
+        // No other test reads the variable
+        env::set_var("PARTY_RECOGNISER_DATA", "env.data");
+        let from_env = RunConfig::from_env();
+        env::remove_var("PARTY_RECOGNISER_DATA");
+
+        assert_eq!(from_env, data("env.data"));
+        let layered = |flags: RunConfig, file: RunConfig| flags.or(file).or(from_env.clone()).data;
+        assert_eq!(
+            layered(data("flags.data"), data("file.data")).unwrap(),
+            "flags.data"
+        );
+        assert_eq!(
+            layered(RunConfig::default(), data("file.data")).unwrap(),
+            "file.data"
+        );
+        assert_eq!(
+            layered(RunConfig::default(), RunConfig::default()).unwrap(),
+            "env.data"
+        );
+        assert_eq!(RunConfig::from_env(), RunConfig::default());
+    }
+
+    // Writes the contents to a file of the name under the temp directory
+    // and loads it
+    fn load(name: &str, contents: &str) -> io::Result<RunConfig> {
+        let path = env::temp_dir().join(format!("{}-{}", std::process::id(), name));
+        let filename = path.to_str().unwrap();
+        fs::write(filename, contents).unwrap();
+        let res = RunConfig::load(filename);
+        fs::remove_file(filename).unwrap();
+        res
+    }
+
+    #[test]
+    fn reads_the_same_keys_from_toml_and_yaml() {
+        let toml = load(
+            "experiment.toml",
+            "data = \"votes.data\"\nsmoothing = 0.5\nmetrics = [\"accuracy\", \"lift\"]\n\n\
+             [output]\nsave-model = \"model.json\"\n",
+        )
+        .unwrap();
+        let yaml = load(
+            "experiment.yaml",
+            "data: votes.data\nsmoothing: 0.5\nmetrics: [accuracy, lift]\n\
+             output:\n  save-model: model.json\n",
+        )
+        .unwrap();
+
+        assert_eq!(yaml, toml);
+        assert_eq!(toml.data.as_deref(), Some("votes.data"));
+        assert_eq!(toml.smoothing, Some(0.5));
+        assert_eq!(toml.output.save_model.as_deref(), Some("model.json"));
+        assert_eq!(
+            load("experiment.yml", "data: votes.data\n").unwrap().data,
+            toml.data
+        );
+        assert!(load("experiment.yaml", "datum: votes.data\n").is_err());
+    }
+}
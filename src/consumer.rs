@@ -0,0 +1,163 @@
+use std::time::Duration;
+
+use kafka::consumer::{Consumer, FetchOffset, GroupOffsetStorage};
+use kafka::producer::{Producer, Record, RequiredAcks};
+
+use crate::audit::AuditLog;
+use crate::model::{LoadOptions, Model};
+use crate::server::{attributes_from_map, predict_response, ErrorResponse, PredictRequest};
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ConsumerConfig {
+    /// Model saved with --save-model
+    #[arg(long = "model", value_name = "FILE", env = "PARTY_RECOGNISER_MODEL")]
+    pub model_path: String,
+
+    /// Bootstrap brokers, e.g. localhost:9092,localhost:9093
+    #[arg(
+        long,
+        value_delimiter = ',',
+        required = true,
+        env = "PARTY_RECOGNISER_BROKERS"
+    )]
+    pub brokers: Vec<String>,
+
+    /// Topic of the records to classify
+    #[arg(long, default_value = "votes")]
+    pub topic: String,
+
+    /// Topic the predictions are written to
+    #[arg(long, default_value = "predictions")]
+    pub output_topic: String,
+
+    /// Consumer group the offsets are committed under
+    #[arg(long, default_value = "party-recogniser")]
+    pub group: String,
+}
+
+// Reads records like the body of POST /predict from the input topic and
+// writes the prediction, or an {"error": ...} object for a bad record, to
+// the output topic under the same key. Offsets are committed after the
+// predictions of a poll are written, so a record can be classified twice
+// after a crash but is never dropped.
+pub fn consume(
+    config: &ConsumerConfig,
+    load_options: &LoadOptions,
+    audit: Option<&AuditLog>,
+) -> Result<(), String> {
+    let model = Model::load_with(&config.model_path, load_options)
+        .map_err(|e| format!("Couldn't load model: {}", e))?;
+
+    let mut consumer = Consumer::from_hosts(config.brokers.clone())
+        .with_topic(config.topic.clone())
+        .with_group(config.group.clone())
+        .with_fallback_offset(FetchOffset::Earliest)
+        .with_offset_storage(Some(GroupOffsetStorage::Kafka))
+        .create()
+        .map_err(|e| format!("Couldn't connect to {}: {}", config.brokers.join(","), e))?;
+    let mut producer = Producer::from_hosts(config.brokers.clone())
+        .with_ack_timeout(Duration::from_secs(1))
+        .with_required_acks(RequiredAcks::One)
+        .create()
+        .map_err(|e| format!("Couldn't connect to {}: {}", config.brokers.join(","), e))?;
+
+    tracing::info!("Classifying {} into {}", config.topic, config.output_topic);
+
+    loop {
+        let message_sets = consumer.poll().map_err(|e| e.to_string())?;
+
+        for message_set in message_sets.iter() {
+            for message in message_set.messages() {
+                let reply = classify(&model, message.value, audit);
+                producer
+                    .send(&Record::from_key_value(
+                        &config.output_topic,
+                        message.key,
+                        reply.as_str(),
+                    ))
+                    .map_err(|e| format!("Couldn't write prediction: {}", e))?;
+            }
+
+            consumer
+                .consume_messageset(message_set)
+                .map_err(|e| e.to_string())?;
+        }
+
+        consumer
+            .commit_consumed()
+            .map_err(|e| format!("Couldn't commit offsets: {}", e))?;
+    }
+}
+
+// The JSON written for a single record
+fn classify(model: &Model, value: &[u8], audit: Option<&AuditLog>) -> String {
+    let res = serde_json::from_slice::<PredictRequest>(value)
+        .map_err(|e| e.to_string())
+        .and_then(|request| attributes_from_map(&request.attributes));
+
+    match res {
+        Ok(attributes) => {
+            let (class, response) = predict_response(model, &attributes);
+
+            if let Some(audit) = audit {
+                audit
+                    .record(
+                        &model.fingerprint(),
+                        &attributes,
+                        class,
+                        &response.probabilities.0,
+                    )
+                    .expect("Couldn't write audit log");
+            }
+
+            serde_json::to_string(&response)
+        }
+        Err(error) => serde_json::to_string(&ErrorResponse { error }),
+    }
+    .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+
+    use super::*;
+    use crate::data::try_parse_row;
+
+    #[test]
+    fn replies_like_the_http_server_and_audits_the_predictions() {
+        let model = Model::from_rows(
+            include_str!("../house-votes-84.data")
+                .lines()
+                .map(|line| try_parse_row(line).unwrap()),
+        );
+        let filename = std::env::temp_dir().join(format!("consumer-{}.jsonl", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        let audit = AuditLog::open(filename, false).unwrap();
+
+        let record = br#"{"attributes": {"physician-fee-freeze": "y"}}"#;
+        let mut votes = BTreeMap::new();
+        votes.insert("physician-fee-freeze".to_string(), "y".to_string());
+        let (_, response) = predict_response(&model, &attributes_from_map(&votes).unwrap());
+        assert_eq!(
+            classify(&model, record, Some(&audit)),
+            serde_json::to_string(&response).unwrap()
+        );
+
+        // Bad records get an error under their key instead
+        let reply = classify(
+            &model,
+            br#"{"attributes": {"lobbying": "y"}}"#,
+            Some(&audit),
+        );
+        let reply: serde_json::Value = serde_json::from_str(&reply).unwrap();
+        assert!(reply["error"].as_str().unwrap().contains("lobbying"));
+        let reply: serde_json::Value =
+            serde_json::from_str(&classify(&model, b"not json", None)).unwrap();
+        assert!(reply["error"].is_string());
+
+        assert_eq!(fs::read_to_string(filename).unwrap().lines().count(), 1);
+        fs::remove_file(filename).unwrap();
+    }
+}
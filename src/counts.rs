@@ -0,0 +1,127 @@
+use std::iter::FromIterator;
+
+use crate::data::{Choice, Class, Row, ATTRIBUTES_COUNT, CHOICES, CLASSES, CLASSES_COUNT};
+
+// Counts of every class, of every choice within a class and of every pair of
+// choices within a class, which the variants of naive Bayes that don't
+// assume the attributes are independent are built from. Nothing is
+// smoothed.
+#[derive(Debug, Clone)]
+pub struct JointCounts {
+    rows_count: u32,
+    class_counts: [u32; CLASSES_COUNT],
+    // Packed [class][attribute][choice]
+    choice_counts: Vec<u32>,
+    // Packed [class][first attribute][first choice][second attribute][second
+    // choice], with both orders of every pair
+    pair_counts: Vec<u32>,
+}
+
+impl Default for JointCounts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JointCounts {
+    pub fn new() -> Self {
+        let choices = ATTRIBUTES_COUNT * CHOICES.len();
+
+        JointCounts {
+            rows_count: 0,
+            class_counts: [0; CLASSES_COUNT],
+            choice_counts: vec![0; CLASSES_COUNT * choices],
+            pair_counts: vec![0; CLASSES_COUNT * choices * choices],
+        }
+    }
+
+    fn choice_idx(class: Class, attribute: usize, choice: Choice) -> usize {
+        (class.index() * ATTRIBUTES_COUNT + attribute) * CHOICES.len() + choice.index()
+    }
+
+    fn pair_idx(
+        class: Class,
+        first: usize,
+        first_choice: Choice,
+        second: usize,
+        second_choice: Choice,
+    ) -> usize {
+        Self::choice_idx(class, first, first_choice) * ATTRIBUTES_COUNT * CHOICES.len()
+            + second * CHOICES.len()
+            + second_choice.index()
+    }
+
+    pub fn add(&mut self, row: &Row) {
+        self.rows_count += 1;
+        self.class_counts[row.class.index()] += 1;
+
+        for (first, &first_choice) in row.attributes.iter().enumerate() {
+            self.choice_counts[Self::choice_idx(row.class, first, first_choice)] += 1;
+
+            for (second, &second_choice) in row.attributes.iter().enumerate() {
+                self.pair_counts
+                    [Self::pair_idx(row.class, first, first_choice, second, second_choice)] += 1;
+            }
+        }
+    }
+
+    pub fn rows_count(&self) -> u32 {
+        self.rows_count
+    }
+
+    pub fn class_count(&self, class: Class) -> u32 {
+        self.class_counts[class.index()]
+    }
+
+    // Rows of the class that voted the choice on the attribute
+    pub fn count(&self, class: Class, attribute: usize, choice: Choice) -> u32 {
+        self.choice_counts[Self::choice_idx(class, attribute, choice)]
+    }
+
+    // Rows of the class that voted both choices on the two attributes
+    pub fn pair_count(
+        &self,
+        class: Class,
+        first: usize,
+        first_choice: Choice,
+        second: usize,
+        second_choice: Choice,
+    ) -> u32 {
+        self.pair_counts[Self::pair_idx(class, first, first_choice, second, second_choice)]
+    }
+
+    // I(first; second | class) in bits, 0 when the attributes are
+    // independent within every class. Unknown votes count as a choice of
+    // their own, like they do in the model.
+    pub fn conditional_mutual_information(&self, first: usize, second: usize) -> f64 {
+        let total = self.rows_count as f64;
+        let mut res = 0.0;
+
+        for &class in CLASSES.iter() {
+            for &first_choice in CHOICES.iter() {
+                for &second_choice in CHOICES.iter() {
+                    let count = self.pair_count(class, first, first_choice, second, second_choice);
+                    if count == 0 {
+                        continue;
+                    }
+
+                    // P(a, b, c) log P(a, b | c) / (P(a | c) P(b | c))
+                    let ratio = count as f64 * self.class_count(class) as f64
+                        / (self.count(class, first, first_choice) as f64
+                            * self.count(class, second, second_choice) as f64);
+                    res += count as f64 / total * ratio.log2();
+                }
+            }
+        }
+
+        res
+    }
+}
+
+impl<'a> FromIterator<&'a Row> for JointCounts {
+    fn from_iter<I: IntoIterator<Item = &'a Row>>(rows: I) -> Self {
+        let mut res = Self::new();
+        rows.into_iter().for_each(|row| res.add(row));
+        res
+    }
+}
@@ -0,0 +1,494 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use serde::Serialize;
+
+use crate::summation::{self, KahanSum};
+
+// Precision and recall of predicting the positive class when its probability
+// is at least the threshold
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PrecisionRecallPoint {
+    pub threshold: f64,
+    pub precision: f64,
+    pub recall: f64,
+}
+
+// A point for every distinct probability, from the highest threshold to the
+// lowest, so recall never shrinks along the curve. Takes whether every row
+// is of the positive class and the probability it was given of being so,
+// e.g. from threshold::out_of_fold_probabilities.
+pub fn precision_recall_curve(probabilities: &[(bool, f64)]) -> Vec<PrecisionRecallPoint> {
+    let mut sorted = probabilities.to_vec();
+    sorted.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let positives = sorted.iter().filter(|&&(positive, _)| positive).count();
+    let mut res = vec![];
+    let (mut true_positives, mut false_positives) = (0, 0);
+
+    for (i, &(positive, probability)) in sorted.iter().enumerate() {
+        if positive {
+            true_positives += 1;
+        } else {
+            false_positives += 1;
+        }
+
+        // Rows with the same probability are all predicted the same way
+        if sorted.get(i + 1).is_none_or(|next| next.1 != probability) {
+            res.push(PrecisionRecallPoint {
+                threshold: probability,
+                precision: true_positives as f64 / (true_positives + false_positives) as f64,
+                recall: if positives == 0 {
+                    0.0
+                } else {
+                    true_positives as f64 / positives as f64
+                },
+            });
+        }
+    }
+
+    res
+}
+
+// Precision averaged over the curve, weighted by how much recall grows at
+// each point. Unlike the area under the ROC curve, it isn't inflated by the
+// many easy negatives of an imbalanced dataset.
+pub fn average_precision(curve: &[PrecisionRecallPoint]) -> f64 {
+    let mut previous_recall = 0.0;
+
+    summation::sum(curve.iter().map(|point| {
+        let res = (point.recall - previous_recall) * point.precision;
+        previous_recall = point.recall;
+        res
+    }))
+}
+
+// Rates of finding positives and of false alarms when predicting the
+// positive class at a probability of at least the threshold
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct RocPoint {
+    pub threshold: f64,
+    pub false_positive_rate: f64,
+    pub true_positive_rate: f64,
+}
+
+// From predicting nothing positive at (0, 0) to predicting everything
+// positive at (1, 1), with a point for every distinct probability in
+// between
+pub fn roc_curve(probabilities: &[(bool, f64)]) -> Vec<RocPoint> {
+    let mut sorted = probabilities.to_vec();
+    sorted.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let positives = sorted.iter().filter(|&&(positive, _)| positive).count();
+    let negatives = sorted.len() - positives;
+    let rate = |count: usize, total: usize| {
+        if total == 0 {
+            0.0
+        } else {
+            count as f64 / total as f64
+        }
+    };
+    let mut res = vec![RocPoint {
+        threshold: f64::INFINITY,
+        false_positive_rate: 0.0,
+        true_positive_rate: 0.0,
+    }];
+    let (mut true_positives, mut false_positives) = (0, 0);
+
+    for (i, &(positive, probability)) in sorted.iter().enumerate() {
+        if positive {
+            true_positives += 1;
+        } else {
+            false_positives += 1;
+        }
+
+        if sorted.get(i + 1).is_none_or(|next| next.1 != probability) {
+            res.push(RocPoint {
+                threshold: probability,
+                false_positive_rate: rate(false_positives, negatives),
+                true_positive_rate: rate(true_positives, positives),
+            });
+        }
+    }
+
+    res
+}
+
+// Area under the ROC curve by the trapezoidal rule, i.e. the chance that a
+// random positive row is given a higher probability than a random negative
+// one, counting ties as half
+pub fn roc_area(curve: &[RocPoint]) -> f64 {
+    summation::sum(curve.windows(2).map(|pair| {
+        (pair[1].false_positive_rate - pair[0].false_positive_rate)
+            * (pair[1].true_positive_rate + pair[0].true_positive_rate)
+            / 2.0
+    }))
+}
+
+// A tenth of the rows, ranked by the probability of the positive class
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Decile {
+    // 1 for the likeliest rows
+    pub decile: usize,
+    pub rows: usize,
+    pub positives: usize,
+    // How many times more positives the decile has than a random sample of
+    // the same size
+    pub lift: f64,
+    // Share of all positives found in this decile and the ones above it
+    pub cumulative_gain: f64,
+    pub cumulative_lift: f64,
+}
+
+// After targeting the given share of rows, likeliest positives first
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct GainPoint {
+    pub rows_share: f64,
+    // Share of all positives found
+    pub gain: f64,
+    pub lift: f64,
+}
+
+fn ranked(probabilities: &[(bool, f64)]) -> Vec<bool> {
+    let mut sorted = probabilities.to_vec();
+    sorted.sort_by(|a, b| b.1.total_cmp(&a.1));
+    sorted.iter().map(|&(positive, _)| positive).collect()
+}
+
+fn ratio(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+pub fn deciles(probabilities: &[(bool, f64)]) -> Vec<Decile> {
+    let ranked = ranked(probabilities);
+    let total = ranked.len();
+    let positives = ranked.iter().filter(|&&positive| positive).count();
+    let base_rate = ratio(positives, total);
+    let lift = |found: usize, rows: usize| match base_rate {
+        rate if rate > 0.0 => ratio(found, rows) / rate,
+        _ => 0.0,
+    };
+
+    let (mut start, mut cumulative_rows, mut cumulative_positives) = (0, 0, 0);
+    (1..=10)
+        .map(|decile| {
+            let end = (total * decile + 5) / 10;
+            let rows = end - start;
+            let found = ranked[start..end]
+                .iter()
+                .filter(|&&positive| positive)
+                .count();
+            start = end;
+            cumulative_rows += rows;
+            cumulative_positives += found;
+
+            Decile {
+                decile,
+                rows,
+                positives: found,
+                lift: lift(found, rows),
+                cumulative_gain: ratio(cumulative_positives, positives),
+                cumulative_lift: lift(cumulative_positives, cumulative_rows),
+            }
+        })
+        .collect()
+}
+
+// A point after every row, starting from targeting nothing
+pub fn gain_curve(probabilities: &[(bool, f64)]) -> Vec<GainPoint> {
+    let ranked = ranked(probabilities);
+    let positives = ranked.iter().filter(|&&positive| positive).count();
+    let mut found = 0;
+
+    let mut res = vec![GainPoint {
+        rows_share: 0.0,
+        gain: 0.0,
+        lift: 0.0,
+    }];
+    for (i, &positive) in ranked.iter().enumerate() {
+        if positive {
+            found += 1;
+        }
+
+        let rows_share = ratio(i + 1, ranked.len());
+        let gain = ratio(found, positives);
+        res.push(GainPoint {
+            rows_share,
+            gain,
+            lift: gain / rows_share,
+        });
+    }
+
+    res
+}
+
+pub fn write_gain_csv(curve: &[GainPoint], filename: &str) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writeln!(writer, "rows_share,gain,lift")?;
+
+    for point in curve {
+        writeln!(writer, "{},{},{}", point.rows_share, point.gain, point.lift)?;
+    }
+
+    writer.flush()
+}
+
+pub const CALIBRATION_BINS: usize = 10;
+
+// Rows whose probability of the positive class fell in [lower, upper)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CalibrationBin {
+    pub lower: f64,
+    pub upper: f64,
+    pub rows: usize,
+    // Average probability of the positive class the rows were given, and
+    // the share of them that were of it. They're equal for a perfectly
+    // calibrated model.
+    pub predicted: f64,
+    pub observed: f64,
+}
+
+// Bins of equal width, the last one including 1. Bins without rows are
+// kept, so every run has the same bins.
+pub fn calibration_curve(probabilities: &[(bool, f64)], bins: usize) -> Vec<CalibrationBin> {
+    let mut sums = vec![(0, KahanSum::new(), 0); bins];
+
+    for &(positive, probability) in probabilities {
+        let bin = ((probability * bins as f64) as usize).min(bins - 1);
+        sums[bin].0 += 1;
+        sums[bin].1.add(probability);
+        if positive {
+            sums[bin].2 += 1;
+        }
+    }
+
+    sums.iter()
+        .enumerate()
+        .map(|(i, &(rows, probability_sum, positives))| CalibrationBin {
+            lower: i as f64 / bins as f64,
+            upper: (i + 1) as f64 / bins as f64,
+            rows,
+            predicted: if rows == 0 {
+                0.0
+            } else {
+                probability_sum.total() / rows as f64
+            },
+            observed: ratio(positives, rows),
+        })
+        .collect()
+}
+
+// Expected calibration error: how far the predicted probabilities are from
+// the observed shares, averaged over the bins by their number of rows
+pub fn expected_calibration_error(curve: &[CalibrationBin]) -> f64 {
+    let total: usize = curve.iter().map(|bin| bin.rows).sum();
+
+    summation::sum(
+        curve
+            .iter()
+            .map(|bin| ratio(bin.rows, total) * (bin.observed - bin.predicted).abs()),
+    )
+}
+
+pub fn write_calibration_csv(curve: &[CalibrationBin], filename: &str) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writeln!(writer, "lower,upper,rows,predicted,observed")?;
+
+    for bin in curve {
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            bin.lower, bin.upper, bin.rows, bin.predicted, bin.observed
+        )?;
+    }
+
+    writer.flush()
+}
+
+pub fn write_precision_recall_csv(
+    curve: &[PrecisionRecallPoint],
+    filename: &str,
+) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    writeln!(writer, "threshold,precision,recall")?;
+
+    for point in curve {
+        writeln!(
+            writer,
+            "{},{},{}",
+            point.threshold, point.precision, point.recall
+        )?;
+    }
+
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    const PROBABILITIES: [(bool, f64); 6] = [
+        (true, 0.9),
+        (false, 0.6),
+        (true, 0.7),
+        (true, 0.4),
+        (false, 0.4),
+        (false, 0.1),
+    ];
+
+    #[test]
+    fn traces_precision_and_recall_from_the_highest_threshold() {
+        let curve = precision_recall_curve(&PROBABILITIES);
+        let point = |threshold, precision, recall| PrecisionRecallPoint {
+            threshold,
+            precision,
+            recall,
+        };
+
+        // Both rows at 0.4 are added at once
+        assert_eq!(
+            curve,
+            [
+                point(0.9, 1.0, 1.0 / 3.0),
+                point(0.7, 1.0, 2.0 / 3.0),
+                point(0.6, 2.0 / 3.0, 2.0 / 3.0),
+                point(0.4, 3.0 / 5.0, 1.0),
+                point(0.1, 0.5, 1.0),
+            ]
+        );
+        let expected = (1.0 + 1.0 + 3.0 / 5.0) / 3.0;
+        assert!((average_precision(&curve) - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn averages_to_one_when_the_positives_come_first() {
+        let separated = [(true, 0.9), (true, 0.8), (false, 0.3), (false, 0.2)];
+
+        assert_eq!(average_precision(&precision_recall_curve(&separated)), 1.0);
+    }
+
+    #[test]
+    fn writes_the_precision_recall_curve_as_csv() {
+        let filename = std::env::temp_dir().join(format!("pr-{}.csv", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        let curve = precision_recall_curve(&PROBABILITIES[..2]);
+        write_precision_recall_csv(&curve, filename).unwrap();
+        let contents = fs::read_to_string(filename).unwrap();
+        fs::remove_file(filename).unwrap();
+
+        assert_eq!(contents, "threshold,precision,recall\n0.9,1,1\n0.6,0.5,1\n");
+    }
+
+    // 20 rows ranked by probability, positives at ranks 1 to 4 and 11
+    fn ranked_rows() -> Vec<(bool, f64)> {
+        (0..20)
+            .map(|i| (i < 4 || i == 10, (20 - i) as f64 / 20.0))
+            .rev()
+            .collect()
+    }
+
+    #[test]
+    fn lifts_the_deciles_by_the_base_rate() {
+        let deciles = deciles(&ranked_rows());
+
+        assert_eq!(deciles.len(), 10);
+        assert!(deciles.iter().all(|decile| decile.rows == 2));
+        let positives: Vec<_> = deciles.iter().map(|decile| decile.positives).collect();
+        assert_eq!(positives, [2, 2, 0, 0, 0, 1, 0, 0, 0, 0]);
+        // A quarter of the rows are positives
+        assert_eq!(deciles[0].lift, 4.0);
+        assert_eq!(deciles[1].cumulative_gain, 0.8);
+        assert_eq!(deciles[1].cumulative_lift, 4.0);
+        assert_eq!(deciles[5].lift, 2.0);
+        assert_eq!(deciles[9].cumulative_gain, 1.0);
+        assert_eq!(deciles[9].cumulative_lift, 1.0);
+    }
+
+    #[test]
+    fn gains_a_point_per_row() {
+        let curve = gain_curve(&ranked_rows());
+        let point = |rows_share, gain, lift| GainPoint {
+            rows_share,
+            gain,
+            lift,
+        };
+
+        assert_eq!(curve.len(), 21);
+        assert_eq!(curve[0], point(0.0, 0.0, 0.0));
+        assert_eq!(curve[4], point(0.2, 0.8, 4.0));
+        assert_eq!(curve[20], point(1.0, 1.0, 1.0));
+        assert!(deciles(&[]).iter().all(|decile| decile.rows == 0));
+    }
+
+    #[test]
+    fn bins_the_probabilities_up_to_and_including_one() {
+        let probabilities = [(true, 1.0), (true, 0.75), (false, 0.25), (false, 0.0)];
+        let curve = calibration_curve(&probabilities, 4);
+        let bin = |lower, upper, rows, predicted, observed| CalibrationBin {
+            lower,
+            upper,
+            rows,
+            predicted,
+            observed,
+        };
+
+        assert_eq!(
+            curve,
+            [
+                bin(0.0, 0.25, 1, 0.0, 0.0),
+                bin(0.25, 0.5, 1, 0.25, 0.0),
+                // Empty bins are kept
+                bin(0.5, 0.75, 0, 0.0, 0.0),
+                bin(0.75, 1.0, 2, 0.875, 1.0),
+            ]
+        );
+        assert_eq!(expected_calibration_error(&curve), 0.125);
+    }
+
+    #[test]
+    fn writes_the_calibration_curve_as_csv() {
+        let filename = std::env::temp_dir().join(format!("calibration-{}.csv", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        write_calibration_csv(&calibration_curve(&[(true, 0.75)], 2), filename).unwrap();
+        let contents = fs::read_to_string(filename).unwrap();
+        fs::remove_file(filename).unwrap();
+
+        assert_eq!(
+            contents,
+            "lower,upper,rows,predicted,observed\n0,0.5,0,0,0\n0.5,1,1,0.75,1\n"
+        );
+    }
+
+    #[test]
+    fn traces_the_roc_curve_through_ties() {
+        let curve = roc_curve(&PROBABILITIES);
+        let rates: Vec<_> = curve
+            .iter()
+            .map(|point| (point.false_positive_rate, point.true_positive_rate))
+            .collect();
+        let third = 1.0 / 3.0;
+
+        assert_eq!(curve[0].threshold, f64::INFINITY);
+        assert_eq!(
+            rates,
+            [
+                (0.0, 0.0),
+                (0.0, third),
+                (0.0, 2.0 * third),
+                (third, 2.0 * third),
+                // Both rows at 0.4 at once
+                (2.0 * third, 1.0),
+                (1.0, 1.0),
+            ]
+        );
+        // 7 of the 9 pairs of a positive and a negative are ordered right
+        // and one is tied
+        assert!((roc_area(&curve) - 7.5 / 9.0).abs() < 1e-12);
+    }
+}
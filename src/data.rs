@@ -0,0 +1,709 @@
+use encoding_rs::{Encoding, UTF_8};
+use memmap2::Mmap;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{thread_rng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead};
+
+use crate::anonymize::Identifiers;
+
+// The house-votes-84 dataset built into the binary, so it runs without the
+// file next to it
+#[cfg(feature = "embedded-data")]
+pub const EMBEDDED_DATA: &str = include_str!("../house-votes-84.data");
+
+pub const ATTRIBUTES_COUNT: usize = 16;
+pub const CLASSES: [Class; 2] = [Class::Republican, Class::Democrat];
+pub const CHOICES: [Choice; 3] = [Choice::Yes, Choice::No, Choice::Unknown];
+pub const CLASSES_COUNT: usize = CLASSES.len();
+pub const ATTRIBUTE_NAMES: [&str; ATTRIBUTES_COUNT] = [
+    "handicapped-infants",
+    "water-project-cost-sharing",
+    "adoption-of-the-budget-resolution",
+    "physician-fee-freeze",
+    "el-salvador-aid",
+    "religious-groups-in-schools",
+    "anti-satellite-test-ban",
+    "aid-to-nicaraguan-contras",
+    "mx-missile",
+    "immigration",
+    "synfuels-corporation-cutback",
+    "education-spending",
+    "superfund-right-to-sue",
+    "crime",
+    "duty-free-exports",
+    "export-administration-act-south-africa",
+];
+
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub enum Choice {
+    Yes,
+    No,
+    Unknown,
+}
+
+impl Choice {
+    pub fn index(self) -> usize {
+        match self {
+            Choice::Yes => 0,
+            Choice::No => 1,
+            Choice::Unknown => 2,
+        }
+    }
+
+    // The vote as written in the dataset
+    pub fn name(self) -> &'static str {
+        match self {
+            Choice::Yes => "y",
+            Choice::No => "n",
+            Choice::Unknown => "?",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Class {
+    Republican,
+    Democrat,
+}
+
+impl Class {
+    pub fn index(self) -> usize {
+        match self {
+            Class::Republican => 0,
+            Class::Democrat => 1,
+        }
+    }
+
+    // The label used for the class in the dataset
+    pub fn name(self) -> &'static str {
+        match self {
+            Class::Republican => "republican",
+            Class::Democrat => "democrat",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Row {
+    pub class: Class,
+    pub attributes: Vec<Choice>,
+}
+
+// Written the way the dataset has it, e.g. democrat,y,n,?,...
+impl fmt::Display for Row {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.class.name())?;
+        for choice in &self.attributes {
+            write!(f, ",{}", choice.name())?;
+        }
+
+        Ok(())
+    }
+}
+
+pub fn choice_str_to_enum(c: &str) -> Choice {
+    if c == "y" {
+        return Choice::Yes;
+    }
+
+    if c == "n" {
+        return Choice::No;
+    }
+
+    Choice::Unknown
+}
+
+pub fn attribute_index(name: &str) -> Option<usize> {
+    ATTRIBUTE_NAMES.iter().position(|&x| x == name)
+}
+
+// Unlike the dataset parser, anything besides y, n and ? is rejected, since
+// this is meant for input typed in by users
+pub fn parse_vote(vote: &str) -> Result<Choice, String> {
+    match vote.trim() {
+        "y" => Ok(Choice::Yes),
+        "n" => Ok(Choice::No),
+        "?" => Ok(Choice::Unknown),
+        other => Err(format!("Unknown vote '{}'", other)),
+    }
+}
+
+// Parses the votes of a record without a class, e.g. "y,n,?,..."
+pub fn parse_attributes(votes: &str) -> Result<Vec<Choice>, String> {
+    let attributes = votes
+        .split(',')
+        .map(parse_vote)
+        .collect::<Result<Vec<Choice>, String>>()?;
+
+    if attributes.len() != ATTRIBUTES_COUNT {
+        return Err(format!(
+            "Expected {} votes, got {}",
+            ATTRIBUTES_COUNT,
+            attributes.len()
+        ));
+    }
+
+    Ok(attributes)
+}
+
+pub fn split_for_crossvalidation(data: Vec<Row>, splits: usize) -> Result<Vec<Vec<Row>>, String> {
+    split_for_crossvalidation_with_rng(data, splits, &mut thread_rng())
+}
+
+// Same as split_for_crossvalidation, but shuffles with the given generator,
+// e.g. a seeded one for reproducible splits. The shuffle only depends on the
+// number of items, so splitting the indices of rows splits them the same way
+// as the rows themselves. The sizes of the splits differ by at most one.
+// Fails unless every split gets at least one item.
+pub fn split_for_crossvalidation_with_rng<T: Clone, R: Rng>(
+    mut data: Vec<T>,
+    splits: usize,
+    rng: &mut R,
+) -> Result<Vec<Vec<T>>, String> {
+    if splits == 0 || data.len() < splits {
+        return Err(format!(
+            "Can't split {} rows into {} folds",
+            data.len(),
+            splits
+        ));
+    }
+
+    data.shuffle(rng);
+    let chunk_size = data.len() / splits;
+
+    let mut res: Vec<Vec<T>> = data
+        .chunks_exact(chunk_size)
+        .take(splits)
+        .map(|x| x.to_vec())
+        .collect();
+
+    // The items left over from equal splits go one to each of the first
+    // splits
+    for (split, item) in res.iter_mut().zip(&data[splits * chunk_size..]) {
+        split.push(item.clone());
+    }
+
+    Ok(res)
+}
+
+// Splits off about test_size of the rows for testing, keeping the order the
+// rows came in. Stratified splits take that share of every class separately,
+// so both halves have the same mix of parties.
+pub fn train_test_split<R: Rng>(
+    data: Vec<Row>,
+    test_size: f64,
+    stratify: bool,
+    rng: &mut R,
+) -> (Vec<Row>, Vec<Row>) {
+    let groups: Vec<Vec<usize>> = if stratify {
+        CLASSES
+            .iter()
+            .map(|&class| {
+                (0..data.len())
+                    .filter(|&i| data[i].class == class)
+                    .collect()
+            })
+            .collect()
+    } else {
+        vec![(0..data.len()).collect()]
+    };
+
+    let mut is_test = vec![false; data.len()];
+    for mut group in groups {
+        group.shuffle(rng);
+        let count = (group.len() as f64 * test_size).round() as usize;
+        for i in &group[..count] {
+            is_test[*i] = true;
+        }
+    }
+
+    let (test, train): (Vec<_>, Vec<_>) = data
+        .into_iter()
+        .zip(is_test)
+        .partition(|(_, is_test)| *is_test);
+
+    (
+        train.into_iter().map(|(row, _)| row).collect(),
+        test.into_iter().map(|(row, _)| row).collect(),
+    )
+}
+
+// Keeps about fraction of the rows that go by, drawing for each in turn from
+// one generator of the seed. A stream of rows is sampled the same way as
+// the same rows in memory, so a model trained on the stream matches the one
+// that was cross-validated.
+pub struct Sampler {
+    fraction: f64,
+    rng: StdRng,
+}
+
+impl Sampler {
+    pub fn new(fraction: f64, seed: u64) -> Self {
+        Sampler {
+            fraction,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn keep(&mut self) -> bool {
+        self.rng.gen_bool(self.fraction)
+    }
+}
+
+pub fn try_parse_row(line: &str) -> Result<Row, String> {
+    try_parse_row_bytes(line.as_bytes())
+}
+
+// Parses the row straight from the underlying bytes without allocating
+// anything besides the attributes
+pub fn try_parse_row_bytes(line: &[u8]) -> Result<Row, String> {
+    let mut fields = line.split(|&b| b == b',');
+
+    let class = match fields.next() {
+        Some(b"republican") => Class::Republican,
+        Some(b"democrat") => Class::Democrat,
+        label => {
+            return Err(format!(
+                "Unknown class '{}'",
+                String::from_utf8_lossy(label.unwrap_or_default())
+            ))
+        }
+    };
+
+    let attributes: Vec<Choice> = fields
+        .take(ATTRIBUTES_COUNT)
+        .map(|x| match x {
+            b"y" => Choice::Yes,
+            b"n" => Choice::No,
+            _ => Choice::Unknown,
+        })
+        .collect();
+
+    if attributes.len() != ATTRIBUTES_COUNT {
+        return Err("Missing attributes".to_string());
+    }
+
+    Ok(Row { class, attributes })
+}
+
+// What to do with rows that can't be parsed, e.g. with an unknown class or
+// too few votes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum OnError {
+    // Stop at the first one
+    Fail,
+    Skip,
+    // Fill in missing votes with ?, and skip the rows that can't be fixed
+    // like that
+    Fix,
+}
+
+// Parses rows line by line by an OnError policy, keeping count of what was
+// skipped and fixed
+#[derive(Debug)]
+pub struct RowReader {
+    on_error: OnError,
+    identifiers: Identifiers,
+    line: usize,
+    // Lines skipped for every reason
+    pub skipped: BTreeMap<String, Vec<usize>>,
+    pub fixed: Vec<usize>,
+}
+
+impl RowReader {
+    pub fn new(on_error: OnError) -> Self {
+        RowReader {
+            on_error,
+            identifiers: Identifiers::default(),
+            line: 0,
+            skipped: BTreeMap::new(),
+            fixed: vec![],
+        }
+    }
+
+    // Drops these columns of every line before parsing it
+    pub fn with_identifiers(mut self, identifiers: Identifiers) -> Self {
+        self.identifiers = identifiers;
+        self
+    }
+
+    pub fn on_error(&self) -> OnError {
+        self.on_error
+    }
+
+    // The 1-based line of the row that was read last
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    // None if the line was skipped. Only fails with OnError::Fail.
+    pub fn read(&mut self, line: &[u8]) -> Result<Option<Row>, String> {
+        self.line += 1;
+
+        let without_identifiers;
+        let line = if self.identifiers.is_empty() {
+            line
+        } else {
+            without_identifiers = self.identifiers.split(line).0;
+            &without_identifiers
+        };
+
+        let error = match try_parse_row_bytes(line) {
+            Ok(row) => return Ok(Some(row)),
+            Err(error) => error,
+        };
+
+        match self.on_error {
+            OnError::Fail => return Err(format!("Line {}: {}", self.line, error)),
+            OnError::Fix => {
+                if let Some(row) = fix_row_bytes(line) {
+                    self.fixed.push(self.line);
+                    return Ok(Some(row));
+                }
+            }
+            OnError::Skip => {}
+        }
+
+        self.skipped.entry(error).or_default().push(self.line);
+        Ok(None)
+    }
+
+    pub fn skipped_count(&self) -> usize {
+        self.skipped.values().map(Vec::len).sum()
+    }
+}
+
+// A row with a known class and too few votes, padded with unknown votes
+fn fix_row_bytes(line: &[u8]) -> Option<Row> {
+    let mut padded = line.to_vec();
+    let fields = line.split(|&b| b == b',').count();
+    for _ in fields..=ATTRIBUTES_COUNT {
+        padded.extend_from_slice(b",?");
+    }
+
+    try_parse_row_bytes(&padded).ok()
+}
+
+const UTF_8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+// Encodings by their WHATWG label, e.g. utf-8 or latin1. Files are split
+// into lines before decoding, so only encodings that write a newline as the
+// byte b'\n' can be read, which rules out UTF-16.
+pub fn encoding_for_label(label: &str) -> Result<&'static Encoding, String> {
+    match Encoding::for_label(label.as_bytes()) {
+        Some(encoding) if encoding.is_ascii_compatible() => Ok(encoding),
+        Some(encoding) => Err(format!("{} files can't be read", encoding.name())),
+        None => Err(format!("Unknown encoding '{}'", label)),
+    }
+}
+
+pub fn read_lines(
+    filename: &str,
+    encoding: &'static Encoding,
+) -> io::Result<impl Iterator<Item = io::Result<String>>> {
+    let file = File::open(filename)?;
+    Ok(decode_lines(io::BufReader::new(file), encoding))
+}
+
+// Lazily decodes the lines, without the UTF-8 byte order mark some editors
+// start files with. Bytes that aren't valid in the encoding become U+FFFD,
+// so a bad line fails to parse instead of the whole file.
+pub fn decode_lines<R: BufRead>(
+    reader: R,
+    encoding: &'static Encoding,
+) -> impl Iterator<Item = io::Result<String>> {
+    reader.split(b'\n').enumerate().map(move |(i, line)| {
+        let line = line?;
+        let mut line = line.strip_suffix(b"\r").unwrap_or(&line);
+        if i == 0 {
+            line = line.strip_prefix(UTF_8_BOM).unwrap_or(line);
+        }
+
+        Ok(encoding.decode_without_bom_handling(line).0.into_owned())
+    })
+}
+
+// Lazily parses the rows of the file one line at a time, so that callers
+// which only need a single pass never hold the whole dataset in memory.
+// Fails if the file can't be opened, and lines that can't be read or
+// parsed are errors with their line.
+pub fn stream_input(filename: &str) -> Result<impl Iterator<Item = Result<Row, String>>, String> {
+    let lines =
+        read_lines(filename, UTF_8).map_err(|e| format!("Couldn't open {}: {}", filename, e))?;
+
+    Ok(lines.enumerate().map(|(i, line)| {
+        line.map_err(|e| e.to_string())
+            .and_then(|line| try_parse_row(&line))
+            .map_err(|e| format!("Line {}: {}", i + 1, e))
+    }))
+}
+
+pub fn read_input(filename: &str) -> Result<Vec<Row>, String> {
+    stream_input(filename)?.collect()
+}
+
+// Same as read_input, but scans a memory map of the file instead of reading
+// it into a String per line, parsing the rows by the reader's policy. Every
+// row comes with its line.
+pub fn read_input_mmap(
+    filename: &str,
+    reader: &mut RowReader,
+) -> Result<Vec<(usize, Row)>, String> {
+    let file = File::open(filename).map_err(|e| format!("Couldn't open {}: {}", filename, e))?;
+
+    // SAFETY: The map is only read from and is dropped before returning.
+    // Like with any mmap, the file must not be truncated while we read it.
+    let mmap =
+        unsafe { Mmap::map(&file) }.map_err(|e| format!("Couldn't map {}: {}", filename, e))?;
+    let contents = mmap.strip_prefix(UTF_8_BOM).unwrap_or(&mmap);
+    let contents = contents.strip_suffix(b"\n").unwrap_or(contents);
+
+    if contents.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let mut res = vec![];
+    for line in contents.split(|&b| b == b'\n') {
+        if let Some(row) = reader.read(line.strip_suffix(b"\r").unwrap_or(line))? {
+            res.push((reader.line(), row));
+        }
+    }
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    const LINE: &str = "democrat,y,n,?,y,n,?,y,n,?,y,n,?,y,n,?,y";
+
+    #[test]
+    fn parses_a_row() {
+        let row = try_parse_row(LINE).unwrap();
+
+        assert_eq!(row.class, Class::Democrat);
+        assert_eq!(row.attributes.len(), ATTRIBUTES_COUNT);
+        assert_eq!(
+            &row.attributes[..3],
+            [Choice::Yes, Choice::No, Choice::Unknown]
+        );
+        assert_eq!(row.to_string(), LINE);
+    }
+
+    #[test]
+    fn rejects_unknown_classes_and_missing_attributes() {
+        assert_eq!(
+            try_parse_row("green,y,n").unwrap_err(),
+            "Unknown class 'green'"
+        );
+        assert_eq!(try_parse_row("").unwrap_err(), "Unknown class ''");
+        assert_eq!(
+            try_parse_row("republican,y,n").unwrap_err(),
+            "Missing attributes"
+        );
+    }
+
+    #[test]
+    fn reads_by_the_policy() {
+        let short = b"republican,y,n";
+
+        let mut reader = RowReader::new(OnError::Fail);
+        assert!(reader.read(LINE.as_bytes()).unwrap().is_some());
+        assert_eq!(
+            reader.read(short).unwrap_err(),
+            "Line 2: Missing attributes"
+        );
+
+        let mut reader = RowReader::new(OnError::Skip);
+        assert_eq!(reader.read(short).unwrap(), None);
+        assert_eq!(reader.read(b"green").unwrap(), None);
+        assert_eq!(reader.skipped_count(), 2);
+        assert_eq!(reader.skipped["Missing attributes"], [1]);
+
+        let mut reader = RowReader::new(OnError::Fix);
+        let row = reader.read(short).unwrap().unwrap();
+        assert_eq!(
+            &row.attributes[..3],
+            [Choice::Yes, Choice::No, Choice::Unknown]
+        );
+        assert_eq!(reader.fixed, [1]);
+        assert_eq!(reader.read(b"green").unwrap(), None);
+    }
+
+    #[test]
+    fn drops_identifiers() {
+        let mut reader =
+            RowReader::new(OnError::Fail).with_identifiers(Identifiers::new(&[1]).unwrap());
+        let line = format!("Member 1,{}", LINE);
+
+        assert_eq!(
+            reader.read(line.as_bytes()).unwrap(),
+            Some(try_parse_row(LINE).unwrap())
+        );
+    }
+
+    #[test]
+    fn splits_every_item_into_one_fold() {
+        for (items, splits) in [(10, 3), (435, 10), (5, 4), (4, 4), (7, 2)] {
+            let folds = split_for_crossvalidation_with_rng(
+                (0..items).collect(),
+                splits,
+                &mut StdRng::seed_from_u64(1),
+            )
+            .unwrap();
+
+            assert_eq!(folds.len(), splits);
+            let sizes: Vec<usize> = folds.iter().map(Vec::len).collect();
+            assert!(sizes.iter().max().unwrap() - sizes.iter().min().unwrap() <= 1);
+
+            let mut all: Vec<usize> = folds.into_iter().flatten().collect();
+            all.sort_unstable();
+            assert_eq!(all, (0..items).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn needs_an_item_for_every_fold() {
+        let mut rng = StdRng::seed_from_u64(1);
+
+        assert!(split_for_crossvalidation_with_rng(vec![1, 2, 3], 0, &mut rng).is_err());
+        assert!(split_for_crossvalidation_with_rng(vec![1, 2, 3], 4, &mut rng).is_err());
+        assert!(split_for_crossvalidation_with_rng(Vec::<u8>::new(), 1, &mut rng).is_err());
+    }
+
+    #[test]
+    fn reports_files_it_cant_open() {
+        assert!(read_lines("missing.data", UTF_8).is_err());
+        assert!(stream_input("missing.data").is_err());
+        assert!(read_input("missing.data")
+            .unwrap_err()
+            .starts_with("Couldn't open missing.data"));
+        assert!(
+            read_input_mmap("missing.data", &mut RowReader::new(OnError::Fail))
+                .unwrap_err()
+                .starts_with("Couldn't open missing.data")
+        );
+    }
+
+    #[test]
+    fn samples_the_same_rows_for_a_seed() {
+        let draws = |seed| {
+            let mut sampler = Sampler::new(0.25, seed);
+            (0..1000).map(|_| sampler.keep()).collect::<Vec<_>>()
+        };
+        let kept = draws(1).iter().filter(|&&keep| keep).count();
+
+        assert_eq!(draws(1), draws(1));
+        assert_ne!(draws(1), draws(2));
+        assert!((200..300).contains(&kept), "{}", kept);
+    }
+
+    #[test]
+    fn maps_the_rows_read_input_reads() {
+        let rows = read_input("house-votes-84.data").unwrap();
+        let mapped =
+            read_input_mmap("house-votes-84.data", &mut RowReader::new(OnError::Fail)).unwrap();
+
+        assert_eq!(mapped.len(), rows.len());
+        for (i, ((line, row), expected)) in mapped.iter().zip(&rows).enumerate() {
+            assert_eq!(*line, i + 1);
+            assert_eq!(row, expected);
+        }
+
+        // With a byte order mark and Windows line endings
+        let filename = std::env::temp_dir().join(format!("mmap-{}.data", std::process::id()));
+        fs::write(&filename, format!("\u{feff}{}\r\n{}\r\n", LINE, LINE)).unwrap();
+        let mapped = read_input_mmap(
+            filename.to_str().unwrap(),
+            &mut RowReader::new(OnError::Fail),
+        )
+        .unwrap();
+        assert_eq!(mapped.len(), 2);
+        assert_eq!(mapped[1].1.to_string(), LINE);
+        fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn parses_the_votes_of_a_record() {
+        let votes = &LINE["democrat,".len()..];
+        assert_eq!(
+            parse_attributes(votes).unwrap(),
+            try_parse_row(LINE).unwrap().attributes
+        );
+        assert_eq!(
+            parse_attributes(&votes.replace(',', " ,")).unwrap(),
+            try_parse_row(LINE).unwrap().attributes
+        );
+
+        assert_eq!(
+            parse_attributes("y,n").unwrap_err(),
+            "Expected 16 votes, got 2"
+        );
+        assert_eq!(
+            parse_attributes(&votes.replacen('y', "yes", 1)).unwrap_err(),
+            "Unknown vote 'yes'"
+        );
+    }
+
+    #[test]
+    fn splits_every_class_by_the_test_size() {
+        let rows: Vec<Row> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let count = |rows: &[Row], class| rows.iter().filter(|row| row.class == class).count();
+        let in_order = |part: &[Row]| {
+            let mut rest = rows.iter();
+            part.iter().all(|row| rest.any(|x| x == row))
+        };
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let (train, test) = train_test_split(rows.clone(), 0.2, true, &mut rng);
+        assert_eq!((train.len(), test.len()), (348, 87));
+        // 20% of the 267 democrats and the 168 republicans
+        assert_eq!(count(&test, Class::Democrat), 53);
+        assert_eq!(count(&test, Class::Republican), 34);
+        assert!(in_order(&train) && in_order(&test));
+
+        let (train, test) = train_test_split(rows.clone(), 0.2, false, &mut rng);
+        assert_eq!((train.len(), test.len()), (348, 87));
+    }
+
+    #[test]
+    fn decodes_lines_without_the_byte_order_mark() {
+        let filename = std::env::temp_dir().join(format!("latin1-{}.data", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        fs::write(filename, b"\xEF\xBB\xBFr\xE9publicain\r\nna\xEFve\n").unwrap();
+        let read = |label| -> Vec<String> {
+            read_lines(filename, encoding_for_label(label).unwrap())
+                .unwrap()
+                .map(Result::unwrap)
+                .collect()
+        };
+
+        assert_eq!(read("latin1"), ["républicain", "naïve"]);
+        // Bytes that aren't valid UTF-8 are replaced
+        assert_eq!(read("utf-8"), ["r\u{fffd}publicain", "na\u{fffd}ve"]);
+        fs::remove_file(filename).unwrap();
+
+        assert_eq!(
+            encoding_for_label("utf-16").unwrap_err(),
+            "UTF-16LE files can't be read"
+        );
+        assert_eq!(
+            encoding_for_label("klingon").unwrap_err(),
+            "Unknown encoding 'klingon'"
+        );
+    }
+}
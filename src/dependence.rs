@@ -0,0 +1,77 @@
+use serde::Serialize;
+
+use crate::counts::JointCounts;
+use crate::data::{Row, ATTRIBUTES_COUNT, CHOICES, CLASSES_COUNT};
+use crate::significance::chi_squared_p_value;
+
+// How far two attributes are from being independent given the class, which
+// naive Bayes assumes they are
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PairDependence {
+    pub first: usize,
+    pub second: usize,
+    // I(first; second | class) in bits, 0 when they're independent
+    pub mutual_information: f64,
+    // G-test of independence within every class, summed over the classes
+    pub g: f64,
+    pub degrees_of_freedom: usize,
+    pub p_value: f64,
+}
+
+// Every pair of attributes, the most dependent first. The G statistic is
+// 2 N ln(2) times the mutual information in bits, and is chi-squared with
+// (choices - 1)^2 degrees of freedom per class under independence.
+pub fn attribute_dependence(rows: &[Row]) -> Vec<PairDependence> {
+    let counts: JointCounts = rows.iter().collect();
+    let degrees_of_freedom = (CHOICES.len() - 1).pow(2) * CLASSES_COUNT;
+    let mut res: Vec<PairDependence> = (0..ATTRIBUTES_COUNT)
+        .flat_map(|first| (first + 1..ATTRIBUTES_COUNT).map(move |second| (first, second)))
+        .map(|(first, second)| {
+            let mutual_information = counts.conditional_mutual_information(first, second);
+            let g = 2.0 * rows.len() as f64 * std::f64::consts::LN_2 * mutual_information;
+
+            PairDependence {
+                first,
+                second,
+                mutual_information,
+                g,
+                degrees_of_freedom,
+                p_value: chi_squared_p_value(g, degrees_of_freedom as f64),
+            }
+        })
+        .collect();
+
+    res.sort_by(|a, b| b.mutual_information.total_cmp(&a.mutual_information));
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::try_parse_row;
+
+    #[test]
+    fn puts_copied_attributes_first() {
+        // With the vote on the budget resolution copied into the first attribute
+        let rows: Vec<Row> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| {
+                let mut row = try_parse_row(line).unwrap();
+                row.attributes[0] = row.attributes[2];
+                row
+            })
+            .collect();
+        let pairs = attribute_dependence(&rows);
+
+        assert_eq!(pairs.len(), ATTRIBUTES_COUNT * (ATTRIBUTES_COUNT - 1) / 2);
+        assert_eq!((pairs[0].first, pairs[0].second), (0, 2));
+        assert_eq!(pairs[0].degrees_of_freedom, 8);
+        assert!(pairs[0].p_value < 1e-6);
+        assert!(pairs
+            .windows(2)
+            .all(|x| x[0].mutual_information >= x[1].mutual_information));
+        assert!(pairs
+            .iter()
+            .all(|pair| pair.g >= 0.0 && pair.p_value <= 1.0));
+    }
+}
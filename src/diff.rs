@@ -0,0 +1,155 @@
+use serde::Serialize;
+
+use crate::data::{ATTRIBUTES_COUNT, ATTRIBUTE_NAMES, CHOICES, CLASSES};
+use crate::model::Model;
+use crate::output::SCHEMA_VERSION;
+
+// How a model changed after retraining, e.g. on newer data. Probabilities
+// are compared as the models would use them, so a change in smoothing shows
+// up too.
+#[derive(Debug, Serialize)]
+pub struct ModelDiff {
+    pub schema_version: u32,
+    pub version_before: u32,
+    pub version_after: u32,
+    pub rows_before: u32,
+    pub rows_after: u32,
+    pub priors: Vec<PriorShift>,
+    // The largest shift of every attribute, largest first
+    pub attributes: Vec<AttributeShift>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PriorShift {
+    pub class: &'static str,
+    pub before: f64,
+    pub after: f64,
+}
+
+// Of P(choice | class)
+#[derive(Debug, Serialize)]
+pub struct AttributeShift {
+    pub attribute: &'static str,
+    pub class: &'static str,
+    pub choice: &'static str,
+    pub before: f64,
+    pub after: f64,
+}
+
+impl PriorShift {
+    pub fn change(&self) -> f64 {
+        self.after - self.before
+    }
+}
+
+impl AttributeShift {
+    pub fn change(&self) -> f64 {
+        self.after - self.before
+    }
+}
+
+impl ModelDiff {
+    pub fn largest_prior_shift(&self) -> Option<&PriorShift> {
+        self.priors
+            .iter()
+            .max_by(|a, b| a.change().abs().total_cmp(&b.change().abs()))
+    }
+
+    pub fn largest_attribute_shift(&self) -> Option<&AttributeShift> {
+        self.attributes.first()
+    }
+}
+
+pub fn diff(before: &Model, after: &Model) -> ModelDiff {
+    let priors = CLASSES
+        .iter()
+        .map(|&class| PriorShift {
+            class: class.name(),
+            before: before.prior(class),
+            after: after.prior(class),
+        })
+        .collect();
+
+    let mut attributes: Vec<AttributeShift> = (0..ATTRIBUTES_COUNT)
+        .map(|attribute| {
+            CLASSES
+                .iter()
+                .flat_map(|&class| CHOICES.iter().map(move |&choice| (class, choice)))
+                .map(|(class, choice)| AttributeShift {
+                    attribute: ATTRIBUTE_NAMES[attribute],
+                    class: class.name(),
+                    choice: choice.name(),
+                    before: before.conditional_probability(class, attribute, choice),
+                    after: after.conditional_probability(class, attribute, choice),
+                })
+                .max_by(|a, b| a.change().abs().total_cmp(&b.change().abs()))
+                .unwrap()
+        })
+        .collect();
+
+    attributes.sort_by(|a, b| b.change().abs().total_cmp(&a.change().abs()));
+
+    ModelDiff {
+        schema_version: SCHEMA_VERSION,
+        version_before: before.version(),
+        version_after: after.version(),
+        rows_before: before.rows_count(),
+        rows_after: after.rows_count(),
+        priors,
+        attributes,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{try_parse_row, Class, Row};
+
+    fn house_votes() -> Vec<Row> {
+        include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn finds_nothing_between_the_same_models() {
+        let model = Model::from_rows(house_votes());
+        let diff = diff(&model, &model.clone());
+
+        assert_eq!(diff.rows_before, diff.rows_after);
+        assert_eq!(diff.attributes.len(), ATTRIBUTES_COUNT);
+        assert!(diff.priors.iter().all(|shift| shift.change() == 0.0));
+        assert!(diff.attributes.iter().all(|shift| shift.change() == 0.0));
+    }
+
+    #[test]
+    fn puts_the_largest_shifts_first() {
+        let rows = house_votes();
+        let democrats = rows.iter().filter(|row| row.class == Class::Democrat);
+        let before = Model::from_rows(rows.iter().cloned());
+        let after = Model::from_rows(rows.iter().cloned().chain(democrats.cloned()));
+        let diff = diff(&before, &after);
+
+        assert_eq!(diff.rows_before, 435);
+        assert_eq!(diff.rows_after, 435 + 267);
+        assert!(diff.priors[Class::Democrat.index()].change() > 0.0);
+        assert!((diff.priors.iter().map(PriorShift::change).sum::<f64>()).abs() < 1e-9);
+        assert!(diff.largest_prior_shift().unwrap().change().abs() > 0.0);
+
+        // The votes of republicans didn't change, and the democrats' are
+        // only smoothed less
+        assert!(diff
+            .attributes
+            .iter()
+            .all(|shift| shift.class == "democrat"));
+        assert!(diff
+            .attributes
+            .windows(2)
+            .all(|pair| pair[0].change().abs() >= pair[1].change().abs()));
+        assert_eq!(
+            diff.largest_attribute_shift().unwrap().attribute,
+            diff.attributes[0].attribute
+        );
+    }
+}
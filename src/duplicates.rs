@@ -0,0 +1,131 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::data::{Choice, Row};
+
+// Rows that share their votes with other rows. Duplicated records bias the
+// counts a model is trained on, and rows that only differ in their class
+// can't both be predicted correctly.
+#[derive(Debug, Clone, Default)]
+pub struct Duplicates {
+    // Indices of the rows with the same votes, in the order they first
+    // appear. Only votes shared by more than one row are included.
+    pub groups: Vec<Vec<usize>>,
+    // Rows that are identical to an earlier row, class included
+    pub exact_count: usize,
+}
+
+impl Duplicates {
+    // Groups whose rows don't all have the same class
+    pub fn conflicts<'a>(&'a self, rows: &'a [Row]) -> impl Iterator<Item = &'a Vec<usize>> {
+        self.groups
+            .iter()
+            .filter(move |group| group.iter().any(|&i| rows[i].class != rows[group[0]].class))
+    }
+}
+
+pub fn find_duplicates(rows: &[Row]) -> Duplicates {
+    let mut group_of: HashMap<&[Choice], usize> = HashMap::new();
+    let mut groups: Vec<Vec<usize>> = vec![];
+
+    for (i, row) in rows.iter().enumerate() {
+        let group = *group_of.entry(&row.attributes).or_insert_with(|| {
+            groups.push(vec![]);
+            groups.len() - 1
+        });
+        groups[group].push(i);
+    }
+
+    groups.retain(|group| group.len() > 1);
+
+    let exact_count = groups
+        .iter()
+        .map(|group| {
+            let mut seen = HashSet::new();
+            group
+                .iter()
+                .filter(|&&i| !seen.insert(rows[i].class))
+                .count()
+        })
+        .sum();
+
+    Duplicates {
+        groups,
+        exact_count,
+    }
+}
+
+// Drops rows identical to one seen before, keeping the first of them. Rows
+// are remembered as they go by, so streams can be filtered too.
+#[derive(Debug, Default)]
+pub struct Dedup {
+    seen: HashSet<Row>,
+}
+
+impl Dedup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_new(&mut self, row: &Row) -> bool {
+        if self.seen.contains(row) {
+            return false;
+        }
+
+        self.seen.insert(row.clone());
+        true
+    }
+}
+
+pub fn dedup(rows: Vec<Row>) -> Vec<Row> {
+    let mut dedup = Dedup::new();
+    rows.into_iter().filter(|row| dedup.is_new(row)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::try_parse_row;
+
+    const VOTES: &str = "y,n,?,y,n,?,y,n,?,y,n,?,y,n,?,y";
+    const OTHER_VOTES: &str = "n,n,?,y,n,?,y,n,?,y,n,?,y,n,?,y";
+
+    fn rows(lines: &[(&str, &str)]) -> Vec<Row> {
+        lines
+            .iter()
+            .map(|(class, votes)| try_parse_row(&format!("{},{}", class, votes)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn finds_duplicates_and_label_conflicts() {
+        let rows = rows(&[
+            ("republican", VOTES),
+            ("democrat", OTHER_VOTES),
+            ("republican", VOTES),
+            ("democrat", VOTES),
+            ("democrat", "y,y,y,y,y,y,y,y,y,y,y,y,y,y,y,y"),
+            ("democrat", OTHER_VOTES),
+        ]);
+        let duplicates = find_duplicates(&rows);
+
+        assert_eq!(duplicates.groups, [vec![0, 2, 3], vec![1, 5]]);
+        // Rows 2 and 5 repeat an earlier row, row 3 only its votes
+        assert_eq!(duplicates.exact_count, 2);
+        let conflicts: Vec<_> = duplicates.conflicts(&rows).collect();
+        assert_eq!(conflicts, [&vec![0, 2, 3]]);
+    }
+
+    #[test]
+    fn keeps_the_first_of_identical_rows() {
+        let rows = rows(&[
+            ("republican", VOTES),
+            ("republican", VOTES),
+            ("democrat", VOTES),
+            ("republican", OTHER_VOTES),
+            ("republican", VOTES),
+        ]);
+        let expected = vec![rows[0].clone(), rows[2].clone(), rows[3].clone()];
+
+        assert_eq!(dedup(rows), expected);
+    }
+}
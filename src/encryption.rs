@@ -0,0 +1,114 @@
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use rand::{thread_rng, RngCore};
+
+// Encrypted files start with this, followed by the salt, the nonce and the
+// ciphertext
+const MAGIC: &[u8] = b"PRNB-ENC1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+// What a file is encrypted with. The key is derived from it with Argon2, so
+// a passphrase doesn't have to be as long as a key.
+pub struct Secret(Vec<u8>);
+
+impl Secret {
+    pub fn passphrase(passphrase: &str) -> Self {
+        Secret(passphrase.as_bytes().to_vec())
+    }
+
+    // The whole file is the secret, e.g. 32 bytes from /dev/urandom
+    pub fn key_file(filename: &str) -> io::Result<Self> {
+        let contents = fs::read(filename)?;
+        if contents.is_empty() {
+            return Err(invalid_data("Key file is empty"));
+        }
+
+        Ok(Secret(contents))
+    }
+
+    fn derive_key(&self, salt: &[u8]) -> io::Result<Key> {
+        let mut key = Key::default();
+        Argon2::default()
+            .hash_password_into(&self.0, salt, &mut key)
+            .map_err(|e| io::Error::other(e.to_string()))?;
+        Ok(key)
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+// XChaCha20-Poly1305 with a random salt and nonce, so encrypting the same
+// data twice gives different files
+pub fn encrypt(plaintext: &[u8], secret: &Secret) -> io::Result<Vec<u8>> {
+    let mut salt = [0; SALT_LEN];
+    let mut nonce = [0; NONCE_LEN];
+    thread_rng().fill_bytes(&mut salt);
+    thread_rng().fill_bytes(&mut nonce);
+
+    let cipher = XChaCha20Poly1305::new(&secret.derive_key(&salt)?);
+    let ciphertext = cipher
+        .encrypt(&XNonce::from(nonce), plaintext)
+        .map_err(|_| io::Error::other("Couldn't encrypt"))?;
+
+    let mut res = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + ciphertext.len());
+    res.extend_from_slice(MAGIC);
+    res.extend_from_slice(&salt);
+    res.extend_from_slice(&nonce);
+    res.extend_from_slice(&ciphertext);
+    Ok(res)
+}
+
+// Fails the same way for a wrong secret and for a tampered file, since the
+// two can't be told apart
+pub fn decrypt(data: &[u8], secret: &Secret) -> io::Result<Vec<u8>> {
+    if !is_encrypted(data) || data.len() < MAGIC.len() + SALT_LEN + NONCE_LEN {
+        return Err(invalid_data("Not an encrypted file"));
+    }
+
+    let (salt, rest) = data[MAGIC.len()..].split_at(SALT_LEN);
+    let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+    let nonce = XNonce::try_from(nonce).unwrap();
+
+    XChaCha20Poly1305::new(&secret.derive_key(salt)?)
+        .decrypt(&nonce, ciphertext)
+        .map_err(|_| invalid_data("Couldn't decrypt, wrong secret or corrupted file"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decrypts_what_it_encrypts() {
+        let secret = Secret::passphrase("correct horse");
+        let encrypted = encrypt(b"counts", &secret).unwrap();
+
+        assert!(is_encrypted(&encrypted));
+        assert_ne!(encrypted, encrypt(b"counts", &secret).unwrap());
+        assert_eq!(decrypt(&encrypted, &secret).unwrap(), b"counts");
+    }
+
+    #[test]
+    fn fails_with_the_wrong_secret_or_a_tampered_file() {
+        let secret = Secret::passphrase("correct horse");
+        let mut encrypted = encrypt(b"counts", &secret).unwrap();
+
+        assert!(decrypt(&encrypted, &Secret::passphrase("battery staple")).is_err());
+        *encrypted.last_mut().unwrap() ^= 1;
+        assert!(decrypt(&encrypted, &secret).is_err());
+        assert!(decrypt(b"counts", &secret).is_err());
+        assert!(decrypt(MAGIC, &secret).is_err());
+    }
+}
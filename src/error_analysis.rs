@@ -0,0 +1,123 @@
+use crate::data::{Choice, Class};
+use crate::evaluation::FoldResult;
+
+// Attributes listed per misclassified row
+pub const TOP_CONTRIBUTIONS: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Misclassification {
+    pub fold: usize,
+    // Where the row is in the data, see FoldResult::testing_indices
+    pub index: usize,
+    pub actual: Class,
+    pub predicted: Class,
+    // Probability of the predicted class minus that of the actual one, so
+    // small margins are near misses
+    pub margin: f64,
+    // The votes that pushed the prediction to the wrong class most
+    pub contributions: Vec<Contribution>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contribution {
+    pub attribute: usize,
+    pub choice: Choice,
+    // log10 of how many times likelier the vote is for the predicted class
+    // than for the actual one
+    pub log_ratio: f64,
+}
+
+// Every misclassified row of every fold, in the order of the data
+pub fn misclassifications(folds: &[FoldResult]) -> Vec<Misclassification> {
+    let mut res: Vec<Misclassification> = folds
+        .iter()
+        .flat_map(|fold| {
+            fold.testing_set
+                .iter()
+                .zip(&fold.testing_indices)
+                .zip(fold.predictions.iter().copied().zip(&fold.probabilities))
+                .filter(|((row, _), (predicted, _))| row.class != *predicted)
+                .map(move |((row, &index), (predicted, probabilities))| {
+                    let model = &fold.model;
+                    let mut contributions: Vec<Contribution> = row
+                        .attributes
+                        .iter()
+                        .enumerate()
+                        .map(|(attribute, &choice)| Contribution {
+                            attribute,
+                            choice,
+                            log_ratio: (model
+                                .conditional_probability(predicted, attribute, choice)
+                                / model.conditional_probability(row.class, attribute, choice))
+                            .log10(),
+                        })
+                        .filter(|contribution| contribution.log_ratio > 0.0)
+                        .collect();
+                    contributions.sort_by(|a, b| b.log_ratio.total_cmp(&a.log_ratio));
+                    contributions.truncate(TOP_CONTRIBUTIONS);
+
+                    Misclassification {
+                        fold: fold.fold,
+                        index,
+                        actual: row.class,
+                        predicted,
+                        margin: probabilities[predicted.index()] - probabilities[row.class.index()],
+                        contributions,
+                    }
+                })
+        })
+        .collect();
+
+    res.sort_by_key(|misclassification| misclassification.index);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{try_parse_row, Row};
+    use crate::evaluation::CrossValidation;
+
+    #[test]
+    fn lists_the_misclassified_rows_with_the_votes_that_misled_them() {
+        let rows: Vec<Row> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let crossvalidation = CrossValidation {
+            seed: Some(1),
+            ..CrossValidation::new(5)
+        };
+        let folds: Vec<_> = crossvalidation.run(rows.clone()).unwrap().collect();
+        let errors: u32 = folds
+            .iter()
+            .map(|fold| {
+                fold.confusion.get(Class::Democrat, Class::Republican)
+                    + fold.confusion.get(Class::Republican, Class::Democrat)
+            })
+            .sum();
+
+        let misclassifications = misclassifications(&folds);
+        assert_eq!(misclassifications.len(), errors as usize);
+        assert!(misclassifications
+            .windows(2)
+            .all(|x| x[0].index < x[1].index));
+
+        for misclassification in &misclassifications {
+            let row = &rows[misclassification.index];
+            assert_eq!(misclassification.actual, row.class);
+            assert_ne!(misclassification.predicted, row.class);
+            assert!(misclassification.margin >= 0.0);
+
+            let contributions = &misclassification.contributions;
+            assert!(contributions.len() <= TOP_CONTRIBUTIONS);
+            assert!(contributions.iter().all(|x| x.log_ratio > 0.0));
+            assert!(contributions
+                .windows(2)
+                .all(|x| x[0].log_ratio >= x[1].log_ratio));
+            for contribution in contributions {
+                assert_eq!(row.attributes[contribution.attribute], contribution.choice);
+            }
+        }
+    }
+}
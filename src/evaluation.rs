@@ -0,0 +1,490 @@
+use std::fmt;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Serialize;
+use tracing::{debug, info_span};
+
+use crate::aode::AodeModel;
+use crate::augment::Augmentation;
+use crate::bernoulli::BernoulliModel;
+use crate::classifier::{Classifier, ModelKind};
+use crate::complement::ComplementModel;
+use crate::data::{split_for_crossvalidation_with_rng, Class, Row, CLASSES, CLASSES_COUNT};
+use crate::joint::{AttributeGroups, JointModel};
+use crate::model::{MissingVotes, Model, PriorMode, Trainer, DEFAULT_SMOOTHING};
+use crate::tan::TanModel;
+
+// Rows are the actual classes and columns the predicted ones, both in the
+// order of CLASSES
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConfusionMatrix {
+    pub counts: [[u32; CLASSES_COUNT]; CLASSES_COUNT],
+}
+
+impl ConfusionMatrix {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, actual: Class, predicted: Class) {
+        self.counts[actual.index()][predicted.index()] += 1;
+    }
+
+    pub fn merge(&mut self, other: &ConfusionMatrix) {
+        for (row, other_row) in self.counts.iter_mut().zip(&other.counts) {
+            for (count, other_count) in row.iter_mut().zip(other_row) {
+                *count += other_count;
+            }
+        }
+    }
+
+    pub fn get(&self, actual: Class, predicted: Class) -> u32 {
+        self.counts[actual.index()][predicted.index()]
+    }
+
+    pub fn total(&self) -> u32 {
+        self.counts.iter().flatten().sum()
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        let correct: u32 = CLASSES.iter().map(|&class| self.get(class, class)).sum();
+        correct as f64 / self.total() as f64
+    }
+}
+
+impl fmt::Display for ConfusionMatrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>12}", "")?;
+        for class in CLASSES.iter() {
+            write!(f, "{:>12}", class.name())?;
+        }
+
+        for &actual in CLASSES.iter() {
+            write!(f, "\n{:>12}", actual.name())?;
+            for &predicted in CLASSES.iter() {
+                write!(f, "{:>12}", self.get(actual, predicted))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub fn confusion_matrix(model: &Model, testing_set: &[Row]) -> ConfusionMatrix {
+    let mut res = ConfusionMatrix::new();
+
+    for (row, prediction) in testing_set.iter().zip(model.predict_batch(testing_set)) {
+        res.add(row.class, prediction);
+    }
+
+    res
+}
+
+pub struct FoldResult {
+    pub fold: usize,
+    pub accuracy: f64,
+    // Model::score of the testing set
+    pub log_likelihood: f64,
+    pub confusion: ConfusionMatrix,
+    // Trained on every fold except this one. With another kind of model,
+    // this is the naive Bayes model of the same rows, and the predictions
+    // are of the other one.
+    pub model: Model,
+    // The rows of this fold, which the model hasn't seen
+    pub testing_set: Vec<Row>,
+    // Of every row of the testing set
+    pub predictions: Vec<Class>,
+    pub probabilities: Vec<[f64; CLASSES_COUNT]>,
+    // Where each row of the testing set is in the data
+    pub testing_indices: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrossValidation {
+    pub splits: usize,
+    // Shuffle with a fixed seed, so runs can be reproduced
+    pub seed: Option<u64>,
+    pub smoothing: f64,
+    pub missing_votes: MissingVotes,
+    pub prior_mode: PriorMode,
+    pub feature_selection: Option<usize>,
+    pub augmentation: Option<Augmentation>,
+    // Train differentially private models with this epsilon, see
+    // Trainer::build_private
+    pub epsilon: Option<f64>,
+    pub model: ModelKind,
+    // Model these attributes together with naive Bayes, see
+    // crate::joint
+    pub joint: Option<AttributeGroups>,
+}
+
+impl Default for CrossValidation {
+    fn default() -> Self {
+        CrossValidation {
+            splits: 10,
+            seed: None,
+            smoothing: DEFAULT_SMOOTHING,
+            missing_votes: MissingVotes::Category,
+            prior_mode: PriorMode::Learned,
+            feature_selection: None,
+            augmentation: None,
+            epsilon: None,
+            model: ModelKind::NaiveBayes,
+            joint: None,
+        }
+    }
+}
+
+impl CrossValidation {
+    // Runs with the default settings, shuffling randomly
+    pub fn new(splits: usize) -> Self {
+        CrossValidation {
+            splits,
+            ..Self::default()
+        }
+    }
+
+    // Fails unless there are at least as many rows as splits
+    pub fn run(&self, data: Vec<Row>) -> Result<impl Iterator<Item = FoldResult>, String> {
+        crossvalidate_with(data, *self, None)
+    }
+
+    // Only these folds of the same split as run, e.g. for a shard of a
+    // search spread over machines. Augmentation and epsilon draw from the
+    // same generator every fold, so they come out differently than with run.
+    pub fn run_folds(
+        &self,
+        data: Vec<Row>,
+        folds: Vec<usize>,
+    ) -> Result<impl Iterator<Item = FoldResult>, String> {
+        crossvalidate_with(data, *self, Some(folds))
+    }
+}
+
+// Shuffles the data into splits and lazily trains and evaluates a model per
+// fold, so callers can report on each fold as soon as it's done
+pub fn crossvalidate(
+    data: Vec<Row>,
+    splits: usize,
+) -> Result<impl Iterator<Item = FoldResult>, String> {
+    CrossValidation::new(splits).run(data)
+}
+
+fn crossvalidate_with(
+    data: Vec<Row>,
+    options: CrossValidation,
+    folds: Option<Vec<usize>>,
+) -> Result<impl Iterator<Item = FoldResult>, String> {
+    let splits = options.splits;
+    // Also used for augmenting the training rows once the data is split
+    let mut rng = match options.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let split_indices = info_span!("split", splits, seed = options.seed).in_scope(|| {
+        let split_indices =
+            split_for_crossvalidation_with_rng((0..data.len()).collect(), splits, &mut rng)?;
+        debug!(sizes = ?split_indices.iter().map(Vec::len).collect::<Vec<_>>(), "Split data");
+        Ok::<_, String>(split_indices)
+    })?;
+
+    let folds = folds.unwrap_or_else(|| (0..split_indices.len()).collect());
+    Ok(folds.into_iter().map(move |fold| {
+        let _span = info_span!("fold", fold).entered();
+        let testing_indices = split_indices[fold].clone();
+        let testing_set: Vec<Row> = testing_indices.iter().map(|&i| data[i].clone()).collect();
+
+        let (model, other) = info_span!("train").in_scope(|| {
+            let mut trainer = Trainer::new()
+                .with_smoothing(options.smoothing)
+                .with_missing_votes(options.missing_votes)
+                .with_prior_mode(options.prior_mode)
+                .with_feature_selection(options.feature_selection);
+            // The other models are trained from every row at once
+            let mut rows =
+                (options.model != ModelKind::NaiveBayes || options.joint.is_some()).then(Vec::new);
+            let mut add = |row: &Row| {
+                trainer.add(row);
+                if let Some(rows) = &mut rows {
+                    rows.push(row.clone());
+                }
+            };
+            split_indices
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| i != fold)
+                .flat_map(|(_, split)| split)
+                .map(|&i| &data[i])
+                .for_each(|row| match &options.augmentation {
+                    Some(augmentation) => augmentation
+                        .augment(row, &mut rng)
+                        .iter()
+                        .for_each(&mut add),
+                    None => add(row),
+                });
+            let model = match options.epsilon {
+                Some(epsilon) => trainer.build_private(epsilon, &mut rng),
+                None => trainer.build(),
+            };
+            let other: Option<Box<dyn Classifier>> =
+                rows.map(|rows| match (&options.joint, options.model) {
+                    (Some(groups), _) => {
+                        Box::new(JointModel::new(&rows, groups, options.smoothing))
+                            as Box<dyn Classifier>
+                    }
+                    (None, ModelKind::NaiveBayes) => unreachable!(),
+                    (None, ModelKind::Tan) => {
+                        Box::new(TanModel::new(&rows.iter().collect(), options.smoothing))
+                    }
+                    (None, ModelKind::Aode) => {
+                        Box::new(AodeModel::new(&rows.iter().collect(), options.smoothing))
+                    }
+                    (None, ModelKind::Bernoulli) => Box::new(BernoulliModel::new(
+                        &rows.iter().collect(),
+                        options.smoothing,
+                    )),
+                    (None, ModelKind::Complement) => Box::new(ComplementModel::new(
+                        &rows.iter().collect(),
+                        options.smoothing,
+                    )),
+                    (None, ModelKind::Auto) => {
+                        unreachable!("--model auto is resolved before cross-validation")
+                    }
+                });
+            debug!(rows = model.rows_count(), "Trained model");
+            (model, other)
+        });
+
+        info_span!("evaluate").in_scope(|| {
+            let classifier: &dyn Classifier = match &other {
+                Some(other) => other.as_ref(),
+                None => &model,
+            };
+            let predictions: Vec<Class> = match &other {
+                Some(other) => testing_set
+                    .iter()
+                    .map(|row| other.classify(&row.attributes))
+                    .collect(),
+                None => model.predict_batch(&testing_set),
+            };
+            let probabilities = testing_set
+                .iter()
+                .map(|row| classifier.probabilities(&row.attributes))
+                .collect();
+
+            let mut confusion = ConfusionMatrix::new();
+            for (row, &prediction) in testing_set.iter().zip(&predictions) {
+                confusion.add(row.class, prediction);
+            }
+            let accuracy = confusion.accuracy();
+            let log_likelihood = classifier.score(&testing_set);
+            debug!(
+                rows = testing_set.len(),
+                accuracy, log_likelihood, "Evaluated model"
+            );
+
+            FoldResult {
+                fold,
+                accuracy,
+                log_likelihood,
+                confusion,
+                model,
+                testing_set,
+                testing_indices,
+                predictions,
+                probabilities,
+            }
+        })
+    }))
+}
+
+// Estimates accuracy by training on bootstrap samples, i.e. as many rows as
+// the dataset drawn with replacement, and testing on the rows each sample
+// left out. Every row is tested many times, which suits datasets too small
+// for k-fold cross-validation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bootstrap {
+    pub samples: usize,
+    pub seed: Option<u64>,
+    pub smoothing: f64,
+}
+
+impl Default for Bootstrap {
+    fn default() -> Self {
+        Bootstrap {
+            samples: 200,
+            seed: None,
+            smoothing: DEFAULT_SMOOTHING,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct BootstrapEstimate {
+    // Samples that left out at least one row
+    pub samples: usize,
+    // Of a model trained and tested on the whole dataset, which is
+    // optimistic
+    pub resubstitution_accuracy: f64,
+    // Over the left out rows of every sample, which is pessimistic since
+    // each sample only has about 63.2% of the distinct rows
+    pub out_of_bag_accuracy: f64,
+    // 0.368 * resubstitution + 0.632 * out of bag
+    pub accuracy_632: f64,
+    // Like .632, but weighs the out of bag accuracy more when the model
+    // overfits, see Efron and Tibshirani (1997)
+    pub accuracy_632_plus: f64,
+}
+
+impl Bootstrap {
+    pub fn run(&self, data: &[Row]) -> BootstrapEstimate {
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+
+        let mut trainer = Trainer::new().with_smoothing(self.smoothing);
+        data.iter().for_each(|row| trainer.add(row));
+        let predictions = trainer.build().predict_batch(data);
+        let resubstitution_error = predictions
+            .iter()
+            .zip(data)
+            .filter(|(&prediction, row)| prediction != row.class)
+            .count() as f64
+            / data.len() as f64;
+
+        let (mut samples, mut tested, mut errors) = (0, 0, 0);
+        for sample in 0..self.samples {
+            let _span = info_span!("sample", sample).entered();
+            let mut in_bag = vec![false; data.len()];
+            let mut trainer = Trainer::new().with_smoothing(self.smoothing);
+
+            for _ in 0..data.len() {
+                let i = rng.gen_range(0..data.len());
+                in_bag[i] = true;
+                trainer.add(&data[i]);
+            }
+
+            let out_of_bag: Vec<Row> = data
+                .iter()
+                .zip(&in_bag)
+                .filter(|(_, &in_bag)| !in_bag)
+                .map(|(row, _)| row.clone())
+                .collect();
+            if out_of_bag.is_empty() {
+                continue;
+            }
+
+            let model = trainer.build();
+            samples += 1;
+            tested += out_of_bag.len();
+            errors += out_of_bag
+                .iter()
+                .zip(model.predict_batch(&out_of_bag))
+                .filter(|(row, prediction)| *prediction != row.class)
+                .count();
+            debug!(rows = out_of_bag.len(), "Tested sample");
+        }
+
+        let out_of_bag_error = if tested == 0 {
+            resubstitution_error
+        } else {
+            errors as f64 / tested as f64
+        };
+
+        // Error rate if the predictions had nothing to do with the rows
+        let no_information_error: f64 = CLASSES
+            .iter()
+            .map(|&class| {
+                let actual = data.iter().filter(|row| row.class == class).count();
+                let predicted = predictions.iter().filter(|&&p| p == class).count();
+                actual as f64 / data.len() as f64 * (1.0 - predicted as f64 / data.len() as f64)
+            })
+            .sum();
+
+        let error_632 = 0.368 * resubstitution_error + 0.632 * out_of_bag_error;
+        let out_of_bag_error_plus = out_of_bag_error.min(no_information_error);
+        let overfitting = if out_of_bag_error_plus > resubstitution_error
+            && no_information_error > resubstitution_error
+        {
+            (out_of_bag_error_plus - resubstitution_error)
+                / (no_information_error - resubstitution_error)
+        } else {
+            0.0
+        };
+        let weight = 0.632 / (1.0 - 0.368 * overfitting);
+        let error_632_plus = (1.0 - weight) * resubstitution_error + weight * out_of_bag_error_plus;
+
+        BootstrapEstimate {
+            samples,
+            resubstitution_accuracy: 1.0 - resubstitution_error,
+            out_of_bag_accuracy: 1.0 - out_of_bag_error,
+            accuracy_632: 1.0 - error_632,
+            accuracy_632_plus: 1.0 - error_632_plus,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::try_parse_row;
+
+    fn house_votes() -> Vec<Row> {
+        include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn fails_with_more_folds_than_rows() {
+        let rows = house_votes()[..3].to_vec();
+
+        assert!(CrossValidation::new(4).run(rows.clone()).is_err());
+        assert!(crossvalidate(rows.clone(), 0).is_err());
+        assert_eq!(CrossValidation::new(3).run(rows).unwrap().count(), 3);
+    }
+
+    #[test]
+    fn only_augments_the_training_rows() {
+        let crossvalidation = CrossValidation {
+            seed: Some(1),
+            augmentation: Some(Augmentation::new(2)),
+            ..CrossValidation::new(5)
+        };
+
+        for fold in crossvalidation.run(house_votes()).unwrap() {
+            assert_eq!(fold.testing_set.len(), 87);
+            assert_eq!(fold.model.rows_count(), 3 * (435 - 87));
+        }
+    }
+
+    #[test]
+    fn estimates_accuracy_between_out_of_bag_and_resubstitution() {
+        let bootstrap = Bootstrap {
+            samples: 20,
+            seed: Some(1),
+            ..Bootstrap::default()
+        };
+        let estimate = bootstrap.run(&house_votes());
+
+        assert_eq!(estimate.samples, 20);
+        assert!(estimate.out_of_bag_accuracy < estimate.resubstitution_accuracy);
+        let expected =
+            0.368 * estimate.resubstitution_accuracy + 0.632 * estimate.out_of_bag_accuracy;
+        assert!((estimate.accuracy_632 - expected).abs() < 1e-12);
+        // .632+ leans towards the out of bag accuracy when the model overfits
+        assert!(estimate.accuracy_632_plus <= estimate.accuracy_632);
+        assert!(estimate.accuracy_632_plus >= estimate.out_of_bag_accuracy);
+        assert_eq!(bootstrap.run(&house_votes()), estimate);
+    }
+
+    #[test]
+    fn scores_every_fold_on_its_testing_rows() {
+        for fold in crossvalidate(house_votes(), 5).unwrap() {
+            assert_eq!(fold.log_likelihood, fold.model.score(&fold.testing_set));
+        }
+    }
+}
@@ -0,0 +1,156 @@
+// C interface for embedding the classifier. The header is generated into
+// include/ by cbindgen when building with the ffi feature.
+
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+use std::ptr;
+
+use crate::data::{ATTRIBUTES_COUNT, CHOICES, CLASSES_COUNT};
+use crate::model::Model;
+
+// Spelled out so that they show up as plain numbers in the header
+pub const NB_ATTRIBUTES_COUNT: usize = 16;
+pub const NB_CLASSES_COUNT: usize = 2;
+
+const _: () = assert!(NB_ATTRIBUTES_COUNT == ATTRIBUTES_COUNT && NB_CLASSES_COUNT == CLASSES_COUNT);
+
+// Vote values accepted by nb_predict
+pub const NB_YES: u8 = 0;
+pub const NB_NO: u8 = 1;
+pub const NB_UNKNOWN: u8 = 2;
+
+// Class indices returned by nb_predict
+pub const NB_REPUBLICAN: c_int = 0;
+pub const NB_DEMOCRAT: c_int = 1;
+
+// Opaque handle to a trained model
+pub struct NbModel {
+    model: Model,
+}
+
+/// Loads a model saved with --save-model. Returns NULL if the path isn't
+/// valid UTF-8 or the model can't be read. The model must be released with
+/// nb_free.
+///
+/// # Safety
+///
+/// `path` must be NULL or a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn nb_model_load(path: *const c_char) -> *mut NbModel {
+    if path.is_null() {
+        return ptr::null_mut();
+    }
+
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(path) => path,
+        Err(_) => return ptr::null_mut(),
+    };
+
+    match Model::load(path) {
+        Ok(model) => Box::into_raw(Box::new(NbModel { model })),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Predicts the class of NB_ATTRIBUTES_COUNT votes, each one of NB_YES,
+/// NB_NO or NB_UNKNOWN. Returns NB_REPUBLICAN or NB_DEMOCRAT, or -1 if an
+/// argument is invalid. When `probabilities` isn't NULL, the posterior
+/// probability of each class is written to it, indexed by class.
+///
+/// # Safety
+///
+/// `model` must come from nb_model_load, `votes` must point to `votes_len`
+/// bytes and `probabilities` must be NULL or point to NB_CLASSES_COUNT
+/// doubles.
+#[no_mangle]
+pub unsafe extern "C" fn nb_predict(
+    model: *const NbModel,
+    votes: *const u8,
+    votes_len: usize,
+    probabilities: *mut f64,
+) -> c_int {
+    if model.is_null() || votes.is_null() || votes_len != ATTRIBUTES_COUNT {
+        return -1;
+    }
+
+    let votes = std::slice::from_raw_parts(votes, votes_len);
+
+    if votes.iter().any(|&x| x as usize >= CHOICES.len()) {
+        return -1;
+    }
+
+    let attributes: Vec<_> = votes.iter().map(|&x| CHOICES[x as usize]).collect();
+    let model = &(*model).model;
+
+    if !probabilities.is_null() {
+        let out = std::slice::from_raw_parts_mut(probabilities, CLASSES_COUNT);
+
+        out.copy_from_slice(&model.probabilities(&attributes));
+    }
+
+    model.classify(&attributes).index() as c_int
+}
+
+/// Releases a model returned by nb_model_load. Passing NULL does nothing.
+///
+/// # Safety
+///
+/// `model` must be NULL or come from nb_model_load and not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn nb_free(model: *mut NbModel) {
+    if !model.is_null() {
+        drop(Box::from_raw(model));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use std::fs;
+
+    use super::*;
+    use crate::data::try_parse_row;
+
+    #[test]
+    fn predicts_through_the_c_interface() {
+        let rows: Vec<_> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let model = Model::from_rows(rows.iter().cloned());
+        let filename = std::env::temp_dir().join(format!("ffi-{}.json", std::process::id()));
+        model.save(filename.to_str().unwrap()).unwrap();
+        let path = CString::new(filename.to_str().unwrap()).unwrap();
+
+        unsafe {
+            let handle = nb_model_load(path.as_ptr());
+            assert!(!handle.is_null());
+
+            let votes: Vec<u8> = rows[0].attributes.iter().map(|x| x.index() as u8).collect();
+            let mut probabilities = [0.0; NB_CLASSES_COUNT];
+            assert_eq!(
+                nb_predict(
+                    handle,
+                    votes.as_ptr(),
+                    votes.len(),
+                    probabilities.as_mut_ptr()
+                ),
+                model.classify(&rows[0].attributes).index() as c_int
+            );
+            assert_eq!(probabilities, model.probabilities(&rows[0].attributes));
+
+            assert_eq!(nb_predict(handle, votes.as_ptr(), 3, ptr::null_mut()), -1);
+            let invalid = [NB_UNKNOWN + 1; NB_ATTRIBUTES_COUNT];
+            assert_eq!(
+                nb_predict(handle, invalid.as_ptr(), invalid.len(), ptr::null_mut()),
+                -1
+            );
+            nb_free(handle);
+
+            let missing = CString::new("missing.json").unwrap();
+            assert!(nb_model_load(missing.as_ptr()).is_null());
+            assert!(nb_model_load(ptr::null()).is_null());
+        }
+        fs::remove_file(filename).unwrap();
+    }
+}
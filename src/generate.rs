@@ -0,0 +1,106 @@
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+
+use crate::data::{Row, ATTRIBUTES_COUNT, CHOICES, CLASSES};
+use crate::model::Model;
+
+// Samples rows the way the model sees the data: the class from the priors
+// and then every vote from P(choice | class). The rows can be shared where
+// the original data can't, but they only keep what the model learned, i.e.
+// the votes are independent given the class.
+pub struct Generator {
+    classes: WeightedIndex<f64>,
+    // [class][attribute]
+    choices: Vec<Vec<WeightedIndex<f64>>>,
+}
+
+impl Generator {
+    pub fn new(model: &Model) -> Self {
+        let classes = WeightedIndex::new(CLASSES.iter().map(|&class| model.prior(class)))
+            .expect("Model has no rows");
+        let choices =
+            CLASSES
+                .iter()
+                .map(|&class| {
+                    (0..ATTRIBUTES_COUNT)
+                        .map(|attribute| {
+                            WeightedIndex::new(CHOICES.iter().map(|&choice| {
+                                model.conditional_probability(class, attribute, choice)
+                            }))
+                            .expect("Model has no probabilities for the class")
+                        })
+                        .collect()
+                })
+                .collect();
+
+        Generator { classes, choices }
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Row {
+        let class = self.classes.sample(rng);
+
+        Row {
+            class: CLASSES[class],
+            attributes: self.choices[class]
+                .iter()
+                .map(|choices| CHOICES[choices.sample(rng)])
+                .collect(),
+        }
+    }
+}
+
+pub fn generate<'a, R: Rng>(
+    model: &Model,
+    rows: usize,
+    rng: &'a mut R,
+) -> impl Iterator<Item = Row> + 'a {
+    let generator = Generator::new(model);
+    (0..rows).map(move |_| generator.sample(rng))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::data::try_parse_row;
+
+    fn model() -> Model {
+        Model::from_rows(
+            include_str!("../house-votes-84.data")
+                .lines()
+                .map(|line| try_parse_row(line).unwrap()),
+        )
+    }
+
+    #[test]
+    fn samples_what_the_model_learned() {
+        let model = model();
+        let rows: Vec<Row> = generate(&model, 20_000, &mut StdRng::seed_from_u64(1)).collect();
+        let relearned = Model::from_rows(rows.iter().cloned());
+
+        assert_eq!(rows.len(), 20_000);
+        for &class in CLASSES.iter() {
+            assert!((relearned.prior(class) - model.prior(class)).abs() < 0.02);
+
+            for attribute in 0..ATTRIBUTES_COUNT {
+                for &choice in CHOICES.iter() {
+                    let expected = model.conditional_probability(class, attribute, choice);
+                    let actual = relearned.conditional_probability(class, attribute, choice);
+                    assert!((actual - expected).abs() < 0.03);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn samples_the_same_rows_for_a_seed() {
+        let model = model();
+        let sample =
+            |seed| -> Vec<Row> { generate(&model, 10, &mut StdRng::seed_from_u64(seed)).collect() };
+
+        assert_eq!(sample(1), sample(1));
+        assert_ne!(sample(1), sample(2));
+    }
+}
@@ -0,0 +1,297 @@
+use std::sync::mpsc;
+
+use party_recogniser_core::{Float, LogTables};
+use wgpu::util::DeviceExt;
+
+// Rows scored by each invocation group of the shader
+const WORKGROUP_SIZE: u32 = 64;
+// Score differences below this, in log10, are rounding of the f32 the
+// shader works in
+pub const TOLERANCE: f64 = 1e-3;
+
+// A thread per row adds up the class weight and the weights of the choices
+// of the row like LogTables::score, into a flat [row][class] table
+const SHADER: &str = "
+struct Dimensions {
+    rows: u32,
+    attributes: u32,
+    choices: u32,
+    classes: u32,
+}
+
+@group(0) @binding(0) var<uniform> dimensions: Dimensions;
+// The class weights, then the [attribute][choice][class] weights
+@group(0) @binding(1) var<storage, read> weights: array<f32>;
+// [row][attribute]
+@group(0) @binding(2) var<storage, read> choices: array<u32>;
+@group(0) @binding(3) var<storage, read_write> scores: array<f32>;
+
+@compute @workgroup_size(64)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let row = id.x;
+    if row >= dimensions.rows {
+        return;
+    }
+
+    for (var c = 0u; c < dimensions.classes; c++) {
+        var score = weights[c];
+        for (var i = 0u; i < dimensions.attributes; i++) {
+            let choice = choices[row * dimensions.attributes + i];
+            score += weights[dimensions.classes + (i * dimensions.choices + choice) * dimensions.classes + c];
+        }
+        scores[row * dimensions.classes + c] = score;
+    }
+}
+";
+
+// Scores batches of rows with a compute shader, for scoring jobs of
+// millions of rows. The weights are uploaded once and the rows in batches
+// as big as the device takes. The shader works in f32, so the scores can be
+// off by about TOLERANCE from LogTables::score_batch.
+pub struct GpuScorer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::ComputePipeline,
+    weights: wgpu::Buffer,
+    attributes_count: usize,
+    choices_count: usize,
+    classes_count: usize,
+    batch_rows: usize,
+    adapter: String,
+}
+
+impl GpuScorer {
+    // Fails when there's no GPU, or no driver for it
+    pub fn new(tables: &LogTables) -> Result<Self, String> {
+        let instance =
+            wgpu::Instance::new(wgpu::InstanceDescriptor::new_without_display_handle_from_env());
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            ..Default::default()
+        }))
+        .map_err(|e| format!("No GPU to score on: {}", e))?;
+        let (device, queue) = pollster::block_on(adapter.request_device(&wgpu::DeviceDescriptor {
+            label: Some("scoring"),
+            required_limits: wgpu::Limits::downlevel_defaults().using_resolution(adapter.limits()),
+            ..Default::default()
+        }))
+        .map_err(|e| format!("Couldn't open the GPU: {}", e))?;
+
+        // Errors of the device would panic otherwise
+        let scope = device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("scoring"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: Some("scoring"),
+            layout: None,
+            module: &module,
+            entry_point: Some("main"),
+            compilation_options: Default::default(),
+            cache: None,
+        });
+        if let Some(e) = pollster::block_on(scope.pop()) {
+            return Err(format!("The GPU can't run the scoring shader: {}", e));
+        }
+
+        let weights: Vec<f32> = tables
+            .class_weights()
+            .iter()
+            .chain(tables.attr_weights())
+            .map(|&weight| weight as f32)
+            .collect();
+        let weights = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("weights"),
+            contents: bytemuck::cast_slice(&weights),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        // The choices of a batch have to fit a binding, and its workgroups
+        // one dispatch
+        let limits = device.limits();
+        let row_bytes = (tables.attributes_count().max(tables.classes_count()) * 4) as u64;
+        let batch_rows = (limits.max_storage_buffer_binding_size / row_bytes)
+            .min(limits.max_compute_workgroups_per_dimension as u64 * WORKGROUP_SIZE as u64)
+            as usize;
+
+        Ok(GpuScorer {
+            device,
+            queue,
+            pipeline,
+            weights,
+            attributes_count: tables.attributes_count(),
+            choices_count: tables.choices_count(),
+            classes_count: tables.classes_count(),
+            batch_rows,
+            adapter: adapter.get_info().name,
+        })
+    }
+
+    // Name of the GPU, for logs
+    pub fn adapter(&self) -> &str {
+        &self.adapter
+    }
+
+    // Flat [row][class] scores like LogTables::score_batch, with `choice`
+    // giving the choice of a row for an attribute
+    pub fn score_batch<R, F>(&self, rows: &[R], choice: F) -> Result<Vec<Float>, String>
+    where
+        F: Fn(&R, usize) -> usize,
+    {
+        let choice = &choice;
+        let mut res = Vec::with_capacity(rows.len() * self.classes_count);
+
+        for batch in rows.chunks(self.batch_rows.max(1)) {
+            let choices: Vec<u32> = batch
+                .iter()
+                .flat_map(|row| (0..self.attributes_count).map(move |i| choice(row, i) as u32))
+                .collect();
+            res.extend(
+                self.score_choices(batch.len(), &choices)?
+                    .into_iter()
+                    .map(|score| score as Float),
+            );
+        }
+
+        Ok(res)
+    }
+
+    fn score_choices(&self, rows: usize, choices: &[u32]) -> Result<Vec<f32>, String> {
+        let scope = self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let dimensions = [
+            rows as u32,
+            self.attributes_count as u32,
+            self.choices_count as u32,
+            self.classes_count as u32,
+        ];
+        let dimensions = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("dimensions"),
+                contents: bytemuck::cast_slice(&dimensions),
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let choices = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("choices"),
+                contents: bytemuck::cast_slice(choices),
+                usage: wgpu::BufferUsages::STORAGE,
+            });
+        let size = (rows * self.classes_count * 4) as u64;
+        let scores = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("scores"),
+            size,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        // Storage buffers can't be mapped, so the scores are copied out
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("scoring"),
+            layout: &self.pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: dimensions.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: self.weights.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: choices.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: scores.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("scoring"),
+            });
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("scoring"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.pipeline);
+            pass.set_bind_group(0, &bind_group, &[]);
+            pass.dispatch_workgroups((rows as u32).div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+        encoder.copy_buffer_to_buffer(&scores, 0, &readback, 0, size);
+        self.queue.submit([encoder.finish()]);
+        if let Some(e) = pollster::block_on(scope.pop()) {
+            return Err(format!("GPU scoring failed: {}", e));
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        readback.map_async(wgpu::MapMode::Read, .., move |result| {
+            let _ = sender.send(result);
+        });
+        self.device
+            .poll(wgpu::PollType::wait_indefinitely())
+            .map_err(|e| format!("GPU scoring failed: {}", e))?;
+        receiver
+            .recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("Couldn't read the GPU scores: {}", e))?;
+
+        let view = readback
+            .get_mapped_range(..)
+            .map_err(|e| format!("Couldn't read the GPU scores: {}", e))?;
+        let res = bytemuck::cast_slice(&view).to_vec();
+        drop(view);
+        readback.unmap();
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{try_parse_row, Row};
+    use crate::model::Trainer;
+
+    #[test]
+    fn scores_like_the_cpu_or_fails_without_a_gpu() {
+        let rows: Vec<Row> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let mut trainer = Trainer::new();
+        rows.iter().for_each(|row| trainer.add(row));
+        let tables = trainer.build().log_tables().clone();
+        let choice = |row: &Row, i: usize| row.attributes[i].index();
+
+        let scorer = match GpuScorer::new(&tables) {
+            Ok(scorer) => scorer,
+            // Scoring falls back to the CPU then
+            Err(e) => {
+                assert!(e.starts_with("No GPU") || e.starts_with("Couldn't open"));
+                return;
+            }
+        };
+        let gpu = scorer.score_batch(&rows, choice).unwrap();
+        let cpu = tables.score_batch(&rows, choice);
+        assert_eq!(gpu.len(), cpu.len());
+        for (gpu, cpu) in gpu.iter().zip(&cpu) {
+            // Only a conversion with the f32 feature
+            #[allow(clippy::useless_conversion)]
+            let difference = f64::from(gpu - cpu);
+            assert!(difference.abs() < TOLERANCE);
+        }
+    }
+}
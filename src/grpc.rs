@@ -0,0 +1,269 @@
+use std::convert::TryFrom;
+use std::io;
+use std::time::Instant;
+
+use tonic::service::interceptor::InterceptedService;
+use tonic::service::Interceptor;
+use tonic::transport::Server;
+use tonic::{Request, Response, Status};
+
+use crate::access::Denied;
+use crate::data::{attribute_index, Choice, ATTRIBUTE_NAMES, CLASSES};
+use crate::server::{bearer_token, shutdown_signal, AppState, PredictResponse, NOT_READY};
+
+pub mod proto {
+    tonic::include_proto!("party_recogniser");
+}
+
+use proto::party_recogniser_server::{PartyRecogniser, PartyRecogniserServer};
+
+pub struct Service {
+    state: AppState,
+}
+
+fn attributes_from_request(request: &proto::PredictRequest) -> Result<Vec<Choice>, Status> {
+    let mut attributes = vec![Choice::Unknown; ATTRIBUTE_NAMES.len()];
+
+    for (name, &vote) in &request.attributes {
+        let idx = attribute_index(name)
+            .ok_or_else(|| Status::invalid_argument(format!("Unknown attribute '{}'", name)))?;
+
+        attributes[idx] = match proto::Vote::try_from(vote) {
+            Ok(proto::Vote::Yes) => Choice::Yes,
+            Ok(proto::Vote::No) => Choice::No,
+            Ok(proto::Vote::Unknown) => Choice::Unknown,
+            Err(_) => return Err(Status::invalid_argument(format!("Unknown vote {}", vote))),
+        };
+    }
+
+    Ok(attributes)
+}
+
+fn not_ready() -> Status {
+    Status::unavailable(NOT_READY)
+}
+
+fn to_proto(response: PredictResponse) -> proto::PredictResponse {
+    proto::PredictResponse {
+        class: response.class.to_string(),
+        probabilities: CLASSES
+            .iter()
+            .map(|class| proto::ClassProbability {
+                class: class.name().to_string(),
+                probability: response.probabilities.get(*class),
+            })
+            .collect(),
+    }
+}
+
+#[tonic::async_trait]
+impl PartyRecogniser for Service {
+    async fn predict(
+        &self,
+        request: Request<proto::PredictRequest>,
+    ) -> Result<Response<proto::PredictResponse>, Status> {
+        let start = Instant::now();
+        let res = attributes_from_request(request.get_ref()).and_then(|attributes| {
+            let response = self.state.predict(&attributes).ok_or_else(not_ready)?;
+            Ok(Response::new(to_proto(response)))
+        });
+
+        self.state.observe("grpc_predict", start, &res);
+        res
+    }
+
+    async fn batch_predict(
+        &self,
+        request: Request<proto::BatchPredictRequest>,
+    ) -> Result<Response<proto::BatchPredictResponse>, Status> {
+        let start = Instant::now();
+        let res = request
+            .get_ref()
+            .records
+            .iter()
+            .map(attributes_from_request)
+            .collect::<Result<Vec<_>, Status>>()
+            .and_then(|records| {
+                self.state
+                    .check_batch_size(records.len())
+                    .map_err(Status::resource_exhausted)?;
+
+                let predictions = self.state.predict_batch(&records).ok_or_else(not_ready)?;
+
+                Ok(Response::new(proto::BatchPredictResponse {
+                    predictions: predictions.into_iter().map(to_proto).collect(),
+                }))
+            });
+
+        self.state.observe("grpc_batch_predict", start, &res);
+        res
+    }
+
+    async fn get_model_info(
+        &self,
+        _request: Request<proto::GetModelInfoRequest>,
+    ) -> Result<Response<proto::ModelInfo>, Status> {
+        let model = self.state.model.read().unwrap();
+        let model = model.as_ref().ok_or_else(not_ready)?;
+
+        Ok(Response::new(proto::ModelInfo {
+            rows: model.rows_count(),
+            classes: CLASSES
+                .iter()
+                .map(|&class| proto::ClassInfo {
+                    class: class.name().to_string(),
+                    rows: model.class_count(class),
+                })
+                .collect(),
+            attributes: ATTRIBUTE_NAMES.iter().map(|x| x.to_string()).collect(),
+        }))
+    }
+}
+
+// Applies the same API keys and rate limit as the HTTP server
+#[derive(Clone)]
+pub struct AccessInterceptor {
+    state: AppState,
+}
+
+impl Interceptor for AccessInterceptor {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let api_key = request
+            .metadata()
+            .get("authorization")
+            .and_then(|value| value.to_str().ok())
+            .and_then(bearer_token);
+        let addr = request.remote_addr().map(|addr| addr.ip());
+
+        match self.state.access.check(api_key, addr) {
+            Ok(()) => Ok(request),
+            Err(denied @ Denied::Unauthorized) => Err(Status::unauthenticated(denied.to_string())),
+            Err(denied @ Denied::RateLimited) => {
+                Err(Status::resource_exhausted(denied.to_string()))
+            }
+        }
+    }
+}
+
+pub fn service(
+    state: AppState,
+) -> InterceptedService<PartyRecogniserServer<Service>, AccessInterceptor> {
+    let interceptor = AccessInterceptor {
+        state: state.clone(),
+    };
+    PartyRecogniserServer::with_interceptor(Service { state }, interceptor)
+}
+
+pub async fn serve(state: AppState, addr: &str) -> io::Result<()> {
+    let addr = addr
+        .parse()
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "Invalid gRPC address"))?;
+
+    tracing::info!("Serving gRPC on {}", addr);
+
+    Server::builder()
+        .add_service(service(state))
+        .serve_with_shutdown(addr, shutdown_signal())
+        .await
+        .map_err(io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::sync::{Arc, RwLock};
+
+    use super::*;
+    use crate::access::AccessControl;
+    use crate::data::{try_parse_row, Class};
+    use crate::metrics::Metrics;
+    use crate::model::Model;
+
+    fn service(model: Option<Model>) -> Service {
+        Service {
+            state: AppState {
+                model: Arc::new(RwLock::new(model)),
+                metrics: Arc::new(Metrics::new()),
+                max_batch_size: 2,
+                access: Arc::new(AccessControl::new(vec![], None)),
+                audit: None,
+                trainable: true,
+            },
+        }
+    }
+
+    fn request(votes: &[(&str, proto::Vote)]) -> proto::PredictRequest {
+        proto::PredictRequest {
+            attributes: votes
+                .iter()
+                .map(|&(name, vote)| (name.to_string(), vote as i32))
+                .collect::<BTreeMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn predicts_like_the_http_server() {
+        let model = Model::from_rows(
+            include_str!("../house-votes-84.data")
+                .lines()
+                .map(|line| try_parse_row(line).unwrap()),
+        );
+        let service = service(Some(model.clone()));
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let mut attributes = vec![Choice::Unknown; ATTRIBUTE_NAMES.len()];
+        attributes[3] = Choice::Yes;
+        let votes = request(&[("physician-fee-freeze", proto::Vote::Yes)]);
+
+        let response = runtime
+            .block_on(service.predict(Request::new(votes.clone())))
+            .unwrap()
+            .into_inner();
+        assert_eq!(response.class, model.classify(&attributes).name());
+        assert_eq!(
+            response.probabilities[Class::Democrat.index()].probability,
+            model.probabilities(&attributes)[Class::Democrat.index()]
+        );
+
+        let batch = |records| {
+            runtime.block_on(
+                service.batch_predict(Request::new(proto::BatchPredictRequest { records })),
+            )
+        };
+        let predictions = batch(vec![votes.clone(), request(&[])])
+            .unwrap()
+            .into_inner();
+        assert_eq!(predictions.predictions.len(), 2);
+        assert_eq!(predictions.predictions[0], response);
+        assert_eq!(
+            batch(vec![votes.clone(); 3]).unwrap_err().code(),
+            tonic::Code::ResourceExhausted
+        );
+
+        let unknown = request(&[("lobbying", proto::Vote::Yes)]);
+        let status = runtime
+            .block_on(service.predict(Request::new(unknown)))
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        let info = runtime
+            .block_on(service.get_model_info(Request::new(proto::GetModelInfoRequest {})))
+            .unwrap()
+            .into_inner();
+        assert_eq!(info.rows, model.rows_count());
+        assert_eq!(info.attributes.len(), ATTRIBUTE_NAMES.len());
+    }
+
+    #[test]
+    fn is_unavailable_without_a_model() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let status = runtime
+            .block_on(service(None).predict(Request::new(request(&[]))))
+            .unwrap_err();
+
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+
+use crate::config::{OutputConfig, RunConfig};
+use crate::manifest;
+
+const HEADER: &str = "timestamp,config_hash,seed,folds,rows,accuracy,log_likelihood,average_precision,expected_calibration_error";
+
+// One line of the history file
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    // RFC 3339 in UTC, of when the run started
+    pub timestamp: String,
+    pub config_hash: String,
+    pub seed: u64,
+    pub folds: usize,
+    pub rows: usize,
+    // Averages over the folds
+    pub accuracy: f64,
+    pub log_likelihood: f64,
+    // Left empty when the run didn't report them
+    pub average_precision: Option<f64>,
+    pub expected_calibration_error: Option<f64>,
+}
+
+// The first 16 hex digits of the SHA-256 of the settings that change what
+// is trained, so runs of the same settings can be grouped. The seed has a
+// column of its own, and the output and what's reported don't change the
+// models.
+pub fn config_hash(config: &RunConfig) -> String {
+    let settings = RunConfig {
+        seed: None,
+        metrics: None,
+        top_attributes: None,
+        output: OutputConfig::default(),
+        ..config.clone()
+    };
+    let mut hash = manifest::hash_bytes(&serde_json::to_vec(&settings).unwrap());
+    hash.truncate(16);
+    hash
+}
+
+// Adds the entry to the end of the file, writing the header first if the
+// file is new. Files with another header aren't appended to, so the columns
+// never get mixed up.
+pub fn append(filename: &str, entry: &HistoryEntry) -> io::Result<()> {
+    let existing = match fs::read_to_string(filename) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(e) => return Err(e),
+    };
+    if let Some(header) = existing.lines().next() {
+        if header != HEADER {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} has other columns than a run history", filename),
+            ));
+        }
+    }
+
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(filename)?;
+    if existing.is_empty() {
+        writeln!(file, "{}", HEADER)?;
+    } else if !existing.ends_with('\n') {
+        writeln!(file)?;
+    }
+
+    let optional = |value: Option<f64>| value.map_or(String::new(), |value| value.to_string());
+    writeln!(
+        file,
+        "{},{},{},{},{},{},{},{},{}",
+        entry.timestamp,
+        entry.config_hash,
+        entry.seed,
+        entry.folds,
+        entry.rows,
+        entry.accuracy,
+        entry.log_likelihood,
+        optional(entry.average_precision),
+        optional(entry.expected_calibration_error)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(seed: u64) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: "2026-10-14T12:00:00Z".to_string(),
+            config_hash: "0123456789abcdef".to_string(),
+            seed,
+            folds: 10,
+            rows: 435,
+            accuracy: 0.9,
+            log_likelihood: -0.25,
+            average_precision: None,
+            expected_calibration_error: Some(0.05),
+        }
+    }
+
+    #[test]
+    fn appends_a_line_per_run_under_one_header() {
+        let filename = std::env::temp_dir().join(format!("history-{}.csv", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        let _ = fs::remove_file(filename);
+
+        append(filename, &entry(1)).unwrap();
+        append(filename, &entry(2)).unwrap();
+        let contents = fs::read_to_string(filename).unwrap();
+        assert_eq!(
+            contents,
+            format!(
+                "{}\n2026-10-14T12:00:00Z,0123456789abcdef,1,10,435,0.9,-0.25,,0.05\n\
+                 2026-10-14T12:00:00Z,0123456789abcdef,2,10,435,0.9,-0.25,,0.05\n",
+                HEADER
+            )
+        );
+
+        fs::write(filename, "name,accuracy\n").unwrap();
+        assert!(append(filename, &entry(3)).is_err());
+        assert_eq!(fs::read_to_string(filename).unwrap(), "name,accuracy\n");
+        fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn hashes_only_what_changes_the_models() {
+        let config = RunConfig {
+            smoothing: Some(0.5),
+            ..RunConfig::default()
+        };
+        let hash = config_hash(&config);
+        assert_eq!(hash.len(), 16);
+
+        let reported = RunConfig {
+            seed: Some(1),
+            top_attributes: Some(3),
+            ..config.clone()
+        };
+        assert_eq!(config_hash(&reported), hash);
+        let retrained = RunConfig {
+            smoothing: Some(1.0),
+            ..config
+        };
+        assert_ne!(config_hash(&retrained), hash);
+    }
+}
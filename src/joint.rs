@@ -0,0 +1,246 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::classifier::{normalize_log_probabilities, Classifier};
+use crate::data::{Choice, Row, ATTRIBUTES_COUNT, CHOICES, CLASSES, CLASSES_COUNT};
+
+// Every choice of a group is a combination of its attributes' choices, so
+// the counts grow threefold with each attribute
+pub const MAX_GROUP_SIZE: usize = 6;
+
+// Attributes modelled together as one feature, numbered from 1 in dataset
+// order and written like 3+4
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct AttributeGroup(pub Vec<usize>);
+
+impl FromStr for AttributeGroup {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split('+')
+            .map(|attribute| {
+                attribute.trim().parse().map_err(|_| {
+                    format!("Expected attributes joined with +, like 3+4, got '{}'", s)
+                })
+            })
+            .collect::<Result<_, _>>()
+            .map(AttributeGroup)
+    }
+}
+
+// Which group each attribute is in, checked so that every attribute is in
+// at most one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AttributeGroups {
+    group_of: [Option<usize>; ATTRIBUTES_COUNT],
+}
+
+impl AttributeGroups {
+    pub fn new(groups: &[AttributeGroup]) -> Result<Self, String> {
+        let mut group_of = [None; ATTRIBUTES_COUNT];
+
+        for (i, AttributeGroup(attributes)) in groups.iter().enumerate() {
+            if !(2..=MAX_GROUP_SIZE).contains(&attributes.len()) {
+                return Err(format!(
+                    "Groups have to be of 2 to {} attributes, got {}",
+                    MAX_GROUP_SIZE,
+                    attributes.len()
+                ));
+            }
+
+            for &attribute in attributes {
+                if !(1..=ATTRIBUTES_COUNT).contains(&attribute) {
+                    return Err(format!(
+                        "Attribute {} isn't between 1 and {}",
+                        attribute, ATTRIBUTES_COUNT
+                    ));
+                }
+                if group_of[attribute - 1].is_some() {
+                    return Err(format!("Attribute {} is in more than one group", attribute));
+                }
+                group_of[attribute - 1] = Some(i);
+            }
+        }
+
+        Ok(AttributeGroups { group_of })
+    }
+
+    // The 0-based attributes of every feature, the groups first and then
+    // the attributes that aren't in one on their own
+    pub fn features(&self) -> Vec<Vec<usize>> {
+        let groups_count = self.group_of.iter().flatten().max().map_or(0, |i| i + 1);
+        let mut features = vec![vec![]; groups_count];
+
+        for (attribute, group) in self.group_of.iter().enumerate() {
+            match group {
+                Some(group) => features[*group].push(attribute),
+                None => features.push(vec![attribute]),
+            }
+        }
+
+        features
+    }
+}
+
+// Naive Bayes over features instead of attributes, where a group of
+// attributes is one feature whose choice is the combination of their votes.
+// The votes of a group can depend on each other in any way, while the
+// features are still assumed to be independent given the class.
+#[derive(Debug, Clone)]
+pub struct JointModel {
+    features: Vec<Vec<usize>>,
+    log_priors: [f64; CLASSES_COUNT],
+    // Natural log of P(combination | class), [feature][class][combination]
+    log_conditionals: Vec<Vec<Vec<f64>>>,
+}
+
+impl JointModel {
+    // Every combination starts with the smoothing count, like every choice
+    // in Trainer. Without groups this is the same as naive Bayes.
+    pub fn new(rows: &[Row], groups: &AttributeGroups, smoothing: f64) -> Self {
+        let features = groups.features();
+        let mut class_counts = [0u32; CLASSES_COUNT];
+        let mut counts: Vec<Vec<Vec<u32>>> = features
+            .iter()
+            .map(|feature| vec![vec![0; combinations(feature)]; CLASSES_COUNT])
+            .collect();
+
+        for row in rows {
+            class_counts[row.class.index()] += 1;
+            for (feature, counts) in features.iter().zip(&mut counts) {
+                counts[row.class.index()][combination(feature, &row.attributes)] += 1;
+            }
+        }
+
+        let total = rows.len() as f64;
+        let log_priors = CLASSES.map(|class| (class_counts[class.index()] as f64 / total).ln());
+        let log_conditionals = counts
+            .iter()
+            .map(|counts| {
+                CLASSES
+                    .iter()
+                    .map(|class| {
+                        let counts = &counts[class.index()];
+                        let class_count = class_counts[class.index()] as f64;
+                        counts
+                            .iter()
+                            .map(|&count| {
+                                ((count as f64 + smoothing)
+                                    / (class_count + smoothing * counts.len() as f64))
+                                    .ln()
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        JointModel {
+            features,
+            log_priors,
+            log_conditionals,
+        }
+    }
+}
+
+impl Classifier for JointModel {
+    fn log_probabilities(&self, attributes: &[Choice]) -> [f64; CLASSES_COUNT] {
+        let scores = CLASSES.map(|class| {
+            self.features
+                .iter()
+                .zip(&self.log_conditionals)
+                .map(|(feature, log_conditionals)| {
+                    log_conditionals[class.index()][combination(feature, attributes)]
+                })
+                .sum::<f64>()
+                + self.log_priors[class.index()]
+        });
+
+        normalize_log_probabilities(scores)
+    }
+}
+
+fn combinations(feature: &[usize]) -> usize {
+    CHOICES.len().pow(feature.len() as u32)
+}
+
+// The votes on the attributes of a feature as one number, in base
+// CHOICES.len()
+fn combination(feature: &[usize], attributes: &[Choice]) -> usize {
+    feature.iter().fold(0, |acc, &attribute| {
+        acc * CHOICES.len() + attributes[attribute].index()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::try_parse_row;
+    use crate::model::{Model, DEFAULT_SMOOTHING};
+
+    fn groups(groups: &[&str]) -> Result<AttributeGroups, String> {
+        let groups: Vec<AttributeGroup> = groups
+            .iter()
+            .map(|group| group.parse())
+            .collect::<Result<_, _>>()?;
+        AttributeGroups::new(&groups)
+    }
+
+    #[test]
+    fn puts_the_groups_before_the_other_attributes() {
+        let features = groups(&["3+4", "16 + 1"]).unwrap().features();
+
+        assert_eq!(features.len(), ATTRIBUTES_COUNT - 2);
+        assert_eq!(features[..3], [vec![2, 3], vec![0, 15], vec![1]]);
+        assert_eq!(features.last().unwrap(), &[14]);
+    }
+
+    #[test]
+    fn rejects_bad_groups() {
+        let error = |group: &[&str]| groups(group).unwrap_err();
+
+        assert_eq!(
+            error(&["3-4"]),
+            "Expected attributes joined with +, like 3+4, got '3-4'"
+        );
+        assert_eq!(
+            error(&["3"]),
+            "Groups have to be of 2 to 6 attributes, got 1"
+        );
+        assert_eq!(error(&["3+17"]), "Attribute 17 isn't between 1 and 16");
+        assert_eq!(
+            error(&["3+4", "4+5"]),
+            "Attribute 4 is in more than one group"
+        );
+    }
+
+    #[test]
+    fn models_the_votes_of_a_group_together() {
+        // Democrats voted the same on the first two bills and republicans
+        // didn't, so neither vote says anything about the class alone
+        let rest = ",n".repeat(ATTRIBUTES_COUNT - 2);
+        let rows: Vec<Row> = [
+            "democrat,y,y",
+            "democrat,n,n",
+            "republican,y,n",
+            "republican,n,y",
+        ]
+        .iter()
+        .map(|votes| try_parse_row(&format!("{}{}", votes, rest)).unwrap())
+        .collect();
+        let joint = JointModel::new(&rows, &groups(&["1+2"]).unwrap(), DEFAULT_SMOOTHING);
+        let ungrouped = JointModel::new(&rows, &groups(&[]).unwrap(), DEFAULT_SMOOTHING);
+        let naive_bayes = Model::from_rows(rows.iter().cloned());
+
+        for row in &rows {
+            assert_eq!(joint.classify(&row.attributes), row.class);
+            let (ungrouped, expected) = (
+                ungrouped.probabilities(&row.attributes),
+                naive_bayes.probabilities(&row.attributes),
+            );
+            assert!((ungrouped[0] - expected[0]).abs() < 1e-6);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+#[cfg(feature = "server")]
+pub mod access;
+pub mod anonymize;
+pub mod aode;
+pub mod attribute_metadata;
+pub mod audit;
+pub mod augment;
+pub mod bench;
+pub mod bernoulli;
+pub mod cache;
+#[cfg(feature = "terminal-plots")]
+pub mod charts;
+pub mod checkpoint;
+pub mod classifier;
+#[cfg(feature = "cloud")]
+pub mod cloud;
+pub mod complement;
+pub mod config;
+#[cfg(feature = "kafka")]
+pub mod consumer;
+pub mod counts;
+pub mod curves;
+pub mod data;
+pub mod dependence;
+pub mod diff;
+pub mod duplicates;
+pub mod encryption;
+pub mod error_analysis;
+pub mod evaluation;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod generate;
+#[cfg(feature = "gpu")]
+pub mod gpu;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+pub mod history;
+pub mod joint;
+pub mod manifest;
+#[cfg(feature = "server")]
+pub mod metrics;
+#[cfg(feature = "mlflow")]
+pub mod mlflow;
+pub mod model;
+pub mod model_card;
+pub mod outliers;
+pub mod output;
+#[cfg(feature = "plots")]
+pub mod plots;
+pub mod privacy;
+pub mod progress;
+pub mod quantized;
+pub mod registry;
+pub mod report;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod shard;
+pub mod significance;
+pub mod signing;
+pub mod stats;
+pub mod summation;
+pub mod tan;
+pub mod threshold;
+#[cfg(feature = "tui")]
+pub mod tui;
+pub mod tune;
+pub mod validate;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "xlsx")]
+pub mod xlsx;
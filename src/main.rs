@@ -1,196 +1,4523 @@
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+#[cfg(feature = "gpu")]
+use std::convert::TryInto;
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, SystemTime};
 
-use std::io::{self, BufRead};
-use std::{collections::HashMap, fs::File};
+use ed25519_dalek::SigningKey;
+use encoding_rs::{Encoding, UTF_8};
+use notify::{Event, RecursiveMode, Watcher};
+use party_recogniser_naive_bayes::anonymize::{self, Anonymizer, Identifiers};
+use party_recogniser_naive_bayes::attribute_metadata::AttributeMetadata;
+use party_recogniser_naive_bayes::audit::AuditLog;
+use party_recogniser_naive_bayes::augment::{Augmentation, DEFAULT_FLIP_PROBABILITY};
+use party_recogniser_naive_bayes::bench;
+use party_recogniser_naive_bayes::cache::{self, Cache};
+#[cfg(feature = "terminal-plots")]
+use party_recogniser_naive_bayes::charts;
+use party_recogniser_naive_bayes::checkpoint::{self, Checkpoint};
+use party_recogniser_naive_bayes::classifier::{ModelKind, AUTO_CANDIDATES};
+#[cfg(feature = "cloud")]
+use party_recogniser_naive_bayes::cloud;
+use party_recogniser_naive_bayes::config::{
+    Metric, OutputConfig, RunConfig, DEFAULT_CHECKPOINT_EVERY, DEFAULT_METRICS,
+    DEFAULT_POSITIVE_CLASS, DEFAULT_TOP_ATTRIBUTES,
+};
+#[cfg(feature = "kafka")]
+use party_recogniser_naive_bayes::consumer;
+use party_recogniser_naive_bayes::curves;
+#[cfg(feature = "embedded-data")]
+use party_recogniser_naive_bayes::data::EMBEDDED_DATA;
+use party_recogniser_naive_bayes::data::{
+    decode_lines, encoding_for_label, parse_attributes, read_input_mmap, read_lines,
+    train_test_split, Choice, Class, OnError, Row, RowReader, Sampler, ATTRIBUTES_COUNT, CLASSES,
+    CLASSES_COUNT,
+};
+use party_recogniser_naive_bayes::dependence;
+use party_recogniser_naive_bayes::diff::{self, ModelDiff};
+use party_recogniser_naive_bayes::duplicates::{self, find_duplicates, Dedup};
+use party_recogniser_naive_bayes::encryption::Secret;
+use party_recogniser_naive_bayes::error_analysis;
+use party_recogniser_naive_bayes::evaluation::{
+    Bootstrap, ConfusionMatrix, CrossValidation, FoldResult,
+};
+use party_recogniser_naive_bayes::generate;
+#[cfg(feature = "gpu")]
+use party_recogniser_naive_bayes::gpu;
+use party_recogniser_naive_bayes::history::{self, HistoryEntry};
+use party_recogniser_naive_bayes::joint::{AttributeGroup, AttributeGroups};
+use party_recogniser_naive_bayes::manifest::{self, DatasetInfo, Manifest, MANIFEST_VERSION};
+#[cfg(feature = "mlflow")]
+use party_recogniser_naive_bayes::mlflow::{self, TrackedRun};
+use party_recogniser_naive_bayes::model::{
+    Float, LoadOptions, MissingVotes, Model, PriorMode, Trainer, DEFAULT_SMOOTHING,
+};
+use party_recogniser_naive_bayes::model_card::{CrossValidationSummary, DataSummary, ModelCard};
+use party_recogniser_naive_bayes::outliers::{find_outliers, DEFAULT_PERCENTILE};
+use party_recogniser_naive_bayes::output::{self, Format, RunReport, SCHEMA_VERSION};
+#[cfg(feature = "plots")]
+use party_recogniser_naive_bayes::plots;
+use party_recogniser_naive_bayes::progress::Progress;
+use party_recogniser_naive_bayes::quantized::QuantizedModel;
+use party_recogniser_naive_bayes::registry::Registry;
+use party_recogniser_naive_bayes::report;
+#[cfg(feature = "server")]
+use party_recogniser_naive_bayes::server;
+use party_recogniser_naive_bayes::shard::{self, Shard, ShardReport};
+use party_recogniser_naive_bayes::significance;
+use party_recogniser_naive_bayes::signing;
+use party_recogniser_naive_bayes::stats::DatasetStats;
+use party_recogniser_naive_bayes::summation;
+use party_recogniser_naive_bayes::threshold::{self, Objective};
+#[cfg(feature = "tui")]
+use party_recogniser_naive_bayes::tui;
+use party_recogniser_naive_bayes::tune::{self, Candidate, Search, TuneResult};
+use party_recogniser_naive_bayes::validate;
+#[cfg(feature = "xlsx")]
+use party_recogniser_naive_bayes::xlsx;
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
+use semver::Version;
+use tracing::{debug, info, info_span, warn, Level};
+use tracing_subscriber::fmt::format::FmtSpan;
 
 const FILENAME: &str = "house-votes-84.data";
-const ATTRIBUTES_COUNT: usize = 16;
-const CROSSVALIDATION_SPLITS: usize = 10;
+const CONFIDENCE_BAR_WIDTH: usize = 30;
+const DEFAULT_MLFLOW_EXPERIMENT: &str = "party-recogniser";
 
-#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
-enum Choice {
-    Yes,
-    No,
-    Unknown,
+#[derive(Parser, Debug)]
+#[command(about = "Recognises party affiliation from congressional votes using naive Bayes")]
+struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Log more details, -vv also logs how long each step took. Without
+    /// it, the level can be set with PARTY_RECOGNISER_LOG, e.g. to warn.
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    verbose: u8,
+
+    #[arg(long, value_enum, default_value_t = LogFormat::Text, global = true)]
+    log_format: LogFormat,
+
+    /// Only log errors and don't show progress bars
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// How results are printed to stdout
+    #[arg(long, value_enum, default_value_t = Format::Text, global = true)]
+    format: Format,
+
+    /// Don't color the output, same as setting NO_COLOR
+    #[arg(long, global = true)]
+    no_color: bool,
+
+    /// Append every prediction to this JSON Lines file
+    #[arg(
+        long,
+        value_name = "FILE",
+        env = "PARTY_RECOGNISER_AUDIT_LOG",
+        global = true
+    )]
+    audit_log: Option<String>,
+
+    /// Write the votes to the audit log instead of only their hash
+    #[arg(long, global = true, requires = "audit_log")]
+    audit_log_votes: bool,
+
+    #[command(flatten)]
+    keys: KeyArgs,
+
+    /// TOML file with readable names and descriptions of the attributes,
+    /// used instead of their short names in reports
+    #[arg(
+        long,
+        value_name = "FILE",
+        env = "PARTY_RECOGNISER_ATTRIBUTE_METADATA",
+        global = true
+    )]
+    attribute_metadata: Option<String>,
+
+    /// TOML or YAML file with the settings of the run. Flags override its
+    /// values.
+    #[arg(long, value_name = "FILE")]
+    config: Option<String>,
+
+    /// Dataset to cross-validate on [default: house-votes-84.data]
+    #[arg(long, value_name = "FILE")]
+    data: Option<String>,
+
+    /// What to do with rows that can't be parsed [default: fail]
+    #[arg(long, value_enum, global = true)]
+    on_error: Option<OnError>,
+
+    /// Encoding of the data, e.g. latin1 [default: utf-8]
+    #[arg(long, value_name = "LABEL", value_parser = parse_encoding, global = true)]
+    encoding: Option<String>,
+
+    /// Columns of the data, numbered from 1, that identify a member and are
+    /// dropped before parsing, e.g. 1 for a name before the class
+    #[arg(long, value_delimiter = ',', value_name = "COLUMNS", global = true)]
+    identifiers: Option<Vec<usize>>,
+
+    /// Sheet to read when the data is an .xlsx file [default: the first]
+    #[arg(long, value_name = "NAME")]
+    sheet: Option<String>,
+
+    /// Drop rows identical to an earlier one before training
+    #[arg(long)]
+    dedup: bool,
+
+    /// Train and test on about this share of the rows, picked with --seed,
+    /// e.g. 0.1 for a quick experiment
+    #[arg(long, value_name = "FRACTION")]
+    sample: Option<f64>,
+
+    /// Suppress the votes on --quasi-identifiers of rows that fewer than K
+    /// rows share them with, for k-anonymity
+    #[arg(long, value_name = "K")]
+    k_anonymity: Option<usize>,
+
+    /// Attributes, numbered from 1, that could single a member out with
+    /// --k-anonymity, e.g. 1,2,3 [default: all]
+    #[arg(long, value_delimiter = ',', value_name = "ATTRIBUTES")]
+    quasi_identifiers: Option<Vec<usize>>,
+
+    /// Number of cross-validation folds [default: 10]
+    #[arg(long)]
+    folds: Option<usize>,
+
+    /// Seed for shuffling the folds, random by default
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Pseudo-count every vote starts with [default: 1]
+    #[arg(long)]
+    smoothing: Option<f64>,
+
+    /// Whether unknown votes are counted as a choice or ignored
+    /// [default: category]
+    #[arg(long, value_enum, value_name = "HOW")]
+    missing_votes: Option<MissingVotes>,
+
+    /// Whether the priors are the shares of the classes in the data or
+    /// uniform [default: learned]
+    #[arg(long, value_enum, value_name = "MODE")]
+    prior_mode: Option<PriorMode>,
+
+    /// Only predict from the K attributes that tell the classes apart best
+    /// [default: all]
+    #[arg(long, value_name = "K")]
+    feature_selection: Option<usize>,
+
+    /// Add this many noisy copies of every training row
+    #[arg(long, value_name = "COPIES")]
+    augment: Option<usize>,
+
+    /// Chance of flipping each vote of a copy from --augment [default: 0.1]
+    #[arg(long, value_name = "P")]
+    flip_probability: Option<f64>,
+
+    /// Add Laplace noise to the counts for epsilon-differential privacy
+    #[arg(long)]
+    epsilon: Option<f64>,
+
+    /// Kind of model to cross-validate, or auto for whichever does best
+    /// [default: nb]
+    #[arg(long, value_enum, value_name = "KIND")]
+    model: Option<ModelKind>,
+
+    /// Attributes to model together as one feature, numbered from 1, e.g.
+    /// 3+4,7+8
+    #[arg(long, value_delimiter = ',', value_name = "GROUPS")]
+    joint: Option<Vec<AttributeGroup>>,
+
+    /// What to report, e.g. accuracy,confusion [default: all]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    metrics: Option<Vec<Metric>>,
+
+    /// Attributes to report per class with --metrics attributes [default: 5]
+    #[arg(long, value_name = "K")]
+    top_attributes: Option<usize>,
+
+    /// The class precision, recall, lift and calibration are about
+    /// [default: republican]
+    #[arg(long, value_enum, value_name = "LABEL")]
+    positive_class: Option<Class>,
+
+    /// Memory-map the dataset instead of reading it line by line
+    #[arg(long)]
+    mmap: bool,
+
+    /// Print the memory footprint of the trained model
+    #[arg(long)]
+    mem_report: bool,
+
+    /// List every misclassified row with the votes that misled the model
+    #[arg(long)]
+    error_analysis: bool,
+
+    /// Train on the whole dataset and save the model as JSON
+    #[arg(long, value_name = "FILE")]
+    save_model: Option<String>,
+
+    /// Save the model and metrics of every fold to this directory
+    #[arg(long, value_name = "DIR")]
+    save_fold_models: Option<String>,
+
+    /// Save and export the model of the most accurate fold instead of
+    /// training on the whole dataset
+    #[arg(long)]
+    best_fold: bool,
+
+    /// After cross-validation, train on the whole dataset and save the
+    /// model, even with --best-fold
+    #[arg(long, value_name = "FILE")]
+    train_final: Option<String>,
+
+    /// Train on the whole dataset and export an i16 fixed-point model
+    #[arg(long, value_name = "FILE")]
+    export_quantized: Option<String>,
+
+    /// Write a JSON manifest with the settings, dataset hash and results
+    #[arg(long, value_name = "FILE")]
+    manifest: Option<String>,
+
+    /// Write the cross-validated precision-recall curve of finding
+    /// the positive class as CSV
+    #[arg(long, value_name = "FILE")]
+    pr_curve: Option<String>,
+
+    /// Write the cross-validated cumulative gain curve of finding
+    /// the positive class as CSV
+    #[arg(long, value_name = "FILE")]
+    gain_curve: Option<String>,
+
+    /// Write the cross-validated calibration curve as CSV
+    #[arg(long, value_name = "FILE")]
+    calibration_curve: Option<String>,
+
+    /// Write SVG plots of the fold accuracies and the cross-validated ROC,
+    /// precision-recall and calibration curves to the directory
+    #[arg(long, value_name = "DIR")]
+    plots: Option<String>,
+
+    /// After the text report, chart the fold accuracies, the ROC curve and
+    /// how the rows spread over the probability of the positive class in
+    /// the terminal
+    #[arg(long)]
+    charts: bool,
+
+    /// Append a line with the time, config hash, seed and metrics of the
+    /// run to this CSV, to follow accuracy across retraining
+    #[arg(long, value_name = "FILE")]
+    history: Option<String>,
+
+    /// Log the settings, metrics and saved model of the run to the MLflow
+    /// tracking server at this URI, with the token in MLFLOW_TRACKING_TOKEN
+    #[arg(long, value_name = "URI")]
+    mlflow: Option<String>,
+
+    /// MLflow experiment to log the run to, created if it doesn't exist
+    /// [default: party-recogniser]
+    #[arg(long, value_name = "NAME")]
+    mlflow_experiment: Option<String>,
+
+    /// Write the card that's saved with the model, of its training data,
+    /// settings, cross-validation and limitations, as Markdown
+    #[arg(long, value_name = "FILE")]
+    model_card: Option<String>,
+
+    /// Write the metrics of the run, like the settings, folds and most
+    /// discriminative attributes, as a standalone HTML page
+    #[arg(long, value_name = "FILE")]
+    report: Option<String>,
+
+    /// Save the counts of training on the whole dataset here every so
+    /// often, and resume from it if the last run was interrupted
+    #[arg(long, value_name = "FILE")]
+    checkpoint: Option<String>,
+
+    /// Lines between checkpoints, e.g. 100k [default: 10k]
+    #[arg(long, value_name = "LINES", value_parser = parse_count)]
+    checkpoint_every: Option<usize>,
+
+    /// Don't save models when the average accuracy is below this
+    #[arg(long, value_name = "ACCURACY")]
+    min_accuracy: Option<f64>,
+
+    /// Follow cross-validation in a terminal dashboard
+    #[cfg(feature = "tui")]
+    #[arg(long)]
+    tui: bool,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+// What to do with votes the model never saw in training, see
+// Model::unseen_votes
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Unseen {
+    Warn,
+    Error,
+}
+
+// Where batches of rows are scored
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+enum Backend {
+    Cpu,
+    // With a compute shader, or on the CPU when there's no GPU
+    Gpu,
+    // On both, failing if they don't agree
+    Check,
+}
+
+// Without a subcommand, the model is evaluated with cross-validation
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Cross-validate and train with the settings of an experiment file,
+    /// like --config
+    Run {
+        /// TOML or YAML file with every setting of the experiment
+        #[arg(long, value_name = "FILE")]
+        spec: String,
+    },
+    /// Cross-validate and save the models like without a subcommand, again
+    /// when the dataset changes or on a schedule
+    Train {
+        /// Run again whenever the dataset changes, replacing the saved models
+        #[arg(long)]
+        watch: bool,
+        /// Run again on this schedule, e.g. 6h, only replacing the saved
+        /// models when the accuracy doesn't drop
+        #[arg(long, value_name = "INTERVAL", value_parser = humantime::parse_duration, conflicts_with = "watch")]
+        every: Option<Duration>,
+    },
+    /// Predict the party of a single record
+    Predict(PredictArgs),
+    /// Load a model once and predict a record for every line of stdin
+    Repl {
+        /// Model saved with --save-model
+        #[arg(long, value_name = "FILE", env = "PARTY_RECOGNISER_MODEL")]
+        model: String,
+
+        /// What to do with votes the model never saw in training
+        #[arg(long, value_enum, default_value = "warn")]
+        unseen: Unseen,
+    },
+    /// Serve predictions of a saved model over HTTP
+    #[cfg(feature = "server")]
+    Serve(server::ServerConfig),
+    /// Classify records from a Kafka topic into another topic
+    #[cfg(feature = "kafka")]
+    Consume(consumer::ConsumerConfig),
+    /// Score saved models on the same test set and test whether each pair
+    /// of them differs
+    Compare(CompareArgs),
+    /// Cross-validate two config files on the same folds and test whether
+    /// their accuracies differ
+    CompareConfigs {
+        first: String,
+        second: String,
+        /// Overrides the folds of both configs
+        #[arg(long, default_value_t = 10)]
+        folds: usize,
+        /// Times to cross-validate, on new folds each time
+        #[arg(long, default_value_t = 10)]
+        repeats: usize,
+        /// Seed for the folds of the first repetition, random by default.
+        /// Overrides the seeds of both configs.
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Cross-validate combinations of settings and keep the best one as a
+    /// config file
+    Tune(TuneArgs),
+    /// Cross-validate a shard of the folds of every candidate of tune, so a
+    /// big search can be spread over machines, or merge the shards
+    Cv(CvArgs),
+    /// Estimate accuracy with the .632+ bootstrap instead of k-fold
+    /// cross-validation, for very small datasets
+    Bootstrap {
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = FILENAME,
+            env = "PARTY_RECOGNISER_DATA"
+        )]
+        data: String,
+        /// Bootstrap samples to train and test
+        #[arg(long, default_value_t = 200)]
+        samples: usize,
+        /// Seed for drawing the samples, random by default
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Pseudo-count every vote starts with
+        #[arg(long, default_value_t = DEFAULT_SMOOTHING)]
+        smoothing: f64,
+    },
+    /// Summarise a dataset before training on it
+    Stats {
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = FILENAME,
+            env = "PARTY_RECOGNISER_DATA"
+        )]
+        data: String,
+    },
+    /// Test which pairs of attributes depend on each other within a party,
+    /// against the assumption of naive Bayes
+    Diagnostics {
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = FILENAME,
+            env = "PARTY_RECOGNISER_DATA"
+        )]
+        data: String,
+        /// Number of pairs to show, the most dependent first
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Check a dataset for malformed lines, conflicting labels and missing
+    /// classes, exiting with an error if anything is found
+    Validate {
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = FILENAME,
+            env = "PARTY_RECOGNISER_DATA"
+        )]
+        data: String,
+        /// Sheet to read when the data is an .xlsx file, the first by default
+        #[arg(long, value_name = "NAME")]
+        sheet: Option<String>,
+    },
+    /// List the rows that are least likely under the model
+    Outliers {
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = FILENAME,
+            env = "PARTY_RECOGNISER_DATA"
+        )]
+        data: String,
+        /// Model saved with --save-model, trained on the data by default
+        #[arg(long, value_name = "FILE")]
+        model: Option<String>,
+        /// Flag the rows at or below this percentile of log likelihood
+        #[arg(long, default_value_t = DEFAULT_PERCENTILE)]
+        percentile: f64,
+    },
+    /// Sample a synthetic dataset from a model
+    Generate {
+        /// Model saved with --save-model
+        #[arg(long, value_name = "FILE", env = "PARTY_RECOGNISER_MODEL")]
+        model: String,
+        #[arg(long, default_value_t = 1000)]
+        rows: usize,
+        /// Seed for sampling, random by default
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Write the rows here instead of stdout
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Split a dataset into PREFIX.train and PREFIX.test files
+    Split {
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = FILENAME,
+            env = "PARTY_RECOGNISER_DATA"
+        )]
+        data: String,
+        /// Share of the rows to test on
+        #[arg(long, default_value_t = 0.2)]
+        test_size: f64,
+        /// Keep the share of every party the same in both files
+        #[arg(long)]
+        stratify: bool,
+        /// Seed for picking the rows, random by default
+        #[arg(long)]
+        seed: Option<u64>,
+        #[arg(long, value_name = "PREFIX", default_value = "votes")]
+        out_prefix: String,
+    },
+    /// Write the dataset without who its members are, with the --identifiers
+    /// columns dropped or hashed and optionally rare votes suppressed
+    Anonymize {
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = FILENAME,
+            env = "PARTY_RECOGNISER_DATA"
+        )]
+        data: String,
+        /// Where to write the rows
+        #[arg(long, value_name = "FILE")]
+        output: String,
+        /// Keep the --identifiers columns as hashes salted with SALT instead
+        /// of dropping them, so rows of the same member can still be linked.
+        /// The salt has to stay secret.
+        #[arg(long, value_name = "SALT", env = "PARTY_RECOGNISER_SALT")]
+        hash_identifiers: Option<String>,
+        /// Suppress the votes on --quasi-identifiers of rows that fewer than
+        /// K rows share them with, like when training
+        #[arg(long, value_name = "K")]
+        k_anonymity: Option<usize>,
+        /// Attributes, numbered from 1, that could single a member out with
+        /// --k-anonymity [default: all]
+        #[arg(long, value_delimiter = ',', value_name = "ATTRIBUTES")]
+        quasi_identifiers: Option<Vec<usize>>,
+    },
+    /// Add labelled rows to a saved model without retraining it, and show
+    /// how it changed
+    Ingest {
+        /// Model saved with --save-model
+        #[arg(long, value_name = "FILE", env = "PARTY_RECOGNISER_MODEL")]
+        model: String,
+        /// The new rows, in the format of the dataset
+        #[arg(long, value_name = "FILE")]
+        data: String,
+        /// Write the updated model here instead of replacing the model
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+        /// Number of attributes to show, the ones that moved most first
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+    },
+    /// Score a saved model on a labelled test set it wasn't trained on
+    Evaluate(EvaluateArgs),
+    /// Pick the probability the positive class needs to be predicted, from
+    /// cross-validated predictions, and store it in the model
+    TuneThreshold {
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = FILENAME,
+            env = "PARTY_RECOGNISER_DATA"
+        )]
+        data: String,
+        /// Model saved with --save-model, its smoothing is cross-validated
+        #[arg(long, value_name = "FILE", env = "PARTY_RECOGNISER_MODEL")]
+        model: String,
+        #[arg(long, value_enum, default_value_t = Objective::F1)]
+        optimize: Objective,
+        /// Class the threshold is for, and that F1 is of
+        #[arg(long, value_enum, value_name = "LABEL", default_value_t = DEFAULT_POSITIVE_CLASS)]
+        positive_class: Class,
+        #[arg(long, default_value_t = 10)]
+        folds: usize,
+        /// Seed for shuffling the folds, random by default
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Distance between the thresholds that are tried
+        #[arg(long, default_value_t = 0.05)]
+        step: f64,
+        /// Write the tuned model here instead of replacing the model
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Measure how fast predictions are
+    Bench {
+        #[command(subcommand)]
+        command: BenchCommand,
+    },
+    /// Manage saved models
+    Model(ModelArgs),
+    /// Manage the datasets downloaded from s3:// and gs:// URIs
+    Cache(CacheArgs),
+    /// Print a shell completion script, e.g. for ~/.bash_completion
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct KeyArgs {
+    /// Encrypt saved models with this passphrase, and decrypt the models
+    /// that are loaded
+    #[arg(
+        long,
+        env = "PARTY_RECOGNISER_MODEL_PASSPHRASE",
+        hide_env_values = true,
+        global = true,
+        conflicts_with = "model_key_file"
+    )]
+    model_passphrase: Option<String>,
+
+    /// Same as --model-passphrase with the contents of a file
+    #[arg(
+        long,
+        value_name = "FILE",
+        env = "PARTY_RECOGNISER_MODEL_KEY_FILE",
+        global = true
+    )]
+    model_key_file: Option<String>,
+
+    /// Sign models saved with --save-model or --export-quantized with this
+    /// key from model keygen
+    #[arg(long, value_name = "FILE", global = true)]
+    signing_key: Option<String>,
+
+    /// Only load models signed with the key this public key belongs to
+    #[arg(
+        long,
+        value_name = "FILE",
+        env = "PARTY_RECOGNISER_VERIFY_KEY",
+        global = true
+    )]
+    verify_key: Option<String>,
+}
+
+impl KeyArgs {
+    fn secret(&self) -> Option<Secret> {
+        if let Some(filename) = &self.model_key_file {
+            return Some(
+                Secret::key_file(filename)
+                    .unwrap_or_else(|e| exit_with_error(&format!("Couldn't read key file: {}", e))),
+            );
+        }
+
+        self.model_passphrase.as_deref().map(Secret::passphrase)
+    }
+
+    fn load_options(&self) -> LoadOptions {
+        LoadOptions {
+            secret: self.secret(),
+            verifying_key: self.verify_key.as_ref().map(|filename| {
+                signing::load_verifying_key(filename).unwrap_or_else(|e| {
+                    exit_with_error(&format!("Couldn't read verifying key: {}", e))
+                })
+            }),
+        }
+    }
+
+    fn signing_key(&self) -> Option<SigningKey> {
+        self.signing_key.as_ref().map(|filename| {
+            signing::load_signing_key(filename)
+                .unwrap_or_else(|e| exit_with_error(&format!("Couldn't read signing key: {}", e)))
+        })
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct ModelArgs {
+    #[command(subcommand)]
+    command: ModelCommand,
+
+    /// Directory of the local model registry
+    #[arg(
+        long,
+        value_name = "DIR",
+        default_value = "models",
+        env = "PARTY_RECOGNISER_REGISTRY",
+        global = true
+    )]
+    registry: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct CacheArgs {
+    #[command(subcommand)]
+    command: CacheCommand,
+
+    /// Directory of the cache [default: the platform's cache directory]
+    #[arg(long, value_name = "DIR", env = cache::DIR_VARIABLE, global = true)]
+    cache_dir: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// List the cached datasets
+    List,
+    /// Remove every cached dataset
+    Clear,
+}
+
+#[derive(Subcommand, Debug)]
+enum ModelCommand {
+    /// Store a saved model in the registry under a name and version
+    Push {
+        /// Model saved with --save-model
+        model: String,
+        #[arg(long)]
+        name: String,
+        /// Semantic version, e.g. 1.2.0
+        #[arg(long)]
+        version: Version,
+    },
+    /// List the models in the registry
+    List,
+    /// Find a model in the registry and print where it's stored
+    Get {
+        name: String,
+        /// Latest version by default
+        #[arg(long)]
+        version: Option<Version>,
+        /// Copy the model here instead
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+    },
+    /// Create an ed25519 key for signing models, and FILE.pub for
+    /// verifying them
+    Keygen {
+        #[arg(long, value_name = "FILE")]
+        output: String,
+    },
+    /// Sign a model file, writing the signature to MODEL.sig
+    Sign {
+        model: String,
+        /// Signing key from model keygen
+        #[arg(long, value_name = "FILE")]
+        key: String,
+    },
+    /// Show what a model learned
+    Inspect { model: String },
+    /// Print the card saved with a model, as Markdown
+    Card { model: String },
+    /// Show how a model changed after retraining
+    Diff {
+        before: String,
+        after: String,
+        /// Number of attributes to show, the ones that moved most first
+        #[arg(long, default_value_t = 5)]
+        top: usize,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BenchCommand {
+    /// Single-row and batch prediction latency and throughput
+    Predict {
+        /// Model saved with --save-model
+        #[arg(long, value_name = "FILE", env = "PARTY_RECOGNISER_MODEL")]
+        model: String,
+        /// Rows to predict, e.g. 100k or 1M
+        #[arg(long, default_value = "1M", value_parser = parse_count)]
+        iterations: usize,
+        #[arg(long, default_value_t = 1000)]
+        batch_size: usize,
+        /// Seed for sampling the rows that are predicted
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+    /// Time loading, splitting and fitting at several dataset sizes
+    Train {
+        #[arg(
+            long,
+            value_name = "FILE",
+            default_value = FILENAME,
+            env = "PARTY_RECOGNISER_DATA"
+        )]
+        data: String,
+        /// Dataset sizes, e.g. 1k,10k,100k
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_value = "1k,10k,100k",
+            value_parser = parse_count
+        )]
+        sizes: Vec<usize>,
+        /// Sample rows from a model trained on the data instead of
+        /// repeating its rows
+        #[arg(long)]
+        synthetic: bool,
+        #[arg(long, default_value_t = 10)]
+        folds: usize,
+        /// Times to run every size, keeping the fastest
+        #[arg(long, default_value_t = 3)]
+        runs: usize,
+        /// Seed for sampling and splitting the rows
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+    },
+}
+
+// Checked up front so a typo fails before any work is done
+fn parse_encoding(label: &str) -> Result<String, String> {
+    encoding_for_label(label).map(|_| label.to_string())
+}
+
+// Counts like 1000, 10k or 1M
+fn parse_count(value: &str) -> Result<usize, String> {
+    let (digits, multiplier) = match value.char_indices().last() {
+        Some((i, 'k' | 'K')) => (&value[..i], 1_000),
+        Some((i, 'm' | 'M')) => (&value[..i], 1_000_000),
+        _ => (value, 1),
+    };
+
+    match digits.parse::<usize>() {
+        Ok(count) if count > 0 => Ok(count * multiplier),
+        _ => Err(format!(
+            "Expected a positive count like 1000, 10k or 1M, got {}",
+            value
+        )),
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct PredictArgs {
+    /// Model saved with --save-model
+    #[arg(long, value_name = "FILE", env = "PARTY_RECOGNISER_MODEL")]
+    model: String,
+
+    /// The 16 votes in dataset order, e.g. y,n,?,y,...
+    #[arg(long, required_unless_present = "interactive")]
+    votes: Option<String>,
+
+    /// Ask for each vote one by one instead
+    #[arg(long, conflicts_with = "votes")]
+    interactive: bool,
+
+    /// What to do with votes the model never saw in training
+    #[arg(long, value_enum, default_value = "warn")]
+    unseen: Unseen,
+}
+
+#[derive(clap::Args, Debug)]
+struct TuneArgs {
+    #[command(flatten)]
+    search: SearchArgs,
+
+    /// Write the best settings here as a config for --config
+    #[arg(long, value_name = "FILE")]
+    output: Option<String>,
+}
+
+// The candidates of tune and cv, and the folds they're tested on
+#[derive(clap::Args, Debug)]
+struct SearchArgs {
+    #[arg(
+        long,
+        value_name = "FILE",
+        default_value = FILENAME,
+        env = "PARTY_RECOGNISER_DATA"
+    )]
+    data: String,
+
+    #[arg(long, default_value_t = 10)]
+    folds: usize,
+
+    /// Seed for the folds every candidate is tested on, and for drawing
+    /// candidates, random by default
+    #[arg(long)]
+    seed: Option<u64>,
+
+    #[arg(long, value_enum, default_value = "grid")]
+    search: Search,
+
+    /// Candidates to draw with --search random
+    #[arg(long, default_value_t = 20)]
+    trials: usize,
+
+    /// Smoothing values, or their range with --search random
+    #[arg(long, value_delimiter = ',', default_values_t = [0.01, 0.1, 0.5, 1.0, 2.0, 5.0])]
+    smoothing: Vec<f64>,
+
+    #[arg(long, value_enum, value_delimiter = ',', default_values = ["category", "ignore"])]
+    missing_votes: Vec<MissingVotes>,
+
+    #[arg(long, value_enum, value_delimiter = ',', default_values = ["learned", "uniform"])]
+    prior_mode: Vec<PriorMode>,
+
+    /// Numbers of attributes to predict from, where 16 is all of them
+    #[arg(long, value_delimiter = ',', value_name = "K", default_values_t = [4, 8, 16])]
+    feature_selection: Vec<usize>,
+
+    #[arg(long, value_enum, value_delimiter = ',', default_values = ["nb", "tan", "aode"])]
+    models: Vec<ModelKind>,
+
+    /// Candidates cross-validated at once [default: the number of CPUs]
+    #[arg(long)]
+    threads: Option<usize>,
+}
+
+#[derive(clap::Args, Debug)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+struct CvArgs {
+    #[command(subcommand)]
+    command: Option<CvCommand>,
+
+    #[command(flatten)]
+    search: SearchArgs,
+
+    /// Which of how many shards of the folds to cross-validate, e.g. 3/10
+    #[arg(long, required = true)]
+    shard: Option<Shard>,
+
+    /// Write the scores of the shard here, for cv merge
+    #[arg(long, value_name = "FILE", required = true)]
+    output: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum CvCommand {
+    /// Rank the candidates by the scores of every shard, like tune
+    Merge {
+        /// Scores written by every shard with --output
+        #[arg(required = true)]
+        files: Vec<String>,
+
+        /// Write the best settings here as a config for --config
+        #[arg(long, value_name = "FILE")]
+        output: Option<String>,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct CompareArgs {
+    /// Models saved with --save-model
+    #[arg(long, value_name = "FILE", value_delimiter = ',', required = true)]
+    models: Vec<String>,
+
+    /// Labelled rows in the format of the dataset
+    #[arg(long, value_name = "FILE")]
+    test: String,
+
+    /// The class average precision, ROC and calibration are about
+    #[arg(long, value_enum, value_name = "LABEL", default_value_t = DEFAULT_POSITIVE_CLASS)]
+    positive_class: Class,
+}
+
+#[derive(clap::Args, Debug)]
+struct EvaluateArgs {
+    /// Model saved with --save-model
+    #[arg(long, value_name = "FILE", env = "PARTY_RECOGNISER_MODEL")]
+    model: String,
+
+    /// Labelled rows in the format of the dataset
+    #[arg(long, value_name = "FILE")]
+    test: String,
+
+    /// What to report, e.g. accuracy,confusion [default: all]
+    #[arg(long, value_enum, value_delimiter = ',')]
+    metrics: Option<Vec<Metric>>,
+
+    /// Attributes to report per class with --metrics attributes
+    #[arg(long, value_name = "K", default_value_t = DEFAULT_TOP_ATTRIBUTES)]
+    top_attributes: usize,
+
+    /// The class precision, recall, lift and calibration are about
+    #[arg(long, value_enum, value_name = "LABEL", default_value_t = DEFAULT_POSITIVE_CLASS)]
+    positive_class: Class,
+
+    /// Score the rows on the GPU for millions of them, falling back to the
+    /// CPU without one, or on both to check that they agree
+    #[arg(long, value_enum, default_value = "cpu")]
+    backend: Backend,
+}
+
+fn main() {
+    let args = Args::parse();
+    let color = output::use_color(args.no_color);
+    init_logging(
+        &args,
+        output::color_allowed(args.no_color) && io::stderr().is_terminal(),
+    );
+
+    let load_options = args.keys.load_options();
+    let read_options = ReadOptions::new(
+        args.on_error,
+        args.encoding.as_deref(),
+        args.identifiers.as_deref(),
+    );
+    let audit = args.audit_log.as_ref().map(|filename| {
+        AuditLog::open(filename, args.audit_log_votes)
+            .unwrap_or_else(|e| exit_with_error(&format!("Couldn't open audit log: {}", e)))
+    });
+
+    let metadata = match &args.attribute_metadata {
+        Some(filename) => AttributeMetadata::load(filename).unwrap_or_else(|e| {
+            exit_with_error(&format!("Couldn't read attribute metadata: {}", e))
+        }),
+        None => AttributeMetadata::default(),
+    };
+
+    match &args.command {
+        Some(Command::Predict(predict_args)) => predict(
+            predict_args,
+            &metadata,
+            &load_options,
+            audit.as_ref(),
+            args.format,
+            color,
+        ),
+        Some(Command::Repl { model, unseen }) => {
+            repl(model, &metadata, *unseen, &load_options, audit.as_ref())
+        }
+        #[cfg(feature = "server")]
+        Some(Command::Serve(config)) => server::serve(config, &load_options, audit)
+            .unwrap_or_else(|e| exit_with_error(&format!("Couldn't run server: {}", e))),
+        #[cfg(feature = "kafka")]
+        Some(Command::Consume(config)) => consumer::consume(config, &load_options, audit.as_ref())
+            .unwrap_or_else(|e| exit_with_error(&e)),
+        Some(Command::Compare(compare_args)) => compare_models(
+            compare_args,
+            &load_options,
+            &read_options,
+            args.format,
+            color,
+        ),
+        Some(Command::CompareConfigs {
+            first,
+            second,
+            folds,
+            repeats,
+            seed,
+        }) => compare_configs(
+            &[first, second],
+            *folds,
+            *repeats,
+            seed.unwrap_or_else(|| thread_rng().gen()),
+            args.format,
+            color,
+        ),
+        Some(Command::Tune(tune_args)) => {
+            tune_settings(tune_args, &read_options, args.format, color)
+        }
+        Some(Command::Cv(cv_args)) => match &cv_args.command {
+            Some(CvCommand::Merge { files, output }) => {
+                merge_shards(files, output.as_deref(), args.format, color)
+            }
+            None => run_shard(cv_args, &read_options),
+        },
+        Some(Command::Bootstrap {
+            data,
+            samples,
+            seed,
+            smoothing,
+        }) => bootstrap(
+            data,
+            &read_options,
+            Bootstrap {
+                samples: *samples,
+                seed: *seed,
+                smoothing: *smoothing,
+            },
+            args.format,
+            color,
+        ),
+        Some(Command::Stats { data }) => stats(data, &read_options, &metadata, args.format, color),
+        Some(Command::Diagnostics { data, top }) => {
+            diagnostics(data, &read_options, *top, &metadata, args.format, color)
+        }
+        Some(Command::Validate { data, sheet }) => {
+            validate(data, &read_options, sheet.as_deref(), args.format, color)
+        }
+        Some(Command::Outliers {
+            data,
+            model,
+            percentile,
+        }) => outliers(
+            data,
+            model.as_deref(),
+            &load_options,
+            &read_options,
+            *percentile,
+            args.format,
+            color,
+        ),
+        Some(Command::Generate {
+            model,
+            rows,
+            seed,
+            output,
+        }) => generate(model, &load_options, *rows, *seed, output.as_deref()),
+        Some(Command::Split {
+            data,
+            test_size,
+            stratify,
+            seed,
+            out_prefix,
+        }) => split(
+            data,
+            &read_options,
+            *test_size,
+            *stratify,
+            *seed,
+            out_prefix,
+        ),
+        Some(Command::Anonymize {
+            data,
+            output,
+            hash_identifiers,
+            k_anonymity,
+            quasi_identifiers,
+        }) => anonymize_data(
+            data,
+            &read_options,
+            output,
+            hash_identifiers.as_deref(),
+            *k_anonymity,
+            quasi_identifiers.clone(),
+        ),
+        Some(Command::Evaluate(evaluate_args)) => evaluate(
+            evaluate_args,
+            &load_options,
+            &read_options,
+            &metadata,
+            args.format,
+            color,
+        ),
+        Some(Command::Ingest {
+            model,
+            data,
+            output,
+            top,
+        }) => ingest(
+            &args.keys,
+            model,
+            data,
+            &read_options,
+            output.as_deref().unwrap_or(model),
+            *top,
+            args.format,
+            color,
+        ),
+        Some(Command::TuneThreshold {
+            data,
+            model,
+            optimize,
+            positive_class,
+            folds,
+            seed,
+            step,
+            output,
+        }) => tune_threshold(
+            &args.keys,
+            data,
+            &read_options,
+            model,
+            *optimize,
+            *positive_class,
+            CrossValidation {
+                splits: *folds,
+                seed: *seed,
+                ..CrossValidation::default()
+            },
+            *step,
+            output.as_deref().unwrap_or(model),
+            args.format,
+            color,
+        ),
+        Some(Command::Bench {
+            command:
+                BenchCommand::Predict {
+                    model,
+                    iterations,
+                    batch_size,
+                    seed,
+                },
+        }) => bench_predict(
+            model,
+            &load_options,
+            *iterations,
+            *batch_size,
+            *seed,
+            args.format,
+            color,
+        ),
+        Some(Command::Bench {
+            command:
+                BenchCommand::Train {
+                    data,
+                    sizes,
+                    synthetic,
+                    folds,
+                    runs,
+                    seed,
+                },
+        }) => bench_train(
+            data,
+            &read_options,
+            sizes,
+            *synthetic,
+            *folds,
+            *runs,
+            *seed,
+            args.format,
+            color,
+        ),
+        Some(Command::Cache(cache_args)) => cache(cache_args, args.format, color),
+        Some(Command::Model(model_args)) => {
+            model(model_args, &load_options, &metadata, args.format, color)
+        }
+        Some(Command::Completions { shell }) => {
+            let mut command = Args::command();
+            let name = command.get_name().to_string();
+            clap_complete::generate(*shell, &mut command, name, &mut io::stdout());
+        }
+        Some(Command::Train { watch: true, .. }) => watch(&args, &metadata),
+        Some(Command::Train {
+            every: Some(interval),
+            ..
+        }) => every(&args, &metadata, *interval),
+        None | Some(Command::Run { .. }) | Some(Command::Train { .. }) => {
+            crossvalidate(&args, &metadata, None);
+        }
+    }
+}
+
+// Logs go to stderr, so they never mix with the results on stdout
+fn init_logging(args: &Args, color: bool) {
+    let env_level = env::var("PARTY_RECOGNISER_LOG").ok();
+    let (level, span_events) =
+        log_level(args, env_level.as_deref()).unwrap_or_else(|e| exit_with_error(&e));
+
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_span_events(span_events)
+        .with_ansi(color)
+        .with_writer(io::stderr);
+
+    match args.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
+}
+
+// The level of PARTY_RECOGNISER_LOG only counts without -v or --quiet
+fn log_level(args: &Args, env_level: Option<&str>) -> Result<(Level, FmtSpan), String> {
+    Ok(match args.verbose {
+        _ if args.quiet => (Level::ERROR, FmtSpan::NONE),
+        0 => match env_level {
+            Some(level) => match level.parse::<Level>() {
+                Ok(level) => (level, FmtSpan::NONE),
+                Err(_) => return Err(format!("Unknown log level '{}'", level)),
+            },
+            None => (Level::INFO, FmtSpan::NONE),
+        },
+        1 => (Level::DEBUG, FmtSpan::NONE),
+        _ => (Level::TRACE, FmtSpan::CLOSE),
+    })
+}
+
+// Reports a mistake in the user's input without a panic message
+fn exit_with_error(error: &str) -> ! {
+    eprintln!("{}", error);
+    process::exit(1)
+}
+
+// A missing, corrupt, unsigned or encrypted model is the user's to fix
+fn load_model(filename: &str, options: &LoadOptions) -> Model {
+    Model::load_with(filename, options)
+        .unwrap_or_else(|e| exit_with_error(&format!("Couldn't load model {}: {}", filename, e)))
+}
+
+fn predict(
+    args: &PredictArgs,
+    metadata: &AttributeMetadata,
+    load_options: &LoadOptions,
+    audit: Option<&AuditLog>,
+    format: Format,
+    color: bool,
+) {
+    let model = load_model(&args.model, load_options);
+    let attributes = match &args.votes {
+        Some(votes) => parse_attributes(votes).unwrap_or_else(|e| exit_with_error(&e)),
+        None => ask_votes(metadata, io::stdin().lock(), &mut io::stderr()),
+    };
+    check_unseen(&model, metadata, &attributes, args.unseen)
+        .unwrap_or_else(|e| exit_with_error(&e));
+    let (class, probabilities) = model.classify_with_probabilities(&attributes);
+
+    if let Some(audit) = audit {
+        audit
+            .record(&model.fingerprint(), &attributes, class, &probabilities)
+            .expect("Couldn't write audit log");
+    }
+
+    if format == Format::Json {
+        let report = output::prediction_report(&probabilities, class);
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        return;
+    }
+
+    println!("Prediction: {}", class.name());
+    println!(
+        "{}",
+        output::probabilities_table(&probabilities, class, color)
+    );
+
+    if args.interactive {
+        println!("{}", confidence_bar(probabilities[class.index()]));
+    }
+}
+
+// Both configs are cross-validated on the same folds, so the differences
+// between their accuracies are paired
+fn compare_configs(
+    filenames: &[&String; 2],
+    folds: usize,
+    repeats: usize,
+    seed: u64,
+    format: Format,
+    color: bool,
+) {
+    if folds < 2 || repeats == 0 {
+        exit_with_error("Comparing needs at least 2 folds and 1 repetition");
+    }
+
+    let configs = filenames.map(|filename| {
+        let mut config = RunConfig::load(filename).unwrap_or_else(|e| {
+            exit_with_error(&format!("Couldn't load config {}: {}", filename, e))
+        });
+        fill_training_defaults(&mut config);
+        config
+    });
+    if configs[0].data != configs[1].data
+        || configs[0].on_error != configs[1].on_error
+        || configs[0].encoding != configs[1].encoding
+        || configs[0].identifiers != configs[1].identifiers
+        || configs[0].dedup != configs[1].dedup
+    {
+        exit_with_error(
+            "Both configs have to train on the same data, read the same way, with the same dedup",
+        );
+    }
+    if configs
+        .iter()
+        .any(|config| config.model == Some(ModelKind::Auto))
+    {
+        exit_with_error("Configs have to name their model instead of using auto");
+    }
+
+    let read_options = ReadOptions::new(
+        configs[0].on_error,
+        configs[0].encoding.as_deref(),
+        configs[0].identifiers.as_deref(),
+    );
+    let mut data = read_data(configs[0].data.as_deref().unwrap(), &read_options);
+    if configs[0].dedup.unwrap() {
+        data = duplicates::dedup(data);
+    }
+    if data.len() < folds {
+        exit_with_error(&format!(
+            "Can't split {} rows into {} folds",
+            data.len(),
+            folds
+        ));
+    }
+
+    let progress = Progress::bar("Comparing", (2 * repeats) as u64);
+    let accuracies = configs.map(|config| {
+        (0..repeats)
+            .flat_map(|repeat| {
+                let crossvalidation = CrossValidation {
+                    splits: folds,
+                    seed: Some(seed.wrapping_add(repeat as u64)),
+                    ..crossvalidation_of(&config)
+                };
+                let accuracies: Vec<f64> = crossvalidation
+                    .run(data.clone())
+                    .unwrap_or_else(|e| exit_with_error(&e))
+                    .map(|fold| fold.accuracy)
+                    .collect();
+                progress.inc(1);
+                accuracies
+            })
+            .collect::<Vec<f64>>()
+    });
+    progress.finish(&format!("{} folds each", accuracies[0].len()));
+
+    let differences: Vec<f64> = accuracies[0]
+        .iter()
+        .zip(&accuracies[1])
+        .map(|(first, second)| second - first)
+        .collect();
+    // Every fold tests on 1 / folds of the rows and trains on the rest
+    let test = significance::corrected_resampled_t_test(&differences, 1.0 / (folds - 1) as f64);
+
+    let report = output::CompareReport {
+        schema_version: SCHEMA_VERSION,
+        folds,
+        repeats,
+        configs: filenames
+            .iter()
+            .zip(&accuracies)
+            .map(|(filename, accuracies)| {
+                let mean = summation::mean(accuracies.iter().copied());
+                let variance = summation::sum(accuracies.iter().map(|a| (a - mean).powi(2)))
+                    / (accuracies.len() - 1).max(1) as f64;
+                output::ConfigScore {
+                    config: filename.to_string(),
+                    mean_accuracy: mean,
+                    std_accuracy: variance.sqrt(),
+                }
+            })
+            .collect(),
+        test,
+    };
+
+    match format {
+        Format::Text => {
+            println!("{}", output::compare_table(&report, color));
+            println!(
+                "{} - {}: {:+.4}, t = {:.3} with {} degrees of freedom, p = {:.4}",
+                filenames[1],
+                filenames[0],
+                test.mean_difference,
+                test.t,
+                test.degrees_of_freedom,
+                test.p_value
+            );
+            println!(
+                "The difference is {}significant at the 5% level",
+                if test.p_value < 0.05 { "" } else { "not " }
+            );
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+    }
+}
+
+// Every model predicts the same rows, so McNemar's test on which of them
+// each one got right compares a pair
+fn compare_models(
+    args: &CompareArgs,
+    load_options: &LoadOptions,
+    read_options: &ReadOptions,
+    format: Format,
+    color: bool,
+) {
+    if args.models.len() < 2 {
+        exit_with_error("Comparing needs at least 2 models");
+    }
+
+    let models: Vec<Model> = args
+        .models
+        .iter()
+        .map(|filename| load_model(filename, load_options))
+        .collect();
+    let rows = read_data(&args.test, read_options);
+    if rows.is_empty() {
+        exit_with_error(&format!("{} has no rows", args.test));
+    }
+    let positive = args.positive_class;
+
+    let mut scores = vec![];
+    let mut correct = vec![];
+    for (filename, model) in args.models.iter().zip(&models) {
+        let predictions = model.predict_batch(&rows);
+        let right: Vec<bool> = rows
+            .iter()
+            .zip(&predictions)
+            .map(|(row, &prediction)| row.class == prediction)
+            .collect();
+        let probabilities: Vec<(bool, f64)> = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.class == positive,
+                    model.probabilities(&row.attributes)[positive.index()],
+                )
+            })
+            .collect();
+
+        scores.push(output::ModelScore {
+            model: filename.clone(),
+            accuracy: right.iter().filter(|&&right| right).count() as f64 / rows.len() as f64,
+            log_likelihood: model.score(&rows),
+            average_precision: curves::average_precision(&curves::precision_recall_curve(
+                &probabilities,
+            )),
+            roc_area: curves::roc_area(&curves::roc_curve(&probabilities)),
+            expected_calibration_error: curves::expected_calibration_error(
+                &curves::calibration_curve(&probabilities, curves::CALIBRATION_BINS),
+            ),
+        });
+        correct.push(right);
+    }
+
+    let mut pairs = vec![];
+    for i in 0..models.len() {
+        for j in i + 1..models.len() {
+            pairs.push(output::ModelPair {
+                first: args.models[i].clone(),
+                second: args.models[j].clone(),
+                test: significance::mcnemar_test(&correct[i], &correct[j]),
+            });
+        }
+    }
+
+    let report = output::ModelComparisonReport {
+        schema_version: SCHEMA_VERSION,
+        rows: rows.len(),
+        positive_class: positive.name(),
+        models: scores,
+        pairs,
+    };
+
+    match format {
+        Format::Text => {
+            println!("Compared on {} rows", report.rows);
+            println!("{}", output::model_comparison_table(&report, color));
+            println!("{}", output::model_pairs_table(&report.pairs, color));
+            println!("Pairs with p below 0.05 differ significantly at the 5% level");
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+    }
+}
+
+// What a search cross-validates, on folds of the seed
+struct SearchSetup {
+    data: Vec<Row>,
+    seed: u64,
+    candidates: Vec<Candidate>,
+    crossvalidation: CrossValidation,
+    threads: usize,
+}
+
+fn search_setup(args: &SearchArgs, read_options: &ReadOptions) -> SearchSetup {
+    if args.folds < 2 {
+        exit_with_error("Tuning needs at least 2 folds");
+    }
+    if !args.smoothing.iter().all(|&smoothing| smoothing > 0.0) {
+        exit_with_error("Smoothing has to be positive");
+    }
+    if args.models.contains(&ModelKind::Auto) {
+        exit_with_error("List the models to tune instead of auto");
+    }
+    if let Some(&k) = args
+        .feature_selection
+        .iter()
+        .find(|k| !(1..=ATTRIBUTES_COUNT).contains(k))
+    {
+        exit_with_error(&format!(
+            "Can't select {} attributes, only 1 to {}",
+            k, ATTRIBUTES_COUNT
+        ));
+    }
+
+    let data = read_data(&args.data, read_options);
+    if data.len() < args.folds {
+        exit_with_error(&format!(
+            "Can't split {} rows into {} folds",
+            data.len(),
+            args.folds
+        ));
+    }
+
+    let seed = args.seed.unwrap_or_else(|| thread_rng().gen());
+    let space = tune::SearchSpace {
+        smoothing: args.smoothing.clone(),
+        missing_votes: args.missing_votes.clone(),
+        prior_modes: args.prior_mode.clone(),
+        // Selecting every attribute is the same as not selecting
+        feature_selection: args
+            .feature_selection
+            .iter()
+            .map(|&k| (k < ATTRIBUTES_COUNT).then_some(k))
+            .collect(),
+        models: args.models.clone(),
+    };
+    let candidates = match args.search {
+        Search::Grid => space.grid(),
+        Search::Random => space.random(args.trials, &mut StdRng::seed_from_u64(seed)),
+    };
+    if candidates.is_empty() {
+        exit_with_error("No combination of the settings can be trained");
+    }
+
+    SearchSetup {
+        data,
+        seed,
+        candidates,
+        crossvalidation: CrossValidation {
+            splits: args.folds,
+            seed: Some(seed),
+            ..CrossValidation::default()
+        },
+        threads: args
+            .threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |threads| threads.get())),
+    }
+}
+
+fn tune_settings(args: &TuneArgs, read_options: &ReadOptions, format: Format, color: bool) {
+    let setup = search_setup(&args.search, read_options);
+
+    let progress = Progress::bar("Tuning", setup.candidates.len() as u64);
+    let results = tune::search(
+        &setup.data,
+        &setup.candidates,
+        setup.crossvalidation,
+        setup.threads,
+        || progress.inc(1),
+    )
+    .unwrap_or_else(|e| exit_with_error(&e));
+    progress.finish(&format!("{} candidates", results.len()));
+
+    let report = output::TuneReport {
+        schema_version: SCHEMA_VERSION,
+        search: args.search.search,
+        folds: args.search.folds,
+        seed: setup.seed,
+        results,
+    };
+    print_tune(
+        &report,
+        &args.search.data,
+        args.output.as_deref(),
+        format,
+        color,
+    );
+}
+
+// With the best settings written to the output as a config
+fn print_tune(
+    report: &output::TuneReport,
+    data: &str,
+    output: Option<&str>,
+    format: Format,
+    color: bool,
+) {
+    match format {
+        Format::Text => {
+            println!("{}", output::tune_table(&report.results, color));
+            println!("Tested on the folds of seed {}", report.seed);
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(report).unwrap()),
+    }
+
+    if let Some(filename) = output {
+        let best = report.results[0].candidate;
+        let config = RunConfig {
+            data: Some(data.to_string()),
+            folds: Some(report.folds),
+            seed: Some(report.seed),
+            smoothing: Some(best.smoothing),
+            missing_votes: Some(best.missing_votes),
+            prior_mode: Some(best.prior_mode),
+            feature_selection: best.feature_selection,
+            model: Some(best.model),
+            ..RunConfig::default()
+        };
+        fs::write(filename, toml::to_string(&config).unwrap()).unwrap_or_else(|e| {
+            exit_with_error(&format!("Couldn't write config {}: {}", filename, e))
+        });
+        info!("Wrote the best settings to {}", filename);
+    }
+}
+
+// Every shard has to test the same candidates on the same folds, so the
+// seed can't be left to chance
+fn run_shard(args: &CvArgs, read_options: &ReadOptions) {
+    let search = &args.search;
+    if search.seed.is_none() {
+        exit_with_error("Every shard needs the same --seed, so they test the same folds");
+    }
+    let shard = args.shard.unwrap();
+    let filename = args.output.as_deref().unwrap();
+    let setup = search_setup(search, read_options);
+
+    let jobs = shard.jobs(setup.candidates.len(), setup.crossvalidation.splits);
+    let progress = Progress::bar(&format!("Shard {}", shard), jobs.len() as u64);
+    let scores = shard::run(
+        &setup.data,
+        &setup.candidates,
+        setup.crossvalidation,
+        shard,
+        setup.threads,
+        || progress.inc(1),
+    )
+    .unwrap_or_else(|e| exit_with_error(&e));
+    progress.finish(&format!("{} folds", scores.len()));
+
+    let report = ShardReport {
+        schema_version: SCHEMA_VERSION,
+        shard,
+        data: search.data.clone(),
+        rows: setup.data.len(),
+        search: search.search,
+        folds: search.folds,
+        seed: setup.seed,
+        candidates: setup.candidates,
+        scores,
+    };
+    replace_file(filename, |filename| {
+        fs::write(filename, serde_json::to_string_pretty(&report).unwrap())
+    })
+    .unwrap_or_else(|e| exit_with_error(&format!("Couldn't write shard {}: {}", filename, e)));
+    info!("Wrote the scores of shard {} to {}", shard, filename);
+}
+
+fn merge_shards(files: &[String], output: Option<&str>, format: Format, color: bool) {
+    let reports: Vec<ShardReport> = files
+        .iter()
+        .map(|filename| {
+            fs::read_to_string(filename)
+                .map_err(|e| e.to_string())
+                .and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string()))
+                .unwrap_or_else(|e| {
+                    exit_with_error(&format!("Couldn't read shard {}: {}", filename, e))
+                })
+        })
+        .collect();
+    let results = shard::merge(&reports).unwrap_or_else(|e| exit_with_error(&e));
+
+    let first = &reports[0];
+    let report = output::TuneReport {
+        schema_version: SCHEMA_VERSION,
+        search: first.search,
+        folds: first.folds,
+        seed: first.seed,
+        results,
+    };
+    print_tune(&report, &first.data, output, format, color);
+}
+
+fn bootstrap(
+    filename: &str,
+    read_options: &ReadOptions,
+    bootstrap: Bootstrap,
+    format: Format,
+    color: bool,
+) {
+    if bootstrap.samples == 0 {
+        exit_with_error("The number of samples has to be positive");
+    }
+
+    let data = read_data(filename, read_options);
+    if data.is_empty() {
+        exit_with_error(&format!("{} has no rows", filename));
+    }
+
+    let progress = Progress::spinner("Bootstrapping");
+    let estimate = bootstrap.run(&data);
+    progress.finish(&format!("{} samples", estimate.samples));
+
+    match format {
+        Format::Text => println!("{}", output::bootstrap_table(&estimate, color)),
+        Format::Json => {
+            let report = output::BootstrapReport {
+                schema_version: SCHEMA_VERSION,
+                estimate: &estimate,
+            };
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+    }
+}
+
+fn stats(
+    filename: &str,
+    read_options: &ReadOptions,
+    metadata: &AttributeMetadata,
+    format: Format,
+    color: bool,
+) {
+    let rows = read_data(filename, read_options);
+    let stats: DatasetStats = rows.iter().collect();
+    let duplicates = output::duplicates_report(&rows, &find_duplicates(&rows));
+
+    match format {
+        Format::Text => {
+            println!("{}", output::class_distribution_table(&stats, color));
+            println!("{}", output::attribute_stats_table(&stats, metadata, color));
+            println!(
+                "{} rows are identical to an earlier row, {} sets of rows have the same votes but different classes",
+                duplicates.exact_count, duplicates.conflict_count
+            );
+            if !duplicates.groups.is_empty() {
+                println!("{}", output::duplicates_table(&duplicates, color));
+            }
+        }
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&output::stats_report(&stats, metadata, duplicates))
+                .unwrap()
+        ),
+    }
+}
+
+fn diagnostics(
+    filename: &str,
+    read_options: &ReadOptions,
+    top: usize,
+    metadata: &AttributeMetadata,
+    format: Format,
+    color: bool,
+) {
+    let rows = read_data(filename, read_options);
+    let pairs = dependence::attribute_dependence(&rows);
+    let tested = pairs.len();
+    let dependent = pairs
+        .iter()
+        .filter(|pair| pair.p_value < output::DEPENDENCE_SIGNIFICANCE / tested as f64)
+        .count();
+    let reports = output::dependence_reports(&pairs[..top.min(tested)], metadata);
+
+    match format {
+        Format::Text => {
+            println!("{}", output::dependence_table(&reports, tested, color));
+            println!(
+                "{} of {} pairs depend on each other within a party at p < {} after a Bonferroni correction",
+                dependent,
+                tested,
+                output::DEPENDENCE_SIGNIFICANCE
+            );
+        }
+        Format::Json => {
+            let report = output::DiagnosticsReport {
+                schema_version: SCHEMA_VERSION,
+                rows: rows.len(),
+                pairs: reports,
+            };
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+    }
+}
+
+fn validate(
+    filename: &str,
+    read_options: &ReadOptions,
+    sheet: Option<&str>,
+    format: Format,
+    color: bool,
+) {
+    if embedded_data(filename).is_none() && !std::path::Path::new(filename).exists() {
+        exit_with_error(&format!("{} doesn't exist", filename));
+    }
+    let identifiers = &read_options.identifiers;
+    let lines = data_lines(filename, read_options.encoding, sheet).map(|line| {
+        if identifiers.is_empty() {
+            line
+        } else {
+            String::from_utf8_lossy(&identifiers.split(line.as_bytes()).0).into_owned()
+        }
+    });
+    let (valid_rows, findings) = validate::validate(lines);
+
+    match format {
+        Format::Text => {
+            if !findings.is_empty() {
+                println!("{}", output::findings_table(&findings, color));
+            }
+            println!(
+                "{} rows are valid, {} problems found",
+                valid_rows,
+                findings.len()
+            );
+        }
+        Format::Json => {
+            let report = output::ValidateReport {
+                schema_version: SCHEMA_VERSION,
+                valid_rows,
+                findings: &findings,
+            };
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+    }
+
+    if !findings.is_empty() {
+        process::exit(1);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn outliers(
+    filename: &str,
+    model_path: Option<&str>,
+    load_options: &LoadOptions,
+    read_options: &ReadOptions,
+    percentile: f64,
+    format: Format,
+    color: bool,
+) {
+    if !(0.0..=100.0).contains(&percentile) {
+        exit_with_error("The percentile has to be between 0 and 100");
+    }
+
+    let rows = read_data(filename, read_options);
+    let model = match model_path {
+        Some(model_path) => load_model(model_path, load_options),
+        None => Model::from_rows(rows.iter().cloned()),
+    };
+    let outliers = find_outliers(&model, &rows, percentile);
+    let report = output::outliers_report(&model, &rows, &outliers, percentile);
+
+    match format {
+        Format::Text => println!("{}", output::outliers_table(&report, color)),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+    }
+}
+
+// Writes the rows in the format of the dataset, so they can be trained on
+fn generate(
+    model_path: &str,
+    load_options: &LoadOptions,
+    rows: usize,
+    seed: Option<u64>,
+    output: Option<&str>,
+) {
+    let model = load_model(model_path, load_options);
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let writer: Box<dyn Write> = match output {
+        Some(filename) => Box::new(fs::File::create(filename).expect("Couldn't create file")),
+        None => Box::new(io::stdout().lock()),
+    };
+    let mut writer = io::BufWriter::new(writer);
+
+    for row in generate::generate(&model, rows, &mut rng) {
+        writeln!(writer, "{}", row).expect("Couldn't write row");
+    }
+    writer.flush().expect("Couldn't write row");
+
+    if let Some(filename) = output {
+        info!("Wrote {} rows to {}", rows, filename);
+    }
+}
+
+// The rows that parse, in the format of the dataset with the hashes of the
+// identifiers in their columns when there's a salt
+fn anonymize_data(
+    filename: &str,
+    read_options: &ReadOptions,
+    output: &str,
+    salt: Option<&str>,
+    k_anonymity: Option<usize>,
+    quasi_identifiers: Option<Vec<usize>>,
+) {
+    let identifiers = &read_options.identifiers;
+    if salt.is_some() && identifiers.is_empty() {
+        exit_with_error("There are no --identifiers columns to hash");
+    }
+    let quasi_identifiers = quasi_identifiers_of(k_anonymity, quasi_identifiers);
+
+    let mut reader = RowReader::new(read_options.on_error);
+    let mut rows = vec![];
+    for line in data_lines(filename, read_options.encoding, None) {
+        let (rest, values) = identifiers.split(line.as_bytes());
+        if let Some(row) = reader.read(&rest).unwrap_or_else(|e| exit_with_error(&e)) {
+            let pseudonyms: Vec<String> = match salt {
+                Some(salt) => values
+                    .iter()
+                    .map(|value| anonymize::pseudonym(salt, value))
+                    .collect(),
+                None => vec![],
+            };
+            rows.push((pseudonyms, row));
+        }
+    }
+    log_read_summary(&reader);
+
+    let anonymizer = k_anonymity.map(|k| {
+        let quasi_identifiers = quasi_identifiers.as_deref().unwrap();
+        let anonymizer = Anonymizer::new(k, quasi_identifiers, rows.iter().map(|(_, row)| row));
+        report_anonymization(&anonymizer, k);
+        anonymizer
+    });
+
+    let mut written = 0;
+    replace_file(output, |filename| {
+        let mut writer = io::BufWriter::new(fs::File::create(filename)?);
+        for (pseudonyms, row) in rows {
+            let row = match &anonymizer {
+                Some(anonymizer) => anonymizer.anonymize(row),
+                None => Some(row),
+            };
+            let Some(row) = row else {
+                continue;
+            };
+
+            let line = row.to_string();
+            if pseudonyms.is_empty() {
+                writeln!(writer, "{}", line)?;
+            } else {
+                writeln!(writer, "{}", identifiers.join(&line, &pseudonyms))?;
+            }
+            written += 1;
+        }
+        writer.flush()
+    })
+    .unwrap_or_else(|e| exit_with_error(&format!("Couldn't write {}: {}", output, e)));
+    info!("Wrote {} rows to {}", written, output);
+}
+
+fn split(
+    filename: &str,
+    read_options: &ReadOptions,
+    test_size: f64,
+    stratify: bool,
+    seed: Option<u64>,
+    prefix: &str,
+) {
+    if !(test_size > 0.0 && test_size < 1.0) {
+        exit_with_error("The test size has to be between 0 and 1");
+    }
+
+    let mut rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let (train, test) = train_test_split(
+        read_data(filename, read_options),
+        test_size,
+        stratify,
+        &mut rng,
+    );
+
+    for (rows, extension) in [(train, "train"), (test, "test")] {
+        let filename = format!("{}.{}", prefix, extension);
+        let file = fs::File::create(&filename).expect("Couldn't create file");
+        let mut writer = io::BufWriter::new(file);
+        for row in &rows {
+            writeln!(writer, "{}", row).expect("Couldn't write row");
+        }
+        writer.flush().expect("Couldn't write row");
+        info!("Wrote {} rows to {}", rows.len(), filename);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn ingest(
+    keys: &KeyArgs,
+    model_path: &str,
+    filename: &str,
+    read_options: &ReadOptions,
+    output: &str,
+    top: usize,
+    format: Format,
+    color: bool,
+) {
+    let before = load_model(model_path, &keys.load_options());
+    let rows = read_data(filename, read_options);
+    let after = before.partial_fit(&rows);
+    info!(
+        "Added {} rows, version {} is trained on {}",
+        rows.len(),
+        after.version(),
+        after.rows_count()
+    );
+
+    let diff = diff::diff(&before, &after);
+    match format {
+        Format::Text => print_diff(&diff, top, color),
+        Format::Json => println!("{}", serde_json::to_string_pretty(&diff).unwrap()),
+    }
+
+    save_model(&after, output, keys, keys.signing_key().as_ref());
+}
+
+// The metrics of cross-validation, of a single model on rows it hasn't seen
+fn evaluate(
+    args: &EvaluateArgs,
+    load_options: &LoadOptions,
+    read_options: &ReadOptions,
+    metadata: &AttributeMetadata,
+    format: Format,
+    color: bool,
+) {
+    let model = load_model(&args.model, load_options);
+    let rows = read_data(&args.test, read_options);
+    if rows.is_empty() {
+        exit_with_error(&format!("{} has no rows", args.test));
+    }
+    let metrics = args.metrics.as_deref().unwrap_or(&DEFAULT_METRICS);
+    let positive = args.positive_class;
+
+    // Everything is worked out from the scores, so that they're only
+    // computed once on whichever backend
+    let scores = batch_scores(&model, &rows, args.backend);
+    let (confusion, report) = evaluation_report(&model, &rows, &scores, args, metadata);
+
+    match format {
+        Format::Text => {
+            println!("Evaluated on {} rows", report.rows);
+            for metric in metrics {
+                match metric {
+                    Metric::Accuracy => println!(
+                        "Accuracy: {:.4}, log-likelihood: {:.4}",
+                        report.accuracy.unwrap(),
+                        report.log_likelihood.unwrap()
+                    ),
+                    Metric::Confusion => {
+                        println!("{}", output::confusion_table(&confusion, color))
+                    }
+                    Metric::Attributes => println!(
+                        "{}",
+                        output::attributes_table(report.attributes.as_ref().unwrap(), color)
+                    ),
+                    Metric::PrecisionRecall => println!(
+                        "Average precision of finding {}s: {:.4}",
+                        positive.name(),
+                        report.average_precision.unwrap()
+                    ),
+                    Metric::Lift => println!(
+                        "{}",
+                        output::lift_table(report.lift.as_ref().unwrap(), positive, color)
+                    ),
+                    Metric::Calibration => println!(
+                        "{}",
+                        output::calibration_table(report.calibration.as_ref().unwrap(), color)
+                    ),
+                }
+            }
+        }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+    }
+}
+
+// The metrics of the model on the rows, from their scores
+fn evaluation_report(
+    model: &Model,
+    rows: &[Row],
+    scores: &[[Float; CLASSES_COUNT]],
+    args: &EvaluateArgs,
+    metadata: &AttributeMetadata,
+) -> (ConfusionMatrix, output::EvaluationReport) {
+    let metrics = args.metrics.as_deref().unwrap_or(&DEFAULT_METRICS);
+    let positive = args.positive_class;
+
+    let mut confusion = ConfusionMatrix::new();
+    for (row, prediction) in rows.iter().zip(model.decide_batch(scores)) {
+        confusion.add(row.class, prediction);
+    }
+    let probabilities: Vec<(bool, f64)> = rows
+        .iter()
+        .zip(scores)
+        .map(|(row, scores)| {
+            (
+                row.class == positive,
+                Model::to_probabilities(scores)[positive.index()],
+            )
+        })
+        .collect();
+    let log_likelihood = summation::mean(
+        rows.iter()
+            .zip(scores)
+            .map(|(row, scores)| Model::to_log_probabilities(scores)[row.class.index()]),
+    );
+    let report = output::EvaluationReport {
+        schema_version: SCHEMA_VERSION,
+        rows: rows.len(),
+        accuracy: metrics
+            .contains(&Metric::Accuracy)
+            .then(|| confusion.accuracy()),
+        log_likelihood: metrics
+            .contains(&Metric::Accuracy)
+            .then_some(log_likelihood),
+        confusion_matrix: metrics
+            .contains(&Metric::Confusion)
+            .then(|| output::confusion_report(&confusion)),
+        attributes: metrics
+            .contains(&Metric::Attributes)
+            .then(|| output::attributes_reports(model, metadata, args.top_attributes)),
+        average_precision: metrics
+            .contains(&Metric::PrecisionRecall)
+            .then(|| curves::average_precision(&curves::precision_recall_curve(&probabilities))),
+        lift: metrics
+            .contains(&Metric::Lift)
+            .then(|| curves::deciles(&probabilities)),
+        calibration: metrics.contains(&Metric::Calibration).then(|| {
+            let bins = curves::calibration_curve(&probabilities, curves::CALIBRATION_BINS);
+            output::CalibrationReport {
+                expected_calibration_error: curves::expected_calibration_error(&bins),
+                bins,
+            }
+        }),
+        positive_class: positive.name(),
+    };
+
+    (confusion, report)
+}
+
+fn batch_scores(model: &Model, rows: &[Row], backend: Backend) -> Vec<[Float; CLASSES_COUNT]> {
+    match backend {
+        Backend::Cpu => model.score_batch(rows),
+        Backend::Gpu => gpu_scores(model, rows).unwrap_or_else(|e| {
+            warn!("{}, scoring on the CPU", e);
+            model.score_batch(rows)
+        }),
+        Backend::Check => {
+            let cpu = model.score_batch(rows);
+            let gpu = gpu_scores(model, rows)
+                .unwrap_or_else(|e| exit_with_error(&format!("Can't check the GPU: {}", e)));
+            check_scores(model, &cpu, &gpu);
+            cpu
+        }
+    }
+}
+
+#[cfg(feature = "gpu")]
+fn gpu_scores(model: &Model, rows: &[Row]) -> Result<Vec<[Float; CLASSES_COUNT]>, String> {
+    let _span = info_span!("gpu", rows = rows.len()).entered();
+    let scorer = gpu::GpuScorer::new(model.log_tables())?;
+    info!("Scoring on {}", scorer.adapter());
+    let scores = scorer.score_batch(rows, |row, i| row.attributes[i].index())?;
+    Ok(scores
+        .chunks_exact(CLASSES_COUNT)
+        .map(|scores| scores.try_into().unwrap())
+        .collect())
+}
+
+#[cfg(not(feature = "gpu"))]
+fn gpu_scores(_model: &Model, _rows: &[Row]) -> Result<Vec<[Float; CLASSES_COUNT]>, String> {
+    exit_with_error("--backend gpu and check need the gpu feature")
+}
+
+// The GPU works in f32, so its scores only have to be close, but then
+// they should only pick another class for rows that are close calls
+#[cfg(feature = "gpu")]
+fn check_scores(model: &Model, cpu: &[[Float; CLASSES_COUNT]], gpu: &[[Float; CLASSES_COUNT]]) {
+    let difference = cpu
+        .iter()
+        .flatten()
+        .zip(gpu.iter().flatten())
+        .map(|(&cpu, &gpu)| {
+            // Only a conversion with the f32 feature
+            #[allow(clippy::useless_conversion)]
+            let difference = f64::from(cpu - gpu);
+            difference.abs()
+        })
+        .fold(0.0, f64::max);
+    let disagreements = model
+        .decide_batch(cpu)
+        .into_iter()
+        .zip(model.decide_batch(gpu))
+        .filter(|(cpu, gpu)| cpu != gpu)
+        .count();
+
+    if difference > gpu::TOLERANCE {
+        exit_with_error(&format!(
+            "The GPU scores are off by up to {:e}, more than {:e}",
+            difference,
+            gpu::TOLERANCE
+        ));
+    }
+    if disagreements > 0 {
+        warn!(
+            "The GPU predicts another class for {} close calls",
+            disagreements
+        );
+    }
+    info!(
+        "The GPU agrees with the CPU, its scores are off by up to {:e}",
+        difference
+    );
+}
+
+#[cfg(not(feature = "gpu"))]
+fn check_scores(_model: &Model, _cpu: &[[Float; CLASSES_COUNT]], _gpu: &[[Float; CLASSES_COUNT]]) {
+    unreachable!("gpu_scores exits without the gpu feature")
+}
+
+#[allow(clippy::too_many_arguments)]
+fn tune_threshold(
+    keys: &KeyArgs,
+    filename: &str,
+    read_options: &ReadOptions,
+    model_path: &str,
+    objective: Objective,
+    positive: Class,
+    crossvalidation: CrossValidation,
+    step: f64,
+    output: &str,
+    format: Format,
+    color: bool,
+) {
+    if !(step > 0.0 && step <= 1.0) {
+        exit_with_error("The step has to be above 0 and at most 1");
+    }
+
+    let model = load_model(model_path, &keys.load_options());
+    let data = read_data(filename, read_options);
+    if data.len() < crossvalidation.splits {
+        exit_with_error(&format!(
+            "Can't split {} rows into {} folds",
+            data.len(),
+            crossvalidation.splits
+        ));
+    }
+
+    let crossvalidation = CrossValidation {
+        smoothing: model.smoothing(),
+        missing_votes: model.missing_votes(),
+        prior_mode: model.prior_mode(),
+        feature_selection: model.feature_selection(),
+        ..crossvalidation
+    };
+    let probabilities =
+        threshold::out_of_fold_probabilities(&print_folds(data, &crossvalidation), positive);
+    let curve = threshold::sweep(&probabilities, step);
+    let chosen = threshold::best(&curve, objective);
+
+    match format {
+        Format::Text => {
+            println!("{}", output::threshold_table(&curve, &chosen, color));
+            println!(
+                "Chose {:.2} with {} {:.4}",
+                chosen.threshold,
+                objective.to_possible_value().unwrap().get_name(),
+                chosen.get(objective)
+            );
+        }
+        Format::Json => {
+            let report = output::ThresholdReport {
+                schema_version: SCHEMA_VERSION,
+                objective,
+                threshold: chosen.threshold,
+                curve: &curve,
+            };
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+    }
+
+    let model = model
+        .with_threshold(Some(chosen.threshold))
+        .with_threshold_class(positive);
+    save_model(&model, output, keys, keys.signing_key().as_ref());
+}
+
+fn bench_predict(
+    model_path: &str,
+    load_options: &LoadOptions,
+    iterations: usize,
+    batch_size: usize,
+    seed: u64,
+    format: Format,
+    color: bool,
+) {
+    if batch_size == 0 {
+        exit_with_error("The batch size has to be positive");
+    }
+
+    let model = load_model(model_path, load_options);
+    let rows = bench::sample_rows(&model, seed);
+
+    let progress = Progress::spinner("Benchmarking single rows");
+    let single = bench::bench_single(&model, &rows, iterations);
+    progress.finish(&format!("{} calls", single.calls));
+
+    let progress = Progress::spinner("Benchmarking batches");
+    let batch = bench::bench_batch(&model, &rows, batch_size, iterations);
+    progress.finish(&format!("{} calls", batch.calls));
+
+    match format {
+        Format::Text => {
+            let batch_name = format!("Batch of {}", batch_size);
+            println!(
+                "{}",
+                output::bench_table(&[("Single row", &single), (&batch_name, &batch)], color)
+            );
+        }
+        Format::Json => {
+            let report = output::BenchReport {
+                schema_version: SCHEMA_VERSION,
+                single: &single,
+                batch: &batch,
+            };
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn bench_train(
+    filename: &str,
+    read_options: &ReadOptions,
+    sizes: &[usize],
+    synthetic: bool,
+    folds: usize,
+    runs: usize,
+    seed: u64,
+    format: Format,
+    color: bool,
+) {
+    if folds == 0 {
+        exit_with_error("The number of folds has to be positive");
+    }
+    if let Some(size) = sizes.iter().find(|&&size| size < folds) {
+        exit_with_error(&format!(
+            "Can't split {} rows into {} folds, use a larger size",
+            size, folds
+        ));
+    }
+
+    let data = read_data(filename, read_options);
+    if data.is_empty() {
+        exit_with_error(&format!("{} has no rows", filename));
+    }
+
+    let timings: Vec<_> = sizes
+        .iter()
+        .map(|&size| {
+            let input = bench::training_input(&data, size, synthetic, seed);
+            let progress = Progress::spinner(&format!("Benchmarking {} rows", size));
+            let timings = bench::bench_train(&input, folds, seed, runs)
+                .unwrap_or_else(|e| exit_with_error(&e));
+            progress.finish(&format!("{:?}", timings.total()));
+            timings
+        })
+        .collect();
+
+    match format {
+        Format::Text => println!("{}", output::train_bench_table(&timings, color)),
+        Format::Json => {
+            let report = output::TrainBenchReport {
+                schema_version: SCHEMA_VERSION,
+                synthetic,
+                sizes: &timings,
+            };
+            println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        }
+    }
+}
+
+fn cache(args: &CacheArgs, format: Format, color: bool) {
+    let cache = match &args.cache_dir {
+        Some(dir) => Cache::new(dir),
+        None => Cache::from_env().unwrap_or_else(|| {
+            exit_with_error("There's no home directory to cache in, give one with --cache-dir")
+        }),
+    };
+
+    match args.command {
+        CacheCommand::List => {
+            let entries = cache.list().unwrap_or_else(|e| {
+                exit_with_error(&format!(
+                    "Couldn't read cache {}: {}",
+                    cache.root().display(),
+                    e
+                ))
+            });
+
+            match format {
+                Format::Text => println!("{}", output::cache_table(&entries, color)),
+                Format::Json => println!("{}", serde_json::to_string_pretty(&entries).unwrap()),
+            }
+        }
+        CacheCommand::Clear => {
+            let count = cache.clear().unwrap_or_else(|e| {
+                exit_with_error(&format!(
+                    "Couldn't clear cache {}: {}",
+                    cache.root().display(),
+                    e
+                ))
+            });
+            info!("Removed {} datasets from {}", count, cache.root().display());
+        }
+    }
+}
+
+fn model(
+    args: &ModelArgs,
+    load_options: &LoadOptions,
+    metadata: &AttributeMetadata,
+    format: Format,
+    color: bool,
+) {
+    let registry = Registry::new(&args.registry);
+
+    match &args.command {
+        ModelCommand::Push {
+            model,
+            name,
+            version,
+        } => {
+            let entry = registry
+                .push(name, version, model, load_options)
+                .unwrap_or_else(|e| exit_with_error(&format!("Couldn't push model: {}", e)));
+
+            match format {
+                Format::Text => println!(
+                    "Pushed {} {} to {}",
+                    name,
+                    version,
+                    entry.model_path.display()
+                ),
+                Format::Json => {
+                    println!("{}", serde_json::to_string_pretty(&entry.metadata).unwrap())
+                }
+            }
+        }
+        ModelCommand::List => {
+            let entries = registry.list().expect("Couldn't read registry");
+
+            match format {
+                Format::Text => println!("{}", output::registry_table(&entries, color)),
+                Format::Json => {
+                    let metadata: Vec<_> = entries.iter().map(|entry| &entry.metadata).collect();
+                    println!("{}", serde_json::to_string_pretty(&metadata).unwrap());
+                }
+            }
+        }
+        ModelCommand::Get {
+            name,
+            version,
+            output,
+        } => {
+            let entry = registry
+                .get(name, version.as_ref())
+                .unwrap_or_else(|e| exit_with_error(&e.to_string()));
+
+            if let Some(filename) = output {
+                fs::copy(&entry.model_path, filename).expect("Couldn't copy model");
+                info!("Copied {} {} to {}", name, entry.metadata.version, filename);
+            }
+
+            match format {
+                Format::Text if output.is_none() => println!("{}", entry.model_path.display()),
+                Format::Text => {}
+                Format::Json => {
+                    println!("{}", serde_json::to_string_pretty(&entry.metadata).unwrap())
+                }
+            }
+        }
+        ModelCommand::Keygen { output } => {
+            let public_path = signing::save_keypair(&signing::generate_signing_key(), output)
+                .expect("Couldn't write keys");
+            println!(
+                "Wrote the signing key to {} and the verifying key to {}",
+                output,
+                public_path.display()
+            );
+        }
+        ModelCommand::Sign { model, key } => {
+            let key = signing::load_signing_key(key)
+                .unwrap_or_else(|e| exit_with_error(&format!("Couldn't read signing key: {}", e)));
+            let path = signing::sign_file(model, &key).expect("Couldn't sign model");
+            println!("Wrote the signature to {}", path.display());
+        }
+        ModelCommand::Inspect { model } => {
+            let model = load_model(model, load_options);
+
+            match format {
+                Format::Text => {
+                    println!(
+                        "Version {}, trained on {} rows with smoothing {}",
+                        model.version(),
+                        model.rows_count(),
+                        model.smoothing()
+                    );
+                    if model.missing_votes() == MissingVotes::Ignore {
+                        println!("Ignores unknown votes");
+                    }
+                    if model.prior_mode() == PriorMode::Uniform {
+                        println!("Starts every class as likely");
+                    }
+                    if model.feature_selection().is_some() {
+                        println!(
+                            "Only predicts from attributes {}",
+                            (0..ATTRIBUTES_COUNT)
+                                .filter(|&i| model.is_selected(i))
+                                .map(|i| metadata.name(i))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        );
+                    }
+                    if let Some(threshold) = model.threshold() {
+                        println!(
+                            "Predicts a {} at a probability of {} or more",
+                            model.threshold_class().name(),
+                            threshold
+                        );
+                    }
+                    println!("{}", output::priors_table(&model, color));
+                    println!(
+                        "{}",
+                        output::conditional_probabilities_table(&model, metadata, color)
+                    );
+                    if metadata.has_descriptions() {
+                        println!("{}", output::attribute_descriptions_table(metadata, color));
+                    }
+                }
+                Format::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&output::inspect_report(&model, metadata))
+                        .unwrap()
+                ),
+            }
+        }
+        ModelCommand::Card { model: filename } => {
+            let model = load_model(filename, load_options);
+            let card = model.card().unwrap_or_else(|| {
+                exit_with_error(&format!(
+                    "{} has no card, only models saved after cross-validation do",
+                    filename
+                ))
+            });
+
+            match format {
+                Format::Text => print!("{}", card.to_markdown()),
+                Format::Json => println!("{}", serde_json::to_string_pretty(card).unwrap()),
+            }
+        }
+        ModelCommand::Diff { before, after, top } => {
+            let diff = diff::diff(
+                &load_model(before, load_options),
+                &load_model(after, load_options),
+            );
+
+            match format {
+                Format::Text => print_diff(&diff, *top, color),
+                Format::Json => println!("{}", serde_json::to_string_pretty(&diff).unwrap()),
+            }
+        }
+    }
+}
+
+fn print_diff(diff: &ModelDiff, top: usize, color: bool) {
+    println!(
+        "Version {} before and {} after",
+        diff.version_before, diff.version_after
+    );
+    println!(
+        "Trained on {} rows before and {} after",
+        diff.rows_before, diff.rows_after
+    );
+    if let Some(shift) = diff.largest_prior_shift() {
+        println!(
+            "Largest prior shift: {} {:+.4}",
+            shift.class,
+            shift.change()
+        );
+    }
+    if let Some(shift) = diff.largest_attribute_shift() {
+        println!(
+            "Largest conditional shift: P({}={} | {}) {:+.4}",
+            shift.attribute,
+            shift.choice,
+            shift.class,
+            shift.change()
+        );
+    }
+
+    println!("{}", output::prior_shifts_table(&diff.priors, color));
+    println!(
+        "{}",
+        output::attribute_shifts_table(&diff.attributes[..top.min(diff.attributes.len())], color)
+    );
+}
+
+// Asks how each attribute was voted on, by its name in the attribute
+// metadata. Skipped questions, and the ones left when the input ends, count
+// as unknown. The questions go to stderr, so that stdout only has the
+// results.
+fn ask_votes(
+    metadata: &AttributeMetadata,
+    input: impl BufRead,
+    prompts: &mut impl Write,
+) -> Vec<Choice> {
+    let mut lines = input.lines();
+    let mut attributes = vec![Choice::Unknown; ATTRIBUTES_COUNT];
+
+    for i in 0..ATTRIBUTES_COUNT {
+        loop {
+            write!(
+                prompts,
+                "[{}/{}] {}? (y/n/skip) ",
+                i + 1,
+                ATTRIBUTES_COUNT,
+                metadata.name(i)
+            )
+            .and_then(|_| prompts.flush())
+            .expect("Couldn't write prompt");
+
+            let line = match lines.next() {
+                Some(line) => line.expect("Couldn't read answer"),
+                None => {
+                    writeln!(prompts).expect("Couldn't write prompt");
+                    return attributes;
+                }
+            };
+
+            attributes[i] = match line.trim() {
+                "y" | "yes" => Choice::Yes,
+                "n" | "no" => Choice::No,
+                "" | "s" | "skip" | "?" => Choice::Unknown,
+                _ => {
+                    writeln!(prompts, "Please answer y, n or skip").expect("Couldn't write prompt");
+                    continue;
+                }
+            };
+
+            break;
+        }
+    }
+
+    attributes
+}
+
+// Warns about the votes the model never saw, or fails with them, naming the
+// attributes by the attribute metadata
+fn check_unseen(
+    model: &Model,
+    metadata: &AttributeMetadata,
+    attributes: &[Choice],
+    unseen: Unseen,
+) -> Result<(), String> {
+    let votes = model.unseen_votes(attributes);
+    if votes.is_empty() {
+        return Ok(());
+    }
+
+    let votes: Vec<String> = votes
+        .iter()
+        .map(|&i| format!("{} on {}", attributes[i].name(), metadata.name(i)))
+        .collect();
+    let message = format!("Never saw these votes in training: {}", votes.join(", "));
+    match unseen {
+        Unseen::Warn => {
+            warn!("{}", message);
+            Ok(())
+        }
+        Unseen::Error => Err(message),
+    }
+}
+
+fn repl(
+    model_path: &str,
+    metadata: &AttributeMetadata,
+    unseen: Unseen,
+    load_options: &LoadOptions,
+    audit: Option<&AuditLog>,
+) {
+    let model = load_model(model_path, load_options);
+
+    answer_records(
+        &model,
+        metadata,
+        unseen,
+        audit,
+        io::stdin().lock(),
+        &mut io::stdout(),
+        &mut io::stderr(),
+    );
+}
+
+// Each line holds the votes of a record like --votes, and is answered with a
+// line such as "democrat republican=0.0009 democrat=0.9991". Lines that can't
+// be parsed get an error in `errors` instead, so the output stays parsable.
+fn answer_records(
+    model: &Model,
+    metadata: &AttributeMetadata,
+    unseen: Unseen,
+    audit: Option<&AuditLog>,
+    input: impl BufRead,
+    output: &mut impl Write,
+    errors: &mut impl Write,
+) {
+    for line in input.lines() {
+        let line = line.unwrap_or_else(|e| exit_with_error(&format!("Couldn't read line: {}", e)));
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let attributes = match parse_attributes(&line) {
+            Ok(attributes) => attributes,
+            Err(e) => {
+                writeln!(errors, "{}", e).expect("Couldn't write error");
+                continue;
+            }
+        };
+        if let Err(e) = check_unseen(model, metadata, &attributes, unseen) {
+            writeln!(errors, "{}", e).expect("Couldn't write error");
+            continue;
+        }
+
+        let (class, probabilities) = model.classify_with_probabilities(&attributes);
+        let mut answer = class.name().to_string();
+
+        if let Some(audit) = audit {
+            audit
+                .record(&model.fingerprint(), &attributes, class, &probabilities)
+                .expect("Couldn't write audit log");
+        }
+
+        for class in CLASSES.iter() {
+            answer += &format!(" {}={:.4}", class.name(), probabilities[class.index()]);
+        }
+
+        writeln!(output, "{}", answer).expect("Couldn't write prediction");
+    }
+}
+
+fn confidence_bar(confidence: f64) -> String {
+    let filled = (confidence * CONFIDENCE_BAR_WIDTH as f64).round() as usize;
+
+    format!(
+        "Confidence: [{}{}] {:.1}%",
+        "#".repeat(filled),
+        "-".repeat(CONFIDENCE_BAR_WIDTH - filled),
+        confidence * 100.0
+    )
+}
+
+// The file of --config or of run --spec
+fn config_file(args: &Args) -> Option<&str> {
+    match (&args.command, &args.config) {
+        (Some(Command::Run { .. }), Some(_)) => {
+            exit_with_error("Give the settings with either --config or run --spec")
+        }
+        (Some(Command::Run { spec }), None) => Some(spec),
+        (_, config) => config.as_deref(),
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-enum Class {
-    Republican,
-    Democrat,
+// A cross-validation run with every setting resolved
+struct Run {
+    // All settings are filled in, for the manifest
+    config: RunConfig,
+    data: String,
+    read_options: ReadOptions,
+    sheet: Option<String>,
+    dedup: bool,
+    sample: Option<f64>,
+    k_anonymity: Option<usize>,
+    quasi_identifiers: Vec<usize>,
+    crossvalidation: CrossValidation,
+    metrics: Vec<Metric>,
+    top_attributes: usize,
+    positive_class: Class,
+    color: bool,
+    mem_report: bool,
+    error_analysis: bool,
+    save_model: Option<String>,
+    save_fold_models: Option<String>,
+    best_fold: bool,
+    min_accuracy: Option<f64>,
+    checkpoint: Option<String>,
+    checkpoint_every: usize,
+    train_final: Option<String>,
+    export_quantized: Option<String>,
+    manifest: Option<String>,
+    pr_curve: Option<String>,
+    gain_curve: Option<String>,
+    calibration_curve: Option<String>,
+    plots: Option<String>,
+    charts: bool,
+    history: Option<String>,
+    mlflow: Option<String>,
+    mlflow_experiment: String,
+    model_card: Option<String>,
+    report: Option<String>,
 }
 
-#[derive(Debug, Clone)]
-struct Row {
-    class: Class,
-    attributes: Vec<Choice>,
+impl Run {
+    // The flags are layered over the config file
+    fn new(args: &Args) -> Self {
+        let file = match config_file(args) {
+            Some(filename) => RunConfig::load(filename)
+                .unwrap_or_else(|e| exit_with_error(&format!("Couldn't load config: {}", e))),
+            None => RunConfig::default(),
+        };
+
+        let flags = RunConfig {
+            data: args.data.clone(),
+            on_error: args.on_error,
+            encoding: args.encoding.clone(),
+            identifiers: args.identifiers.clone(),
+            sheet: args.sheet.clone(),
+            dedup: args.dedup.then_some(true),
+            sample: args.sample,
+            k_anonymity: args.k_anonymity,
+            quasi_identifiers: args.quasi_identifiers.clone(),
+            folds: args.folds,
+            seed: args.seed,
+            smoothing: args.smoothing,
+            missing_votes: args.missing_votes,
+            prior_mode: args.prior_mode,
+            feature_selection: args.feature_selection,
+            augment: args.augment,
+            flip_probability: args.flip_probability,
+            epsilon: args.epsilon,
+            model: args.model,
+            joint: args.joint.clone(),
+            metrics: args.metrics.clone(),
+            top_attributes: args.top_attributes,
+            positive_class: args.positive_class,
+            output: OutputConfig {
+                color: args.no_color.then_some(false),
+                mem_report: args.mem_report.then_some(true),
+                error_analysis: args.error_analysis.then_some(true),
+                save_model: args.save_model.clone(),
+                save_fold_models: args.save_fold_models.clone(),
+                best_fold: args.best_fold.then_some(true),
+                min_accuracy: args.min_accuracy,
+                checkpoint: args.checkpoint.clone(),
+                checkpoint_every: args.checkpoint_every,
+                train_final: args.train_final.clone(),
+                export_quantized: args.export_quantized.clone(),
+                manifest: args.manifest.clone(),
+                pr_curve: args.pr_curve.clone(),
+                gain_curve: args.gain_curve.clone(),
+                calibration_curve: args.calibration_curve.clone(),
+                plots: args.plots.clone(),
+                charts: args.charts.then_some(true),
+                history: args.history.clone(),
+                mlflow: args.mlflow.clone(),
+                mlflow_experiment: args.mlflow_experiment.clone(),
+                model_card: args.model_card.clone(),
+                report: args.report.clone(),
+            },
+        };
+
+        let mut config = flags.or(file).or(RunConfig::from_env());
+        fill_training_defaults(&mut config);
+        config
+            .metrics
+            .get_or_insert_with(|| DEFAULT_METRICS.to_vec());
+        config.top_attributes.get_or_insert(DEFAULT_TOP_ATTRIBUTES);
+        config.positive_class.get_or_insert(DEFAULT_POSITIVE_CLASS);
+        config.output.color.get_or_insert(true);
+        config.output.mem_report.get_or_insert(false);
+        config.output.error_analysis.get_or_insert(false);
+        config.output.best_fold.get_or_insert(false);
+        config.output.charts.get_or_insert(false);
+        config
+            .output
+            .mlflow_experiment
+            .get_or_insert_with(|| DEFAULT_MLFLOW_EXPERIMENT.to_string());
+        // The other kinds are only cross-validated
+        if config.model != Some(ModelKind::NaiveBayes) || config.joint.is_some() {
+            let output = &config.output;
+            if output.save_model.is_some()
+                || output.save_fold_models.is_some()
+                || output.train_final.is_some()
+                || output.export_quantized.is_some()
+                || output.checkpoint.is_some()
+                || output.model_card.is_some()
+                || output.best_fold == Some(true)
+                || output.error_analysis == Some(true)
+                || output.mem_report == Some(true)
+            {
+                exit_with_error(
+                    "Only plain naive Bayes models can be saved, explained or measured, use --model nb without --joint",
+                );
+            }
+            // They come from a naive Bayes model of the whole dataset
+            config
+                .metrics
+                .as_mut()
+                .unwrap()
+                .retain(|&metric| metric != Metric::Attributes);
+        }
+        config
+            .output
+            .checkpoint_every
+            .get_or_insert(DEFAULT_CHECKPOINT_EVERY);
+
+        Run {
+            data: config.data.clone().unwrap(),
+            read_options: ReadOptions::new(
+                config.on_error,
+                config.encoding.as_deref(),
+                config.identifiers.as_deref(),
+            ),
+            sheet: config.sheet.clone(),
+            dedup: config.dedup.unwrap(),
+            sample: config.sample,
+            k_anonymity: config.k_anonymity,
+            quasi_identifiers: config.quasi_identifiers.clone().unwrap_or_default(),
+            crossvalidation: crossvalidation_of(&config),
+            metrics: config.metrics.clone().unwrap(),
+            top_attributes: config.top_attributes.unwrap(),
+            positive_class: config.positive_class.unwrap(),
+            color: output::use_color(!config.output.color.unwrap()),
+            mem_report: config.output.mem_report.unwrap(),
+            error_analysis: config.output.error_analysis.unwrap(),
+            save_model: config.output.save_model.clone(),
+            save_fold_models: config.output.save_fold_models.clone(),
+            best_fold: config.output.best_fold.unwrap(),
+            min_accuracy: config.output.min_accuracy,
+            checkpoint: config.output.checkpoint.clone(),
+            checkpoint_every: config.output.checkpoint_every.unwrap(),
+            train_final: config.output.train_final.clone(),
+            export_quantized: config.output.export_quantized.clone(),
+            manifest: config.output.manifest.clone(),
+            pr_curve: config.output.pr_curve.clone(),
+            gain_curve: config.output.gain_curve.clone(),
+            calibration_curve: config.output.calibration_curve.clone(),
+            plots: config.output.plots.clone(),
+            charts: config.output.charts.unwrap(),
+            history: config.output.history.clone(),
+            mlflow: config.output.mlflow.clone(),
+            mlflow_experiment: config.output.mlflow_experiment.clone().unwrap(),
+            model_card: config.output.model_card.clone(),
+            report: config.output.report.clone(),
+            config,
+        }
+    }
+
+    // Whether anything needs a model of the whole dataset. With --best-fold
+    // only --train-final and the attributes do.
+    fn needs_full_model(&self) -> bool {
+        self.metrics.contains(&Metric::Attributes)
+            || self.train_final.is_some()
+            || (!self.best_fold && (self.save_model.is_some() || self.export_quantized.is_some()))
+    }
+}
+
+// Fills in the settings of how models are trained and tested, and exits if
+// they're out of range
+fn fill_training_defaults(config: &mut RunConfig) {
+    let defaults = CrossValidation::default();
+    config.data.get_or_insert_with(|| FILENAME.to_string());
+    config.on_error.get_or_insert(OnError::Fail);
+    config.encoding.get_or_insert_with(|| "utf-8".to_string());
+    config.dedup.get_or_insert(false);
+    if *config.folds.get_or_insert(defaults.splits) < 2 {
+        exit_with_error("Cross-validation needs at least 2 folds");
+    }
+    // Without a seed the folds are still random, but the seed is known
+    // and can be written to the manifest
+    config.seed.get_or_insert_with(|| thread_rng().gen());
+    config.smoothing.get_or_insert(defaults.smoothing);
+    config.missing_votes.get_or_insert(defaults.missing_votes);
+    config.prior_mode.get_or_insert(defaults.prior_mode);
+    if !config
+        .feature_selection
+        .is_none_or(|k| (1..=ATTRIBUTES_COUNT).contains(&k))
+    {
+        exit_with_error(&format!(
+            "Feature selection keeps 1 to {} attributes",
+            ATTRIBUTES_COUNT
+        ));
+    }
+    // Only meaningful with augmentation, so it's left out without it
+    if config.augment.is_some() {
+        config
+            .flip_probability
+            .get_or_insert(DEFAULT_FLIP_PROBABILITY);
+    }
+    if !config
+        .flip_probability
+        .is_none_or(|p| (0.0..=1.0).contains(&p))
+    {
+        exit_with_error("The flip probability has to be between 0 and 1");
+    }
+    if !config
+        .sample
+        .is_none_or(|fraction| fraction > 0.0 && fraction <= 1.0)
+    {
+        exit_with_error("The sample has to be above 0 and at most 1");
+    }
+    config.quasi_identifiers =
+        quasi_identifiers_of(config.k_anonymity, config.quasi_identifiers.take());
+    if !config.epsilon.is_none_or(|epsilon| epsilon > 0.0) {
+        exit_with_error("Epsilon has to be positive");
+    }
+    let model = *config.model.get_or_insert(ModelKind::NaiveBayes);
+    if model != ModelKind::NaiveBayes && config.epsilon.is_some() {
+        exit_with_error("Only naive Bayes models can be trained with --epsilon");
+    }
+    if model != ModelKind::NaiveBayes && config.missing_votes == Some(MissingVotes::Ignore) {
+        exit_with_error("Only naive Bayes models can ignore unknown votes");
+    }
+    if model != ModelKind::NaiveBayes && config.prior_mode == Some(PriorMode::Uniform) {
+        exit_with_error("Only naive Bayes models can have uniform priors");
+    }
+    if model != ModelKind::NaiveBayes && config.feature_selection.is_some() {
+        exit_with_error("Only naive Bayes models can select attributes");
+    }
+    if let Some(groups) = &config.joint {
+        if model != ModelKind::NaiveBayes {
+            exit_with_error("Attributes can only be modelled together with --model nb");
+        }
+        if config.epsilon.is_some() {
+            exit_with_error("Models with --joint can't be trained with --epsilon");
+        }
+        if config.missing_votes == Some(MissingVotes::Ignore) {
+            exit_with_error("Models with --joint count unknown votes as a category");
+        }
+        if config.prior_mode == Some(PriorMode::Uniform) || config.feature_selection.is_some() {
+            exit_with_error("Models with --joint have learned priors and every attribute");
+        }
+        if let Err(e) = AttributeGroups::new(groups) {
+            exit_with_error(&e);
+        }
+    }
 }
 
-fn choice_str_to_enum(c: &str) -> Choice {
-    if c == "y" {
-        return Choice::Yes;
+// The quasi-identifiers of --k-anonymity, all of the attributes by default,
+// exiting if they're out of range or given without it
+fn quasi_identifiers_of(
+    k_anonymity: Option<usize>,
+    quasi_identifiers: Option<Vec<usize>>,
+) -> Option<Vec<usize>> {
+    let Some(k) = k_anonymity else {
+        if quasi_identifiers.is_some() {
+            exit_with_error("Quasi-identifiers only apply with --k-anonymity");
+        }
+        return None;
+    };
+
+    if k < 2 {
+        exit_with_error("K-anonymity needs a k of at least 2");
+    }
+    let quasi_identifiers = quasi_identifiers.unwrap_or_else(|| (1..=ATTRIBUTES_COUNT).collect());
+    if quasi_identifiers.is_empty() {
+        exit_with_error("K-anonymity needs at least one quasi-identifier");
     }
+    if let Some(&attribute) = quasi_identifiers
+        .iter()
+        .find(|attribute| !(1..=ATTRIBUTES_COUNT).contains(attribute))
+    {
+        exit_with_error(&format!(
+            "Attribute {} isn't between 1 and {}",
+            attribute, ATTRIBUTES_COUNT
+        ));
+    }
+    Some(quasi_identifiers)
+}
 
-    if c == "n" {
-        return Choice::No;
+// Of a config filled in by fill_training_defaults
+fn crossvalidation_of(config: &RunConfig) -> CrossValidation {
+    CrossValidation {
+        splits: config.folds.unwrap(),
+        seed: config.seed,
+        smoothing: config.smoothing.unwrap(),
+        missing_votes: config.missing_votes.unwrap(),
+        prior_mode: config.prior_mode.unwrap(),
+        feature_selection: config.feature_selection,
+        augmentation: config.augment.map(|copies| Augmentation {
+            copies,
+            flip_probability: config.flip_probability.unwrap(),
+        }),
+        epsilon: config.epsilon,
+        model: config.model.unwrap(),
+        joint: config.joint.as_ref().map(|groups| {
+            AttributeGroups::new(groups).expect("Groups are checked by fill_training_defaults")
+        }),
     }
+}
+
+// Cross-validates every kind of model on the same folds for --model auto,
+// the best first
+fn select_model(data: &[Row], crossvalidation: &CrossValidation) -> Vec<TuneResult> {
+    let candidates = AUTO_CANDIDATES.map(|model| tune::Candidate {
+        smoothing: crossvalidation.smoothing,
+        missing_votes: crossvalidation.missing_votes,
+        prior_mode: crossvalidation.prior_mode,
+        feature_selection: crossvalidation.feature_selection,
+        model,
+    });
+    let threads = thread::available_parallelism().map_or(1, |threads| threads.get());
+    let progress = Progress::bar("Picking a model", candidates.len() as u64);
+    let results = tune::search(data, &candidates, *crossvalidation, threads, || {
+        progress.inc(1)
+    })
+    .unwrap_or_else(|e| exit_with_error(&e));
+
+    progress.finish(&format!("{} models", results.len()));
+    results
+}
 
-    Choice::Unknown
+fn print_folds(data: Vec<Row>, crossvalidation: &CrossValidation) -> Vec<FoldResult> {
+    let progress = Progress::bar("Cross-validation", crossvalidation.splits as u64);
+    let folds: Vec<FoldResult> = crossvalidation
+        .run(data)
+        .unwrap_or_else(|e| exit_with_error(&e))
+        .inspect(|_| progress.inc(1))
+        .collect();
+
+    progress.finish(&format!("{} folds", folds.len()));
+    folds
+}
+
+// The dataset built into the binary, when it's asked for and the file isn't
+// there
+#[cfg(feature = "embedded-data")]
+fn embedded_data(filename: &str) -> Option<&'static str> {
+    (filename == FILENAME && !std::path::Path::new(filename).exists()).then_some(EMBEDDED_DATA)
+}
+
+#[cfg(not(feature = "embedded-data"))]
+fn embedded_data(_filename: &str) -> Option<&'static str> {
+    None
 }
 
-fn split_for_crossvalidation(mut data: Vec<Row>) -> Vec<Vec<Row>> {
-    data.shuffle(&mut thread_rng());
-    let chunk_size = data.len() / CROSSVALIDATION_SPLITS;
-    let remainder = data.len() % CROSSVALIDATION_SPLITS;
+// How the datasets of the subcommands are read, from --on-error,
+// --encoding and --identifiers
+#[derive(Debug, Clone)]
+struct ReadOptions {
+    on_error: OnError,
+    encoding: &'static Encoding,
+    identifiers: Identifiers,
+}
 
-    let mut res: Vec<Vec<Row>> = data.chunks_exact(chunk_size).map(|x| x.to_vec()).collect();
-    let res_len = res.len();
+impl ReadOptions {
+    fn new(
+        on_error: Option<OnError>,
+        encoding: Option<&str>,
+        identifiers: Option<&[usize]>,
+    ) -> Self {
+        ReadOptions {
+            on_error: on_error.unwrap_or(OnError::Fail),
+            encoding: encoding_for_label(encoding.unwrap_or("utf-8"))
+                .unwrap_or_else(|e| exit_with_error(&e)),
+            identifiers: Identifiers::new(identifiers.unwrap_or_default())
+                .unwrap_or_else(|e| exit_with_error(&e)),
+        }
+    }
 
-    // Add remainders
-    for i in 0..remainder {
-        res[i].push(data[res_len + i].clone());
+    fn reader(&self) -> RowReader {
+        RowReader::new(self.on_error).with_identifiers(self.identifiers.clone())
     }
+}
 
-    res
+fn read_data(filename: &str, options: &ReadOptions) -> Vec<Row> {
+    let mut reader = options.reader();
+    let data = read_rows(filename, options.encoding, None, &mut reader)
+        .map(|(_, row)| row)
+        .collect();
+
+    log_read_summary(&reader);
+    data
 }
 
-fn read_input() -> Vec<Row> {
-    let file = File::open(FILENAME).expect("Couldn't open file");
-    let lines = io::BufReader::new(file).lines();
-    let mut data: Vec<Row> = vec![];
+fn is_spreadsheet(filename: &str) -> bool {
+    filename.ends_with(".xlsx")
+}
 
-    for line in lines {
-        let line = line.expect("Couldn't read line");
-        let split_line: Vec<&str> = line.split(',').collect();
+#[cfg(feature = "xlsx")]
+fn spreadsheet_lines(filename: &str, sheet: Option<&str>) -> Vec<String> {
+    xlsx::read_lines(filename, sheet).unwrap_or_else(|e| exit_with_error(&e))
+}
 
-        let class = match split_line[0] {
-            "republican" => Class::Republican,
-            "democrat" => Class::Democrat,
-            _ => panic!("Unknown class"),
-        };
+#[cfg(not(feature = "xlsx"))]
+fn spreadsheet_lines(filename: &str, _sheet: Option<&str>) -> Vec<String> {
+    exit_with_error(&format!(
+        "Can't read {}, .xlsx files need the xlsx feature",
+        filename
+    ))
+}
+
+fn is_cloud_uri(filename: &str) -> bool {
+    filename.starts_with("s3://") || filename.starts_with("gs://")
+}
+
+#[cfg(feature = "cloud")]
+fn cloud_bytes(uri: &str) -> Vec<u8> {
+    let _span = info_span!("download", uri).entered();
+    let cache = Cache::from_env();
+    cloud::download(uri, cache.as_ref()).unwrap_or_else(|e| exit_with_error(&e))
+}
+
+#[cfg(not(feature = "cloud"))]
+fn cloud_bytes(uri: &str) -> Vec<u8> {
+    exit_with_error(&format!(
+        "Can't read {}, s3:// and gs:// URIs need the cloud feature",
+        uri
+    ))
+}
+
+#[cfg(feature = "terminal-plots")]
+fn print_charts(folds: &[FoldResult], probabilities: &[(bool, f64)], positive: Class) {
+    println!("{}", charts::charts(folds, probabilities, positive));
+}
 
-        let attributes: Vec<Choice> = split_line[1..=ATTRIBUTES_COUNT]
+#[cfg(not(feature = "terminal-plots"))]
+fn print_charts(_: &[FoldResult], _: &[(bool, f64)], _: Class) {
+    exit_with_error("--charts needs the terminal-plots feature")
+}
+
+fn cross_validation_summary(folds: &[FoldResult], report: &RunReport) -> CrossValidationSummary {
+    let accuracy = output::average_accuracy(folds);
+    let variance = summation::sum(folds.iter().map(|fold| (fold.accuracy - accuracy).powi(2)))
+        / (folds.len().max(2) - 1) as f64;
+
+    CrossValidationSummary {
+        folds: folds.len(),
+        accuracy,
+        accuracy_std: variance.sqrt(),
+        log_likelihood: output::average_log_likelihood(folds),
+        average_precision: report.average_precision,
+        expected_calibration_error: report
+            .calibration
+            .as_ref()
+            .map(|calibration| calibration.expected_calibration_error),
+    }
+}
+
+// The model is only uploaded when it was saved
+#[cfg(feature = "mlflow")]
+fn log_to_mlflow(
+    uri: &str,
+    run: &Run,
+    folds: &[FoldResult],
+    report: &RunReport,
+    started_at: SystemTime,
+    saved: bool,
+) {
+    let mut metrics = vec![
+        ("accuracy".to_string(), output::average_accuracy(folds), 0),
+        (
+            "log_likelihood".to_string(),
+            output::average_log_likelihood(folds),
+            0,
+        ),
+    ];
+    metrics.extend(
+        folds
             .iter()
-            .map(|x| choice_str_to_enum(x))
-            .collect();
+            .map(|fold| ("fold_accuracy".to_string(), fold.accuracy, fold.fold)),
+    );
+    if let Some(average_precision) = report.average_precision {
+        metrics.push(("average_precision".to_string(), average_precision, 0));
+    }
+    if let Some(calibration) = &report.calibration {
+        metrics.push((
+            "expected_calibration_error".to_string(),
+            calibration.expected_calibration_error,
+            0,
+        ));
+    }
+
+    let tracked = TrackedRun {
+        name: None,
+        started_at: Some(started_at),
+        params: run.config.settings(),
+        metrics,
+        tags: vec![
+            (
+                "mlflow.source.name".to_string(),
+                env!("CARGO_PKG_NAME").to_string(),
+            ),
+            ("config_hash".to_string(), history::config_hash(&run.config)),
+        ],
+        artifacts: run.save_model.iter().filter(|_| saved).cloned().collect(),
+    };
 
-        data.push(Row { class, attributes });
+    match mlflow::log_run(uri, &run.mlflow_experiment, &tracked) {
+        Ok(run_id) => info!("Logged the run to MLflow as {}", run_id),
+        // The run itself went fine, so it isn't failed for this
+        Err(e) => warn!("Couldn't log the run to MLflow: {}", e),
     }
+}
 
-    data
+#[cfg(not(feature = "mlflow"))]
+fn log_to_mlflow(uri: &str, run: &Run, _: &[FoldResult], _: &RunReport, _: SystemTime, _: bool) {
+    exit_with_error(&format!(
+        "Can't log the run to {} on {}, --mlflow needs the mlflow feature",
+        run.mlflow_experiment, uri
+    ))
 }
 
-#[derive(Debug)]
-struct Model {
-    attr_probs: HashMap<Class, Vec<HashMap<Choice, f64>>>,
-    class_probs: HashMap<Class, f64>,
+#[cfg(feature = "plots")]
+fn write_plots(dir: &str, folds: &[FoldResult], probabilities: &[(bool, f64)], positive: Class) {
+    plots::write_plots(dir, folds, probabilities, positive).unwrap_or_else(|e| exit_with_error(&e))
 }
 
-impl Model {
-    fn new(data: &[&Row]) -> Self {
-        let mut attr_probs: HashMap<Class, Vec<HashMap<Choice, f64>>> = HashMap::new();
-        let mut class_probs: HashMap<Class, f64> = HashMap::new();
+#[cfg(not(feature = "plots"))]
+fn write_plots(dir: &str, _: &[FoldResult], _: &[(bool, f64)], _: Class) {
+    exit_with_error(&format!(
+        "Can't write plots to {}, --plots needs the plots feature",
+        dir
+    ))
+}
 
-        attr_probs.insert(Class::Republican, vec![]);
-        attr_probs.insert(Class::Democrat, vec![]);
+// Lines of the dataset whether it's a file, a spreadsheet, in object storage
+// or built in
+fn data_lines(
+    filename: &str,
+    encoding: &'static Encoding,
+    sheet: Option<&str>,
+) -> Box<dyn Iterator<Item = String>> {
+    if is_spreadsheet(filename) {
+        return Box::new(spreadsheet_lines(filename, sheet).into_iter());
+    }
+    if is_cloud_uri(filename) {
+        let bytes = cloud_bytes(filename);
+        return exit_on_read_error(filename, decode_lines(io::Cursor::new(bytes), encoding));
+    }
 
-        // Zero out all attribute probabilities
-        for attrs in attr_probs.values_mut() {
-            for _ in 0..ATTRIBUTES_COUNT {
-                let mut new_hashmap = HashMap::new();
-                new_hashmap.insert(Choice::Yes, 1f64 / data.len() as f64);
-                new_hashmap.insert(Choice::No, 1f64 / data.len() as f64);
-                new_hashmap.insert(Choice::Unknown, 1f64 / data.len() as f64);
-                attrs.push(new_hashmap);
-            }
+    match embedded_data(filename) {
+        Some(contents) => {
+            info!("{} not found, using the embedded dataset", filename);
+            Box::new(contents.lines().map(str::to_string))
         }
+        None => {
+            let lines = read_lines(filename, encoding)
+                .unwrap_or_else(|e| exit_with_error(&format!("Couldn't open {}: {}", filename, e)));
+            exit_on_read_error(filename, lines)
+        }
+    }
+}
+
+fn exit_on_read_error(
+    filename: &str,
+    lines: impl Iterator<Item = io::Result<String>> + 'static,
+) -> Box<dyn Iterator<Item = String>> {
+    let filename = filename.to_string();
+    Box::new(lines.map(move |line| {
+        line.unwrap_or_else(|e| exit_with_error(&format!("Couldn't read {}: {}", filename, e)))
+    }))
+}
 
-        let mut republicans_prob = 0f64;
-        let mut democrats_prob = 0f64;
+// The rows that could be read by the policy, with the line of every row
+fn read_rows<'a>(
+    filename: &str,
+    encoding: &'static Encoding,
+    sheet: Option<&str>,
+    reader: &'a mut RowReader,
+) -> impl Iterator<Item = (usize, Row)> + 'a {
+    data_lines(filename, encoding, sheet).filter_map(move |line| {
+        let row = reader
+            .read(line.as_bytes())
+            .unwrap_or_else(|e| exit_with_error(&e));
+        row.map(|row| (reader.line(), row))
+    })
+}
 
-        for row in data {
-            if row.class == Class::Republican {
-                republicans_prob += 1f64 / data.len() as f64;
-            } else {
-                democrats_prob += 1f64 / data.len() as f64;
-            }
+fn load_data(
+    filename: &str,
+    encoding: &'static Encoding,
+    sheet: Option<&str>,
+    mmap: bool,
+    reader: &mut RowReader,
+) -> (Vec<Row>, Vec<usize>) {
+    let _span = info_span!("load", file = filename, mmap).entered();
+    let progress = Progress::spinner("Loading data");
+
+    // The map is scanned as UTF-8, so it's only used for UTF-8 files on disk
+    let (lines, data): (Vec<usize>, Vec<Row>) = if mmap
+        && encoding == UTF_8
+        && !is_spreadsheet(filename)
+        && !is_cloud_uri(filename)
+        && embedded_data(filename).is_none()
+    {
+        read_input_mmap(filename, reader)
+            .unwrap_or_else(|e| exit_with_error(&e))
+            .into_iter()
+            .unzip()
+    } else {
+        if mmap {
+            info!("Reading line by line, --mmap only works with UTF-8 files");
+        }
+        read_rows(filename, encoding, sheet, reader)
+            .inspect(|_| progress.inc(1))
+            .unzip()
+    };
+
+    progress.finish(&format!("{} rows", data.len()));
+    (data, lines)
+}
+
+// Up to the first 10 lines, e.g. "4, 9, 12 and 3 more"
+fn line_list(lines: &[usize]) -> String {
+    const SHOWN: usize = 10;
+    let shown: Vec<String> = lines
+        .iter()
+        .take(SHOWN)
+        .map(|line| line.to_string())
+        .collect();
+
+    match lines.len().checked_sub(SHOWN) {
+        Some(more) if more > 0 => format!("{} and {} more", shown.join(", "), more),
+        _ => shown.join(", "),
+    }
+}
+
+fn log_read_summary(reader: &RowReader) {
+    for (reason, lines) in &reader.skipped {
+        warn!(
+            "Skipped {} rows: {} on lines {}",
+            lines.len(),
+            reason,
+            line_list(lines)
+        );
+    }
+
+    if !reader.fixed.is_empty() {
+        warn!(
+            "Filled in missing votes with ? on {} rows, lines {}",
+            reader.fixed.len(),
+            line_list(&reader.fixed)
+        );
+    }
+}
+
+// Written next to the file and renamed over it, so whatever loads the file
+// never sees it half-written
+fn replace_file(filename: &str, write: impl FnOnce(&str) -> io::Result<()>) -> io::Result<()> {
+    let temporary = format!("{}.tmp", filename);
+    write(&temporary)?;
+    fs::rename(&temporary, filename)
+}
+
+// Encrypted if there's a secret and signed if there's a signing key
+fn save_model(model: &Model, filename: &str, keys: &KeyArgs, signing_key: Option<&SigningKey>) {
+    replace_file(filename, |filename| match keys.secret() {
+        Some(secret) => model.save_encrypted(filename, &secret),
+        None => model.save(filename),
+    })
+    .unwrap_or_else(|e| exit_with_error(&format!("Couldn't save model {}: {}", filename, e)));
+    info!("Saved model to {}", filename);
+    sign_artifact(filename, signing_key);
+}
+
+// As fold-<n>.json, with the metrics of the fold in fold-<n>.metrics.json
+fn save_fold_models(
+    folds: &[FoldResult],
+    dir: &str,
+    keys: &KeyArgs,
+    signing_key: Option<&SigningKey>,
+) {
+    fs::create_dir_all(dir).unwrap_or_else(|e| {
+        exit_with_error(&format!(
+            "Couldn't create fold model directory {}: {}",
+            dir, e
+        ))
+    });
+
+    for fold in folds {
+        let path = std::path::Path::new(dir).join(format!("fold-{}.json", fold.fold));
+        save_model(&fold.model, &path.to_string_lossy(), keys, signing_key);
+
+        let report = output::FoldModelReport {
+            schema_version: SCHEMA_VERSION,
+            fold: fold.fold,
+            accuracy: fold.accuracy,
+            testing_rows: fold.testing_set.len(),
+            confusion_matrix: output::confusion_report(&fold.confusion),
+        };
+        let path = path.with_extension("metrics.json");
+        fs::write(&path, serde_json::to_string_pretty(&report).unwrap()).unwrap_or_else(|e| {
+            exit_with_error(&format!(
+                "Couldn't write fold metrics {}: {}",
+                path.display(),
+                e
+            ))
+        });
+    }
+}
+
+// The most accurate fold, where ties go to the earlier fold
+fn best_fold(folds: &[FoldResult]) -> Option<&FoldResult> {
+    folds
+        .iter()
+        .max_by(|a, b| a.accuracy.total_cmp(&b.accuracy).then(b.fold.cmp(&a.fold)))
+}
+
+fn sign_artifact(filename: &str, key: Option<&SigningKey>) {
+    if let Some(key) = key {
+        let path = signing::sign_file(filename, key)
+            .unwrap_or_else(|e| exit_with_error(&format!("Couldn't sign {}: {}", filename, e)));
+        info!("Signed {} in {}", filename, path.display());
+    }
+}
+
+// Trains on the whole dataset for --metrics attributes, --train-final,
+// --save-model and --export-quantized. With --checkpoint, the counts are
+// saved every --checkpoint-every lines, and a pass that was interrupted
+// carries on from the last checkpoint.
+fn train_full(run: &Run) -> Model {
+    let _span = info_span!("train", file = run.data).entered();
+    let progress = Progress::spinner("Training");
+    let seed = run.config.seed.unwrap();
+    let mut dedup = Dedup::new();
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    let settings = checkpoint::settings_hash(&run.config);
+    let checkpoint = run.checkpoint.as_deref().and_then(|filename| {
+        let checkpoint = Checkpoint::load(filename)
+            .unwrap_or_else(|e| exit_with_error(&format!("Couldn't read checkpoint: {}", e)))?;
+        let random = run.crossvalidation.augmentation.is_some()
+            || run.crossvalidation.epsilon.is_some()
+            || run.sample.is_some();
+
+        if checkpoint.data != run.data || checkpoint.settings != settings {
+            warn!(
+                "Ignoring {}, it's of a different dataset or training settings",
+                filename
+            );
+            None
+        } else if random && checkpoint.seed != seed {
+            warn!(
+                "Ignoring {}, it can only be resumed with --seed {}",
+                filename, checkpoint.seed
+            );
+            None
+        } else {
+            info!("Resuming training after line {}", checkpoint.line);
+            Some(checkpoint)
+        }
+    });
+    let resumed_at = checkpoint.as_ref().map_or(0, |checkpoint| checkpoint.line);
+    let mut trainer = match checkpoint {
+        Some(checkpoint) => checkpoint.trainer,
+        None => Trainer::new()
+            .with_smoothing(run.crossvalidation.smoothing)
+            .with_missing_votes(run.crossvalidation.missing_votes)
+            .with_prior_mode(run.crossvalidation.prior_mode)
+            .with_feature_selection(run.crossvalidation.feature_selection),
+    };
+    let mut next_checkpoint = resumed_at + run.checkpoint_every;
+
+    // The rows are counted in a pass of their own, so that the training
+    // pass can still stream them
+    let anonymizer = run.k_anonymity.map(|k| {
+        let mut reader = run.read_options.reader();
+        let mut dedup = Dedup::new();
+        let mut sampler = run.sample.map(|fraction| Sampler::new(fraction, seed));
+        let rows = read_rows(
+            &run.data,
+            run.read_options.encoding,
+            run.sheet.as_deref(),
+            &mut reader,
+        )
+        .filter(|(_, row)| !run.dedup || dedup.is_new(row))
+        .filter(|_| sampler.as_mut().is_none_or(Sampler::keep))
+        .map(|(_, row)| row);
+        let anonymizer = Anonymizer::new(k, &run.quasi_identifiers, rows);
+        report_anonymization(&anonymizer, k);
+        anonymizer
+    });
+
+    // Already summarised when the data was loaded
+    let mut reader = run.read_options.reader();
+    let mut sampler = run.sample.map(|fraction| Sampler::new(fraction, seed));
+    for (line, row) in read_rows(
+        &run.data,
+        run.read_options.encoding,
+        run.sheet.as_deref(),
+        &mut reader,
+    )
+    .filter(|(_, row)| !run.dedup || dedup.is_new(row))
+    .filter(|_| sampler.as_mut().is_none_or(Sampler::keep))
+    .filter_map(|(line, row)| match &anonymizer {
+        Some(anonymizer) => anonymizer.anonymize(row).map(|row| (line, row)),
+        None => Some((line, row)),
+    }) {
+        // Rows that are already counted still go through dedup, sampling and
+        // augmentation, so that a resumed pass ends up with the same counts
+        let augmented = run
+            .crossvalidation
+            .augmentation
+            .as_ref()
+            .map(|augmentation| augmentation.augment(&row, &mut rng));
+        if line <= resumed_at {
+            continue;
+        }
+
+        match augmented {
+            Some(rows) => rows.iter().for_each(|row| trainer.add(row)),
+            None => trainer.add(&row),
+        }
+        progress.inc(1);
+
+        if let Some(filename) = run
+            .checkpoint
+            .as_deref()
+            .filter(|_| line >= next_checkpoint)
+        {
+            let checkpoint = Checkpoint {
+                data: run.data.clone(),
+                seed,
+                settings: settings.clone(),
+                line,
+                trainer: trainer.clone(),
+            };
+            checkpoint.save(filename).unwrap_or_else(|e| {
+                exit_with_error(&format!("Couldn't save checkpoint {}: {}", filename, e))
+            });
+            debug!("Saved checkpoint at line {}", line);
+            next_checkpoint = line + run.checkpoint_every;
+        }
+    }
 
-            for i in 0..ATTRIBUTES_COUNT {
-                let choice = row.attributes[i];
-                let attribute_prob = &mut attr_probs.get_mut(&row.class).unwrap()[i];
-                let choice_prob = attribute_prob.get_mut(&choice).unwrap();
-                *choice_prob += 1f64 / data.len() as f64;
+    // The pass is done, the next one starts over
+    if let Some(filename) = &run.checkpoint {
+        match fs::remove_file(filename) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                exit_with_error(&format!("Couldn't remove checkpoint {}: {}", filename, e))
             }
+            _ => {}
+        }
+    }
+
+    let model = match run.crossvalidation.epsilon {
+        Some(epsilon) => trainer.build_private(epsilon, &mut rng),
+        None => trainer.build(),
+    };
+
+    progress.finish(&format!("{} rows", model.rows_count()));
+    model
+}
+
+fn report_anonymization(anonymizer: &Anonymizer, k: usize) {
+    if anonymizer.suppressed == 0 {
+        info!("Every row is already {}-anonymous", k);
+    } else if anonymizer.drops_suppressed {
+        info!(
+            "Dropped {} rows, too few to be {}-anonymous even with their quasi-identifiers suppressed",
+            anonymizer.suppressed, k
+        );
+    } else {
+        info!(
+            "Suppressed the quasi-identifiers of {} rows for {}-anonymity",
+            anonymizer.suppressed, k
+        );
+    }
+}
+
+// Cross-validates and saves the models on a schedule, only replacing them
+// when the accuracy doesn't drop. The data and the config file are read
+// again every time.
+fn every(args: &Args, metadata: &AttributeMetadata, interval: Duration) {
+    let mut promoted = None;
+
+    loop {
+        if let Some(accuracy) = crossvalidate(args, metadata, promoted) {
+            promoted = Some(accuracy);
         }
 
-        class_probs.insert(Class::Republican, republicans_prob);
-        class_probs.insert(Class::Democrat, democrats_prob);
+        info!("Running again in {}", humantime::format_duration(interval));
+        thread::sleep(interval);
+    }
+}
+
+// Cross-validates, then again after every change to the dataset. Changes
+// are waited out until the file has been quiet for a moment, so a batch of
+// appended rows retrains once.
+fn watch(args: &Args, metadata: &AttributeMetadata) {
+    const QUIET: Duration = Duration::from_millis(500);
+
+    crossvalidate(args, metadata, None);
+
+    let data = Run::new(args).data;
+    if is_cloud_uri(&data) || !Path::new(&data).is_file() {
+        exit_with_error(&format!("Can't watch {}, it isn't a local file", data));
+    }
+    let path = fs::canonicalize(&data).expect("Couldn't resolve dataset path");
+
+    // Editors often replace the file instead of writing to it, so it's the
+    // directory that's watched
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).expect("Couldn't watch dataset");
+    watcher
+        .watch(path.parent().unwrap(), RecursiveMode::NonRecursive)
+        .expect("Couldn't watch dataset");
+    info!("Watching {} for changes", data);
 
-        Model {
-            attr_probs,
-            class_probs,
+    let changed = |event: notify::Result<Event>| {
+        let event = event.expect("Couldn't watch dataset");
+        (event.kind.is_create() || event.kind.is_modify()) && event.paths.contains(&path)
+    };
+
+    loop {
+        if !changed(receiver.recv().expect("Dataset watcher stopped")) {
+            continue;
         }
+        while receiver.recv_timeout(QUIET).is_ok() {}
+
+        info!("{} changed, running again", data);
+        crossvalidate(args, metadata, None);
     }
+}
+
+// Returns the average accuracy if the models were saved. They aren't when the
+// accuracy is below --min-accuracy or the baseline, e.g. the accuracy of the
+// models that are already saved.
+fn crossvalidate(args: &Args, metadata: &AttributeMetadata, baseline: Option<f64>) -> Option<f64> {
+    let started_at = SystemTime::now();
+    let mut run = Run::new(args);
+    let color = run.color;
+    let mut reader = run.read_options.reader();
+    // lines has the line of every row that's cross-validated, to report
+    // rows by their line even after dedup
+    let (mut data, mut lines) = load_data(
+        &run.data,
+        run.read_options.encoding,
+        run.sheet.as_deref(),
+        args.mmap,
+        &mut reader,
+    );
+    let rows = data.len();
 
-    fn predict_class(&self, row: &Row, class: Class) -> f64 {
-        let mut res = 0f64;
+    let duplicates = find_duplicates(&data);
+    let conflicts = duplicates.conflicts(&data).count();
+    if conflicts > 0 {
+        warn!(
+            "{} sets of rows have the same votes but different classes",
+            conflicts
+        );
+    }
+    if duplicates.exact_count > 0 {
+        if run.dedup {
+            let mut dedup = Dedup::new();
+            (lines, data) = lines
+                .into_iter()
+                .zip(data)
+                .filter(|(_, row)| dedup.is_new(row))
+                .unzip();
+            info!("Dropped {} duplicate rows", duplicates.exact_count);
+        } else {
+            warn!(
+                "{} rows are identical to an earlier row, --dedup drops them",
+                duplicates.exact_count
+            );
+        }
+    }
+    if data.len() < run.crossvalidation.splits {
+        exit_with_error(&format!(
+            "Can't split {} rows into {} folds",
+            data.len(),
+            run.crossvalidation.splits
+        ));
+    }
 
-        res += self.class_probs[&class].log10();
+    if let Some(fraction) = run.sample {
+        let mut sampler = Sampler::new(fraction, run.config.seed.unwrap());
+        (lines, data) = lines
+            .into_iter()
+            .zip(data)
+            .filter(|_| sampler.keep())
+            .unzip();
+        info!("Sampled {} of the rows", data.len());
+        if data.len() < run.crossvalidation.splits {
+            exit_with_error(&format!(
+                "Can't split the {} sampled rows into {} folds",
+                data.len(),
+                run.crossvalidation.splits
+            ));
+        }
+    }
 
-        for i in 0..ATTRIBUTES_COUNT {
-            res += self.attr_probs[&class][i][&row.attributes[i]].log10();
+    if let Some(k) = run.k_anonymity {
+        let anonymizer = Anonymizer::new(k, &run.quasi_identifiers, &data);
+        (lines, data) = lines
+            .into_iter()
+            .zip(data)
+            .filter_map(|(line, row)| anonymizer.anonymize(row).map(|row| (line, row)))
+            .unzip();
+        report_anonymization(&anonymizer, k);
+        if data.len() < run.crossvalidation.splits {
+            exit_with_error(&format!(
+                "Can't split the {} anonymised rows into {} folds",
+                data.len(),
+                run.crossvalidation.splits
+            ));
         }
+    }
+
+    let model_selection = (run.crossvalidation.model == ModelKind::Auto).then(|| {
+        let results = select_model(&data, &run.crossvalidation);
+        run.crossvalidation.model = results[0].candidate.model;
+        info!(
+            "Picked {} with an accuracy of {:.4}",
+            results[0].candidate.model.name(),
+            results[0].accuracy
+        );
+        results
+    });
+
+    // For the model card, before the rows are cross-validated
+    let data_summary = DataSummary::new(&run.data, &data, duplicates.exact_count);
 
-        res
+    #[cfg(feature = "tui")]
+    let folds = if args.tui {
+        tui::run(data, &run.crossvalidation)
+            .unwrap_or_else(|e| exit_with_error(&format!("Couldn't run dashboard: {}", e)))
+    } else {
+        print_folds(data, &run.crossvalidation)
+    };
+    #[cfg(not(feature = "tui"))]
+    let folds = print_folds(data, &run.crossvalidation);
+
+    // Quitting the dashboard early leaves fewer folds than splits
+    let mut confusion = ConfusionMatrix::new();
+    for fold in &folds {
+        confusion.merge(&fold.confusion);
+    }
+
+    let memory = folds
+        .last()
+        .filter(|_| run.mem_report)
+        .map(|fold| fold.model.memory_report());
+
+    // Trained once, whichever of them needs it
+    let full_model = run.needs_full_model().then(|| train_full(&run));
+    let attributes = full_model
+        .as_ref()
+        .filter(|_| run.metrics.contains(&Metric::Attributes))
+        .map(|model| output::attributes_reports(model, metadata, run.top_attributes));
+
+    // Out-of-fold probabilities for the curves
+    let precision_recall = run.metrics.contains(&Metric::PrecisionRecall);
+    let lift = run.metrics.contains(&Metric::Lift);
+    let calibration = run.metrics.contains(&Metric::Calibration);
+    let probabilities = (precision_recall
+        || lift
+        || calibration
+        || run.pr_curve.is_some()
+        || run.gain_curve.is_some()
+        || run.calibration_curve.is_some()
+        || run.plots.is_some()
+        || run.charts)
+        .then(|| threshold::out_of_fold_probabilities(&folds, run.positive_class));
+    let pr_curve = probabilities
+        .as_ref()
+        .filter(|_| precision_recall || run.pr_curve.is_some())
+        .map(|probabilities| curves::precision_recall_curve(probabilities));
+    if let (Some(filename), Some(curve)) = (&run.pr_curve, &pr_curve) {
+        curves::write_precision_recall_csv(curve, filename)
+            .expect("Couldn't write precision-recall curve");
+        info!("Wrote precision-recall curve to {}", filename);
+    }
+    if let (Some(filename), Some(probabilities)) = (&run.gain_curve, &probabilities) {
+        curves::write_gain_csv(&curves::gain_curve(probabilities), filename)
+            .expect("Couldn't write gain curve");
+        info!("Wrote gain curve to {}", filename);
+    }
+    let calibration_curve = probabilities
+        .as_ref()
+        .filter(|_| calibration || run.calibration_curve.is_some())
+        .map(|probabilities| curves::calibration_curve(probabilities, curves::CALIBRATION_BINS));
+    if let (Some(filename), Some(curve)) = (&run.calibration_curve, &calibration_curve) {
+        curves::write_calibration_csv(curve, filename).expect("Couldn't write calibration curve");
+        info!("Wrote calibration curve to {}", filename);
+    }
+    if let (Some(dir), Some(probabilities)) = (&run.plots, &probabilities) {
+        write_plots(dir, &folds, probabilities, run.positive_class);
+        info!("Wrote plots to {}", dir);
     }
 
-    fn predict(&self, row: &Row) -> Class {
-        let republican_prob = self.predict_class(row, Class::Republican);
-        let democrat_prob = self.predict_class(row, Class::Democrat);
+    let accuracy = run.metrics.contains(&Metric::Accuracy);
+    let report = RunReport {
+        schema_version: SCHEMA_VERSION,
+        folds: accuracy.then(|| output::fold_reports(&folds)),
+        average_accuracy: accuracy.then(|| output::average_accuracy(&folds)),
+        average_log_likelihood: accuracy.then(|| output::average_log_likelihood(&folds)),
+        confusion_matrix: run
+            .metrics
+            .contains(&Metric::Confusion)
+            .then(|| output::confusion_report(&confusion)),
+        attributes,
+        average_precision: pr_curve
+            .as_ref()
+            .filter(|_| precision_recall)
+            .map(|curve| curves::average_precision(curve)),
+        lift: probabilities
+            .as_ref()
+            .filter(|_| lift)
+            .map(|probabilities| curves::deciles(probabilities)),
+        calibration: calibration_curve.filter(|_| calibration).map(|bins| {
+            output::CalibrationReport {
+                expected_calibration_error: curves::expected_calibration_error(&bins),
+                bins,
+            }
+        }),
+        misclassified: run.error_analysis.then(|| {
+            output::misclassification_reports(
+                &error_analysis::misclassifications(&folds),
+                &lines,
+                metadata,
+            )
+        }),
+        memory,
+        positive_class: probabilities.as_ref().map(|_| run.positive_class.name()),
+        model_selection,
+    };
+
+    match args.format {
+        Format::Text => {
+            if let Some(results) = &report.model_selection {
+                println!("{}", output::tune_table(results, color));
+            }
+
+            for metric in &run.metrics {
+                match metric {
+                    Metric::Accuracy => println!("{}", output::folds_table(&folds, color)),
+                    Metric::Confusion => {
+                        println!("{}", output::confusion_table(&confusion, color))
+                    }
+                    Metric::Attributes => {
+                        if let Some(attributes) = &report.attributes {
+                            println!("{}", output::attributes_table(attributes, color))
+                        }
+                    }
+                    Metric::PrecisionRecall => {
+                        if let Some(average_precision) = report.average_precision {
+                            println!(
+                                "Average precision of finding {}s: {:.4}",
+                                run.positive_class.name(),
+                                average_precision
+                            );
+                        }
+                    }
+                    Metric::Lift => {
+                        if let Some(deciles) = &report.lift {
+                            println!("{}", output::lift_table(deciles, run.positive_class, color))
+                        }
+                    }
+                    Metric::Calibration => {
+                        if let Some(calibration) = &report.calibration {
+                            println!("{}", output::calibration_table(calibration, color))
+                        }
+                    }
+                }
+            }
+
+            if let Some(misclassified) = &report.misclassified {
+                println!("{}", output::misclassifications_table(misclassified, color));
+            }
+
+            if let Some(memory) = &memory {
+                println!("{}", output::memory_table(memory, color));
+            }
 
-        if republican_prob > democrat_prob {
-            return Class::Republican;
+            if let (true, Some(probabilities)) = (run.charts, &probabilities) {
+                print_charts(&folds, probabilities, run.positive_class);
+            }
         }
+        Format::Json => println!("{}", serde_json::to_string_pretty(&report).unwrap()),
+    }
 
-        Class::Democrat
+    if let Some(filename) = &run.report {
+        fs::write(filename, report::to_html(&report, &run.config.settings())).unwrap_or_else(|e| {
+            exit_with_error(&format!("Couldn't write report {}: {}", filename, e))
+        });
+        info!("Wrote report to {}", filename);
     }
 
-    fn get_accuracy(&self, testing_set: &[Row]) -> f64 {
-        let mut res = 0f64;
+    // Saved models are only replaced when the new ones are good enough
+    let average_accuracy = output::average_accuracy(&folds);
+    let bar = run
+        .min_accuracy
+        .into_iter()
+        .chain(baseline)
+        .reduce(f64::max);
+    let promote = match bar {
+        Some(bar) if average_accuracy < bar => {
+            warn!(
+                "Not saving models, the accuracy of {:.4} is below {:.4}",
+                average_accuracy, bar
+            );
+            false
+        }
+        _ => true,
+    };
 
-        for row in testing_set {
-            let prediction = self.predict(row);
+    if promote {
+        // Read before the model is trained, so a bad key fails early
+        let signing_key = args.keys.signing_key();
+        if let Some(dir) = &run.save_fold_models {
+            save_fold_models(&folds, dir, &args.keys, signing_key.as_ref());
+        }
 
-            if prediction == row.class {
-                res += 1f64 / testing_set.len() as f64;
+        let best_fold = best_fold(&folds);
+        let artifact = if run.best_fold {
+            if let Some(fold) = best_fold {
+                info!(
+                    "Using the model of fold {} with accuracy {:.4}",
+                    fold.fold, fold.accuracy
+                );
             }
+            best_fold.map(|fold| &fold.model)
+        } else {
+            full_model.as_ref()
+        };
+
+        let card = ModelCard::new(
+            manifest::timestamp(started_at),
+            data_summary,
+            &run.config,
+            cross_validation_summary(&folds, &report),
+        );
+        let mut artifact_card = card.clone();
+        if let (true, Some(fold)) = (run.best_fold, best_fold) {
+            artifact_card.limitations.push(format!(
+                "Only trained on the rows outside of fold {}, not the whole dataset",
+                fold.fold
+            ));
+        }
+
+        if let (Some(filename), Some(model)) = (&run.save_model, artifact) {
+            let model = model.clone().with_card(Some(artifact_card.clone()));
+            save_model(&model, filename, &args.keys, signing_key.as_ref());
+        }
+
+        if let (Some(filename), Some(model)) = (&run.train_final, &full_model) {
+            let model = model.clone().with_card(Some(card));
+            save_model(&model, filename, &args.keys, signing_key.as_ref());
+        }
+
+        if let Some(filename) = &run.model_card {
+            fs::write(filename, artifact_card.to_markdown()).expect("Couldn't write model card");
+            info!("Wrote model card to {}", filename);
+        }
+
+        if let (Some(filename), Some(model)) = (&run.export_quantized, artifact) {
+            let quantized = QuantizedModel::from_model(model);
+            replace_file(filename, |filename| quantized.save(filename))
+                .expect("Couldn't export quantized model");
+            info!("Exported quantized model to {}", filename);
+            sign_artifact(filename, signing_key.as_ref());
         }
+    }
+
+    if let Some(filename) = &run.history {
+        let entry = HistoryEntry {
+            timestamp: manifest::timestamp(started_at),
+            config_hash: history::config_hash(&run.config),
+            seed: run.config.seed.unwrap(),
+            folds: folds.len(),
+            rows,
+            accuracy: average_accuracy,
+            log_likelihood: output::average_log_likelihood(&folds),
+            average_precision: report.average_precision,
+            expected_calibration_error: report
+                .calibration
+                .as_ref()
+                .map(|calibration| calibration.expected_calibration_error),
+        };
+        history::append(filename, &entry)
+            .unwrap_or_else(|e| exit_with_error(&format!("Couldn't append to history: {}", e)));
+        info!("Appended the run to {}", filename);
+    }
+
+    if let Some(uri) = &run.mlflow {
+        log_to_mlflow(uri, &run, &folds, &report, started_at, promote);
+    }
+
+    if let Some(filename) = &run.manifest {
+        let manifest = Manifest {
+            manifest_version: MANIFEST_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION"),
+            args: env::args().collect(),
+            config_file: config_file(args).map(str::to_string),
+            dataset: DatasetInfo {
+                sha256: match embedded_data(&run.data) {
+                    Some(contents) => manifest::hash_bytes(contents.as_bytes()),
+                    None if is_cloud_uri(&run.data) => {
+                        manifest::hash_bytes(&cloud_bytes(&run.data))
+                    }
+                    None => manifest::hash_file(&run.data).unwrap_or_else(|e| {
+                        exit_with_error(&format!("Couldn't hash {}: {}", run.data, e))
+                    }),
+                },
+                path: run.data.clone(),
+                rows,
+            },
+            config: run.config,
+            started_at: manifest::timestamp(started_at),
+            finished_at: manifest::timestamp(SystemTime::now()),
+            results: report,
+        };
 
-        res
+        manifest.save(filename).unwrap_or_else(|e| {
+            exit_with_error(&format!("Couldn't write manifest {}: {}", filename, e))
+        });
+        info!("Wrote manifest to {}", filename);
     }
+
+    log_read_summary(&reader);
+    promote.then_some(average_accuracy)
 }
 
-fn main() {
-    let data = read_input();
-    let split_data: Vec<Vec<Row>> = split_for_crossvalidation(data);
-    let mut avg_accuracy = None;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use party_recogniser_naive_bayes::data::try_parse_row;
 
-    for testing_set_idx in 0..split_data.len() {
-        let mut training_set = split_data.clone();
-        training_set.remove(testing_set_idx);
-        let training_set_merged: Vec<&Row> = training_set.iter().flatten().collect();
-        let model = Model::new(&training_set_merged);
+    #[test]
+    fn asks_for_votes_by_their_names() {
+        let metadata = AttributeMetadata::default();
+        let mut prompts = vec![];
 
-        let accuracy = model.get_accuracy(&split_data[testing_set_idx]);
-        println!("Accuracy {}: {}", testing_set_idx, accuracy);
+        let attributes = ask_votes(&metadata, &b"y\nmaybe\nn\nskip\n"[..], &mut prompts);
+        let prompts = String::from_utf8(prompts).unwrap();
 
-        if let Some(average_acc) = &mut avg_accuracy {
-            *average_acc += accuracy / CROSSVALIDATION_SPLITS as f64;
-        } else {
-            avg_accuracy = Some(accuracy / CROSSVALIDATION_SPLITS as f64);
+        assert_eq!(
+            attributes[..4],
+            [Choice::Yes, Choice::No, Choice::Unknown, Choice::Unknown]
+        );
+        assert!(attributes[4..]
+            .iter()
+            .all(|&choice| choice == Choice::Unknown));
+        assert!(prompts.starts_with(&format!("[1/16] {}? ", metadata.name(0))));
+        assert!(prompts.contains("Please answer y, n or skip"));
+        // Up to the question the input ended at
+        assert!(prompts.contains(metadata.name(3)));
+        assert!(!prompts.contains(metadata.name(4)));
+    }
+
+    #[test]
+    fn names_unseen_votes_by_the_metadata() {
+        let row = try_parse_row("democrat,y,n,y,n,y,n,y,n,y,n,y,n,y,n,y,n").unwrap();
+        let model = Model::from_rows(vec![row.clone()]);
+        let metadata = AttributeMetadata::default();
+        let mut attributes = row.attributes.clone();
+        attributes[1] = Choice::Yes;
+
+        assert_eq!(
+            check_unseen(&model, &metadata, &attributes, Unseen::Error).unwrap_err(),
+            format!(
+                "Never saw these votes in training: y on {}",
+                metadata.name(1)
+            )
+        );
+        assert!(check_unseen(&model, &metadata, &attributes, Unseen::Warn).is_ok());
+        assert!(check_unseen(&model, &metadata, &row.attributes, Unseen::Error).is_ok());
+    }
+
+    #[test]
+    fn watches_under_train() {
+        let args = Args::try_parse_from(["party", "train", "--watch"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Train { watch: true, .. })
+        ));
+        assert!(Args::try_parse_from(["party", "--watch"]).is_err());
+    }
+
+    #[test]
+    fn schedules_under_train() {
+        let args = Args::try_parse_from(["party", "train", "--every", "6h"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Command::Train { every: Some(interval), .. })
+                if interval == Duration::from_secs(6 * 60 * 60)
+        ));
+        assert!(Args::try_parse_from(["party", "--every", "6h"]).is_err());
+        assert!(Args::try_parse_from(["party", "train", "--every", "6h", "--watch"]).is_err());
+    }
+
+    #[test]
+    fn answers_a_record_per_line() {
+        let rows: Vec<_> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let model = Model::from_rows(rows.iter().cloned());
+        let votes = |row: &Row| row.to_string().split_once(',').unwrap().1.to_string();
+        let input = format!("{}\n\ny,n\n{}\n", votes(&rows[0]), votes(&rows[1]));
+        let mut output = vec![];
+        let mut errors = vec![];
+
+        answer_records(
+            &model,
+            &AttributeMetadata::default(),
+            Unseen::Warn,
+            None,
+            input.as_bytes(),
+            &mut output,
+            &mut errors,
+        );
+
+        let output = String::from_utf8(output).unwrap();
+        let answers: Vec<_> = output.lines().collect();
+        assert_eq!(answers.len(), 2);
+        for (answer, row) in answers.iter().zip(&rows) {
+            let (class, probabilities) = model.classify_with_probabilities(&row.attributes);
+            assert_eq!(
+                *answer,
+                format!(
+                    "{} republican={:.4} democrat={:.4}",
+                    class.name(),
+                    probabilities[Class::Republican.index()],
+                    probabilities[Class::Democrat.index()]
+                )
+            );
+        }
+        // Blank lines are skipped, and bad ones don't end the session
+        assert_eq!(
+            String::from_utf8(errors).unwrap(),
+            "Expected 16 votes, got 2\n"
+        );
+    }
+
+    #[test]
+    fn logs_at_the_level_of_the_flags() {
+        let level = |args: &[&str], env_level| {
+            let args = Args::try_parse_from(["party"].iter().chain(args)).unwrap();
+            log_level(&args, env_level)
+        };
+
+        assert_eq!(level(&[], None), Ok((Level::INFO, FmtSpan::NONE)));
+        assert_eq!(level(&["-v"], None), Ok((Level::DEBUG, FmtSpan::NONE)));
+        assert_eq!(level(&["-vv"], None), Ok((Level::TRACE, FmtSpan::CLOSE)));
+        assert_eq!(level(&["--quiet"], None), Ok((Level::ERROR, FmtSpan::NONE)));
+        assert_eq!(level(&[], Some("warn")), Ok((Level::WARN, FmtSpan::NONE)));
+        assert_eq!(
+            level(&["-v"], Some("warn")),
+            Ok((Level::DEBUG, FmtSpan::NONE))
+        );
+        assert_eq!(
+            level(&[], Some("loud")),
+            Err("Unknown log level 'loud'".to_string())
+        );
+        assert!(Args::try_parse_from(["party", "-v", "--quiet"]).is_err());
+    }
+
+    #[test]
+    fn completes_subcommands_flags_and_values() {
+        let mut command = Args::command();
+        let mut script = vec![];
+        clap_complete::generate(Shell::Bash, &mut command, "party", &mut script);
+        let script = String::from_utf8(script).unwrap();
+
+        assert!(script.contains("predict"));
+        assert!(script.contains("--positive-class"));
+        assert!(script.contains("--log-format"));
+        assert!(script.contains("text json"));
+
+        for shell in [Shell::Zsh, Shell::Fish] {
+            let mut script = vec![];
+            clap_complete::generate(shell, &mut command, "party", &mut script);
+            assert!(String::from_utf8(script).unwrap().contains("predict"));
+        }
+    }
+
+    #[cfg(feature = "embedded-data")]
+    #[test]
+    fn only_embeds_the_default_dataset_when_its_missing() {
+        let rows: Vec<Row> = EMBEDDED_DATA
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        assert_eq!(rows.len(), 435);
+
+        // The tests run next to the file
+        assert!(Path::new(FILENAME).exists());
+        assert_eq!(embedded_data(FILENAME), None);
+        assert_eq!(embedded_data("missing.data"), None);
+    }
+
+    #[test]
+    fn saves_every_fold_and_picks_the_earliest_best_one() {
+        let rows = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let crossvalidation = CrossValidation {
+            seed: Some(1),
+            ..CrossValidation::new(5)
+        };
+        let mut folds: Vec<_> = crossvalidation.run(rows).unwrap().collect();
+        folds[1].accuracy = 1.0;
+        folds[3].accuracy = 1.0;
+        assert_eq!(best_fold(&folds).unwrap().fold, folds[1].fold);
+        assert!(best_fold(&[]).is_none());
+
+        let dir = env::temp_dir().join(format!("fold-models-{}", process::id()));
+        let keys = Args::try_parse_from(["party"]).unwrap().keys;
+        save_fold_models(&folds, &dir.to_string_lossy(), &keys, None);
+
+        for fold in &folds {
+            let path = dir.join(format!("fold-{}.json", fold.fold));
+            let model = Model::load(&path.to_string_lossy()).unwrap();
+            assert_eq!(model.rows_count(), fold.model.rows_count());
+
+            let metrics = fs::read_to_string(path.with_extension("metrics.json")).unwrap();
+            let metrics: serde_json::Value = serde_json::from_str(&metrics).unwrap();
+            assert_eq!(metrics["fold"], fold.fold);
+            assert_eq!(metrics["testing_rows"], fold.testing_set.len());
         }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn trains_a_final_model_even_with_the_best_fold() {
+        let run = |args: &[&str]| {
+            let args = ["party", "--metrics", "accuracy"].iter().chain(args);
+            Run::new(&Args::try_parse_from(args).unwrap())
+        };
+
+        assert!(!run(&[]).needs_full_model());
+        assert!(run(&["--save-model", "model.json"]).needs_full_model());
+        assert!(!run(&["--save-model", "model.json", "--best-fold"]).needs_full_model());
+        let train_final = run(&["--best-fold", "--train-final", "final.json"]);
+        assert!(train_final.needs_full_model());
+        assert_eq!(train_final.train_final.as_deref(), Some("final.json"));
+    }
+
+    #[test]
+    fn evaluates_the_metrics_that_were_asked_for() {
+        let rows: Vec<Row> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let (train, test) = rows.split_at(300);
+        let model = Model::from_rows(train.iter().cloned());
+        let evaluate_args = |metrics: &str| {
+            let args = [
+                "party",
+                "evaluate",
+                "--model",
+                "m.json",
+                "--test",
+                "t.data",
+                "--metrics",
+                metrics,
+            ];
+            match Args::try_parse_from(args).unwrap().command {
+                Some(Command::Evaluate(args)) => args,
+                _ => unreachable!(),
+            }
+        };
+        let report = |metrics| {
+            let scores = model.score_batch(test);
+            let metadata = AttributeMetadata::default();
+            evaluation_report(&model, test, &scores, &evaluate_args(metrics), &metadata)
+        };
+
+        let (confusion, accuracy) = report("accuracy");
+        assert_eq!(accuracy.rows, test.len());
+        assert_eq!(accuracy.accuracy, Some(model.get_accuracy(test)));
+        assert!((accuracy.log_likelihood.unwrap() - model.score(test)).abs() < 1e-9);
+        assert_eq!(confusion.total() as usize, test.len());
+        assert!(accuracy.confusion_matrix.is_none() && accuracy.lift.is_none());
+
+        let (_, lift) = report("lift,calibration");
+        assert!(lift.accuracy.is_none());
+        assert_eq!(lift.lift.unwrap().len(), 10);
+        assert_eq!(
+            lift.calibration.unwrap().bins.len(),
+            curves::CALIBRATION_BINS
+        );
+        assert_eq!(lift.positive_class, "republican");
+    }
+
+    #[test]
+    fn picks_the_most_accurate_kind_of_model() {
+        let rows: Vec<Row> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let crossvalidation = CrossValidation {
+            seed: Some(1),
+            ..CrossValidation::new(3)
+        };
+        let results = select_model(&rows, &crossvalidation);
+
+        let mut models: Vec<_> = results.iter().map(|x| x.candidate.model).collect();
+        assert_eq!(models.len(), AUTO_CANDIDATES.len());
+        models.sort_by_key(|model| model.name());
+        models.dedup();
+        assert_eq!(models.len(), AUTO_CANDIDATES.len());
+        assert!(results.windows(2).all(|x| x[0].accuracy >= x[1].accuracy));
     }
 
-    println!("Average accuracy: {}", avg_accuracy.unwrap());
+    #[test]
+    fn runs_with_the_settings_of_the_spec() {
+        let spec = env::temp_dir().join(format!("spec-{}.yaml", process::id()));
+        fs::write(&spec, "data: votes.data\nsmoothing: 0.5\n").unwrap();
+        let args = Args::try_parse_from(["party", "run", "--spec", spec.to_str().unwrap()]);
+        let run = Run::new(&args.unwrap());
+        fs::remove_file(spec).unwrap();
+
+        assert_eq!(run.data, "votes.data");
+        assert_eq!(run.crossvalidation.smoothing, 0.5);
+    }
 }
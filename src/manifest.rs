@@ -0,0 +1,150 @@
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::time::SystemTime;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::config::RunConfig;
+use crate::output::RunReport;
+
+// Bumped whenever a field of the manifest is renamed, removed or changes
+// meaning
+pub const MANIFEST_VERSION: u32 = 1;
+
+// Everything needed to reproduce a run and compare it with other runs
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    pub manifest_version: u32,
+    pub crate_version: &'static str,
+    // Command line the run was started with
+    pub args: Vec<String>,
+    pub config_file: Option<String>,
+    // The settings after layering the flags, config file, environment and
+    // defaults, including the seed the folds were shuffled with
+    pub config: RunConfig,
+    pub dataset: DatasetInfo,
+    // RFC 3339 in UTC
+    pub started_at: String,
+    pub finished_at: String,
+    pub results: RunReport,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DatasetInfo {
+    pub path: String,
+    pub sha256: String,
+    pub rows: usize,
+}
+
+impl Manifest {
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        writer.flush()
+    }
+}
+
+pub fn timestamp(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+// Hex SHA-256 of the file, so a manifest can tell whether the data changed
+pub fn hash_file(filename: &str) -> io::Result<String> {
+    let mut reader = BufReader::new(File::open(filename)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = [0; 8192];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hex(&hasher.finalize()))
+}
+
+// Same as hash_file, for data that's already in memory
+pub fn hash_bytes(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    use serde_json::Value;
+
+    use super::*;
+
+    #[test]
+    fn hashes_files_like_their_bytes() {
+        let filename = std::env::temp_dir().join(format!("hash-{}.data", std::process::id()));
+        let contents = include_str!("../house-votes-84.data");
+        fs::write(&filename, contents).unwrap();
+        let hashed = hash_file(filename.to_str().unwrap());
+        fs::remove_file(&filename).unwrap();
+
+        assert_eq!(hashed.unwrap(), hash_bytes(contents.as_bytes()));
+        assert_eq!(
+            hash_bytes(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert!(hash_file("missing.data").is_err());
+    }
+
+    #[test]
+    fn saves_what_the_run_needs_to_be_reproduced() {
+        let manifest = Manifest {
+            manifest_version: MANIFEST_VERSION,
+            crate_version: env!("CARGO_PKG_VERSION"),
+            args: vec!["party".to_string(), "--seed".to_string(), "42".to_string()],
+            config_file: None,
+            config: RunConfig {
+                seed: Some(42),
+                ..RunConfig::default()
+            },
+            dataset: DatasetInfo {
+                path: "house-votes-84.data".to_string(),
+                sha256: hash_bytes(b""),
+                rows: 435,
+            },
+            started_at: timestamp(UNIX_EPOCH),
+            finished_at: timestamp(UNIX_EPOCH + Duration::from_secs(90)),
+            results: RunReport {
+                schema_version: 2,
+                folds: None,
+                average_accuracy: Some(0.9),
+                average_log_likelihood: None,
+                confusion_matrix: None,
+                attributes: None,
+                average_precision: None,
+                lift: None,
+                calibration: None,
+                misclassified: None,
+                memory: None,
+                positive_class: None,
+                model_selection: None,
+            },
+        };
+        let filename = std::env::temp_dir().join(format!("manifest-{}.json", std::process::id()));
+        manifest.save(filename.to_str().unwrap()).unwrap();
+        let saved: Value = serde_json::from_str(&fs::read_to_string(&filename).unwrap()).unwrap();
+        fs::remove_file(&filename).unwrap();
+
+        assert_eq!(saved["manifest_version"], MANIFEST_VERSION);
+        assert_eq!(saved["config"]["seed"], 42);
+        assert_eq!(saved["dataset"]["rows"], 435);
+        assert_eq!(saved["started_at"], "1970-01-01T00:00:00Z");
+        assert_eq!(saved["finished_at"], "1970-01-01T00:01:30Z");
+        assert_eq!(saved["results"]["average_accuracy"], 0.9);
+    }
+}
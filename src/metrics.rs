@@ -0,0 +1,110 @@
+use std::time::Duration;
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+use crate::data::Class;
+use crate::model::Model;
+
+// Prometheus metrics of the prediction server, rendered by /metrics
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    latency: HistogramVec,
+    predictions: IntCounterVec,
+    model_info: IntGaugeVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let requests = IntCounterVec::new(
+            Opts::new("party_recogniser_requests_total", "Handled requests"),
+            &["endpoint", "status"],
+        )
+        .unwrap();
+
+        // Predictions take microseconds, so with the default buckets starting
+        // at 5ms everything would end up in the first one
+        let latency = HistogramVec::new(
+            HistogramOpts::new(
+                "party_recogniser_request_duration_seconds",
+                "Time spent handling requests",
+            )
+            .buckets(prometheus::exponential_buckets(1e-6, 4.0, 10).unwrap()),
+            &["endpoint"],
+        )
+        .unwrap();
+
+        let predictions = IntCounterVec::new(
+            Opts::new(
+                "party_recogniser_predictions_total",
+                "Predictions per class",
+            ),
+            &["class"],
+        )
+        .unwrap();
+
+        let model_info = IntGaugeVec::new(
+            Opts::new("party_recogniser_model_info", "The served model, always 1"),
+            &["path", "rows", "version", "crate_version"],
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(latency.clone())).unwrap();
+        registry.register(Box::new(predictions.clone())).unwrap();
+        registry.register(Box::new(model_info.clone())).unwrap();
+
+        Metrics {
+            registry,
+            requests,
+            latency,
+            predictions,
+            model_info,
+        }
+    }
+
+    pub fn observe_request(&self, endpoint: &str, ok: bool) {
+        let status = if ok { "ok" } else { "error" };
+        self.requests.with_label_values(&[endpoint, status]).inc();
+    }
+
+    pub fn observe_latency(&self, endpoint: &str, duration: Duration) {
+        self.latency
+            .with_label_values(&[endpoint])
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn observe_prediction(&self, class: Class) {
+        self.predictions.with_label_values(&[class.name()]).inc();
+    }
+
+    // `path` is where the model came from, a file or the /train endpoint
+    pub fn set_model_info(&self, path: &str, model: &Model) {
+        self.model_info.reset();
+        self.model_info
+            .with_label_values(&[
+                path,
+                &model.rows_count().to_string(),
+                &model.version().to_string(),
+                env!("CARGO_PKG_VERSION"),
+            ])
+            .set(1);
+    }
+
+    pub fn render(&self) -> String {
+        let mut res = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut res)
+            .unwrap();
+        String::from_utf8(res).unwrap()
+    }
+}
@@ -0,0 +1,336 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+// Sent as a bearer token when set, like the MLflow client does
+const TOKEN_VARIABLE: &str = "MLFLOW_TRACKING_TOKEN";
+// Artifact URIs of servers that store artifacts for their clients, which is
+// the only kind that can be uploaded to over REST
+const PROXIED_ARTIFACTS: &str = "mlflow-artifacts:";
+
+// Everything logged about a run once it's over
+#[derive(Debug, Clone, Default)]
+pub struct TrackedRun {
+    pub name: Option<String>,
+    pub started_at: Option<SystemTime>,
+    // See RunConfig::settings
+    pub params: Vec<(String, String)>,
+    // Key, value and step, e.g. the fold
+    pub metrics: Vec<(String, f64, usize)>,
+    pub tags: Vec<(String, String)>,
+    // Files uploaded next to the run, under their file names
+    pub artifacts: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ExperimentResponse {
+    experiment: Experiment,
+}
+
+#[derive(Deserialize)]
+struct Experiment {
+    experiment_id: String,
+}
+
+#[derive(Deserialize)]
+struct CreateExperimentResponse {
+    experiment_id: String,
+}
+
+#[derive(Deserialize)]
+struct RunResponse {
+    run: RunData,
+}
+
+#[derive(Deserialize)]
+struct RunData {
+    info: RunInfo,
+}
+
+#[derive(Deserialize)]
+struct RunInfo {
+    run_id: String,
+    artifact_uri: String,
+}
+
+// Logs the run to the experiment of the tracking server at the URI, e.g.
+// http://localhost:5000, creating the experiment first if the server has
+// none of that name. Returns the ID of the new run.
+pub fn log_run(tracking_uri: &str, experiment: &str, run: &TrackedRun) -> Result<String, String> {
+    let client = Client {
+        base: tracking_uri.trim_end_matches('/').to_string(),
+        token: env::var(TOKEN_VARIABLE).ok(),
+    };
+
+    let experiment_id = client.experiment_id(experiment)?;
+    let now = millis(SystemTime::now());
+
+    let mut create = json!({
+        "experiment_id": experiment_id,
+        "start_time": run.started_at.map_or(now, millis),
+        "tags": key_values(&run.tags),
+    });
+    if let Some(name) = &run.name {
+        create["run_name"] = json!(name);
+    }
+    let info = client.post::<RunResponse>("runs/create", &create)?.run.info;
+
+    client.post::<Value>(
+        "runs/log-batch",
+        &json!({
+            "run_id": info.run_id,
+            "params": key_values(&run.params),
+            "metrics": run
+                .metrics
+                .iter()
+                .map(|(key, value, step)| {
+                    json!({"key": key, "value": value, "timestamp": now, "step": step})
+                })
+                .collect::<Vec<_>>(),
+        }),
+    )?;
+
+    for filename in &run.artifacts {
+        client.upload(&info.artifact_uri, filename)?;
+    }
+
+    client.post::<Value>(
+        "runs/update",
+        &json!({
+            "run_id": info.run_id,
+            "status": "FINISHED",
+            "end_time": millis(SystemTime::now()),
+        }),
+    )?;
+
+    Ok(info.run_id)
+}
+
+struct Client {
+    base: String,
+    token: Option<String>,
+}
+
+impl Client {
+    fn authorize<B>(&self, request: ureq::RequestBuilder<B>) -> ureq::RequestBuilder<B> {
+        match &self.token {
+            Some(token) => request.header("Authorization", format!("Bearer {}", token)),
+            None => request,
+        }
+    }
+
+    fn post<T: for<'de> Deserialize<'de>>(
+        &self,
+        endpoint: &str,
+        body: &Value,
+    ) -> Result<T, String> {
+        let url = format!("{}/api/2.0/mlflow/{}", self.base, endpoint);
+        self.authorize(ureq::post(&url))
+            .send_json(body)
+            .and_then(|mut response| response.body_mut().read_json())
+            .map_err(|e| format!("{} failed: {}", url, e))
+    }
+
+    fn experiment_id(&self, name: &str) -> Result<String, String> {
+        let url = format!("{}/api/2.0/mlflow/experiments/get-by-name", self.base);
+        match self
+            .authorize(ureq::get(&url))
+            .query("experiment_name", name)
+            .call()
+        {
+            Ok(mut response) => response
+                .body_mut()
+                .read_json::<ExperimentResponse>()
+                .map(|response| response.experiment.experiment_id)
+                .map_err(|e| format!("{} failed: {}", url, e)),
+            // There's no experiment of that name yet
+            Err(ureq::Error::StatusCode(404)) => self
+                .post::<CreateExperimentResponse>("experiments/create", &json!({"name": name}))
+                .map(|response| response.experiment_id),
+            Err(e) => Err(format!("{} failed: {}", url, e)),
+        }
+    }
+
+    fn upload(&self, artifact_uri: &str, filename: &str) -> Result<(), String> {
+        let location = artifact_uri
+            .strip_prefix(PROXIED_ARTIFACTS)
+            .map(|location| location.trim_start_matches('/'))
+            .ok_or_else(|| {
+                format!(
+                    "The server stores artifacts at {}, only servers started with --serve-artifacts take uploads",
+                    artifact_uri
+                )
+            })?;
+        let name = Path::new(filename)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| filename.to_string());
+        let contents =
+            fs::read(filename).map_err(|e| format!("Couldn't read {}: {}", filename, e))?;
+
+        let url = format!(
+            "{}/api/2.0/mlflow-artifacts/artifacts/{}/{}",
+            self.base, location, name
+        );
+        self.authorize(ureq::put(&url))
+            .send(&contents[..])
+            .map(|_| ())
+            .map_err(|e| format!("Uploading {} to {} failed: {}", filename, url, e))
+    }
+}
+
+fn key_values(pairs: &[(String, String)]) -> Vec<Value> {
+    pairs
+        .iter()
+        .map(|(key, value)| json!({"key": key, "value": value}))
+        .collect()
+}
+
+fn millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    use super::*;
+
+    // Method, path and body of every request the fake server got
+    type Requests = Arc<Mutex<Vec<(String, String, String)>>>;
+
+    // A tracking server without experiments that gives new runs the
+    // artifact URI, returning its URI
+    fn serve(artifact_uri: &'static str, requests: Requests) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let uri = format!("http://{}", listener.local_addr().unwrap());
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let mut parts = request.split(' ');
+                let (method, path) = (parts.next().unwrap(), parts.next().unwrap());
+                let (status, reply) = match path.split('?').next().unwrap() {
+                    "/api/2.0/mlflow/experiments/get-by-name" => ("404 Not Found", json!({})),
+                    "/api/2.0/mlflow/experiments/create" => {
+                        ("200 OK", json!({"experiment_id": "7"}))
+                    }
+                    "/api/2.0/mlflow/runs/create" => (
+                        "200 OK",
+                        json!({"run": {"info": {"run_id": "r1", "artifact_uri": artifact_uri}}}),
+                    ),
+                    _ => ("200 OK", json!({})),
+                };
+                requests.lock().unwrap().push((
+                    method.to_string(),
+                    path.to_string(),
+                    String::from_utf8(body).unwrap(),
+                ));
+
+                let reply = reply.to_string();
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    reply.len(),
+                    reply
+                )
+                .unwrap();
+            }
+        });
+
+        uri
+    }
+
+    fn tracked_run(artifact: &str) -> TrackedRun {
+        TrackedRun {
+            name: Some("nightly".to_string()),
+            params: vec![("smoothing".to_string(), "1".to_string())],
+            metrics: vec![("accuracy".to_string(), 0.9, 1)],
+            artifacts: vec![artifact.to_string()],
+            ..TrackedRun::default()
+        }
+    }
+
+    #[test]
+    fn creates_the_experiment_and_logs_the_run_to_it() {
+        let artifact = std::env::temp_dir().join(format!("mlflow-{}.json", std::process::id()));
+        let artifact = artifact.to_str().unwrap();
+        fs::write(artifact, "{}").unwrap();
+        let requests = Requests::default();
+        let uri = serve("mlflow-artifacts:/7/r1/artifacts", requests.clone());
+
+        let run_id = log_run(&format!("{}/", uri), "votes", &tracked_run(artifact)).unwrap();
+        fs::remove_file(artifact).unwrap();
+        assert_eq!(run_id, "r1");
+
+        let requests = requests.lock().unwrap();
+        let paths: Vec<(&str, &str)> = requests
+            .iter()
+            .map(|(method, path, _)| (method.as_str(), path.as_str()))
+            .collect();
+        let upload = format!(
+            "/api/2.0/mlflow-artifacts/artifacts/7/r1/artifacts/mlflow-{}.json",
+            std::process::id()
+        );
+        assert_eq!(
+            paths,
+            [
+                (
+                    "GET",
+                    "/api/2.0/mlflow/experiments/get-by-name?experiment_name=votes"
+                ),
+                ("POST", "/api/2.0/mlflow/experiments/create"),
+                ("POST", "/api/2.0/mlflow/runs/create"),
+                ("POST", "/api/2.0/mlflow/runs/log-batch"),
+                ("PUT", upload.as_str()),
+                ("POST", "/api/2.0/mlflow/runs/update"),
+            ]
+        );
+
+        let body = |i: usize| serde_json::from_str::<Value>(&requests[i].2).unwrap();
+        assert_eq!(body(2)["experiment_id"], "7");
+        assert_eq!(body(2)["run_name"], "nightly");
+        assert_eq!(
+            body(3)["params"],
+            json!([{"key": "smoothing", "value": "1"}])
+        );
+        assert_eq!(body(3)["metrics"][0]["value"], 0.9);
+        assert_eq!(body(3)["metrics"][0]["step"], 1);
+        assert_eq!(requests[4].2, "{}");
+        assert_eq!(body(5)["status"], "FINISHED");
+    }
+
+    #[test]
+    fn only_uploads_to_servers_that_take_artifacts() {
+        let uri = serve("s3://bucket/7/r1/artifacts", Requests::default());
+
+        let error = log_run(&uri, "votes", &tracked_run("model.json")).unwrap_err();
+        assert!(error.contains("only servers started with --serve-artifacts take uploads"));
+    }
+}
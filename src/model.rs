@@ -0,0 +1,1369 @@
+use std::convert::TryInto;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::mem;
+
+use ed25519_dalek::VerifyingKey;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::classifier::normalize_log_probabilities;
+use crate::data::{
+    stream_input, Choice, Class, Row, ATTRIBUTES_COUNT, CHOICES, CLASSES, CLASSES_COUNT,
+};
+use crate::encryption::{self, Secret};
+use crate::model_card::ModelCard;
+use crate::privacy::noisy_count;
+use crate::signing;
+use crate::summation;
+
+pub use party_recogniser_core::Float;
+use party_recogniser_core::{argmax, LogTables};
+
+#[derive(Debug, Clone)]
+pub struct Model {
+    rows_count: u32,
+    class_counts: [u32; CLASSES_COUNT],
+    // Packed [class][attribute][choice] table of counts
+    attr_counts: Vec<u32>,
+    // Pseudo-count every choice starts with
+    smoothing: f64,
+    missing_votes: MissingVotes,
+    prior_mode: PriorMode,
+    // Only predicts from this many attributes, see Trainer::with_feature_selection
+    feature_selection: Option<usize>,
+    // Predicts threshold_class when its probability is at least this,
+    // instead of whichever class is likelier. See crate::threshold.
+    threshold: Option<f64>,
+    threshold_class: Class,
+    // Starts at 1 and goes up every time rows are added with partial_fit
+    version: u32,
+    // Of the run the model was saved by, see crate::model_card
+    card: Option<ModelCard>,
+    // log10 probabilities, precomputed so that prediction doesn't have to
+    // call log10() for every attribute
+    log_tables: LogTables,
+    // The choices of every attribute that training counted at least once,
+    // in the order of CHOICES
+    vocabulary: Vec<Vec<Choice>>,
+    // Whether each attribute is one of the feature_selection it predicts from
+    selected: Vec<bool>,
+}
+
+// What gets written to model files. The log tables and vocabulary are
+// derived from the counts, so they are recomputed on load instead of being
+// stored.
+#[derive(Serialize, Deserialize)]
+struct SavedModel {
+    rows_count: u32,
+    class_counts: [u32; CLASSES_COUNT],
+    attr_counts: Vec<u32>,
+    // Missing from models saved before smoothing was configurable
+    #[serde(default = "default_smoothing")]
+    smoothing: f64,
+    // Left out when unknown votes are a category, like in models saved
+    // before it was configurable
+    #[serde(default, skip_serializing_if = "MissingVotes::is_category")]
+    missing_votes: MissingVotes,
+    // Both left out for the settings of models saved before they were
+    // configurable
+    #[serde(default, skip_serializing_if = "PriorMode::is_learned")]
+    prior_mode: PriorMode,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    feature_selection: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    threshold: Option<f64>,
+    // Left out for Republicans, the class of thresholds saved before it was
+    // configurable
+    #[serde(
+        default = "default_threshold_class",
+        skip_serializing_if = "is_republican"
+    )]
+    threshold_class: Class,
+    // Missing from models saved before they were versioned, which count as
+    // the first version
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    card: Option<ModelCard>,
+}
+
+pub const DEFAULT_SMOOTHING: f64 = 1.0;
+
+// What training makes of unknown votes. Whether they're worth counting
+// depends on whether abstaining says something about a member.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum MissingVotes {
+    // A third choice next to yes and no, counted like them
+    #[default]
+    Category,
+    // Not counted, so the probabilities of an attribute are only of the
+    // rows that voted on it, and unknown votes aren't evidence either way
+    // when predicting
+    Ignore,
+}
+
+impl MissingVotes {
+    // As given to --missing-votes
+    pub fn name(self) -> &'static str {
+        match self {
+            MissingVotes::Category => "category",
+            MissingVotes::Ignore => "ignore",
+        }
+    }
+
+    fn is_category(&self) -> bool {
+        *self == MissingVotes::Category
+    }
+}
+
+// Where prediction starts from before it looks at the votes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PriorMode {
+    // The share of the training rows in each class
+    #[default]
+    Learned,
+    // Every class as likely, so a class being common in the training data
+    // doesn't sway close calls
+    Uniform,
+}
+
+impl PriorMode {
+    // As given to --prior-mode
+    pub fn name(self) -> &'static str {
+        match self {
+            PriorMode::Learned => "learned",
+            PriorMode::Uniform => "uniform",
+        }
+    }
+
+    fn is_learned(&self) -> bool {
+        *self == PriorMode::Learned
+    }
+}
+
+// How model files are read, see Model::load_with
+#[derive(Default)]
+pub struct LoadOptions {
+    // Decrypts models saved with Model::save_encrypted
+    pub secret: Option<Secret>,
+    // Rejects models that aren't signed with the matching key, see
+    // crate::signing
+    pub verifying_key: Option<VerifyingKey>,
+}
+
+fn default_smoothing() -> f64 {
+    DEFAULT_SMOOTHING
+}
+
+fn default_threshold_class() -> Class {
+    Class::Republican
+}
+
+fn is_republican(class: &Class) -> bool {
+    *class == Class::Republican
+}
+
+// Accumulates counts one row at a time, so a model can be trained in a
+// single pass over data that doesn't fit in memory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Trainer {
+    rows_count: u32,
+    class_counts: [u32; CLASSES_COUNT],
+    // Packed [class][attribute][choice] table of counts
+    attr_counts: Vec<u32>,
+    smoothing: f64,
+    // Missing from checkpoints saved before they were configurable
+    #[serde(default)]
+    missing_votes: MissingVotes,
+    #[serde(default)]
+    prior_mode: PriorMode,
+    #[serde(default)]
+    feature_selection: Option<usize>,
+}
+
+impl Default for Trainer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Trainer {
+    pub fn new() -> Self {
+        Trainer {
+            rows_count: 0,
+            class_counts: [0; CLASSES_COUNT],
+            attr_counts: vec![0; CLASSES_COUNT * ATTRIBUTES_COUNT * CHOICES.len()],
+            smoothing: DEFAULT_SMOOTHING,
+            missing_votes: MissingVotes::Category,
+            prior_mode: PriorMode::Learned,
+            feature_selection: None,
+        }
+    }
+
+    // Sets the pseudo-count every choice starts with, so unseen choices
+    // don't zero out the whole prediction
+    pub fn with_smoothing(mut self, smoothing: f64) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+
+    pub fn smoothing(&self) -> f64 {
+        self.smoothing
+    }
+
+    pub fn with_missing_votes(mut self, missing_votes: MissingVotes) -> Self {
+        self.missing_votes = missing_votes;
+        self
+    }
+
+    pub fn missing_votes(&self) -> MissingVotes {
+        self.missing_votes
+    }
+
+    pub fn with_prior_mode(mut self, prior_mode: PriorMode) -> Self {
+        self.prior_mode = prior_mode;
+        self
+    }
+
+    pub fn prior_mode(&self) -> PriorMode {
+        self.prior_mode
+    }
+
+    // Only predicts from the k attributes that tell the classes apart best,
+    // see Model::discriminative_attributes, or from all of them with None.
+    // The others are still counted.
+    pub fn with_feature_selection(mut self, k: Option<usize>) -> Self {
+        self.feature_selection = k;
+        self
+    }
+
+    pub fn feature_selection(&self) -> Option<usize> {
+        self.feature_selection
+    }
+
+    fn attr_idx(class: Class, attribute: usize, choice: Choice) -> usize {
+        (class.index() * ATTRIBUTES_COUNT + attribute) * CHOICES.len() + choice.index()
+    }
+
+    pub fn add(&mut self, row: &Row) {
+        self.rows_count += 1;
+        self.class_counts[row.class.index()] += 1;
+
+        for (i, &choice) in row.attributes.iter().enumerate() {
+            if choice == Choice::Unknown && self.missing_votes == MissingVotes::Ignore {
+                continue;
+            }
+            self.attr_counts[Self::attr_idx(row.class, i, choice)] += 1;
+        }
+    }
+
+    // Combines the counts of a trainer that has seen a different chunk of
+    // the data
+    pub fn merge(&mut self, other: &Trainer) {
+        self.rows_count += other.rows_count;
+
+        for (count, other_count) in self.class_counts.iter_mut().zip(&other.class_counts) {
+            *count += other_count;
+        }
+
+        for (count, other_count) in self.attr_counts.iter_mut().zip(&other.attr_counts) {
+            *count += other_count;
+        }
+    }
+
+    pub fn build(&self) -> Model {
+        Model::from_counts(
+            self.rows_count,
+            self.class_counts,
+            self.attr_counts.clone(),
+            self.smoothing,
+            self.missing_votes,
+            self.prior_mode,
+            self.feature_selection,
+        )
+    }
+
+    // Builds a model that can be released without revealing much about any
+    // single row, by adding Laplace noise to every count. A smaller epsilon
+    // means more noise and more privacy.
+    pub fn build_private<R: Rng>(&self, epsilon: f64, rng: &mut R) -> Model {
+        let class_counts = self
+            .class_counts
+            .map(|count| noisy_count(count, epsilon, rng));
+        let attr_counts = self
+            .attr_counts
+            .iter()
+            .map(|&count| noisy_count(count, epsilon, rng))
+            .collect();
+
+        Model::from_counts(
+            // The total would give away the true number of rows
+            class_counts.iter().sum(),
+            class_counts,
+            attr_counts,
+            self.smoothing,
+            self.missing_votes,
+            self.prior_mode,
+            self.feature_selection,
+        )
+    }
+}
+
+impl Model {
+    fn from_counts(
+        rows_count: u32,
+        class_counts: [u32; CLASSES_COUNT],
+        attr_counts: Vec<u32>,
+        smoothing: f64,
+        missing_votes: MissingVotes,
+        prior_mode: PriorMode,
+        feature_selection: Option<usize>,
+    ) -> Self {
+        let mut model = Model {
+            rows_count,
+            class_counts,
+            attr_counts,
+            smoothing,
+            missing_votes,
+            prior_mode,
+            feature_selection,
+            threshold: None,
+            threshold_class: Class::Republican,
+            version: 1,
+            card: None,
+            log_tables: LogTables::new(0, 0, vec![], vec![]),
+            vocabulary: vec![],
+            selected: vec![],
+        };
+
+        model.finalize();
+        model
+    }
+
+    pub fn new(data: &[&Row]) -> Self {
+        let mut trainer = Trainer::new();
+
+        for row in data {
+            trainer.add(row);
+        }
+
+        trainer.build()
+    }
+
+    pub fn from_rows<I: IntoIterator<Item = Row>>(rows: I) -> Self {
+        let mut trainer = Trainer::new();
+
+        for row in rows {
+            trainer.add(&row);
+        }
+
+        trainer.build()
+    }
+
+    // Trains on the file in a single streaming pass without loading it.
+    // Fails if it can't be opened, or at the first line that can't be read or
+    // parsed.
+    pub fn from_file(filename: &str) -> Result<Self, String> {
+        let mut trainer = Trainer::new();
+
+        for row in stream_input(filename)? {
+            trainer.add(&row?);
+        }
+
+        Ok(trainer.build())
+    }
+
+    // Precompute the log probabilities used by prediction
+    fn finalize(&mut self) {
+        self.vocabulary = (0..ATTRIBUTES_COUNT)
+            .map(|i| {
+                CHOICES
+                    .iter()
+                    .copied()
+                    .filter(|&choice| {
+                        CLASSES
+                            .iter()
+                            .any(|&class| self.attr_counts[Trainer::attr_idx(class, i, choice)] > 0)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        self.selected = vec![self.feature_selection.is_none(); ATTRIBUTES_COUNT];
+        if let Some(k) = self.feature_selection {
+            for (i, _) in self.discriminative_attributes().into_iter().take(k) {
+                self.selected[i] = true;
+            }
+        }
+
+        let class_weights = CLASSES
+            .iter()
+            .map(|&class| self.prior(class).log10() as Float)
+            .collect();
+
+        // The same probabilities that conditional_probability reports, so
+        // what's inspected is what predicts
+        let mut attr_weights = Vec::with_capacity(ATTRIBUTES_COUNT * CHOICES.len() * CLASSES_COUNT);
+
+        for i in 0..ATTRIBUTES_COUNT {
+            for &choice in CHOICES.iter() {
+                for &class in CLASSES.iter() {
+                    attr_weights.push(if !self.is_counted(i, choice) {
+                        // The same for both classes, so it doesn't sway
+                        // the prediction
+                        0.0
+                    } else {
+                        self.conditional_probability(class, i, choice).log10() as Float
+                    });
+                }
+            }
+        }
+
+        self.log_tables =
+            LogTables::new(ATTRIBUTES_COUNT, CHOICES.len(), class_weights, attr_weights);
+    }
+
+    fn to_saved(&self) -> SavedModel {
+        SavedModel {
+            rows_count: self.rows_count,
+            class_counts: self.class_counts,
+            attr_counts: self.attr_counts.clone(),
+            smoothing: self.smoothing,
+            missing_votes: self.missing_votes,
+            prior_mode: self.prior_mode,
+            feature_selection: self.feature_selection,
+            threshold: self.threshold,
+            threshold_class: self.threshold_class,
+            version: Some(self.version),
+            card: self.card.clone(),
+        }
+    }
+
+    // Hex SHA-256 of the counts, settings and threshold, the same for models that
+    // predict the same way however they were saved or loaded. The version
+    // and card aren't part of it.
+    pub fn fingerprint(&self) -> String {
+        let saved = SavedModel {
+            version: None,
+            card: None,
+            ..self.to_saved()
+        };
+        let json = serde_json::to_vec(&saved).unwrap();
+        Sha256::digest(&json)
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect()
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        serde_json::to_writer_pretty(&mut writer, &self.to_saved())?;
+        writer.flush()
+    }
+
+    // The counts reveal how each class voted, so models trained on private
+    // data shouldn't be stored in plaintext
+    pub fn save_encrypted(&self, filename: &str, secret: &Secret) -> io::Result<()> {
+        let json = serde_json::to_vec_pretty(&self.to_saved())?;
+        fs::write(filename, encryption::encrypt(&json, secret)?)
+    }
+
+    pub fn load(filename: &str) -> io::Result<Self> {
+        Self::load_with(filename, &LoadOptions::default())
+    }
+
+    // Loads plaintext models as well as ones saved with save_encrypted, which
+    // need the secret they were encrypted with
+    pub fn load_with(filename: &str, options: &LoadOptions) -> io::Result<Self> {
+        let contents = fs::read(filename)?;
+        if let Some(key) = &options.verifying_key {
+            signing::verify(filename, &contents, key)?;
+        }
+
+        let saved: SavedModel = if encryption::is_encrypted(&contents) {
+            let secret = options.secret.as_ref().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Model is encrypted, a passphrase or key file is needed",
+                )
+            })?;
+            serde_json::from_slice(&encryption::decrypt(&contents, secret)?)?
+        } else {
+            serde_json::from_slice(&contents)?
+        };
+
+        if saved.attr_counts.len() != CLASSES_COUNT * ATTRIBUTES_COUNT * CHOICES.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Model has unexpected dimensions",
+            ));
+        }
+
+        if !saved
+            .feature_selection
+            .is_none_or(|k| (1..=ATTRIBUTES_COUNT).contains(&k))
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Model selects more attributes than it has, or none",
+            ));
+        }
+
+        if let Some(threshold) = saved.threshold {
+            if !(0.0..=1.0).contains(&threshold) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Model has a threshold outside of 0 to 1",
+                ));
+            }
+        }
+
+        let mut model = Self::from_counts(
+            saved.rows_count,
+            saved.class_counts,
+            saved.attr_counts,
+            saved.smoothing,
+            saved.missing_votes,
+            saved.prior_mode,
+            saved.feature_selection,
+        );
+        model.version = saved.version.unwrap_or(1);
+        model.card = saved.card;
+        Ok(model
+            .with_threshold(saved.threshold)
+            .with_threshold_class(saved.threshold_class))
+    }
+
+    // Number of rows the model was trained on
+    pub fn rows_count(&self) -> u32 {
+        self.rows_count
+    }
+
+    pub fn class_count(&self, class: Class) -> u32 {
+        self.class_counts[class.index()]
+    }
+
+    // Probability of the class before the votes, which prediction starts
+    // from: the share of the training rows that belong to it, unless the
+    // priors are uniform
+    pub fn prior(&self, class: Class) -> f64 {
+        match self.prior_mode {
+            PriorMode::Learned => self.class_count(class) as f64 / self.rows_count as f64,
+            PriorMode::Uniform => 1.0 / CLASSES_COUNT as f64,
+        }
+    }
+
+    pub fn smoothing(&self) -> f64 {
+        self.smoothing
+    }
+
+    pub fn missing_votes(&self) -> MissingVotes {
+        self.missing_votes
+    }
+
+    pub fn prior_mode(&self) -> PriorMode {
+        self.prior_mode
+    }
+
+    pub fn feature_selection(&self) -> Option<usize> {
+        self.feature_selection
+    }
+
+    // Whether prediction looks at the attribute, see
+    // Trainer::with_feature_selection
+    pub fn is_selected(&self, attribute: usize) -> bool {
+        self.selected[attribute]
+    }
+
+    fn is_ignored(&self, choice: Choice) -> bool {
+        choice == Choice::Unknown && self.missing_votes == MissingVotes::Ignore
+    }
+
+    // Whether the vote is evidence prediction weighs
+    fn is_counted(&self, attribute: usize, choice: Choice) -> bool {
+        self.is_selected(attribute) && !self.is_ignored(choice)
+    }
+
+    fn voted_class_count(&self, class: Class, attribute: usize) -> u32 {
+        match self.missing_votes {
+            MissingVotes::Category => self.class_count(class),
+            MissingVotes::Ignore => CHOICES
+                .iter()
+                .map(|&choice| self.attr_counts[Trainer::attr_idx(class, attribute, choice)])
+                .sum(),
+        }
+    }
+
+    // attribute is the 0-based index
+    pub fn vocabulary(&self, attribute: usize) -> &[Choice] {
+        &self.vocabulary[attribute]
+    }
+
+    // Attributes where the vote is one the model never saw in training, so
+    // its probabilities only come from smoothing. Ignored votes are never
+    // unseen.
+    pub fn unseen_votes(&self, attributes: &[Choice]) -> Vec<usize> {
+        attributes
+            .iter()
+            .enumerate()
+            .filter(|&(i, &choice)| {
+                !self.is_ignored(choice) && !self.vocabulary[i].contains(&choice)
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    pub fn threshold(&self) -> Option<f64> {
+        self.threshold
+    }
+
+    pub fn threshold_class(&self) -> Class {
+        self.threshold_class
+    }
+
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn card(&self) -> Option<&ModelCard> {
+        self.card.as_ref()
+    }
+
+    // Counts the rows on top of the ones the model was trained on, as if
+    // they had been in its training data. The new model is the next version
+    // and keeps the threshold, but not the card, which is of the old rows.
+    pub fn partial_fit<'a, I: IntoIterator<Item = &'a Row>>(&self, rows: I) -> Model {
+        let mut trainer = Trainer {
+            rows_count: self.rows_count,
+            class_counts: self.class_counts,
+            attr_counts: self.attr_counts.clone(),
+            smoothing: self.smoothing,
+            missing_votes: self.missing_votes,
+            prior_mode: self.prior_mode,
+            feature_selection: self.feature_selection,
+        };
+        for row in rows {
+            trainer.add(row);
+        }
+
+        let mut model = trainer
+            .build()
+            .with_threshold(self.threshold)
+            .with_threshold_class(self.threshold_class);
+        model.version = self.version + 1;
+        model
+    }
+
+    // Trains on the rows alone, with the settings and threshold of the
+    // model, as its next version
+    pub fn refit<'a, I: IntoIterator<Item = &'a Row>>(&self, rows: I) -> Model {
+        let mut trainer = Trainer::new()
+            .with_smoothing(self.smoothing)
+            .with_missing_votes(self.missing_votes)
+            .with_prior_mode(self.prior_mode)
+            .with_feature_selection(self.feature_selection);
+        for row in rows {
+            trainer.add(row);
+        }
+
+        let mut model = trainer
+            .build()
+            .with_threshold(self.threshold)
+            .with_threshold_class(self.threshold_class);
+        model.version = self.version + 1;
+        model
+    }
+
+    pub fn with_card(mut self, card: Option<ModelCard>) -> Self {
+        self.card = card;
+        self
+    }
+
+    // Sets the probability the threshold class needs to be predicted, or
+    // goes back to predicting the likelier class with None
+    pub fn with_threshold(mut self, threshold: Option<f64>) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    // The class the threshold is for, Republicans by default
+    pub fn with_threshold_class(mut self, class: Class) -> Self {
+        self.threshold_class = class;
+        self
+    }
+
+    // P(choice | class), with every choice starting with the smoothing count.
+    // Ignored unknown votes have a probability of 0, and the other choices
+    // are of the rows that voted.
+    pub fn conditional_probability(&self, class: Class, attribute: usize, choice: Choice) -> f64 {
+        if self.is_ignored(choice) {
+            return 0.0;
+        }
+
+        let choices = match self.missing_votes {
+            MissingVotes::Category => CHOICES.len(),
+            MissingVotes::Ignore => CHOICES.len() - 1,
+        };
+        let voted =
+            self.voted_class_count(class, attribute) as f64 + self.smoothing * choices as f64;
+        // Without smoothing, a class that nobody voted on the attribute in
+        // has nothing to go by, so every choice is as likely
+        if voted == 0.0 {
+            return 1.0 / choices as f64;
+        }
+
+        let count = self.attr_counts[Trainer::attr_idx(class, attribute, choice)];
+        (count as f64 + self.smoothing) / voted
+    }
+
+    // log10 of how many times likelier the choice is for a Republican than
+    // for a Democrat. Negative values point towards Democrats, and ignored
+    // votes are 0.
+    pub fn log_odds(&self, attribute: usize, choice: Choice) -> f64 {
+        if self.is_ignored(choice) {
+            return 0.0;
+        }
+
+        (self.conditional_probability(Class::Republican, attribute, choice)
+            / self.conditional_probability(Class::Democrat, attribute, choice))
+        .log10()
+    }
+
+    // Attributes with the log odds of a yes vote, the ones that tell the
+    // classes apart best first
+    pub fn discriminative_attributes(&self) -> Vec<(usize, f64)> {
+        let mut res: Vec<(usize, f64)> = (0..ATTRIBUTES_COUNT)
+            .map(|i| (i, self.log_odds(i, Choice::Yes)))
+            .collect();
+
+        res.sort_by(|a, b| b.1.abs().total_cmp(&a.1.abs()));
+        res
+    }
+
+    // Attributes where a yes vote points to the class, the strongest first,
+    // with the log odds of the class against the other one
+    pub fn indicative_attributes(&self, class: Class) -> Vec<(usize, f64)> {
+        self.discriminative_attributes()
+            .into_iter()
+            .map(|(i, log_odds)| match class {
+                Class::Republican => (i, log_odds),
+                Class::Democrat => (i, -log_odds),
+            })
+            .filter(|&(_, log_odds)| log_odds > 0.0)
+            .collect()
+    }
+
+    // log10 of P(class) times P(choice | class) of every attribute, i.e. how
+    // likely the model is to see the votes from a member of the class. It's
+    // the score prediction compares, read from the same tables.
+    // Only a conversion with the f32 feature
+    #[allow(clippy::useless_conversion)]
+    pub fn log_likelihood(&self, class: Class, attributes: &[Choice]) -> f64 {
+        f64::from(
+            self.log_tables
+                .score_class(attributes.iter().map(|x| x.index()), class.index()),
+        )
+    }
+
+    // How unlike either class the row looks, whatever it's predicted as: the
+    // negative log likelihood of its likeliest class per counted vote, so
+    // scores don't depend on the number of attributes. Higher is more
+    // unusual, and the class of the row is ignored.
+    // Only a conversion with the f32 feature
+    #[allow(clippy::useless_conversion)]
+    pub fn anomaly_score(&self, row: &Row) -> f64 {
+        // The scores prediction takes the likeliest class of
+        let max_log_likelihood = self
+            .log_tables
+            .score(row.attributes.iter().map(|x| x.index()))
+            .into_iter()
+            .map(f64::from)
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        let votes = row
+            .attributes
+            .iter()
+            .enumerate()
+            .filter(|&(i, &choice)| self.is_counted(i, choice))
+            .count();
+        -max_log_likelihood / votes.max(1) as f64
+    }
+
+    pub fn log_tables(&self) -> &LogTables {
+        &self.log_tables
+    }
+
+    pub fn predict_class(&self, row: &Row, class: Class) -> Float {
+        self.log_tables
+            .score_class(row.attributes.iter().map(|x| x.index()), class.index())
+    }
+
+    pub fn score_batch(&self, rows: &[Row]) -> Vec<[Float; CLASSES_COUNT]> {
+        self.log_tables
+            .score_batch(rows, |row, i| row.attributes[i].index())
+            .chunks_exact(CLASSES_COUNT)
+            .map(|scores| scores.try_into().unwrap())
+            .collect()
+    }
+
+    pub fn predict_batch(&self, rows: &[Row]) -> Vec<Class> {
+        self.decide_batch(&self.score_batch(rows))
+    }
+
+    // Of scores like from score_batch, e.g. worked out on the GPU
+    pub fn decide_batch(&self, scores: &[[Float; CLASSES_COUNT]]) -> Vec<Class> {
+        scores.iter().map(|scores| self.decide(scores)).collect()
+    }
+
+    pub fn classify(&self, attributes: &[Choice]) -> Class {
+        match self.threshold {
+            Some(_) => self.decide(&self.log_tables.score(attributes.iter().map(|x| x.index()))),
+            None => {
+                CLASSES[self
+                    .log_tables
+                    .predict(attributes.iter().map(|x| x.index()))]
+            }
+        }
+    }
+
+    // The class classify picks and the probabilities of every class, from the
+    // same scores
+    pub fn classify_with_probabilities(
+        &self,
+        attributes: &[Choice],
+    ) -> (Class, [f64; CLASSES_COUNT]) {
+        let scores = self.log_tables.score(attributes.iter().map(|x| x.index()));
+        let class = match self.threshold {
+            Some(_) => self.decide(&scores),
+            None => CLASSES[argmax(&scores)],
+        };
+
+        (class, Self::to_probabilities(&scores))
+    }
+
+    fn decide(&self, scores: &[Float]) -> Class {
+        match self.threshold {
+            Some(threshold)
+                if Self::to_probabilities(scores)[self.threshold_class.index()] >= threshold =>
+            {
+                self.threshold_class
+            }
+            Some(_) => *CLASSES
+                .iter()
+                .find(|&&class| class != self.threshold_class)
+                .unwrap(),
+            None => CLASSES[argmax(scores)],
+        }
+    }
+
+    // Posterior probability of each class, in the order of CLASSES
+    pub fn probabilities(&self, attributes: &[Choice]) -> [f64; CLASSES_COUNT] {
+        Self::to_probabilities(&self.log_tables.score(attributes.iter().map(|x| x.index())))
+    }
+
+    // Of the scores of a row
+    pub fn to_probabilities(scores: &[Float]) -> [f64; CLASSES_COUNT] {
+        // Shift by the highest score so the powers can't all underflow
+        let max = scores
+            .iter()
+            .fold(Float::NEG_INFINITY, |acc, &x| acc.max(x));
+        let mut res = [0f64; CLASSES_COUNT];
+
+        for (prob, &score) in res.iter_mut().zip(scores) {
+            // Only a conversion with the f32 feature
+            #[allow(clippy::useless_conversion)]
+            let exponent = f64::from(score - max);
+            *prob = 10f64.powf(exponent);
+        }
+
+        let total: f64 = res.iter().sum();
+        for prob in res.iter_mut() {
+            *prob /= total;
+        }
+
+        res
+    }
+
+    pub fn predict(&self, row: &Row) -> Class {
+        if self.threshold.is_some() {
+            return self.classify(&row.attributes);
+        }
+
+        let republican_prob = self.predict_class(row, Class::Republican);
+        let democrat_prob = self.predict_class(row, Class::Democrat);
+
+        // Ties go to the first class, like with argmax
+        if republican_prob >= democrat_prob {
+            return Class::Republican;
+        }
+
+        Class::Democrat
+    }
+
+    // Counted, so it's exactly the share of right predictions
+    pub fn get_accuracy(&self, testing_set: &[Row]) -> f64 {
+        let right = testing_set
+            .iter()
+            .zip(self.predict_batch(testing_set))
+            .filter(|(row, prediction)| *prediction == row.class)
+            .count();
+
+        right as f64 / testing_set.len() as f64
+    }
+
+    // Average natural log of the probability given to the class of each row,
+    // i.e. minus the log loss. Closer to 0 is better. Unlike accuracy it
+    // tells confident mistakes apart from close calls.
+    pub fn score(&self, rows: &[Row]) -> f64 {
+        summation::mean(
+            rows.iter()
+                .map(|row| self.log_probabilities(&row.attributes)[row.class.index()]),
+        )
+    }
+
+    // Natural log of the probabilities, worked out from the log10 scores so
+    // that they stay finite when a probability itself would round to 0
+    pub fn log_probabilities(&self, attributes: &[Choice]) -> [f64; CLASSES_COUNT] {
+        Self::to_log_probabilities(&self.log_tables.score(attributes.iter().map(|x| x.index())))
+    }
+
+    // Of the scores of a row
+    pub fn to_log_probabilities(scores: &[Float]) -> [f64; CLASSES_COUNT] {
+        let mut res = [0f64; CLASSES_COUNT];
+
+        for (log_probability, &score) in res.iter_mut().zip(scores) {
+            // Only a conversion with the f32 feature
+            #[allow(clippy::useless_conversion)]
+            let score = f64::from(score);
+            *log_probability = score * std::f64::consts::LN_10;
+        }
+
+        normalize_log_probabilities(res)
+    }
+
+    pub fn memory_report(&self) -> MemoryReport {
+        let counts_bytes = mem::size_of_val(&self.rows_count)
+            + mem::size_of_val(&self.class_counts)
+            + self.attr_counts.capacity() * mem::size_of::<u32>();
+        let log_tables_bytes = (self.log_tables.class_weights().len()
+            + self.log_tables.attr_weights().len())
+            * mem::size_of::<Float>();
+
+        MemoryReport {
+            counts_bytes,
+            log_tables_bytes,
+            // Also counts the Vec headers
+            total_bytes: mem::size_of::<Model>()
+                + self.attr_counts.capacity() * mem::size_of::<u32>()
+                + log_tables_bytes,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MemoryReport {
+    pub counts_bytes: usize,
+    pub log_tables_bytes: usize,
+    pub total_bytes: usize,
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Counts: {} bytes", self.counts_bytes)?;
+        writeln!(f, "Log probability tables: {} bytes", self.log_tables_bytes)?;
+        write!(f, "Total: {} bytes", self.total_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::classifier::Classifier;
+    use crate::data::try_parse_row;
+    use crate::tan::TanModel;
+
+    fn house_votes() -> Vec<Row> {
+        include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect()
+    }
+
+    // Worked out in f64 from the counts, without the log tables
+    fn log_likelihood_f64(model: &Model, class: Class, attributes: &[Choice]) -> f64 {
+        attributes
+            .iter()
+            .enumerate()
+            .filter(|&(i, &choice)| model.is_counted(i, choice))
+            .map(|(i, &choice)| model.conditional_probability(class, i, choice).log10())
+            .sum::<f64>()
+            + model.prior(class).log10()
+    }
+
+    fn trainers() -> Vec<Trainer> {
+        let rows = house_votes();
+
+        vec![
+            Trainer::new(),
+            Trainer::new().with_smoothing(0.0),
+            Trainer::new().with_missing_votes(MissingVotes::Ignore),
+            Trainer::new().with_prior_mode(PriorMode::Uniform),
+            Trainer::new().with_feature_selection(Some(4)),
+        ]
+        .into_iter()
+        .map(|mut trainer| {
+            rows.iter().for_each(|row| trainer.add(row));
+            trainer
+        })
+        .collect()
+    }
+
+    #[test]
+    fn tables_score_the_conditional_probabilities() {
+        let rows = house_votes();
+
+        for model in trainers().iter().map(Trainer::build) {
+            for row in &rows {
+                for class in CLASSES {
+                    let expected = log_likelihood_f64(&model, class, &row.attributes);
+                    let actual = model.log_likelihood(class, &row.attributes);
+                    assert!(
+                        (actual - expected).abs() <= 1e-4 * expected.abs().max(1.0),
+                        "{} scored {} instead of {} as a {}",
+                        row,
+                        actual,
+                        expected,
+                        class.name()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn probabilities_sum_to_one() {
+        for model in trainers().iter().map(Trainer::build) {
+            let priors: f64 = CLASSES.iter().map(|&class| model.prior(class)).sum();
+            assert!((priors - 1.0).abs() < 1e-9);
+
+            for class in CLASSES {
+                for i in 0..ATTRIBUTES_COUNT {
+                    let total: f64 = CHOICES
+                        .iter()
+                        .map(|&choice| model.conditional_probability(class, i, choice))
+                        .sum();
+                    assert!((total - 1.0).abs() < 1e-9, "{} of attribute {}", total, i);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn selects_the_most_discriminative_attributes() {
+        let model = Trainer::new().with_feature_selection(Some(4)).build();
+        assert_eq!(
+            (0..ATTRIBUTES_COUNT)
+                .filter(|&i| model.is_selected(i))
+                .count(),
+            4
+        );
+
+        let trainer = trainers().pop().unwrap();
+        let model = trainer.build();
+        let all = trainer.with_feature_selection(None).build();
+        for (i, _) in all.discriminative_attributes().into_iter().take(4) {
+            assert!(model.is_selected(i));
+        }
+    }
+
+    #[test]
+    fn partial_fit_counts_like_training_on_every_row() {
+        let rows = house_votes();
+        let (first, second) = rows.split_at(200);
+
+        let model = Model::from_rows(first.iter().cloned()).partial_fit(second);
+        assert_eq!(model.version(), 2);
+        assert_eq!(
+            model.fingerprint(),
+            Model::from_rows(rows.iter().cloned()).fingerprint()
+        );
+    }
+
+    #[test]
+    fn loads_what_it_saves() {
+        let filename = std::env::temp_dir().join(format!("model-{}.json", std::process::id()));
+        let filename = filename.to_str().unwrap();
+
+        for model in trainers().iter().map(Trainer::build) {
+            let model = model.with_threshold(Some(0.3));
+            model.save(filename).unwrap();
+            let loaded = Model::load(filename).unwrap();
+
+            assert_eq!(loaded.fingerprint(), model.fingerprint());
+            assert_eq!(loaded.prior_mode(), model.prior_mode());
+            assert_eq!(loaded.feature_selection(), model.feature_selection());
+            assert_eq!(loaded.missing_votes(), model.missing_votes());
+            assert_eq!(
+                loaded.predict_batch(&house_votes()),
+                model.predict_batch(&house_votes())
+            );
+        }
+        fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn thresholds_the_configured_class() {
+        let filename = std::env::temp_dir().join(format!("threshold-{}.json", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        let rows = house_votes();
+        let model = Model::from_rows(rows.iter().cloned()).with_threshold(Some(0.0));
+        assert!(model
+            .predict_batch(&rows)
+            .iter()
+            .all(|&c| c == Class::Republican));
+
+        let model = model.with_threshold_class(Class::Democrat);
+        assert!(model
+            .predict_batch(&rows)
+            .iter()
+            .all(|&c| c == Class::Democrat));
+        let model = model.with_threshold(Some(1.0));
+        for row in &rows {
+            let democrat = model.probabilities(&row.attributes)[Class::Democrat.index()] >= 1.0;
+            assert_eq!(model.predict(row) == Class::Democrat, democrat);
+        }
+
+        model.save(filename).unwrap();
+        let loaded = Model::load(filename).unwrap();
+        assert_eq!(loaded.threshold_class(), Class::Democrat);
+        assert_eq!(loaded.predict_batch(&rows), model.predict_batch(&rows));
+        assert_eq!(
+            loaded.partial_fit(&rows[..1]).threshold_class(),
+            Class::Democrat
+        );
+        fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn ties_go_to_the_first_class() {
+        let rows: Vec<Row> = CLASSES
+            .iter()
+            .map(|class| {
+                try_parse_row(&format!("{},y,n,?,y,n,?,y,n,?,y,n,?,y,n,?,y", class.name()))
+            })
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let model = Model::from_rows(rows.clone());
+        let tan = TanModel::new(&rows.iter().collect(), DEFAULT_SMOOTHING);
+
+        for row in &rows {
+            assert_eq!(model.predict(row), CLASSES[0]);
+            assert_eq!(model.classify(&row.attributes), CLASSES[0]);
+            assert_eq!(
+                model.classify_with_probabilities(&row.attributes).0,
+                CLASSES[0]
+            );
+            assert_eq!(tan.classify(&row.attributes), CLASSES[0]);
+        }
+        assert_eq!(model.predict_batch(&rows), [CLASSES[0]; CLASSES_COUNT]);
+    }
+
+    #[cfg(feature = "f32")]
+    #[test]
+    fn f32_tables_predict_like_f64() {
+        let rows = house_votes();
+
+        for missing_votes in [MissingVotes::Category, MissingVotes::Ignore] {
+            let mut trainer = Trainer::new().with_missing_votes(missing_votes);
+            rows.iter().for_each(|row| trainer.add(row));
+            let model = trainer.build();
+
+            let predictions = model.predict_batch(&rows);
+            for (row, prediction) in rows.iter().zip(predictions) {
+                let scores =
+                    CLASSES.map(|class| log_likelihood_f64(&model, class, &row.attributes));
+                // Ties go to the first class
+                let expected = if scores[0] >= scores[1] {
+                    CLASSES[0]
+                } else {
+                    CLASSES[1]
+                };
+
+                assert_eq!(prediction, expected, "{}", row);
+                assert_eq!(model.classify(&row.attributes), expected, "{}", row);
+            }
+        }
+    }
+
+    #[test]
+    // Only a conversion with the f32 feature
+    #[allow(clippy::useless_conversion)]
+    fn scores_batches_like_single_rows() {
+        let rows = house_votes();
+
+        for model in trainers().iter().map(Trainer::build) {
+            // Sizes that don't fill the last chunk too
+            for size in [0, 1, 7, 13, rows.len()] {
+                let scores = model.score_batch(&rows[..size]);
+                assert_eq!(scores.len(), size);
+
+                for (row, scores) in rows.iter().zip(&scores) {
+                    for class in CLASSES {
+                        assert_eq!(
+                            f64::from(scores[class.index()]),
+                            model.log_likelihood(class, &row.attributes)
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn trains_on_a_file_like_on_its_rows() {
+        let model = Model::from_file("house-votes-84.data").unwrap();
+        assert_eq!(
+            model.fingerprint(),
+            Model::from_rows(house_votes()).fingerprint()
+        );
+
+        let filename = std::env::temp_dir().join(format!("stream-{}.data", std::process::id()));
+        fs::write(&filename, "republican,y\n").unwrap();
+        assert!(Model::from_file(filename.to_str().unwrap()).is_err());
+        fs::remove_file(filename).unwrap();
+    }
+
+    #[test]
+    fn reports_the_memory_of_the_packed_tables() {
+        let model = Model::from_rows(house_votes());
+        let report = model.memory_report();
+
+        // One u32 per class, attribute and choice, besides the row and class
+        // counts
+        let counts = CLASSES_COUNT * ATTRIBUTES_COUNT * CHOICES.len();
+        assert_eq!(
+            report.counts_bytes,
+            mem::size_of::<u32>() * (1 + CLASSES_COUNT + counts)
+        );
+        assert_eq!(
+            report.log_tables_bytes,
+            (model.log_tables().class_weights().len() + model.log_tables().attr_weights().len())
+                * mem::size_of::<Float>()
+        );
+        assert!(report.total_bytes > report.counts_bytes + report.log_tables_bytes);
+    }
+
+    #[test]
+    fn saves_the_same_bytes_for_the_same_rows() {
+        let filename = |run| {
+            std::env::temp_dir().join(format!("same-bytes-{}-{}.json", run, std::process::id()))
+        };
+        let saved: Vec<_> = (0..2)
+            .map(|run| {
+                let filename = filename(run);
+                Model::from_rows(house_votes())
+                    .save(filename.to_str().unwrap())
+                    .unwrap();
+                let saved = fs::read(&filename).unwrap();
+                fs::remove_file(&filename).unwrap();
+                saved
+            })
+            .collect();
+
+        assert_eq!(saved[0], saved[1]);
+    }
+
+    #[test]
+    fn scores_rows_unlike_either_class_higher() {
+        let rows = house_votes();
+        let model = Model::from_rows(rows.iter().cloned());
+        let typical = model.anomaly_score(&rows[0]);
+        // The usual votes of neither party
+        let mut unusual = rows[0].clone();
+        for (i, choice) in unusual.attributes.iter_mut().enumerate() {
+            *choice = if i % 2 == 0 { Choice::Yes } else { Choice::No };
+        }
+        let mut other_class = rows[0].clone();
+        other_class.class = match rows[0].class {
+            Class::Republican => Class::Democrat,
+            Class::Democrat => Class::Republican,
+        };
+
+        assert!(typical > 0.0);
+        assert!(model.anomaly_score(&unusual) > typical);
+        assert_eq!(model.anomaly_score(&other_class), typical);
+        let max_log_likelihood = CLASSES
+            .iter()
+            .map(|&class| model.log_likelihood(class, &rows[0].attributes))
+            .fold(f64::NEG_INFINITY, f64::max);
+        assert_eq!(typical, -max_log_likelihood / ATTRIBUTES_COUNT as f64);
+    }
+
+    #[test]
+    fn scores_the_log_probability_of_the_right_class() {
+        let rows = house_votes();
+        let model = Model::from_rows(rows.iter().cloned());
+        let expected = rows
+            .iter()
+            .map(|row| model.probabilities(&row.attributes)[row.class.index()].ln())
+            .sum::<f64>()
+            / rows.len() as f64;
+
+        let score = model.score(&rows);
+        assert!((score - expected).abs() < 1e-9);
+        assert!(score < 0.0);
+
+        // Confident mistakes cost more than they would with accuracy
+        let flipped: Vec<Row> = rows
+            .iter()
+            .map(|row| Row {
+                class: CLASSES[1 - row.class.index()],
+                attributes: row.attributes.clone(),
+            })
+            .collect();
+        assert!(model.score(&flipped) < 10.0 * score);
+    }
+
+    #[test]
+    fn ignores_unknown_votes_with_ignore() {
+        let rows = house_votes();
+        let mut trainer = Trainer::new().with_missing_votes(MissingVotes::Ignore);
+        rows.iter().for_each(|row| trainer.add(row));
+        let model = trainer.build();
+
+        // Only the rows that voted on an attribute count towards it
+        for &class in CLASSES.iter() {
+            for attribute in 0..ATTRIBUTES_COUNT {
+                let voted = model.conditional_probability(class, attribute, Choice::Yes)
+                    + model.conditional_probability(class, attribute, Choice::No);
+                assert!((voted - 1.0).abs() < 1e-9);
+            }
+        }
+
+        // So a row of unknown votes is only predicted from the priors, while
+        // abstaining is evidence when it's a category
+        let abstained = [Choice::Unknown; ATTRIBUTES_COUNT];
+        let probability = model.probabilities(&abstained)[0];
+        assert!((probability - model.prior(CLASSES[0])).abs() < 1e-9);
+        let category = Model::from_rows(rows.iter().cloned());
+        assert!((category.probabilities(&abstained)[0] - category.prior(CLASSES[0])).abs() > 0.01);
+    }
+
+    #[test]
+    fn counts_the_right_predictions_for_the_accuracy() {
+        let rows = house_votes();
+        let mut trainer = Trainer::new();
+        rows.iter().for_each(|row| trainer.add(row));
+        let model = trainer.build();
+
+        // Adding up 1/10 per row would round to just below 1
+        let right = vec![rows[0].clone(); 10];
+        assert_eq!(model.classify(&right[0].attributes), right[0].class);
+        assert_eq!(model.get_accuracy(&right), 1.0);
+    }
+}
@@ -0,0 +1,342 @@
+use std::fmt::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::config::{OutputConfig, RunConfig};
+use crate::data::{Choice, Class, Row, ATTRIBUTES_COUNT, CLASSES};
+use crate::model::MissingVotes;
+
+// Shares above this make a class a majority worth pointing out
+const IMBALANCE: f64 = 0.6;
+// Shares of unknown votes above this are worth pointing out
+const UNKNOWN_VOTES: f64 = 0.05;
+// Expected calibration errors above this make the probabilities misleading
+const MISCALIBRATION: f64 = 0.05;
+// Standard deviations of the fold accuracies above this make the average a
+// rough estimate
+const UNSTABLE: f64 = 0.05;
+
+// What a model was trained on, how it cross-validated and what it can't be
+// relied on for, generated by the run that saved it and stored with it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelCard {
+    // RFC 3339 in UTC
+    pub trained_at: String,
+    pub crate_version: String,
+    pub data: DataSummary,
+    // Of the run, without where its output went
+    pub settings: RunConfig,
+    pub cross_validation: CrossValidationSummary,
+    pub limitations: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataSummary {
+    pub path: String,
+    // After dedup and sampling, the rows that were cross-validated
+    pub rows: usize,
+    pub classes: Vec<ClassShare>,
+    // Of all votes of all rows
+    pub unknown_votes: f64,
+    // Rows identical to an earlier one, whether or not they were dropped
+    pub duplicates: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ClassShare {
+    pub class: Class,
+    pub rows: usize,
+    pub share: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrossValidationSummary {
+    pub folds: usize,
+    // Over the folds
+    pub accuracy: f64,
+    pub accuracy_std: f64,
+    pub log_likelihood: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub average_precision: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_calibration_error: Option<f64>,
+}
+
+impl DataSummary {
+    pub fn new(path: &str, rows: &[Row], duplicates: usize) -> Self {
+        let votes = (rows.len() * ATTRIBUTES_COUNT).max(1) as f64;
+        let unknown = rows
+            .iter()
+            .flat_map(|row| &row.attributes)
+            .filter(|&&choice| choice == Choice::Unknown)
+            .count();
+
+        DataSummary {
+            path: path.to_string(),
+            rows: rows.len(),
+            classes: CLASSES
+                .iter()
+                .map(|&class| {
+                    let count = rows.iter().filter(|row| row.class == class).count();
+                    ClassShare {
+                        class,
+                        rows: count,
+                        share: count as f64 / rows.len().max(1) as f64,
+                    }
+                })
+                .collect(),
+            unknown_votes: unknown as f64 / votes,
+            duplicates,
+        }
+    }
+}
+
+impl ModelCard {
+    // The limitations follow from the data, settings and results
+    pub fn new(
+        trained_at: String,
+        data: DataSummary,
+        settings: &RunConfig,
+        cross_validation: CrossValidationSummary,
+    ) -> Self {
+        let settings = RunConfig {
+            output: OutputConfig::default(),
+            ..settings.clone()
+        };
+        let limitations = limitations(&data, &settings, &cross_validation);
+
+        ModelCard {
+            trained_at,
+            crate_version: env!("CARGO_PKG_VERSION").to_string(),
+            data,
+            settings,
+            cross_validation,
+            limitations,
+        }
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut res = String::new();
+        let data = &self.data;
+        let cv = &self.cross_validation;
+
+        writeln!(res, "# Model card\n").unwrap();
+        writeln!(
+            res,
+            "Trained at {} with party_recogniser_naive_bayes {}.\n",
+            self.trained_at, self.crate_version
+        )
+        .unwrap();
+
+        writeln!(res, "## Training data\n").unwrap();
+        writeln!(
+            res,
+            "{} rows of `{}`, {:.1}% of whose votes are unknown and {} of which are identical to an earlier row.\n",
+            data.rows,
+            data.path,
+            data.unknown_votes * 100.0,
+            data.duplicates
+        )
+        .unwrap();
+        writeln!(res, "| Class | Rows | Share |\n| --- | ---: | ---: |").unwrap();
+        for class in &data.classes {
+            writeln!(
+                res,
+                "| {} | {} | {:.1}% |",
+                class.class.name(),
+                class.rows,
+                class.share * 100.0
+            )
+            .unwrap();
+        }
+
+        writeln!(res, "\n## Settings\n").unwrap();
+        writeln!(res, "| Setting | Value |\n| --- | --- |").unwrap();
+        for (key, value) in self.settings.settings() {
+            writeln!(res, "| {} | `{}` |", key, value).unwrap();
+        }
+
+        writeln!(res, "\n## Cross-validation\n").unwrap();
+        writeln!(res, "Over {} folds.\n", cv.folds).unwrap();
+        writeln!(res, "| Metric | Value |\n| --- | ---: |").unwrap();
+        writeln!(
+            res,
+            "| Accuracy | {:.4} ± {:.4} |",
+            cv.accuracy, cv.accuracy_std
+        )
+        .unwrap();
+        writeln!(res, "| Log-likelihood | {:.4} |", cv.log_likelihood).unwrap();
+        if let Some(average_precision) = cv.average_precision {
+            writeln!(res, "| Average precision | {:.4} |", average_precision).unwrap();
+        }
+        if let Some(error) = cv.expected_calibration_error {
+            writeln!(res, "| Expected calibration error | {:.4} |", error).unwrap();
+        }
+
+        writeln!(res, "\n## Limitations\n").unwrap();
+        for limitation in &self.limitations {
+            writeln!(res, "- {}", limitation).unwrap();
+        }
+
+        res
+    }
+}
+
+fn limitations(
+    data: &DataSummary,
+    settings: &RunConfig,
+    cv: &CrossValidationSummary,
+) -> Vec<String> {
+    let mut res = vec![
+        format!(
+            "Only tells {} apart, from votes on the same {} issues as the training data",
+            CLASSES
+                .map(|class| format!("{}s", class.name()))
+                .join(" and "),
+            ATTRIBUTES_COUNT
+        ),
+        "Assumes the votes are independent of each other given the class".to_string(),
+    ];
+
+    if let Some(majority) = data.classes.iter().find(|class| class.share > IMBALANCE) {
+        res.push(format!(
+            "{:.0}% of the rows are {}s, so close calls lean towards them",
+            majority.share * 100.0,
+            majority.class.name()
+        ));
+    }
+    if data.unknown_votes > UNKNOWN_VOTES {
+        res.push(format!(
+            "{:.1}% of the votes are unknown, which are {}",
+            data.unknown_votes * 100.0,
+            if settings.missing_votes == Some(MissingVotes::Ignore) {
+                "ignored"
+            } else {
+                "counted as a vote of their own"
+            }
+        ));
+    }
+    if data.duplicates > 0 && settings.dedup != Some(true) {
+        res.push(format!(
+            "{} rows are identical to an earlier one and count more than once",
+            data.duplicates
+        ));
+    }
+    if let Some(fraction) = settings.sample {
+        res.push(format!("Trained on a sample of {} of the rows", fraction));
+    }
+    if let Some(k) = settings.k_anonymity {
+        res.push(format!(
+            "Rows that fewer than {} rows share their votes on the quasi-identifiers with had those votes suppressed",
+            k
+        ));
+    }
+    if let Some(copies) = settings.augment {
+        res.push(format!(
+            "Trained on {} noisy copies of every row as well",
+            copies
+        ));
+    }
+    if let Some(k) = settings.feature_selection {
+        res.push(format!(
+            "Only predicts from the {} attributes that told the classes apart best in training",
+            k
+        ));
+    }
+    if let Some(epsilon) = settings.epsilon {
+        res.push(format!(
+            "Its counts are noisy for differential privacy at epsilon {}",
+            epsilon
+        ));
+    }
+    if let Some(error) = cv
+        .expected_calibration_error
+        .filter(|&error| error > MISCALIBRATION)
+    {
+        res.push(format!(
+            "Its probabilities are off by {:.3} on average, so they're better ranked than read as chances",
+            error
+        ));
+    }
+    if cv.accuracy_std > UNSTABLE {
+        res.push(format!(
+            "The accuracy varied by {:.3} between folds, so expect it to differ on new data",
+            cv.accuracy_std
+        ));
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::try_parse_row;
+
+    fn cross_validation(accuracy_std: f64) -> CrossValidationSummary {
+        CrossValidationSummary {
+            folds: 10,
+            accuracy: 0.9,
+            accuracy_std,
+            log_likelihood: -0.25,
+            average_precision: None,
+            expected_calibration_error: Some(0.01),
+        }
+    }
+
+    #[test]
+    fn points_out_the_limitations_of_the_data_and_results() {
+        let rows: Vec<_> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let data = DataSummary::new("house-votes-84.data", &rows, 0);
+        assert_eq!(data.rows, 435);
+        assert_eq!(
+            data.classes.iter().map(|x| x.rows).collect::<Vec<_>>(),
+            [168, 267]
+        );
+
+        let settings = RunConfig {
+            missing_votes: Some(MissingVotes::Ignore),
+            output: OutputConfig {
+                save_model: Some("model.json".to_string()),
+                ..OutputConfig::default()
+            },
+            ..RunConfig::default()
+        };
+        let card = ModelCard::new(
+            "2024-01-01T00:00:00Z".to_string(),
+            data,
+            &settings,
+            cross_validation(0.01),
+        );
+        assert_eq!(card.settings.output, OutputConfig::default());
+        assert_eq!(card.limitations.len(), 4);
+        assert!(card.limitations[2].starts_with("61% of the rows are democrats"));
+        assert!(card.limitations[3].ends_with("which are ignored"));
+
+        let markdown = card.to_markdown();
+        assert!(markdown.contains("| democrat | 267 | 61.4% |"));
+        assert!(markdown.contains("| missing-votes | `ignore` |"));
+        assert!(markdown.contains("| Accuracy | 0.9000 ± 0.0100 |"));
+        assert!(!markdown.contains("Average precision"));
+    }
+
+    #[test]
+    fn only_points_out_unstable_folds() {
+        let data = DataSummary::new("empty.data", &[], 0);
+        let limitations = |std| {
+            ModelCard::new(
+                String::new(),
+                data.clone(),
+                &RunConfig::default(),
+                cross_validation(std),
+            )
+            .limitations
+        };
+
+        assert_eq!(limitations(0.01).len(), 2);
+        assert!(limitations(0.1)[2].starts_with("The accuracy varied by 0.100"));
+    }
+}
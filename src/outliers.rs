@@ -0,0 +1,86 @@
+use crate::data::Row;
+use crate::model::Model;
+
+pub const DEFAULT_PERCENTILE: f64 = 5.0;
+
+// A row the model finds unusually unlikely for its own class, which may be a
+// data error or a member voting against their party
+#[derive(Debug, Clone, Copy)]
+pub struct Outlier {
+    // Into the rows that were scored
+    pub index: usize,
+    pub log_likelihood: f64,
+}
+
+// The rows whose log likelihood is at or below the given percentile of all
+// of them, the least likely first
+pub fn find_outliers(model: &Model, rows: &[Row], percentile: f64) -> Vec<Outlier> {
+    let mut scored: Vec<Outlier> = rows
+        .iter()
+        .enumerate()
+        .map(|(index, row)| Outlier {
+            index,
+            log_likelihood: model.log_likelihood(row.class, &row.attributes),
+        })
+        .collect();
+
+    scored.sort_by(|a, b| a.log_likelihood.total_cmp(&b.log_likelihood));
+
+    if scored.is_empty() {
+        return scored;
+    }
+
+    let last = scored.len() - 1;
+    let threshold = scored[(percentile / 100.0 * last as f64) as usize].log_likelihood;
+
+    scored.retain(|outlier| outlier.log_likelihood <= threshold);
+    scored
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::try_parse_row;
+
+    fn house_votes() -> Vec<Row> {
+        include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn flags_the_least_likely_rows_first() {
+        let rows = house_votes();
+        let model = Model::from_rows(rows.iter().cloned());
+        let outliers = find_outliers(&model, &rows, DEFAULT_PERCENTILE);
+
+        // About 5% of the rows, more with ties at the threshold
+        assert!(outliers.len() >= rows.len() / 20);
+        assert!(outliers.len() < rows.len() / 10);
+        assert!(outliers
+            .windows(2)
+            .all(|pair| pair[0].log_likelihood <= pair[1].log_likelihood));
+        let row = &rows[outliers[0].index];
+        assert_eq!(
+            outliers[0].log_likelihood,
+            model.log_likelihood(row.class, &row.attributes)
+        );
+
+        let threshold = outliers.last().unwrap().log_likelihood;
+        let below = rows
+            .iter()
+            .filter(|row| model.log_likelihood(row.class, &row.attributes) <= threshold)
+            .count();
+        assert_eq!(below, outliers.len());
+    }
+
+    #[test]
+    fn flags_every_row_at_the_last_percentile() {
+        let rows = house_votes();
+        let model = Model::from_rows(rows.iter().cloned());
+
+        assert_eq!(find_outliers(&model, &rows, 100.0).len(), rows.len());
+        assert!(find_outliers(&model, &[], DEFAULT_PERCENTILE).is_empty());
+    }
+}
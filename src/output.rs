@@ -0,0 +1,1620 @@
+use std::env;
+use std::io::{self, IsTerminal};
+
+use comfy_table::presets::UTF8_FULL_CONDENSED;
+#[cfg(not(target_arch = "wasm32"))]
+use comfy_table::{Attribute, Color};
+use comfy_table::{Cell, CellAlignment, Table};
+use serde::Serialize;
+
+use crate::attribute_metadata::AttributeMetadata;
+use crate::bench::{LatencyStats, TrainTimings};
+use crate::cache;
+use crate::curves::{CalibrationBin, Decile};
+use crate::data::{Choice, Class, Row, ATTRIBUTES_COUNT, CHOICES, CLASSES, CLASSES_COUNT};
+use crate::dependence::PairDependence;
+use crate::diff::{AttributeShift, PriorShift};
+use crate::duplicates::Duplicates;
+use crate::error_analysis::Misclassification;
+use crate::evaluation::{BootstrapEstimate, ConfusionMatrix, FoldResult};
+use crate::model::{MemoryReport, MissingVotes, Model, PriorMode};
+use crate::outliers::Outlier;
+use crate::registry::Entry;
+use crate::significance::{McNemarTest, PairedTest};
+use crate::stats::DatasetStats;
+use crate::summation;
+use crate::threshold::{Objective, ThresholdPoint};
+use crate::tune::{Search, TuneResult};
+use crate::validate::Finding;
+#[cfg(target_arch = "wasm32")]
+use plain::{Attribute, Color, PlainCell, PlainTable};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    // Tables for people
+    Text,
+    // A single JSON document for scripts, see SCHEMA_VERSION
+    Json,
+}
+
+// Bumped whenever a field of the JSON output is renamed, removed or changes
+// meaning. Adding fields doesn't bump it.
+pub const SCHEMA_VERSION: u32 = 2;
+
+// Of the tests in dependence_table, before the Bonferroni correction
+pub const DEPENDENCE_SIGNIFICANCE: f64 = 0.05;
+
+// JSON output of a cross-validation run. Metrics that weren't asked for are
+// left out.
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    pub schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub folds: Option<Vec<FoldReport>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average_accuracy: Option<f64>,
+    // Of the testing folds, see Model::score
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average_log_likelihood: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confusion_matrix: Option<ConfusionReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<Vec<ClassAttributesReport>>,
+    // Of finding the positive class, see crate::curves::average_precision
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average_precision: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lift: Option<Vec<Decile>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<CalibrationReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub misclassified: Option<Vec<MisclassificationReport>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryReport>,
+    // What average_precision, lift and calibration are about
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub positive_class: Option<&'static str>,
+    // Every kind of model --model auto tried, the one it picked first
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model_selection: Option<Vec<TuneResult>>,
+}
+
+// JSON output of evaluate, with the metrics of RunReport for a saved model
+// on a test set
+#[derive(Debug, Serialize)]
+pub struct EvaluationReport {
+    pub schema_version: u32,
+    pub rows: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub accuracy: Option<f64>,
+    // See Model::score
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_likelihood: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub confusion_matrix: Option<ConfusionReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub attributes: Option<Vec<ClassAttributesReport>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub average_precision: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lift: Option<Vec<Decile>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub calibration: Option<CalibrationReport>,
+    pub positive_class: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MisclassificationReport {
+    // 1-based line in the dataset
+    pub line: usize,
+    pub fold: usize,
+    pub class: &'static str,
+    pub predicted: &'static str,
+    pub margin: f64,
+    pub attributes: Vec<ErrorAttribute>,
+}
+
+// An attribute whose vote pushed a row towards the wrong class
+#[derive(Debug, Serialize)]
+pub struct ErrorAttribute {
+    pub attribute: String,
+    pub vote: &'static str,
+    // log10 of how many times likelier the vote is for the predicted class
+    pub log_ratio: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CalibrationReport {
+    // See crate::curves::expected_calibration_error
+    pub expected_calibration_error: f64,
+    pub bins: Vec<CalibrationBin>,
+}
+
+// Written next to every model saved with --save-fold-models
+#[derive(Debug, Serialize)]
+pub struct FoldModelReport {
+    pub schema_version: u32,
+    pub fold: usize,
+    pub accuracy: f64,
+    pub testing_rows: usize,
+    pub confusion_matrix: ConfusionReport,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FoldReport {
+    pub fold: usize,
+    pub accuracy: f64,
+    pub log_likelihood: f64,
+}
+
+// counts[actual][predicted], both in the order of classes
+#[derive(Debug, Serialize)]
+pub struct ConfusionReport {
+    pub classes: Vec<&'static str>,
+    pub counts: Vec<Vec<u32>>,
+}
+
+// The attributes where a yes vote points to the class most strongly
+#[derive(Debug, Serialize)]
+pub struct ClassAttributesReport {
+    pub class: &'static str,
+    pub attributes: Vec<AttributeOdds>,
+}
+
+// log10 of how many times likelier a yes vote is for the class than for the
+// other one
+#[derive(Debug, Serialize)]
+pub struct AttributeOdds {
+    pub attribute: String,
+    pub log_odds: f64,
+}
+
+// JSON output of stats
+#[derive(Debug, Serialize)]
+pub struct StatsReport {
+    pub schema_version: u32,
+    pub rows: u32,
+    pub classes: Vec<ClassShare>,
+    pub attributes: Vec<AttributeStats>,
+    pub duplicates: DuplicatesReport,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DuplicatesReport {
+    // Rows identical to an earlier row, which --dedup drops
+    pub exact_count: usize,
+    pub conflict_count: usize,
+    pub groups: Vec<DuplicateGroup>,
+}
+
+// Rows with the same votes, by their 1-based line in the dataset
+#[derive(Debug, Serialize)]
+pub struct DuplicateGroup {
+    pub lines: Vec<usize>,
+    pub classes: Vec<&'static str>,
+    // Whether the rows have different classes
+    pub conflict: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClassShare {
+    pub class: &'static str,
+    pub count: u32,
+    pub share: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AttributeStats {
+    pub attribute: String,
+    pub counts: ChoiceCounts,
+    pub missing_rate: f64,
+    pub by_class: Vec<ClassAttributeStats>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClassAttributeStats {
+    pub class: &'static str,
+    pub counts: ChoiceCounts,
+    pub missing_rate: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChoiceCounts {
+    pub yes: u32,
+    pub no: u32,
+    pub unknown: u32,
+}
+
+// JSON output of compare-configs
+#[derive(Debug, Serialize)]
+pub struct CompareReport {
+    pub schema_version: u32,
+    pub folds: usize,
+    pub repeats: usize,
+    pub configs: Vec<ConfigScore>,
+    // Of the second config's accuracies against the first's
+    pub test: PairedTest,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConfigScore {
+    pub config: String,
+    // Over every fold of every repetition
+    pub mean_accuracy: f64,
+    pub std_accuracy: f64,
+}
+
+// JSON output of compare, of saved models on the same test set
+#[derive(Debug, Serialize)]
+pub struct ModelComparisonReport {
+    pub schema_version: u32,
+    pub rows: usize,
+    pub positive_class: &'static str,
+    pub models: Vec<ModelScore>,
+    // Every pair of models, in the order they were given
+    pub pairs: Vec<ModelPair>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModelScore {
+    pub model: String,
+    pub accuracy: f64,
+    // See Model::score
+    pub log_likelihood: f64,
+    pub average_precision: f64,
+    pub roc_area: f64,
+    pub expected_calibration_error: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModelPair {
+    pub first: String,
+    pub second: String,
+    #[serde(flatten)]
+    pub test: McNemarTest,
+}
+
+// JSON output of tune
+#[derive(Debug, Serialize)]
+pub struct TuneReport {
+    pub schema_version: u32,
+    pub search: Search,
+    pub folds: usize,
+    // Every candidate is tested on the folds of this seed
+    pub seed: u64,
+    // The best first
+    pub results: Vec<TuneResult>,
+}
+
+// JSON output of bootstrap
+#[derive(Debug, Serialize)]
+pub struct BootstrapReport<'a> {
+    pub schema_version: u32,
+    #[serde(flatten)]
+    pub estimate: &'a BootstrapEstimate,
+}
+
+// JSON output of diagnostics
+#[derive(Debug, Serialize)]
+pub struct DiagnosticsReport {
+    pub schema_version: u32,
+    pub rows: usize,
+    // The most dependent first, see crate::dependence
+    pub pairs: Vec<PairDependenceReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PairDependenceReport {
+    pub first: String,
+    pub second: String,
+    pub mutual_information: f64,
+    pub g: f64,
+    pub degrees_of_freedom: usize,
+    pub p_value: f64,
+}
+
+// JSON output of validate
+#[derive(Debug, Serialize)]
+pub struct ValidateReport<'a> {
+    pub schema_version: u32,
+    // Rows without problems of their own
+    pub valid_rows: usize,
+    pub findings: &'a [Finding],
+}
+
+// JSON output of outliers
+#[derive(Debug, Serialize)]
+pub struct OutliersReport {
+    pub schema_version: u32,
+    pub percentile: f64,
+    pub rows: usize,
+    pub outliers: Vec<OutlierReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OutlierReport {
+    // 1-based line in the dataset
+    pub line: usize,
+    pub class: &'static str,
+    pub predicted: &'static str,
+    pub log_likelihood: f64,
+    // See Model::anomaly_score
+    pub anomaly_score: f64,
+}
+
+// JSON output of bench predict, with durations in nanoseconds
+#[derive(Debug, Serialize)]
+pub struct BenchReport<'a> {
+    pub schema_version: u32,
+    pub single: &'a LatencyStats,
+    pub batch: &'a LatencyStats,
+}
+
+// JSON output of bench train, with durations in nanoseconds
+#[derive(Debug, Serialize)]
+pub struct TrainBenchReport<'a> {
+    pub schema_version: u32,
+    pub synthetic: bool,
+    pub sizes: &'a [TrainTimings],
+}
+
+// JSON output of tune-threshold
+#[derive(Debug, Serialize)]
+pub struct ThresholdReport<'a> {
+    pub schema_version: u32,
+    pub objective: Objective,
+    pub threshold: f64,
+    pub curve: &'a [ThresholdPoint],
+}
+
+// JSON output of predict
+#[derive(Debug, Serialize)]
+pub struct PredictionReport {
+    pub schema_version: u32,
+    pub class: &'static str,
+    pub probabilities: Vec<ClassProbability>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ClassProbability {
+    pub class: &'static str,
+    pub probability: f64,
+}
+
+// JSON output of model inspect
+#[derive(Debug, Serialize)]
+pub struct InspectReport {
+    pub schema_version: u32,
+    pub version: u32,
+    pub rows: u32,
+    pub smoothing: f64,
+    pub missing_votes: MissingVotes,
+    pub prior_mode: PriorMode,
+    // See Model::feature_selection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feature_selection: Option<usize>,
+    // See Model::threshold
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold: Option<f64>,
+    // The class the threshold is for, left out without one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub threshold_class: Option<&'static str>,
+    pub priors: Vec<PriorReport>,
+    pub attributes: Vec<AttributeReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PriorReport {
+    pub class: &'static str,
+    pub count: u32,
+    pub prior: f64,
+}
+
+// P(choice | class) of every class and choice
+#[derive(Debug, Serialize)]
+pub struct AttributeReport {
+    pub attribute: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    // See Model::vocabulary
+    pub vocabulary: Vec<&'static str>,
+    // See Model::is_selected
+    pub selected: bool,
+    pub probabilities: Vec<ConditionalProbability>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConditionalProbability {
+    pub class: &'static str,
+    pub choice: &'static str,
+    pub probability: f64,
+}
+
+pub fn fold_reports(folds: &[FoldResult]) -> Vec<FoldReport> {
+    folds
+        .iter()
+        .map(|fold| FoldReport {
+            fold: fold.fold,
+            accuracy: fold.accuracy,
+            log_likelihood: fold.log_likelihood,
+        })
+        .collect()
+}
+
+pub fn average_accuracy(folds: &[FoldResult]) -> f64 {
+    summation::mean(folds.iter().map(|fold| fold.accuracy))
+}
+
+pub fn average_log_likelihood(folds: &[FoldResult]) -> f64 {
+    summation::mean(folds.iter().map(|fold| fold.log_likelihood))
+}
+
+pub fn confusion_report(confusion: &ConfusionMatrix) -> ConfusionReport {
+    ConfusionReport {
+        classes: CLASSES.iter().map(|class| class.name()).collect(),
+        counts: confusion.counts.iter().map(|row| row.to_vec()).collect(),
+    }
+}
+
+pub fn attributes_reports(
+    model: &Model,
+    metadata: &AttributeMetadata,
+    top: usize,
+) -> Vec<ClassAttributesReport> {
+    CLASSES
+        .iter()
+        .map(|&class| ClassAttributesReport {
+            class: class.name(),
+            attributes: model
+                .indicative_attributes(class)
+                .into_iter()
+                .take(top)
+                .map(|(i, log_odds)| AttributeOdds {
+                    attribute: metadata.name(i).to_string(),
+                    log_odds,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+fn duplicate_group(rows: &[Row], group: &[usize]) -> DuplicateGroup {
+    let classes: Vec<&'static str> = CLASSES
+        .iter()
+        .filter(|&&class| group.iter().any(|&i| rows[i].class == class))
+        .map(|class| class.name())
+        .collect();
+
+    DuplicateGroup {
+        lines: group.iter().map(|i| i + 1).collect(),
+        conflict: classes.len() > 1,
+        classes,
+    }
+}
+
+pub fn duplicates_report(rows: &[Row], duplicates: &Duplicates) -> DuplicatesReport {
+    DuplicatesReport {
+        exact_count: duplicates.exact_count,
+        conflict_count: duplicates.conflicts(rows).count(),
+        groups: duplicates
+            .groups
+            .iter()
+            .map(|group| duplicate_group(rows, group))
+            .collect(),
+    }
+}
+
+pub fn dependence_reports(
+    pairs: &[PairDependence],
+    metadata: &AttributeMetadata,
+) -> Vec<PairDependenceReport> {
+    pairs
+        .iter()
+        .map(|pair| PairDependenceReport {
+            first: metadata.name(pair.first).to_string(),
+            second: metadata.name(pair.second).to_string(),
+            mutual_information: pair.mutual_information,
+            g: pair.g,
+            degrees_of_freedom: pair.degrees_of_freedom,
+            p_value: pair.p_value,
+        })
+        .collect()
+}
+
+pub fn stats_report(
+    stats: &DatasetStats,
+    metadata: &AttributeMetadata,
+    duplicates: DuplicatesReport,
+) -> StatsReport {
+    StatsReport {
+        schema_version: SCHEMA_VERSION,
+        rows: stats.rows_count(),
+        classes: CLASSES
+            .iter()
+            .map(|&class| ClassShare {
+                class: class.name(),
+                count: stats.class_count(class),
+                share: stats.class_count(class) as f64 / stats.rows_count() as f64,
+            })
+            .collect(),
+        attributes: (0..ATTRIBUTES_COUNT)
+            .map(|attribute| AttributeStats {
+                attribute: metadata.name(attribute).to_string(),
+                counts: ChoiceCounts {
+                    yes: stats.total_count(attribute, Choice::Yes),
+                    no: stats.total_count(attribute, Choice::No),
+                    unknown: stats.total_count(attribute, Choice::Unknown),
+                },
+                missing_rate: stats.missing_rate(attribute),
+                by_class: CLASSES
+                    .iter()
+                    .map(|&class| ClassAttributeStats {
+                        class: class.name(),
+                        counts: ChoiceCounts {
+                            yes: stats.count(class, attribute, Choice::Yes),
+                            no: stats.count(class, attribute, Choice::No),
+                            unknown: stats.count(class, attribute, Choice::Unknown),
+                        },
+                        missing_rate: stats.class_missing_rate(class, attribute),
+                    })
+                    .collect(),
+            })
+            .collect(),
+        duplicates,
+    }
+}
+
+// lines has the line of every row in the data that was cross-validated
+pub fn misclassification_reports(
+    misclassifications: &[Misclassification],
+    lines: &[usize],
+    metadata: &AttributeMetadata,
+) -> Vec<MisclassificationReport> {
+    misclassifications
+        .iter()
+        .map(|misclassification| MisclassificationReport {
+            line: lines[misclassification.index],
+            fold: misclassification.fold,
+            class: misclassification.actual.name(),
+            predicted: misclassification.predicted.name(),
+            margin: misclassification.margin,
+            attributes: misclassification
+                .contributions
+                .iter()
+                .map(|contribution| ErrorAttribute {
+                    attribute: metadata.name(contribution.attribute).to_string(),
+                    vote: contribution.choice.name(),
+                    log_ratio: contribution.log_ratio,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+pub fn outliers_report(
+    model: &Model,
+    rows: &[Row],
+    outliers: &[Outlier],
+    percentile: f64,
+) -> OutliersReport {
+    OutliersReport {
+        schema_version: SCHEMA_VERSION,
+        percentile,
+        rows: rows.len(),
+        outliers: outliers
+            .iter()
+            .map(|outlier| {
+                let row = &rows[outlier.index];
+                OutlierReport {
+                    line: outlier.index + 1,
+                    class: row.class.name(),
+                    predicted: model.classify(&row.attributes).name(),
+                    log_likelihood: outlier.log_likelihood,
+                    anomaly_score: model.anomaly_score(row),
+                }
+            })
+            .collect(),
+    }
+}
+
+pub fn prediction_report(
+    probabilities: &[f64; CLASSES_COUNT],
+    predicted: Class,
+) -> PredictionReport {
+    PredictionReport {
+        schema_version: SCHEMA_VERSION,
+        class: predicted.name(),
+        probabilities: CLASSES
+            .iter()
+            .map(|class| ClassProbability {
+                class: class.name(),
+                probability: probabilities[class.index()],
+            })
+            .collect(),
+    }
+}
+
+pub fn inspect_report(model: &Model, metadata: &AttributeMetadata) -> InspectReport {
+    InspectReport {
+        schema_version: SCHEMA_VERSION,
+        version: model.version(),
+        rows: model.rows_count(),
+        smoothing: model.smoothing(),
+        missing_votes: model.missing_votes(),
+        prior_mode: model.prior_mode(),
+        feature_selection: model.feature_selection(),
+        threshold: model.threshold(),
+        threshold_class: model.threshold().map(|_| model.threshold_class().name()),
+        priors: CLASSES
+            .iter()
+            .map(|&class| PriorReport {
+                class: class.name(),
+                count: model.class_count(class),
+                prior: model.prior(class),
+            })
+            .collect(),
+        attributes: (0..ATTRIBUTES_COUNT)
+            .map(|attribute| AttributeReport {
+                attribute: metadata.name(attribute).to_string(),
+                description: metadata.description(attribute).map(str::to_string),
+                vocabulary: model
+                    .vocabulary(attribute)
+                    .iter()
+                    .map(|choice| choice.name())
+                    .collect(),
+                selected: model.is_selected(attribute),
+                probabilities: CLASSES
+                    .iter()
+                    .flat_map(|&class| CHOICES.iter().map(move |&choice| (class, choice)))
+                    .map(|(class, choice)| ConditionalProbability {
+                        class: class.name(),
+                        choice: choice.name(),
+                        probability: model.conditional_probability(class, attribute, choice),
+                    })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+// Colors are never used with --no-color or when NO_COLOR is set to a
+// non-empty value, see https://no-color.org
+pub fn color_allowed(no_color: bool) -> bool {
+    !no_color && env::var_os("NO_COLOR").is_none_or(|x| x.is_empty())
+}
+
+// Whether the tables printed to stdout are colored
+pub fn use_color(no_color: bool) -> bool {
+    color_allowed(no_color) && io::stdout().is_terminal()
+}
+
+fn new_table(color: bool) -> Table {
+    let mut table = Table::new();
+    table.load_style(UTF8_FULL_CONDENSED);
+
+    if !color {
+        table.force_no_tty();
+    }
+
+    table
+}
+
+fn number(value: impl ToString) -> Cell {
+    Cell::new(value).set_alignment(CellAlignment::Right)
+}
+
+// Green for good folds, yellow for middling ones and red for bad ones
+fn accuracy_cell(accuracy: f64) -> Cell {
+    let color = if accuracy >= 0.9 {
+        Color::Green
+    } else if accuracy >= 0.8 {
+        Color::Yellow
+    } else {
+        Color::Red
+    };
+
+    number(format!("{:.4}", accuracy)).fg(color)
+}
+
+pub fn folds_table(folds: &[FoldResult], color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Fold", "Accuracy", "Log-likelihood"]);
+
+    for fold in folds {
+        table.add_row(vec![
+            number(fold.fold),
+            accuracy_cell(fold.accuracy),
+            number(format!("{:.4}", fold.log_likelihood)),
+        ]);
+    }
+
+    table.add_row(vec![
+        Cell::new("Average").add_attribute(Attribute::Bold),
+        accuracy_cell(average_accuracy(folds)).add_attribute(Attribute::Bold),
+        number(format!("{:.4}", average_log_likelihood(folds))).add_attribute(Attribute::Bold),
+    ]);
+
+    table
+}
+
+pub fn compare_table(report: &CompareReport, color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Config", "Mean accuracy", "Std"]);
+
+    for score in &report.configs {
+        table.add_row(vec![
+            Cell::new(&score.config).add_attribute(Attribute::Bold),
+            accuracy_cell(score.mean_accuracy),
+            number(format!("{:.4}", score.std_accuracy)),
+        ]);
+    }
+
+    table
+}
+
+pub fn model_comparison_table(report: &ModelComparisonReport, color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec![
+        "Model".to_string(),
+        "Accuracy".to_string(),
+        "Log-likelihood".to_string(),
+        format!("Average precision ({})", report.positive_class),
+        "ROC area".to_string(),
+        "ECE".to_string(),
+    ]);
+
+    for score in &report.models {
+        table.add_row(vec![
+            Cell::new(&score.model).add_attribute(Attribute::Bold),
+            accuracy_cell(score.accuracy),
+            number(format!("{:.4}", score.log_likelihood)),
+            number(format!("{:.4}", score.average_precision)),
+            number(format!("{:.4}", score.roc_area)),
+            number(format!("{:.4}", score.expected_calibration_error)),
+        ]);
+    }
+
+    table
+}
+
+// Differences that are unlikely to be chance are bold
+pub fn model_pairs_table(pairs: &[ModelPair], color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec![
+        "First",
+        "Second",
+        "Only first right",
+        "Only second right",
+        "Chi-squared",
+        "p",
+    ]);
+
+    for pair in pairs {
+        let mut p = number(format!("{:.4}", pair.test.p_value));
+        if pair.test.p_value < 0.05 {
+            p = p.add_attribute(Attribute::Bold);
+        }
+        table.add_row(vec![
+            Cell::new(&pair.first),
+            Cell::new(&pair.second),
+            number(pair.test.first_only),
+            number(pair.test.second_only),
+            number(format!("{:.3}", pair.test.statistic)),
+            p,
+        ]);
+    }
+
+    table
+}
+
+pub fn tune_table(results: &[TuneResult], color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec![
+        "Model",
+        "Unknown votes",
+        "Priors",
+        "Attributes",
+        "Smoothing",
+        "Accuracy",
+        "Log-likelihood",
+    ]);
+
+    for (i, result) in results.iter().enumerate() {
+        let mut row = vec![
+            Cell::new(result.candidate.model.name()),
+            Cell::new(result.candidate.missing_votes.name()),
+            Cell::new(result.candidate.prior_mode.name()),
+            match result.candidate.feature_selection {
+                Some(k) => number(k.to_string()),
+                None => Cell::new("all"),
+            },
+            number(format!("{:.4}", result.candidate.smoothing)),
+            accuracy_cell(result.accuracy),
+            number(format!("{:.4}", result.log_likelihood)),
+        ];
+        // The best one is the one written out
+        if i == 0 {
+            row = row
+                .into_iter()
+                .map(|cell| cell.add_attribute(Attribute::Bold))
+                .collect();
+        }
+        table.add_row(row);
+    }
+
+    table
+}
+
+pub fn bootstrap_table(estimate: &BootstrapEstimate, color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Estimate", "Accuracy"]);
+
+    for (name, accuracy) in [
+        ("Resubstitution", estimate.resubstitution_accuracy),
+        ("Out of bag", estimate.out_of_bag_accuracy),
+        (".632", estimate.accuracy_632),
+    ] {
+        table.add_row(vec![Cell::new(name), accuracy_cell(accuracy)]);
+    }
+
+    table.add_row(vec![
+        Cell::new(".632+").add_attribute(Attribute::Bold),
+        accuracy_cell(estimate.accuracy_632_plus).add_attribute(Attribute::Bold),
+    ]);
+
+    table
+}
+
+pub fn lift_table(deciles: &[Decile], positive: Class, color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec![
+        "Decile".to_string(),
+        "Rows".to_string(),
+        format!("Rows of {}", positive.name()),
+        "Lift".to_string(),
+        "Cumulative gain".to_string(),
+        "Cumulative lift".to_string(),
+    ]);
+
+    for decile in deciles {
+        table.add_row(vec![
+            number(decile.decile),
+            number(decile.rows),
+            number(decile.positives),
+            number(format!("{:.2}", decile.lift)),
+            percent(decile.cumulative_gain),
+            number(format!("{:.2}", decile.cumulative_lift)),
+        ]);
+    }
+
+    table
+}
+
+// Bins where the probabilities are off by more than 0.1 in red
+pub fn calibration_table(report: &CalibrationReport, color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Probability", "Rows", "Predicted", "Observed"]);
+
+    for bin in &report.bins {
+        let observed = number(format!("{:.4}", bin.observed));
+        table.add_row(vec![
+            Cell::new(format!("{:.1}-{:.1}", bin.lower, bin.upper)),
+            number(bin.rows),
+            number(format!("{:.4}", bin.predicted)),
+            if bin.rows > 0 && (bin.observed - bin.predicted).abs() > 0.1 {
+                observed.fg(Color::Red)
+            } else {
+                observed
+            },
+        ]);
+    }
+
+    table.add_row(vec![
+        Cell::new("ECE").add_attribute(Attribute::Bold),
+        Cell::new(""),
+        Cell::new(""),
+        number(format!("{:.4}", report.expected_calibration_error)).add_attribute(Attribute::Bold),
+    ]);
+
+    table
+}
+
+// Correct predictions are on the diagonal, in green, and mistakes in red
+pub fn confusion_table(confusion: &ConfusionMatrix, color: bool) -> Table {
+    let mut table = new_table(color);
+    let mut header = vec![Cell::new("Actual \\ predicted")];
+    header.extend(CLASSES.iter().map(|class| Cell::new(class.name())));
+    table.set_header(header);
+
+    for &actual in CLASSES.iter() {
+        let mut row = vec![Cell::new(actual.name())];
+
+        for &predicted in CLASSES.iter() {
+            let count = confusion.get(actual, predicted);
+            let cell = number(count);
+
+            row.push(if actual == predicted {
+                cell.fg(Color::Green)
+            } else if count > 0 {
+                cell.fg(Color::Red)
+            } else {
+                cell
+            });
+        }
+
+        table.add_row(row);
+    }
+
+    table
+}
+
+// The predicted class is highlighted
+pub fn probabilities_table(
+    probabilities: &[f64; CLASSES_COUNT],
+    predicted: Class,
+    color: bool,
+) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Class", "Probability"]);
+
+    for &class in CLASSES.iter() {
+        let mut row = vec![
+            Cell::new(class.name()),
+            number(format!("{:.4}", probabilities[class.index()])),
+        ];
+
+        if class == predicted {
+            row = row
+                .into_iter()
+                .map(|cell| cell.fg(Color::Green).add_attribute(Attribute::Bold))
+                .collect();
+        }
+
+        table.add_row(row);
+    }
+
+    table
+}
+
+pub fn attributes_table(reports: &[ClassAttributesReport], color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Yes vote points to", "Attribute", "Log odds", "Ratio"]);
+
+    for report in reports {
+        for (i, odds) in report.attributes.iter().enumerate() {
+            // The class is only named once per group
+            let class = if i == 0 { report.class } else { "" };
+            table.add_row(vec![
+                Cell::new(class).add_attribute(Attribute::Bold),
+                Cell::new(&odds.attribute),
+                number(format!("{:.4}", odds.log_odds)),
+                number(format!("{:.1}x", 10f64.powf(odds.log_odds))),
+            ]);
+        }
+    }
+
+    table
+}
+
+fn percent(share: f64) -> Cell {
+    number(format!("{:.1}%", share * 100.0))
+}
+
+pub fn class_distribution_table(stats: &DatasetStats, color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Class", "Rows", "Share"]);
+
+    for &class in CLASSES.iter() {
+        let count = stats.class_count(class);
+        table.add_row(vec![
+            Cell::new(class.name()),
+            number(count),
+            percent(count as f64 / stats.rows_count() as f64),
+        ]);
+    }
+
+    table.add_row(vec![
+        Cell::new("Total").add_attribute(Attribute::Bold),
+        number(stats.rows_count()).add_attribute(Attribute::Bold),
+        Cell::new(""),
+    ]);
+
+    table
+}
+
+// Votes on every attribute, overall and then per class. Attributes that
+// over a tenth of the rows didn't vote on are highlighted.
+pub fn attribute_stats_table(
+    stats: &DatasetStats,
+    metadata: &AttributeMetadata,
+    color: bool,
+) -> Table {
+    let mut table = new_table(color);
+    let mut header = vec![Cell::new("Attribute"), Cell::new("Class")];
+    header.extend(CHOICES.iter().map(|choice| Cell::new(choice.name())));
+    header.push(Cell::new("Missing"));
+    table.set_header(header);
+
+    let missing_cell = |rate: f64| {
+        let cell = percent(rate);
+        if rate > 0.1 {
+            cell.fg(Color::Yellow)
+        } else {
+            cell
+        }
+    };
+
+    for attribute in 0..ATTRIBUTES_COUNT {
+        let mut row = vec![
+            Cell::new(metadata.name(attribute)).add_attribute(Attribute::Bold),
+            Cell::new("all"),
+        ];
+        row.extend(
+            CHOICES
+                .iter()
+                .map(|&choice| number(stats.total_count(attribute, choice))),
+        );
+        row.push(missing_cell(stats.missing_rate(attribute)));
+        table.add_row(row);
+
+        for &class in CLASSES.iter() {
+            let mut row = vec![Cell::new(""), Cell::new(class.name())];
+            row.extend(
+                CHOICES
+                    .iter()
+                    .map(|&choice| number(stats.count(class, attribute, choice))),
+            );
+            row.push(missing_cell(stats.class_missing_rate(class, attribute)));
+            table.add_row(row);
+        }
+    }
+
+    table
+}
+
+// Label conflicts are highlighted, since no model can get all of their rows
+// right
+pub fn duplicates_table(report: &DuplicatesReport, color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Lines with the same votes", "Classes"]);
+
+    for group in &report.groups {
+        let lines: Vec<String> = group.lines.iter().map(|line| line.to_string()).collect();
+        let classes = Cell::new(group.classes.join(", "));
+
+        table.add_row(vec![
+            Cell::new(lines.join(", ")),
+            if group.conflict {
+                classes.fg(Color::Red)
+            } else {
+                classes
+            },
+        ]);
+    }
+
+    table
+}
+
+pub fn findings_table(findings: &[Finding], color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Line", "Problem"]);
+
+    for finding in findings {
+        table.add_row(vec![
+            match finding.line {
+                Some(line) => number(line),
+                None => Cell::new(""),
+            },
+            Cell::new(finding.problem.to_string()).fg(Color::Red),
+        ]);
+    }
+
+    table
+}
+
+// Pairs that are dependent even after a Bonferroni correction for testing
+// every pair are highlighted
+pub fn dependence_table(pairs: &[PairDependenceReport], tested: usize, color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec![
+        "Attribute",
+        "Attribute",
+        "Mutual information (bits)",
+        "G",
+        "p-value",
+    ]);
+
+    for pair in pairs {
+        let p_value = number(format!("{:.2e}", pair.p_value));
+        table.add_row(vec![
+            Cell::new(&pair.first),
+            Cell::new(&pair.second),
+            number(format!("{:.4}", pair.mutual_information)),
+            number(format!("{:.1}", pair.g)),
+            if pair.p_value < DEPENDENCE_SIGNIFICANCE / tested as f64 {
+                p_value.fg(Color::Red)
+            } else {
+                p_value
+            },
+        ]);
+    }
+
+    table
+}
+
+// Rows the model also gets wrong are highlighted
+pub fn outliers_table(report: &OutliersReport, color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec![
+        "Line",
+        "Class",
+        "Predicted",
+        "Log likelihood",
+        "Anomaly score",
+    ]);
+
+    for outlier in &report.outliers {
+        let predicted = Cell::new(outlier.predicted);
+
+        table.add_row(vec![
+            number(outlier.line),
+            Cell::new(outlier.class),
+            if outlier.predicted != outlier.class {
+                predicted.fg(Color::Red)
+            } else {
+                predicted
+            },
+            number(format!("{:.4}", outlier.log_likelihood)),
+            number(format!("{:.4}", outlier.anomaly_score)),
+        ]);
+    }
+
+    table
+}
+
+pub fn misclassifications_table(reports: &[MisclassificationReport], color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec![
+        "Line",
+        "Fold",
+        "Class",
+        "Predicted",
+        "Margin",
+        "Misleading votes",
+    ]);
+
+    for report in reports {
+        table.add_row(vec![
+            number(report.line),
+            number(report.fold),
+            Cell::new(report.class),
+            Cell::new(report.predicted).fg(Color::Red),
+            number(format!("{:.4}", report.margin)),
+            Cell::new(
+                report
+                    .attributes
+                    .iter()
+                    .map(|attribute| format!("{}={}", attribute.attribute, attribute.vote))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            ),
+        ]);
+    }
+
+    table
+}
+
+pub fn bench_table(runs: &[(&str, &LatencyStats)], color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec![
+        "Prediction",
+        "Calls",
+        "Rows/s",
+        "p50",
+        "p90",
+        "p99",
+        "p99.9",
+        "Max",
+    ]);
+
+    for (name, stats) in runs {
+        table.add_row(vec![
+            Cell::new(name).add_attribute(Attribute::Bold),
+            number(stats.calls),
+            number(format!("{:.0}", stats.rows_per_second())),
+            number(format!("{:?}", stats.p50)),
+            number(format!("{:?}", stats.p90)),
+            number(format!("{:?}", stats.p99)),
+            number(format!("{:?}", stats.p999)),
+            number(format!("{:?}", stats.max)),
+        ]);
+    }
+
+    table
+}
+
+pub fn train_bench_table(sizes: &[TrainTimings], color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Rows", "Load", "Split", "Fit", "Total", "Rows/s"]);
+
+    for timings in sizes {
+        table.add_row(vec![
+            number(timings.rows).add_attribute(Attribute::Bold),
+            number(format!("{:?}", timings.load)),
+            number(format!("{:?}", timings.split)),
+            number(format!("{:?}", timings.fit)),
+            number(format!("{:?}", timings.total())),
+            number(format!("{:.0}", timings.rows_per_second())),
+        ]);
+    }
+
+    table
+}
+
+// The chosen threshold is in bold
+pub fn threshold_table(curve: &[ThresholdPoint], chosen: &ThresholdPoint, color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec![
+        "Threshold",
+        "Precision",
+        "Recall",
+        "F1",
+        "Accuracy",
+        "Balanced accuracy",
+    ]);
+
+    for point in curve {
+        let mut row = vec![
+            number(format!("{:.2}", point.threshold)),
+            number(format!("{:.4}", point.precision)),
+            number(format!("{:.4}", point.recall)),
+            number(format!("{:.4}", point.f1)),
+            number(format!("{:.4}", point.accuracy)),
+            number(format!("{:.4}", point.balanced_accuracy)),
+        ];
+        if point == chosen {
+            row = row
+                .into_iter()
+                .map(|cell| cell.add_attribute(Attribute::Bold))
+                .collect();
+        }
+        table.add_row(row);
+    }
+
+    table
+}
+
+pub fn registry_table(entries: &[Entry], color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Name", "Version", "Pushed", "Rows", "SHA-256"]);
+
+    for entry in entries {
+        let metadata = &entry.metadata;
+        table.add_row(vec![
+            Cell::new(&metadata.name).add_attribute(Attribute::Bold),
+            Cell::new(&metadata.version),
+            Cell::new(&metadata.pushed_at),
+            number(metadata.rows),
+            // Enough to tell models apart at a glance
+            Cell::new(&metadata.sha256[..12]),
+        ]);
+    }
+
+    table
+}
+
+pub fn cache_table(entries: &[cache::Metadata], color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["URI", "Downloaded", "Bytes", "SHA-256"]);
+
+    for entry in entries {
+        table.add_row(vec![
+            Cell::new(&entry.uri).add_attribute(Attribute::Bold),
+            Cell::new(&entry.downloaded_at),
+            number(entry.size),
+            // Like registry_table
+            Cell::new(&entry.sha256[..12]),
+        ]);
+    }
+
+    table
+}
+
+// Growing shares in green and shrinking ones in red
+fn change_cell(change: f64) -> Cell {
+    let cell = number(format!("{:+.4}", change));
+
+    if change > 0.0 {
+        cell.fg(Color::Green)
+    } else if change < 0.0 {
+        cell.fg(Color::Red)
+    } else {
+        cell
+    }
+}
+
+pub fn prior_shifts_table(shifts: &[PriorShift], color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Class", "Prior before", "Prior after", "Change"]);
+
+    for shift in shifts {
+        table.add_row(vec![
+            Cell::new(shift.class),
+            number(format!("{:.4}", shift.before)),
+            number(format!("{:.4}", shift.after)),
+            change_cell(shift.change()),
+        ]);
+    }
+
+    table
+}
+
+pub fn attribute_shifts_table(shifts: &[AttributeShift], color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec![
+        "Attribute",
+        "Probability",
+        "Before",
+        "After",
+        "Change",
+    ]);
+
+    for shift in shifts {
+        table.add_row(vec![
+            Cell::new(shift.attribute),
+            Cell::new(format!("P({} | {})", shift.choice, shift.class)),
+            number(format!("{:.4}", shift.before)),
+            number(format!("{:.4}", shift.after)),
+            change_cell(shift.change()),
+        ]);
+    }
+
+    table
+}
+
+// Only the attributes with a description in the metadata file
+pub fn attribute_descriptions_table(metadata: &AttributeMetadata, color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Attribute", "Description"]);
+
+    for attribute in 0..ATTRIBUTES_COUNT {
+        if let Some(description) = metadata.description(attribute) {
+            table.add_row(vec![
+                Cell::new(metadata.name(attribute)),
+                Cell::new(description),
+            ]);
+        }
+    }
+
+    table
+}
+
+pub fn priors_table(model: &Model, color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Class", "Rows", "Prior"]);
+
+    for &class in CLASSES.iter() {
+        table.add_row(vec![
+            Cell::new(class.name()),
+            number(model.class_count(class)),
+            number(format!("{:.4}", model.prior(class))),
+        ]);
+    }
+
+    table
+}
+
+// A row per attribute with P(choice | class) of every class and choice. The
+// class a choice points to is highlighted.
+pub fn conditional_probabilities_table(
+    model: &Model,
+    metadata: &AttributeMetadata,
+    color: bool,
+) -> Table {
+    let mut table = new_table(color);
+    let mut header = vec![Cell::new("Attribute")];
+    for &class in CLASSES.iter() {
+        for &choice in CHOICES.iter() {
+            header.push(Cell::new(format!(
+                "P({} | {})",
+                choice.name(),
+                class.name()
+            )));
+        }
+    }
+    table.set_header(header);
+
+    for attribute in 0..ATTRIBUTES_COUNT {
+        let mut row = vec![Cell::new(metadata.name(attribute))];
+
+        for &class in CLASSES.iter() {
+            for &choice in CHOICES.iter() {
+                let probability = model.conditional_probability(class, attribute, choice);
+                let cell = number(format!("{:.4}", probability));
+                let likeliest = CLASSES.iter().all(|&other| {
+                    model.conditional_probability(other, attribute, choice) <= probability
+                });
+
+                row.push(if likeliest {
+                    cell.fg(Color::Green)
+                } else {
+                    cell
+                });
+            }
+        }
+
+        table.add_row(row);
+    }
+
+    table
+}
+
+pub fn memory_table(report: &MemoryReport, color: bool) -> Table {
+    let mut table = new_table(color);
+    table.set_header(vec!["Memory", "Bytes"]);
+    table.add_row(vec![Cell::new("Counts"), number(report.counts_bytes)]);
+    table.add_row(vec![
+        Cell::new("Log probability tables"),
+        number(report.log_tables_bytes),
+    ]);
+    table.add_row(vec![
+        Cell::new("Total").add_attribute(Attribute::Bold),
+        number(report.total_bytes).add_attribute(Attribute::Bold),
+    ]);
+
+    table
+}
+
+// comfy-table can only style cells with crossterm, so on wasm32 the tables
+// take the same styles and print them plain
+#[cfg(target_arch = "wasm32")]
+mod plain {
+    use comfy_table::{Cell, Table};
+
+    pub enum Attribute {
+        Bold,
+    }
+
+    pub enum Color {
+        Green,
+        Yellow,
+        Red,
+    }
+
+    pub trait PlainCell {
+        fn fg(self, color: Color) -> Self;
+        fn add_attribute(self, attribute: Attribute) -> Self;
+    }
+
+    impl PlainCell for Cell {
+        fn fg(self, _: Color) -> Self {
+            self
+        }
+
+        fn add_attribute(self, _: Attribute) -> Self {
+            self
+        }
+    }
+
+    pub trait PlainTable {
+        fn force_no_tty(&mut self) -> &mut Self;
+    }
+
+    impl PlainTable for Table {
+        fn force_no_tty(&mut self) -> &mut Self {
+            self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_the_confusion_matrix() {
+        let mut confusion = ConfusionMatrix::new();
+        confusion.add(Class::Republican, Class::Republican);
+        confusion.add(Class::Republican, Class::Democrat);
+
+        assert_eq!(
+            confusion_table(&confusion, false).to_string(),
+            "\
+┌────────────────────┬────────────┬──────────┐
+│ Actual \\ predicted ┆ republican ┆ democrat │
+╞════════════════════╪════════════╪══════════╡
+│ republican         ┆          1 ┆        1 │
+│ democrat           ┆          0 ┆        0 │
+└────────────────────┴────────────┴──────────┘"
+        );
+    }
+
+    #[test]
+    fn leaves_the_colors_out_without_color() {
+        let table = probabilities_table(&[0.25, 0.75], Class::Democrat, false).to_string();
+
+        assert!(!table.contains('\x1b'));
+        assert!(table.contains("│ democrat   ┆      0.7500 │"));
+        assert!(!color_allowed(true));
+    }
+
+    #[test]
+    fn leaves_metrics_that_werent_asked_for_out_of_the_json() {
+        let mut confusion = ConfusionMatrix::new();
+        confusion.add(Class::Republican, Class::Democrat);
+        let report = RunReport {
+            schema_version: SCHEMA_VERSION,
+            folds: None,
+            average_accuracy: Some(0.5),
+            average_log_likelihood: None,
+            confusion_matrix: Some(confusion_report(&confusion)),
+            attributes: None,
+            average_precision: None,
+            lift: None,
+            calibration: None,
+            misclassified: None,
+            memory: None,
+            positive_class: None,
+            model_selection: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&report).unwrap(),
+            serde_json::json!({
+                "schema_version": SCHEMA_VERSION,
+                "average_accuracy": 0.5,
+                "confusion_matrix": {
+                    "classes": ["republican", "democrat"],
+                    "counts": [[0, 1], [0, 0]],
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn lists_the_probabilities_in_class_order() {
+        let report = prediction_report(&[0.25, 0.75], Class::Democrat);
+
+        assert_eq!(
+            serde_json::to_string(&report).unwrap(),
+            format!(
+                r#"{{"schema_version":{},"class":"democrat","probabilities":[{{"class":"republican","probability":0.25}},{{"class":"democrat","probability":0.75}}]}}"#,
+                SCHEMA_VERSION
+            )
+        );
+    }
+
+    #[test]
+    fn inspects_the_priors_and_probabilities_of_the_model() {
+        let rows = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| crate::data::try_parse_row(line).unwrap());
+        let model = Model::from_rows(rows);
+        let metadata = AttributeMetadata::default();
+        let report = inspect_report(&model, &metadata);
+
+        assert_eq!(report.rows, 435);
+        let counts: Vec<_> = report.priors.iter().map(|prior| prior.count).collect();
+        assert_eq!(counts, [168, 267]);
+        assert_eq!(report.attributes.len(), ATTRIBUTES_COUNT);
+        assert_eq!(report.attributes[3].attribute, metadata.name(3));
+        for attribute in &report.attributes {
+            for class in CLASSES.iter() {
+                let total: f64 = attribute
+                    .probabilities
+                    .iter()
+                    .filter(|probability| probability.class == class.name())
+                    .map(|probability| probability.probability)
+                    .sum();
+                assert!((total - 1.0).abs() < 1e-9);
+            }
+        }
+        assert!(report.threshold.is_none() && report.threshold_class.is_none());
+
+        let table = conditional_probabilities_table(&model, &metadata, false).to_string();
+        assert!(table.contains("P(y | republican)"));
+        assert!(table.contains(metadata.name(3)));
+        let table = priors_table(&model, false).to_string();
+        assert!(table.contains(&format!("{:.4}", model.prior(Class::Democrat))));
+    }
+}
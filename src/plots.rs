@@ -0,0 +1,248 @@
+use std::fs;
+use std::path::Path;
+
+use plotters::prelude::*;
+
+use crate::curves::{self, CalibrationBin, PrecisionRecallPoint, RocPoint};
+use crate::data::Class;
+use crate::evaluation::FoldResult;
+
+const SIZE: (u32, u32) = (640, 480);
+const FONT: (&str, u32) = ("sans-serif", 20);
+
+// Writes accuracy.svg, roc.svg, precision-recall.svg and calibration.svg to
+// the directory, creating it if needed. The probabilities are of the
+// positive class for every tested row, like for crate::curves.
+pub fn write_plots(
+    dir: &str,
+    folds: &[FoldResult],
+    probabilities: &[(bool, f64)],
+    positive: Class,
+) -> Result<(), String> {
+    fs::create_dir_all(dir).map_err(|e| format!("Couldn't create {}: {}", dir, e))?;
+    let dir = Path::new(dir);
+
+    fold_accuracy(&dir.join("accuracy.svg"), folds)?;
+    roc(
+        &dir.join("roc.svg"),
+        &curves::roc_curve(probabilities),
+        positive,
+    )?;
+    precision_recall(
+        &dir.join("precision-recall.svg"),
+        &curves::precision_recall_curve(probabilities),
+        positive,
+    )?;
+    calibration(
+        &dir.join("calibration.svg"),
+        &curves::calibration_curve(probabilities, curves::CALIBRATION_BINS),
+        positive,
+    )
+}
+
+fn fold_accuracy(path: &Path, folds: &[FoldResult]) -> Result<(), String> {
+    let root = SVGBackend::new(path, SIZE).into_drawing_area();
+    root.fill(&WHITE).map_err(|e| e.to_string())?;
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Accuracy per fold", FONT)
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d((0..folds.len()).into_segmented(), 0.0..1.0)
+        .map_err(|e| e.to_string())?;
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_desc("Fold")
+        .y_desc("Accuracy")
+        .draw()
+        .map_err(|e| e.to_string())?;
+    chart
+        .draw_series(
+            Histogram::vertical(&chart)
+                .style(BLUE.filled())
+                .margin(5)
+                .data(folds.iter().map(|fold| (fold.fold, fold.accuracy))),
+        )
+        .map_err(|e| e.to_string())?;
+
+    root.present().map_err(|e| e.to_string())
+}
+
+fn roc(path: &Path, curve: &[RocPoint], positive: Class) -> Result<(), String> {
+    let root = SVGBackend::new(path, SIZE).into_drawing_area();
+    root.fill(&WHITE).map_err(|e| e.to_string())?;
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption(
+            format!(
+                "ROC of finding {}s, area {:.4}",
+                positive.name(),
+                curves::roc_area(curve)
+            ),
+            FONT,
+        )
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0.0..1.0, 0.0..1.0)
+        .map_err(|e| e.to_string())?;
+    chart
+        .configure_mesh()
+        .x_desc("False positive rate")
+        .y_desc("True positive rate")
+        .draw()
+        .map_err(|e| e.to_string())?;
+    // Guessing at random
+    chart
+        .draw_series(LineSeries::new([(0.0, 0.0), (1.0, 1.0)], &BLACK.mix(0.3)))
+        .map_err(|e| e.to_string())?;
+    chart
+        .draw_series(LineSeries::new(
+            curve
+                .iter()
+                .map(|point| (point.false_positive_rate, point.true_positive_rate)),
+            &BLUE,
+        ))
+        .map_err(|e| e.to_string())?;
+
+    root.present().map_err(|e| e.to_string())
+}
+
+fn precision_recall(
+    path: &Path,
+    curve: &[PrecisionRecallPoint],
+    positive: Class,
+) -> Result<(), String> {
+    let root = SVGBackend::new(path, SIZE).into_drawing_area();
+    root.fill(&WHITE).map_err(|e| e.to_string())?;
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption(
+            format!(
+                "Precision and recall of finding {}s, average precision {:.4}",
+                positive.name(),
+                curves::average_precision(curve)
+            ),
+            FONT,
+        )
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0.0..1.0, 0.0..1.0)
+        .map_err(|e| e.to_string())?;
+    chart
+        .configure_mesh()
+        .x_desc("Recall")
+        .y_desc("Precision")
+        .draw()
+        .map_err(|e| e.to_string())?;
+    chart
+        .draw_series(LineSeries::new(
+            curve.iter().map(|point| (point.recall, point.precision)),
+            &BLUE,
+        ))
+        .map_err(|e| e.to_string())?;
+
+    root.present().map_err(|e| e.to_string())
+}
+
+fn calibration(path: &Path, bins: &[CalibrationBin], positive: Class) -> Result<(), String> {
+    let root = SVGBackend::new(path, SIZE).into_drawing_area();
+    root.fill(&WHITE).map_err(|e| e.to_string())?;
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption(
+            format!(
+                "Calibration of P({}), expected error {:.4}",
+                positive.name(),
+                curves::expected_calibration_error(bins)
+            ),
+            FONT,
+        )
+        .margin(10)
+        .x_label_area_size(40)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0.0..1.0, 0.0..1.0)
+        .map_err(|e| e.to_string())?;
+    chart
+        .configure_mesh()
+        .x_desc("Predicted probability")
+        .y_desc("Observed share")
+        .draw()
+        .map_err(|e| e.to_string())?;
+    // A perfectly calibrated model
+    chart
+        .draw_series(LineSeries::new([(0.0, 0.0), (1.0, 1.0)], &BLACK.mix(0.3)))
+        .map_err(|e| e.to_string())?;
+    // Empty bins have nothing to show
+    let points: Vec<(f64, f64)> = bins
+        .iter()
+        .filter(|bin| bin.rows > 0)
+        .map(|bin| (bin.predicted, bin.observed))
+        .collect();
+    chart
+        .draw_series(LineSeries::new(points.iter().copied(), &BLUE))
+        .map_err(|e| e.to_string())?;
+    chart
+        .draw_series(
+            points
+                .iter()
+                .map(|&point| Circle::new(point, 4, BLUE.filled())),
+        )
+        .map_err(|e| e.to_string())?;
+
+    root.present().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{try_parse_row, Row};
+    use crate::evaluation::CrossValidation;
+
+    #[test]
+    fn writes_a_plot_of_every_curve() {
+        let rows: Vec<Row> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let crossvalidation = CrossValidation {
+            seed: Some(1),
+            ..CrossValidation::new(5)
+        };
+        let folds: Vec<FoldResult> = crossvalidation.run(rows).unwrap().collect();
+        let probabilities: Vec<(bool, f64)> = folds
+            .iter()
+            .flat_map(|fold| {
+                fold.testing_set
+                    .iter()
+                    .zip(&fold.probabilities)
+                    .map(|(row, probabilities)| {
+                        (
+                            row.class == Class::Republican,
+                            probabilities[Class::Republican.index()],
+                        )
+                    })
+            })
+            .collect();
+
+        let dir = std::env::temp_dir().join(format!("plots-{}", std::process::id()));
+        write_plots(
+            dir.to_str().unwrap(),
+            &folds,
+            &probabilities,
+            Class::Republican,
+        )
+        .unwrap();
+
+        for name in ["accuracy", "roc", "precision-recall", "calibration"] {
+            let svg = fs::read_to_string(dir.join(format!("{}.svg", name))).unwrap();
+            assert!(svg.starts_with("<svg"), "{}", name);
+        }
+        let roc = fs::read_to_string(dir.join("roc.svg")).unwrap();
+        assert!(roc.contains("ROC of finding republicans"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
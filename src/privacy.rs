@@ -0,0 +1,68 @@
+use rand::Rng;
+
+use crate::data::ATTRIBUTES_COUNT;
+
+// How much the counts of a model can change when a single row is added or
+// removed: its class count and one count per attribute
+pub const SENSITIVITY: f64 = (1 + ATTRIBUTES_COUNT) as f64;
+
+// Noise for epsilon-differential privacy, drawn from a Laplace
+// distribution by inverting its CDF
+pub fn laplace_noise<R: Rng>(epsilon: f64, rng: &mut R) -> f64 {
+    let scale = SENSITIVITY / epsilon;
+    let u: f64 = rng.gen_range(-0.5..0.5);
+
+    -scale * u.signum() * (1.0 - 2.0 * u.abs()).ln()
+}
+
+// A count with noise added, rounded and kept non-negative. Rounding and
+// clamping only look at the noisy count, so they don't weaken the privacy.
+pub fn noisy_count<R: Rng>(count: u32, epsilon: f64, rng: &mut R) -> u32 {
+    (count as f64 + laplace_noise(epsilon, rng))
+        .round()
+        .max(0.0) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+    use crate::data::{try_parse_row, Class};
+    use crate::model::Trainer;
+
+    #[test]
+    fn draws_noise_of_the_scale_of_epsilon() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let noise: Vec<f64> = (0..100_000).map(|_| laplace_noise(2.0, &mut rng)).collect();
+        let mean = noise.iter().sum::<f64>() / noise.len() as f64;
+        // The mean absolute deviation of a Laplace distribution is its scale
+        let deviation = noise.iter().map(|x| x.abs()).sum::<f64>() / noise.len() as f64;
+        let scale = SENSITIVITY / 2.0;
+
+        assert!(mean.abs() < 0.05 * scale);
+        assert!((deviation - scale).abs() < 0.05 * scale);
+        // Negative noisy counts are clamped to zero
+        let zeros = (0..1000)
+            .filter(|_| noisy_count(0, 0.1, &mut rng) == 0)
+            .count();
+        assert!(zeros > 450);
+    }
+
+    #[test]
+    fn trains_close_to_the_counts_with_a_large_epsilon() {
+        let mut trainer = Trainer::new();
+        for line in include_str!("../house-votes-84.data").lines() {
+            trainer.add(&try_parse_row(line).unwrap());
+        }
+        let model = trainer.build();
+        let private = trainer.build_private(1000.0, &mut StdRng::seed_from_u64(1));
+        let noisy = trainer.build_private(0.01, &mut StdRng::seed_from_u64(1));
+
+        assert!((private.prior(Class::Democrat) - model.prior(Class::Democrat)).abs() < 0.01);
+        assert!(private.rows_count().abs_diff(435) <= 2);
+        // The noise hides the rows
+        assert!(noisy.rows_count().abs_diff(435) > 2);
+    }
+}
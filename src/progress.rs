@@ -0,0 +1,128 @@
+use std::io::{self, IsTerminal};
+use std::time::Duration;
+
+use indicatif::{ProgressBar, ProgressStyle};
+use tracing::Level;
+
+// Progress of a long-running step. It's drawn as a progress bar while it
+// runs when stdout is a terminal, so piped output isn't interleaved with
+// redraws. Either way, a summary is logged when it finishes.
+pub struct Progress {
+    label: String,
+    bar: Option<ProgressBar>,
+}
+
+impl Progress {
+    // A step with a known number of items
+    pub fn bar(label: &str, len: u64) -> Self {
+        Self::new(label, || {
+            ProgressBar::new(len).with_style(
+                ProgressStyle::with_template("{prefix} [{bar:30}] {pos}/{len}")
+                    .unwrap()
+                    .progress_chars("=> "),
+            )
+        })
+    }
+
+    // A step where it isn't known up front how many items there are
+    pub fn spinner(label: &str) -> Self {
+        Self::new(label, || {
+            let bar = ProgressBar::new_spinner()
+                .with_style(ProgressStyle::with_template("{spinner} {prefix} {pos}").unwrap());
+            bar.enable_steady_tick(Duration::from_millis(100));
+            bar
+        })
+    }
+
+    fn new(label: &str, make_bar: impl FnOnce() -> ProgressBar) -> Self {
+        // Also hidden when --quiet turns off the summaries
+        let visible = io::stdout().is_terminal() && tracing::enabled!(Level::INFO);
+        let bar = visible.then(|| {
+            let bar = make_bar();
+            bar.set_prefix(label.to_string());
+            bar
+        });
+
+        Progress {
+            label: label.to_string(),
+            bar,
+        }
+    }
+
+    pub fn inc(&self, delta: u64) {
+        if let Some(bar) = &self.bar {
+            bar.inc(delta);
+        }
+    }
+
+    pub fn finish(&self, summary: &str) {
+        if let Some(bar) = &self.bar {
+            bar.finish_and_clear();
+        }
+
+        tracing::info!("{}: {}", self.label, summary);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::level_filters::LevelFilter;
+
+    use super::*;
+
+    // Runs `f` with the log lines at or above `level` collected
+    fn logs(level: LevelFilter, f: impl FnOnce()) -> String {
+        let lines = Arc::new(Mutex::new(vec![]));
+        let writer = lines.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(level)
+            .with_ansi(false)
+            .without_time()
+            .with_writer(move || Writer(writer.clone()))
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, f);
+        let lines = lines.lock().unwrap().clone();
+        String::from_utf8(lines).unwrap()
+    }
+
+    struct Writer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Writer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn logs_a_summary_when_done() {
+        let logs = logs(LevelFilter::INFO, || {
+            let progress = Progress::bar("Training", 3);
+            // Only drawn in a terminal
+            assert_eq!(progress.bar.is_some(), io::stdout().is_terminal());
+            progress.inc(3);
+            progress.finish("3 folds");
+        });
+
+        assert!(logs.contains("Training: 3 folds"));
+    }
+
+    #[test]
+    fn stays_quiet_with_quiet() {
+        let logs = logs(LevelFilter::ERROR, || {
+            let progress = Progress::spinner("Loading");
+            assert!(progress.bar.is_none());
+            progress.inc(1);
+            progress.finish("1 row");
+        });
+
+        assert!(logs.is_empty());
+    }
+}
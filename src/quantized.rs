@@ -0,0 +1,141 @@
+use std::fs;
+use std::io;
+
+use party_recogniser_core::QuantizedTables;
+
+use crate::data::{Choice, Class, ATTRIBUTES_COUNT, CHOICES, CLASSES};
+use crate::model::{Float, Model};
+
+// A model whose log probabilities are stored as i16 fixed-point numbers, so
+// it can be evaluated with integer arithmetic only, e.g. on microcontrollers
+// without an FPU. The exported bytes can be loaded by party_recogniser_core.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantizedModel {
+    tables: QuantizedTables,
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl QuantizedModel {
+    pub fn from_model(model: &Model) -> Self {
+        let log_tables = model.log_tables();
+        let max_abs = log_tables
+            .class_weights()
+            .iter()
+            .chain(log_tables.attr_weights())
+            .fold(0.0, |acc: Float, &x| acc.max(x.abs()));
+
+        // Use the whole i16 range for the largest magnitude. The scores are
+        // summed in i32, which can't overflow for ATTRIBUTES_COUNT + 1 terms.
+        let scale = if max_abs > 0.0 && max_abs.is_finite() {
+            i16::MAX as Float / max_abs
+        } else {
+            1.0
+        };
+
+        let quantize = |x: &Float| (x * scale).round().max(i16::MIN as Float) as i16;
+
+        // Float is already f32 with the f32 feature
+        #[allow(clippy::unnecessary_cast)]
+        let scale = scale as f32;
+
+        QuantizedModel {
+            tables: QuantizedTables::new(
+                scale,
+                ATTRIBUTES_COUNT,
+                CHOICES.len(),
+                log_tables.class_weights().iter().map(quantize).collect(),
+                log_tables.attr_weights().iter().map(quantize).collect(),
+            ),
+        }
+    }
+
+    pub fn tables(&self) -> &QuantizedTables {
+        &self.tables
+    }
+
+    pub fn predict_class(&self, attributes: &[Choice], class: Class) -> i32 {
+        self.tables
+            .score_class(attributes.iter().map(|x| x.index()), class.index())
+    }
+
+    pub fn predict(&self, attributes: &[Choice]) -> Class {
+        CLASSES[self.tables.predict(attributes.iter().map(|x| x.index()))]
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.tables.to_bytes()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let tables =
+            QuantizedTables::from_bytes(bytes).map_err(|e| invalid_data(&e.to_string()))?;
+
+        let dims = (
+            tables.attributes_count(),
+            tables.choices_count(),
+            tables.classes_count(),
+        );
+
+        if dims != (ATTRIBUTES_COUNT, CHOICES.len(), CLASSES.len()) {
+            return Err(invalid_data("Quantized model has unexpected dimensions"));
+        }
+
+        Ok(QuantizedModel { tables })
+    }
+
+    pub fn save(&self, filename: &str) -> io::Result<()> {
+        fs::write(filename, self.to_bytes())
+    }
+
+    pub fn load(filename: &str) -> io::Result<Self> {
+        Self::from_bytes(&fs::read(filename)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{try_parse_row, Row};
+
+    fn house_votes() -> Vec<Row> {
+        include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn predicts_like_the_model() {
+        let rows = house_votes();
+        let model = Model::from_rows(rows.iter().cloned());
+        let quantized = QuantizedModel::from_model(&model);
+
+        // Rounding can only flip rows both classes score almost the same for
+        let agreeing = rows
+            .iter()
+            .filter(|row| quantized.predict(&row.attributes) == model.classify(&row.attributes))
+            .count();
+        assert!(agreeing * 100 >= rows.len() * 99, "{}", agreeing);
+    }
+
+    #[test]
+    fn loads_what_it_saves() {
+        let quantized = QuantizedModel::from_model(&Model::from_rows(house_votes()));
+        assert_eq!(
+            QuantizedModel::from_bytes(&quantized.to_bytes()).unwrap(),
+            quantized
+        );
+
+        // Tables of another shape than the votes
+        let other = QuantizedTables::new(1.0, 1, 1, vec![0; CLASSES.len()], vec![0; 2]);
+        assert_eq!(
+            QuantizedModel::from_bytes(&other.to_bytes())
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use crate::manifest::{hash_file, timestamp};
+use crate::model::{LoadOptions, Model};
+use crate::signing::signature_path;
+
+const MODEL_FILE: &str = "model.json";
+const METADATA_FILE: &str = "metadata.json";
+
+// A directory of saved models stored under a name and a semantic version,
+// laid out as <root>/<name>/<version>/{model.json,metadata.json}. Pushed
+// versions are never overwritten, so a name and version always refer to the
+// same model.
+pub struct Registry {
+    root: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Metadata {
+    pub name: String,
+    pub version: Version,
+    // RFC 3339 in UTC
+    pub pushed_at: String,
+    // Of the model file, so copies can be checked against the registry
+    pub sha256: String,
+    pub rows: u32,
+    // Where the model was pushed from
+    pub source: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub metadata: Metadata,
+    pub model_path: PathBuf,
+}
+
+fn signature_path_of(path: &Path) -> PathBuf {
+    signature_path(&path.to_string_lossy())
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+// Names become directory names, so anything that could escape the registry
+// or clash between platforms is rejected
+fn check_name(name: &str) -> io::Result<()> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+
+    if !valid {
+        return Err(invalid_input(format!(
+            "Invalid model name '{}', use letters, digits, '-', '_' and '.'",
+            name
+        )));
+    }
+
+    Ok(())
+}
+
+impl Registry {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Registry { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn version_dir(&self, name: &str, version: &Version) -> PathBuf {
+        self.root.join(name).join(version.to_string())
+    }
+
+    // Encrypted models are stored as they are, and need their secret to be
+    // checked. Signatures are stored along with the model.
+    pub fn push(
+        &self,
+        name: &str,
+        version: &Version,
+        model_path: &str,
+        load_options: &LoadOptions,
+    ) -> io::Result<Entry> {
+        check_name(name)?;
+
+        // Make sure it's a model before it's stored as one
+        let model = Model::load_with(model_path, load_options)?;
+        let dir = self.version_dir(name, version);
+
+        if dir.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} {} is already in the registry", name, version),
+            ));
+        }
+
+        fs::create_dir_all(&dir)?;
+        let stored_path = dir.join(MODEL_FILE);
+        fs::copy(model_path, &stored_path)?;
+
+        let signature_path = signature_path(model_path);
+        if signature_path.exists() {
+            fs::copy(&signature_path, signature_path_of(&stored_path))?;
+        }
+
+        let metadata = Metadata {
+            name: name.to_string(),
+            version: version.clone(),
+            pushed_at: timestamp(SystemTime::now()),
+            sha256: hash_file(model_path)?,
+            rows: model.rows_count(),
+            source: model_path.to_string(),
+        };
+
+        let mut writer = BufWriter::new(File::create(dir.join(METADATA_FILE))?);
+        serde_json::to_writer_pretty(&mut writer, &metadata)?;
+        writer.flush()?;
+
+        Ok(Entry {
+            metadata,
+            model_path: stored_path,
+        })
+    }
+
+    fn read_entry(&self, dir: &Path) -> io::Result<Entry> {
+        let reader = BufReader::new(File::open(dir.join(METADATA_FILE))?);
+
+        Ok(Entry {
+            metadata: serde_json::from_reader(reader)?,
+            model_path: dir.join(MODEL_FILE),
+        })
+    }
+
+    // Every version of every model, sorted by name and then version. An
+    // empty or missing registry has no entries.
+    pub fn list(&self) -> io::Result<Vec<Entry>> {
+        let mut res = vec![];
+
+        if !self.root.exists() {
+            return Ok(res);
+        }
+
+        for name_dir in fs::read_dir(&self.root)? {
+            let name_dir = name_dir?;
+            if !name_dir.file_type()?.is_dir() {
+                continue;
+            }
+
+            for version_dir in fs::read_dir(name_dir.path())? {
+                let path = version_dir?.path();
+                if path.join(METADATA_FILE).exists() {
+                    res.push(self.read_entry(&path)?);
+                }
+            }
+        }
+
+        res.sort_by(|a, b| {
+            (&a.metadata.name, &a.metadata.version).cmp(&(&b.metadata.name, &b.metadata.version))
+        });
+        Ok(res)
+    }
+
+    // The given version of the model, or its latest one
+    pub fn get(&self, name: &str, version: Option<&Version>) -> io::Result<Entry> {
+        check_name(name)?;
+
+        let not_found = || {
+            let what = match version {
+                Some(version) => format!("{} {}", name, version),
+                None => name.to_string(),
+            };
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} isn't in the registry", what),
+            )
+        };
+
+        match version {
+            Some(version) => {
+                let dir = self.version_dir(name, version);
+                if !dir.join(METADATA_FILE).exists() {
+                    return Err(not_found());
+                }
+
+                self.read_entry(&dir)
+            }
+            None => self
+                .list()?
+                .into_iter()
+                .filter(|entry| entry.metadata.name == name)
+                .max_by(|a, b| a.metadata.version.cmp(&b.metadata.version))
+                .ok_or_else(not_found),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::try_parse_row;
+
+    // With a model saved in it to push, removed again when dropped
+    struct TempRegistry {
+        registry: Registry,
+        model_path: String,
+    }
+
+    impl TempRegistry {
+        fn new(name: &str) -> Self {
+            let root =
+                std::env::temp_dir().join(format!("registry-{}-{}", name, std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(&root).unwrap();
+
+            let model_path = root.join("pushed.json").to_str().unwrap().to_string();
+            let rows = include_str!("../house-votes-84.data")
+                .lines()
+                .map(|line| try_parse_row(line).unwrap());
+            Model::from_rows(rows).save(&model_path).unwrap();
+
+            TempRegistry {
+                registry: Registry::new(root.join("registry")),
+                model_path,
+            }
+        }
+
+        fn push(&self, name: &str, version: &str) -> io::Result<Entry> {
+            self.registry.push(
+                name,
+                &Version::parse(version).unwrap(),
+                &self.model_path,
+                &LoadOptions::default(),
+            )
+        }
+    }
+
+    impl Drop for TempRegistry {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(self.registry.root().parent().unwrap());
+        }
+    }
+
+    #[test]
+    fn gets_the_latest_version_by_default() {
+        let temp = TempRegistry::new("latest");
+        assert!(temp.registry.list().unwrap().is_empty());
+
+        temp.push("party", "1.10.0").unwrap();
+        temp.push("party", "1.9.0").unwrap();
+        temp.push("other", "2.0.0").unwrap();
+
+        let latest = temp.registry.get("party", None).unwrap();
+        assert_eq!(latest.metadata.version, Version::new(1, 10, 0));
+        assert_eq!(latest.metadata.rows, 435);
+        assert_eq!(latest.metadata.sha256, hash_file(&temp.model_path).unwrap());
+        assert!(Model::load(latest.model_path.to_str().unwrap()).is_ok());
+
+        let older = temp
+            .registry
+            .get("party", Some(&Version::new(1, 9, 0)))
+            .unwrap();
+        assert_eq!(older.metadata.version, Version::new(1, 9, 0));
+
+        let listed: Vec<_> = temp
+            .registry
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|entry| format!("{} {}", entry.metadata.name, entry.metadata.version))
+            .collect();
+        assert_eq!(listed, ["other 2.0.0", "party 1.9.0", "party 1.10.0"]);
+    }
+
+    #[test]
+    fn never_overwrites_a_version() {
+        let temp = TempRegistry::new("overwrite");
+        temp.push("party", "1.0.0").unwrap();
+
+        let e = temp.push("party", "1.0.0").unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::AlreadyExists);
+        let e = temp
+            .registry
+            .get("party", Some(&Version::new(2, 0, 0)))
+            .unwrap_err();
+        assert_eq!(e.to_string(), "party 2.0.0 isn't in the registry");
+        assert_eq!(
+            temp.registry.get("missing", None).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+    }
+
+    #[test]
+    fn rejects_names_that_could_escape_the_registry() {
+        let temp = TempRegistry::new("names");
+
+        for name in ["", "../party", ".hidden", "a/b", "a b"] {
+            let e = temp.push(name, "1.0.0").unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::InvalidInput, "{}", name);
+        }
+        assert!(temp.push("party_2.model-a", "1.0.0").is_ok());
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt::Write;
+
+use crate::output::RunReport;
+
+// Inline, so the page is a single file that can be mailed or attached
+const STYLE: &str = "body { font-family: sans-serif; max-width: 50em; margin: 2em auto; }
+table { border-collapse: collapse; margin-bottom: 1em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; }
+td.number { text-align: right; font-variant-numeric: tabular-nums; }";
+
+// A standalone HTML page of the metrics of a run, for --report. Only the
+// sections of the metrics the run reported are included.
+pub fn to_html(report: &RunReport, settings: &[(String, String)]) -> String {
+    let mut res = String::new();
+
+    writeln!(
+        res,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Cross-validation report</title>\n<style>\n{}\n</style>\n</head>\n<body>",
+        STYLE
+    )
+    .unwrap();
+    writeln!(res, "<h1>Cross-validation report</h1>").unwrap();
+
+    writeln!(res, "<h2>Settings</h2>").unwrap();
+    writeln!(res, "<table>\n<tr><th>Setting</th><th>Value</th></tr>").unwrap();
+    for (key, value) in settings {
+        writeln!(
+            res,
+            "<tr><td>{}</td><td><code>{}</code></td></tr>",
+            escape(key),
+            escape(value)
+        )
+        .unwrap();
+    }
+    writeln!(res, "</table>").unwrap();
+
+    if let Some(folds) = &report.folds {
+        writeln!(res, "<h2>Folds</h2>").unwrap();
+        writeln!(
+            res,
+            "<table>\n<tr><th>Fold</th><th>Accuracy</th><th>Log-likelihood</th></tr>"
+        )
+        .unwrap();
+        for fold in folds {
+            writeln!(
+                res,
+                "<tr><td class=\"number\">{}</td><td class=\"number\">{:.4}</td><td class=\"number\">{:.4}</td></tr>",
+                fold.fold, fold.accuracy, fold.log_likelihood
+            )
+            .unwrap();
+        }
+        writeln!(res, "</table>").unwrap();
+    }
+    if let Some(accuracy) = report.average_accuracy {
+        writeln!(res, "<p>Average accuracy: {:.4}</p>", accuracy).unwrap();
+    }
+
+    if let Some(confusion) = &report.confusion_matrix {
+        writeln!(res, "<h2>Confusion matrix</h2>").unwrap();
+        write!(res, "<table>\n<tr><th>Actual \\ predicted</th>").unwrap();
+        for class in &confusion.classes {
+            write!(res, "<th>{}</th>", class).unwrap();
+        }
+        writeln!(res, "</tr>").unwrap();
+        for (class, counts) in confusion.classes.iter().zip(&confusion.counts) {
+            write!(res, "<tr><th>{}</th>", class).unwrap();
+            for count in counts {
+                write!(res, "<td class=\"number\">{}</td>", count).unwrap();
+            }
+            writeln!(res, "</tr>").unwrap();
+        }
+        writeln!(res, "</table>").unwrap();
+    }
+
+    if let Some(reports) = &report.attributes {
+        writeln!(res, "<h2>Most discriminative attributes</h2>").unwrap();
+        writeln!(
+            res,
+            "<table>\n<tr><th>Yes vote points to</th><th>Attribute</th><th>Log odds</th><th>Ratio</th></tr>"
+        )
+        .unwrap();
+        for report in reports {
+            for odds in &report.attributes {
+                writeln!(
+                    res,
+                    "<tr><td>{}</td><td>{}</td><td class=\"number\">{:.4}</td><td class=\"number\">{:.1}x</td></tr>",
+                    report.class,
+                    escape(&odds.attribute),
+                    odds.log_odds,
+                    10f64.powf(odds.log_odds)
+                )
+                .unwrap();
+            }
+        }
+        writeln!(res, "</table>").unwrap();
+    }
+
+    if let (Some(average_precision), Some(class)) =
+        (report.average_precision, report.positive_class)
+    {
+        writeln!(
+            res,
+            "<p>Average precision of finding {}s: {:.4}</p>",
+            class, average_precision
+        )
+        .unwrap();
+    }
+    if let Some(calibration) = &report.calibration {
+        writeln!(
+            res,
+            "<p>Expected calibration error: {:.4}</p>",
+            calibration.expected_calibration_error
+        )
+        .unwrap();
+    }
+
+    writeln!(res, "</body>\n</html>").unwrap();
+    res
+}
+
+// Attribute names come from --attribute-metadata, and the settings from the
+// command line or a config file
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{AttributeOdds, ClassAttributesReport, FoldReport};
+
+    fn run_report() -> RunReport {
+        RunReport {
+            schema_version: 1,
+            folds: Some(vec![FoldReport {
+                fold: 1,
+                accuracy: 0.9,
+                log_likelihood: -0.25,
+            }]),
+            average_accuracy: Some(0.9),
+            average_log_likelihood: Some(-0.25),
+            confusion_matrix: None,
+            attributes: Some(vec![ClassAttributesReport {
+                class: "republican",
+                attributes: vec![AttributeOdds {
+                    attribute: "<physician-fee-freeze>".to_string(),
+                    log_odds: 1.0,
+                }],
+            }]),
+            average_precision: None,
+            lift: None,
+            calibration: None,
+            misclassified: None,
+            memory: None,
+            positive_class: None,
+            model_selection: None,
+        }
+    }
+
+    #[test]
+    fn reports_the_metrics_of_the_run() {
+        let html = to_html(&run_report(), &[("smoothing".to_string(), "1".to_string())]);
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<td><code>1</code></td>"));
+        assert!(html.contains("<p>Average accuracy: 0.9000</p>"));
+        assert!(html.contains("<td>republican</td><td>&lt;physician-fee-freeze&gt;</td>"));
+        assert!(html.contains("10.0x"));
+        // Metrics the run didn't report are left out
+        assert!(!html.contains("Confusion matrix"));
+    }
+}
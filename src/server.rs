@@ -0,0 +1,906 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Instant;
+
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{ConnectInfo, DefaultBodyLimit, Request, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::ser::SerializeMap;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::access::{AccessControl, Denied};
+use crate::audit::AuditLog;
+use crate::data::{
+    attribute_index, parse_vote, try_parse_row, Choice, Class, ATTRIBUTE_NAMES, CLASSES,
+    CLASSES_COUNT,
+};
+use crate::metrics::Metrics;
+use crate::model::{LoadOptions, Model};
+
+// None until a model is loaded or trained
+pub type SharedModel = Arc<RwLock<Option<Model>>>;
+type ApiError = (StatusCode, Json<ErrorResponse>);
+
+#[derive(clap::Args, Debug, Clone)]
+pub struct ServerConfig {
+    /// Model saved with --save-model. Without one, the server isn't ready
+    /// until a model is trained through /train.
+    #[arg(long = "model", value_name = "FILE", env = "PARTY_RECOGNISER_MODEL")]
+    pub model_path: Option<String>,
+
+    #[arg(long, default_value = "127.0.0.1:3000", env = "PARTY_RECOGNISER_ADDR")]
+    pub addr: String,
+
+    /// Listen on this port instead of the one in --addr
+    #[arg(long, env = "PARTY_RECOGNISER_PORT")]
+    pub port: Option<u16>,
+
+    /// Also serve gRPC on this address
+    #[cfg(feature = "grpc")]
+    #[arg(long, value_name = "ADDR", env = "PARTY_RECOGNISER_GRPC_ADDR")]
+    pub grpc_addr: Option<String>,
+
+    /// Most records accepted by a single batch prediction
+    #[arg(long, default_value_t = 1000)]
+    pub max_batch_size: usize,
+
+    /// Largest accepted request body
+    #[arg(long, default_value_t = 2 * 1024 * 1024)]
+    pub max_body_bytes: usize,
+
+    /// Require this key in an "Authorization: Bearer" header. Can be given
+    /// more than once to accept several keys, or as a comma-separated list
+    /// in the environment.
+    #[arg(
+        long = "api-key",
+        value_name = "KEY",
+        env = "PARTY_RECOGNISER_API_KEYS",
+        value_delimiter = ',',
+        hide_env_values = true
+    )]
+    pub api_keys: Vec<String>,
+
+    /// Most requests per second accepted from a single API key, or from an
+    /// address without a valid one
+    #[arg(long, value_name = "REQUESTS")]
+    pub rate_limit: Option<u32>,
+}
+
+impl ServerConfig {
+    // The address the HTTP server listens on, with --port applied
+    pub fn http_addr(&self) -> String {
+        match self.port {
+            Some(port) => {
+                let host = self.addr.rsplit_once(':').map_or(&*self.addr, |x| x.0);
+                format!("{}:{}", host, port)
+            }
+            None => self.addr.clone(),
+        }
+    }
+}
+
+// Shared by the HTTP and the gRPC handlers
+#[derive(Clone)]
+pub struct AppState {
+    pub model: SharedModel,
+    pub metrics: Arc<Metrics>,
+    pub max_batch_size: usize,
+    pub access: Arc<AccessControl>,
+    pub audit: Option<Arc<AuditLog>>,
+    // Off when only signed models are served, since the server can't sign
+    // the models /train makes
+    pub trainable: bool,
+}
+
+impl AppState {
+    // Predicts and records the prediction in the metrics. Returns None if
+    // no model is loaded yet.
+    pub fn predict(&self, attributes: &[Choice]) -> Option<PredictResponse> {
+        let model = self.model.read().unwrap();
+        let model = model.as_ref()?;
+        let model_version = self.audit.as_ref().map(|_| model.fingerprint());
+        Some(self.respond(model, model_version.as_deref(), attributes))
+    }
+
+    // Predicts once for the response, the metrics and the audit log, which
+    // gets the model version when there is one. Predictions are still
+    // answered when they can't be audited, so a full disk doesn't take the
+    // service down.
+    fn respond(
+        &self,
+        model: &Model,
+        model_version: Option<&str>,
+        attributes: &[Choice],
+    ) -> PredictResponse {
+        let (class, response) = predict_response(model, attributes);
+        self.metrics.observe_prediction(class);
+
+        if let (Some(audit), Some(model_version)) = (&self.audit, model_version) {
+            if let Err(e) =
+                audit.record(model_version, attributes, class, &response.probabilities.0)
+            {
+                tracing::error!("Couldn't write audit log: {}", e);
+            }
+        }
+        response
+    }
+
+    // Same as predict, but only takes the lock once
+    pub fn predict_batch(&self, records: &[Vec<Choice>]) -> Option<Vec<PredictResponse>> {
+        let model = self.model.read().unwrap();
+        let model = model.as_ref()?;
+        let model_version = self.audit.as_ref().map(|_| model.fingerprint());
+
+        Some(
+            records
+                .iter()
+                .map(|attributes| self.respond(model, model_version.as_deref(), attributes))
+                .collect(),
+        )
+    }
+
+    // Rejects batches above the configured maximum size
+    pub fn check_batch_size(&self, size: usize) -> Result<(), String> {
+        if size > self.max_batch_size {
+            return Err(format!(
+                "Batch of {} records is larger than the maximum of {}",
+                size, self.max_batch_size
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.model.read().unwrap().is_some()
+    }
+
+    // Records a handled request along with how long it took
+    pub fn observe<T, E>(&self, endpoint: &str, start: Instant, result: &Result<T, E>) {
+        self.metrics.observe_latency(endpoint, start.elapsed());
+        self.metrics.observe_request(endpoint, result.is_ok());
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PredictRequest {
+    // Votes (y, n or ?) keyed by attribute name. Attributes that are left
+    // out count as unknown. Sorted, so that with several bad attributes the
+    // same one is always reported.
+    pub attributes: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BatchPredictRequest {
+    pub records: Vec<PredictRequest>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BatchPredictResponse {
+    // In the order of the request records
+    pub predictions: Vec<PredictResponse>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PredictResponse {
+    pub class: &'static str,
+    pub probabilities: ClassProbabilities,
+}
+
+// Probability of each class, serialized as an object keyed by class name in
+// the order of CLASSES, so responses are byte-identical between runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClassProbabilities(pub [f64; CLASSES_COUNT]);
+
+impl ClassProbabilities {
+    pub fn get(&self, class: Class) -> f64 {
+        self.0[class.index()]
+    }
+}
+
+impl Serialize for ClassProbabilities {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(CLASSES_COUNT))?;
+
+        for &class in CLASSES.iter() {
+            map.serialize_entry(class.name(), &self.get(class))?;
+        }
+
+        map.end()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrainResponse {
+    pub rows: usize,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorResponse {
+    pub error: String,
+}
+
+pub const NOT_READY: &str = "No model loaded";
+
+fn bad_request(error: String) -> ApiError {
+    (StatusCode::BAD_REQUEST, Json(ErrorResponse { error }))
+}
+
+fn not_ready() -> ApiError {
+    let error = NOT_READY.to_string();
+    (
+        StatusCode::SERVICE_UNAVAILABLE,
+        Json(ErrorResponse { error }),
+    )
+}
+
+// The key of an "Authorization: Bearer <key>" header
+pub fn bearer_token(value: &str) -> Option<&str> {
+    value.strip_prefix("Bearer ").map(str::trim)
+}
+
+async fn check_access(
+    State(state): State<AppState>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    request: Request,
+    next: Next,
+) -> Response {
+    let api_key = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(bearer_token);
+
+    match state.access.check(api_key, Some(addr.ip())) {
+        Ok(()) => next.run(request).await,
+        Err(denied) => {
+            let status = match denied {
+                Denied::Unauthorized => StatusCode::UNAUTHORIZED,
+                Denied::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+            };
+            let error = denied.to_string();
+            (status, Json(ErrorResponse { error })).into_response()
+        }
+    }
+}
+
+pub fn attributes_from_map(votes: &BTreeMap<String, String>) -> Result<Vec<Choice>, String> {
+    let mut attributes = vec![Choice::Unknown; ATTRIBUTE_NAMES.len()];
+
+    for (name, vote) in votes {
+        let idx = attribute_index(name).ok_or_else(|| format!("Unknown attribute '{}'", name))?;
+        attributes[idx] = parse_vote(vote)?;
+    }
+
+    Ok(attributes)
+}
+
+// With the class it predicts, from one pass over the model's tables
+pub fn predict_response(model: &Model, attributes: &[Choice]) -> (Class, PredictResponse) {
+    let (class, probabilities) = model.classify_with_probabilities(attributes);
+    let response = PredictResponse {
+        class: class.name(),
+        probabilities: ClassProbabilities(probabilities),
+    };
+
+    (class, response)
+}
+
+async fn predict(
+    State(state): State<AppState>,
+    Json(request): Json<PredictRequest>,
+) -> Result<Json<PredictResponse>, ApiError> {
+    let start = Instant::now();
+    let res = attributes_from_map(&request.attributes)
+        .map_err(bad_request)
+        .and_then(|attributes| state.predict(&attributes).ok_or_else(not_ready))
+        .map(Json);
+
+    state.observe("predict", start, &res);
+    res
+}
+
+async fn predict_batch(
+    State(state): State<AppState>,
+    Json(request): Json<BatchPredictRequest>,
+) -> Result<Json<BatchPredictResponse>, ApiError> {
+    let start = Instant::now();
+    let res = batch_predictions(&state, &request);
+    state.observe("predict_batch", start, &res);
+    res
+}
+
+fn batch_predictions(
+    state: &AppState,
+    request: &BatchPredictRequest,
+) -> Result<Json<BatchPredictResponse>, ApiError> {
+    state
+        .check_batch_size(request.records.len())
+        .map_err(|error| (StatusCode::PAYLOAD_TOO_LARGE, Json(ErrorResponse { error })))?;
+
+    let records = request
+        .records
+        .iter()
+        .enumerate()
+        .map(|(i, record)| {
+            attributes_from_map(&record.attributes).map_err(|e| format!("Record {}: {}", i, e))
+        })
+        .collect::<Result<Vec<_>, String>>()
+        .map_err(bad_request)?;
+
+    let predictions = state.predict_batch(&records).ok_or_else(not_ready)?;
+    Ok(Json(BatchPredictResponse { predictions }))
+}
+
+// Retrains on the dataset in the body, which is in the same format as the
+// dataset file, and replaces the served model. The model file on disk is
+// left as it is.
+async fn train(
+    State(state): State<AppState>,
+    body: String,
+) -> Result<Json<TrainResponse>, ApiError> {
+    let start = Instant::now();
+    let res = if state.trainable {
+        retrain(&state, &body).map_err(bad_request)
+    } else {
+        let error =
+            "Only models signed with the --verify-key key are served, which /train can't sign"
+                .to_string();
+        Err((StatusCode::FORBIDDEN, Json(ErrorResponse { error })))
+    };
+    state.observe("train", start, &res);
+    res
+}
+
+fn retrain(state: &AppState, body: &str) -> Result<Json<TrainResponse>, String> {
+    let rows = body
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(i, line)| try_parse_row(line).map_err(|e| format!("Line {}: {}", i + 1, e)))
+        .collect::<Result<Vec<_>, String>>()?;
+
+    if rows.is_empty() {
+        return Err("No rows to train on".to_string());
+    }
+
+    let rows_count = rows.len();
+    // With the settings of the served model, so a tuned model isn't
+    // replaced by one with the defaults
+    let model = match state.model.read().unwrap().as_ref() {
+        Some(served) => served.refit(&rows),
+        None => Model::from_rows(rows),
+    };
+    state.metrics.set_model_info("/train", &model);
+    *state.model.write().unwrap() = Some(model);
+
+    Ok(Json(TrainResponse { rows: rows_count }))
+}
+
+// Each text message is parsed like a /predict body and answered with a
+// prediction, or an error, in the order the messages arrive
+async fn predict_stream(State(state): State<AppState>, ws: WebSocketUpgrade) -> Response {
+    ws.on_upgrade(move |socket| stream_predictions(socket, state))
+}
+
+async fn stream_predictions(mut socket: WebSocket, state: AppState) {
+    while let Some(Ok(message)) = socket.recv().await {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let start = Instant::now();
+        let res = serde_json::from_str::<PredictRequest>(&text)
+            .map_err(|e| e.to_string())
+            .and_then(|request| attributes_from_map(&request.attributes))
+            .and_then(|attributes| {
+                state
+                    .predict(&attributes)
+                    .ok_or_else(|| NOT_READY.to_string())
+            });
+
+        state.observe("predict_stream", start, &res);
+
+        let reply = match res {
+            Ok(response) => serde_json::to_string(&response),
+            Err(error) => serde_json::to_string(&ErrorResponse { error }),
+        }
+        .unwrap();
+
+        if socket.send(Message::Text(reply.into())).await.is_err() {
+            break;
+        }
+    }
+}
+
+async fn metrics(State(state): State<AppState>) -> String {
+    state.metrics.render()
+}
+
+// The process is up, whether or not it can serve predictions yet
+async fn healthz() -> &'static str {
+    "ok"
+}
+
+async fn readyz(State(state): State<AppState>) -> (StatusCode, &'static str) {
+    if state.is_ready() {
+        (StatusCode::OK, "ready")
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "not ready")
+    }
+}
+
+// Resolves on SIGTERM or Ctrl-C, so that in-flight requests can finish
+// before the server exits
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.ok();
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(_) => std::future::pending().await,
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+pub fn router(state: AppState, max_body_bytes: usize) -> Router {
+    // The probes stay open, so orchestrators don't need an API key
+    Router::new()
+        .route("/predict", post(predict))
+        .route("/predict/batch", post(predict_batch))
+        .route("/predict/stream", get(predict_stream))
+        .route("/train", post(train))
+        .route("/metrics", get(metrics))
+        .route_layer(middleware::from_fn_with_state(state.clone(), check_access))
+        .route("/healthz", get(healthz))
+        .route("/readyz", get(readyz))
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .with_state(state)
+}
+
+// The options decrypt the model and check its signature
+pub fn serve(
+    config: &ServerConfig,
+    load_options: &LoadOptions,
+    audit: Option<AuditLog>,
+) -> io::Result<()> {
+    let metrics = Metrics::new();
+    let model = match &config.model_path {
+        Some(path) => {
+            let model = Model::load_with(path, load_options)?;
+            metrics.set_model_info(path, &model);
+            Some(model)
+        }
+        None => None,
+    };
+
+    let state = AppState {
+        model: Arc::new(RwLock::new(model)),
+        metrics: Arc::new(metrics),
+        max_batch_size: config.max_batch_size,
+        access: Arc::new(AccessControl::new(
+            config.api_keys.clone(),
+            config.rate_limit,
+        )),
+        audit: audit.map(Arc::new),
+        trainable: load_options.verifying_key.is_none(),
+    };
+
+    let runtime = tokio::runtime::Runtime::new()?;
+
+    runtime.block_on(async {
+        let http = async {
+            let listener = tokio::net::TcpListener::bind(config.http_addr()).await?;
+            tracing::info!("Listening on {}", listener.local_addr()?);
+            // The peer address is what clients are rate limited by
+            let app = router(state.clone(), config.max_body_bytes)
+                .into_make_service_with_connect_info::<SocketAddr>();
+
+            axum::serve(listener, app)
+                .with_graceful_shutdown(shutdown_signal())
+                .await?;
+
+            tracing::info!("Shut down");
+            Ok(())
+        };
+
+        #[cfg(feature = "grpc")]
+        if let Some(grpc_addr) = &config.grpc_addr {
+            let grpc = crate::grpc::serve(state.clone(), grpc_addr);
+            return tokio::try_join!(http, grpc).map(|_| ());
+        }
+
+        http.await
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::body::{self, Body};
+    use futures_util::{SinkExt, StreamExt};
+    use tokio_tungstenite::tungstenite;
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::data::Row;
+
+    fn house_votes() -> Vec<Row> {
+        include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect()
+    }
+
+    fn state(model: Option<Model>, audit: Option<AuditLog>) -> AppState {
+        AppState {
+            model: Arc::new(RwLock::new(model)),
+            metrics: Arc::new(Metrics::new()),
+            max_batch_size: 2,
+            access: Arc::new(AccessControl::new(vec![], None)),
+            audit: audit.map(Arc::new),
+            trainable: true,
+        }
+    }
+
+    // Sends the request through the router like from a client on localhost,
+    // returning the status and body of the response
+    fn call(state: &AppState, method: &str, uri: &str, body: &str) -> (StatusCode, String) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0))))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        runtime.block_on(async {
+            let response = router(state.clone(), 1024 * 1024)
+                .oneshot(request)
+                .await
+                .unwrap();
+            let status = response.status();
+            let body = body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        })
+    }
+
+    #[test]
+    fn predicts_once_for_the_response_the_metrics_and_the_audit_log() {
+        let rows = house_votes();
+        let model = Model::from_rows(rows.iter().cloned());
+        let filename = std::env::temp_dir().join(format!("audit-{}.jsonl", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        let state = state(
+            Some(model.clone()),
+            Some(AuditLog::open(filename, false).unwrap()),
+        );
+
+        let records: Vec<Vec<Choice>> =
+            rows[..2].iter().map(|row| row.attributes.clone()).collect();
+        let mut responses = state.predict_batch(&records).unwrap();
+        responses.push(state.predict(&rows[2].attributes).unwrap());
+
+        for (row, response) in rows.iter().zip(&responses) {
+            assert_eq!(response.class, model.classify(&row.attributes).name());
+            assert_eq!(
+                response.probabilities.0,
+                model.probabilities(&row.attributes)
+            );
+        }
+        let audited = std::fs::read_to_string(filename).unwrap();
+        std::fs::remove_file(filename).unwrap();
+        assert_eq!(audited.lines().count(), 3);
+        let counted: u64 = state
+            .metrics
+            .render()
+            .lines()
+            .filter(|line| line.starts_with("party_recogniser_predictions_total{"))
+            .map(|line| line.rsplit(' ').next().unwrap().parse::<u64>().unwrap())
+            .sum();
+        assert_eq!(counted, 3);
+    }
+
+    #[test]
+    fn predicts_nothing_without_a_model() {
+        let state = state(None, None);
+
+        assert!(state.predict(&house_votes()[0].attributes).is_none());
+        assert!(state.predict_batch(&[]).is_none());
+    }
+
+    #[test]
+    fn predicts_over_http() {
+        let model = Model::from_rows(house_votes());
+        let app = state(Some(model.clone()), None);
+        let mut attributes = vec![Choice::Unknown; ATTRIBUTE_NAMES.len()];
+        attributes[3] = Choice::Yes;
+
+        let (status, body) = call(
+            &app,
+            "POST",
+            "/predict",
+            r#"{"attributes": {"physician-fee-freeze": "y"}}"#,
+        );
+        assert_eq!(status, StatusCode::OK);
+        let expected = serde_json::to_string(&predict_response(&model, &attributes).1).unwrap();
+        assert_eq!(body, expected);
+
+        let (status, body) = call(
+            &app,
+            "POST",
+            "/predict",
+            r#"{"attributes": {"lobbying": "y"}}"#,
+        );
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body, r#"{"error":"Unknown attribute 'lobbying'"}"#);
+
+        let (status, _) = call(&app, "POST", "/predict", "{}");
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+
+        let (status, body) = call(
+            &state(None, None),
+            "POST",
+            "/predict",
+            r#"{"attributes": {}}"#,
+        );
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert!(body.contains(NOT_READY));
+    }
+
+    #[test]
+    fn streams_predictions_over_a_websocket() {
+        let model = Model::from_rows(house_votes());
+        let app = state(Some(model.clone()), None);
+        let mut attributes = vec![Choice::Unknown; ATTRIBUTE_NAMES.len()];
+        attributes[3] = Choice::Yes;
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let replies = runtime.block_on(async {
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            let service = router(app.clone(), 1024 * 1024)
+                .into_make_service_with_connect_info::<SocketAddr>();
+            tokio::spawn(async move { axum::serve(listener, service).await });
+
+            let (mut socket, _) =
+                tokio_tungstenite::connect_async(format!("ws://{}/predict/stream", addr))
+                    .await
+                    .unwrap();
+            let messages = [
+                r#"{"attributes": {"physician-fee-freeze": "y"}}"#,
+                r#"{"attributes": {"lobbying": "y"}}"#,
+                "not json",
+            ];
+            let mut replies = vec![];
+            for message in messages {
+                socket
+                    .send(tungstenite::Message::Text(message.into()))
+                    .await
+                    .unwrap();
+                let reply = socket.next().await.unwrap().unwrap();
+                replies.push(reply.into_text().unwrap().to_string());
+            }
+            socket.close(None).await.unwrap();
+            replies
+        });
+
+        let expected = serde_json::to_string(&predict_response(&model, &attributes).1).unwrap();
+        assert_eq!(replies[0], expected);
+        assert_eq!(replies[1], r#"{"error":"Unknown attribute 'lobbying'"}"#);
+        // A bad message is answered with an error without closing the stream
+        assert!(replies[2].starts_with(r#"{"error":"#));
+    }
+
+    #[test]
+    fn counts_requests_in_the_metrics() {
+        let app = state(Some(Model::from_rows(house_votes())), None);
+        call(&app, "POST", "/predict", r#"{"attributes": {}}"#);
+        call(
+            &app,
+            "POST",
+            "/predict",
+            r#"{"attributes": {"lobbying": "y"}}"#,
+        );
+
+        let (status, body) = call(&app, "GET", "/metrics", "");
+        assert_eq!(status, StatusCode::OK);
+        assert!(
+            body.contains(r#"party_recogniser_requests_total{endpoint="predict",status="ok"} 1"#)
+        );
+        assert!(body
+            .contains(r#"party_recogniser_requests_total{endpoint="predict",status="error"} 1"#));
+        assert!(body
+            .contains(r#"party_recogniser_request_duration_seconds_count{endpoint="predict"} 2"#));
+
+        // The model info follows the served model
+        call(
+            &app,
+            "POST",
+            "/train",
+            include_str!("../house-votes-84.data"),
+        );
+        let (_, body) = call(&app, "GET", "/metrics", "");
+        assert!(body.contains(r#"path="/train",rows="435""#));
+    }
+
+    #[test]
+    fn is_ready_once_a_model_is_trained() {
+        let app = state(None, None);
+
+        assert_eq!(
+            call(&app, "GET", "/healthz", ""),
+            (StatusCode::OK, "ok".to_string())
+        );
+        assert_eq!(
+            call(&app, "GET", "/readyz", ""),
+            (StatusCode::SERVICE_UNAVAILABLE, "not ready".to_string())
+        );
+
+        let (status, body) = call(
+            &app,
+            "POST",
+            "/train",
+            include_str!("../house-votes-84.data"),
+        );
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, r#"{"rows":435}"#);
+        assert_eq!(
+            call(&app, "GET", "/readyz", ""),
+            (StatusCode::OK, "ready".to_string())
+        );
+    }
+
+    #[test]
+    fn leaves_the_probes_open() {
+        let mut app = state(None, None);
+        app.access = Arc::new(AccessControl::new(vec!["secret".to_string()], None));
+
+        assert_eq!(call(&app, "GET", "/healthz", "").0, StatusCode::OK);
+        assert_eq!(
+            call(&app, "GET", "/metrics", "").0,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    #[test]
+    fn limits_the_batch_size() {
+        let model = Model::from_rows(house_votes());
+        let app = state(Some(model.clone()), None);
+        let mut attributes = vec![Choice::Unknown; ATTRIBUTE_NAMES.len()];
+        attributes[3] = Choice::Yes;
+        let votes = r#"{"attributes": {"physician-fee-freeze": "y"}}"#;
+
+        let (status, body) = call(
+            &app,
+            "POST",
+            "/predict/batch",
+            &format!(r#"{{"records": [{}, {{"attributes": {{}}}}]}}"#, votes),
+        );
+        assert_eq!(status, StatusCode::OK);
+        let expected = BatchPredictResponse {
+            predictions: vec![
+                predict_response(&model, &attributes).1,
+                predict_response(&model, &[Choice::Unknown; ATTRIBUTE_NAMES.len()]).1,
+            ],
+        };
+        assert_eq!(body, serde_json::to_string(&expected).unwrap());
+
+        let (status, body) = call(
+            &app,
+            "POST",
+            "/predict/batch",
+            &format!(r#"{{"records": [{0}, {0}, {0}]}}"#, votes),
+        );
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            body,
+            r#"{"error":"Batch of 3 records is larger than the maximum of 2"}"#
+        );
+
+        let (status, body) = call(
+            &app,
+            "POST",
+            "/predict/batch",
+            &format!(
+                r#"{{"records": [{}, {{"attributes": {{"lobbying": "y"}}}}]}}"#,
+                votes
+            ),
+        );
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(
+            body,
+            r#"{"error":"Record 1: Unknown attribute 'lobbying'"}"#
+        );
+    }
+
+    #[test]
+    fn limits_the_body_size() {
+        let app = state(Some(Model::from_rows(house_votes())), None);
+        let request = Request::builder()
+            .method("POST")
+            .uri("/predict")
+            .header(header::CONTENT_TYPE, "application/json")
+            .extension(ConnectInfo(SocketAddr::from(([127, 0, 0, 1], 0))))
+            .body(Body::from(r#"{"attributes": {}}"#))
+            .unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let response = runtime.block_on(router(app, 8).oneshot(request)).unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[test]
+    fn listens_on_the_port_of_port() {
+        use clap::{Args, FromArgMatches};
+
+        let config = |args: &[&str]| {
+            let command = ServerConfig::augment_args(clap::Command::new("serve"));
+            let matches = command
+                .try_get_matches_from(["serve"].iter().chain(args))
+                .unwrap();
+            ServerConfig::from_arg_matches(&matches).unwrap()
+        };
+
+        assert_eq!(config(&[]).http_addr(), "127.0.0.1:3000");
+        assert_eq!(config(&["--port", "8080"]).http_addr(), "127.0.0.1:8080");
+        assert_eq!(
+            config(&["--addr", "[::1]:3000", "--port", "8080"]).http_addr(),
+            "[::1]:8080"
+        );
+        assert_eq!(
+            config(&["--api-key", "a", "--api-key", "b,c"]).api_keys,
+            ["a", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn orders_the_output_by_class_and_attribute() {
+        let probabilities = ClassProbabilities([0.25, 0.75]);
+        assert_eq!(
+            serde_json::to_string(&probabilities).unwrap(),
+            r#"{"republican":0.25,"democrat":0.75}"#
+        );
+
+        // The first bad attribute by name is reported, whatever the order
+        // of the request
+        let votes: BTreeMap<String, String> = [("zebra", "y"), ("crime", "maybe"), ("apple", "y")]
+            .iter()
+            .map(|&(name, vote)| (name.to_string(), vote.to_string()))
+            .collect();
+        assert_eq!(
+            attributes_from_map(&votes).unwrap_err(),
+            "Unknown attribute 'apple'"
+        );
+    }
+}
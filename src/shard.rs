@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::Row;
+use crate::evaluation::CrossValidation;
+use crate::summation;
+use crate::tune::{self, Candidate, Search, TuneResult};
+
+// One of count parts of the folds of every candidate of a search, numbered
+// from 1 like in 3/10, so a big search can be spread over machines
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Shard {
+    pub index: usize,
+    pub count: usize,
+}
+
+impl FromStr for Shard {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, count) = s
+            .split_once('/')
+            .and_then(|(index, count)| Some((index.parse().ok()?, count.parse().ok()?)))
+            .ok_or_else(|| format!("Expected a shard like 3/10, got '{}'", s))?;
+        if !(1..=count).contains(&index) {
+            return Err(format!("Shard {} isn't between 1 and {}", index, count));
+        }
+        Ok(Shard { index, count })
+    }
+}
+
+impl fmt::Display for Shard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl Shard {
+    // The folds of each candidate that are this shard's, by the index of the
+    // candidate. Every fold of every candidate is a job, and the jobs are
+    // dealt out in turn, so each shard gets about as many of every kind of
+    // model.
+    pub fn jobs(&self, candidates: usize, folds: usize) -> BTreeMap<usize, Vec<usize>> {
+        let mut res: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+
+        for job in (self.index - 1..candidates * folds).step_by(self.count) {
+            res.entry(job / folds).or_default().push(job % folds);
+        }
+
+        res
+    }
+}
+
+// Scores of one fold of one candidate
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct FoldScore {
+    pub candidate: usize,
+    pub fold: usize,
+    pub accuracy: f64,
+    pub log_likelihood: f64,
+}
+
+// What a shard writes for merge. Shards of the same search agree on
+// everything but the shard and the scores.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ShardReport {
+    pub schema_version: u32,
+    pub shard: Shard,
+    pub data: String,
+    pub rows: usize,
+    pub search: Search,
+    pub folds: usize,
+    pub seed: u64,
+    // Every candidate of the search, not just the ones of this shard
+    pub candidates: Vec<Candidate>,
+    pub scores: Vec<FoldScore>,
+}
+
+// Cross-validates the folds of the candidates that are the shard's, spread
+// over the threads like tune::search. on_done is called as each candidate
+// finishes its folds. Fails if the data can't be split into the folds.
+pub fn run(
+    data: &[Row],
+    candidates: &[Candidate],
+    crossvalidation: CrossValidation,
+    shard: Shard,
+    threads: usize,
+    on_done: impl Fn() + Sync,
+) -> Result<Vec<FoldScore>, String> {
+    let folds = crossvalidation.splits;
+    let jobs: Vec<(usize, Vec<usize>)> = shard.jobs(candidates.len(), folds).into_iter().collect();
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(vec![]);
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, jobs.len().max(1)) {
+            scope.spawn(|| loop {
+                let job = next.fetch_add(1, Ordering::Relaxed);
+                let Some((i, folds)) = jobs.get(job) else {
+                    break;
+                };
+                let candidate = candidates[*i];
+
+                let scores = CrossValidation {
+                    smoothing: candidate.smoothing,
+                    missing_votes: candidate.missing_votes,
+                    prior_mode: candidate.prior_mode,
+                    feature_selection: candidate.feature_selection,
+                    model: candidate.model,
+                    ..crossvalidation
+                }
+                .run_folds(data.to_vec(), folds.clone())
+                .map(|folds| {
+                    folds
+                        .map(|fold| FoldScore {
+                            candidate: *i,
+                            fold: fold.fold,
+                            accuracy: fold.accuracy,
+                            log_likelihood: fold.log_likelihood,
+                        })
+                        .collect::<Vec<_>>()
+                });
+
+                results.lock().unwrap().push(scores);
+                on_done();
+            });
+        }
+    });
+
+    let mut results: Vec<FoldScore> = results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .flatten()
+        .collect();
+    results.sort_by_key(|score| (score.candidate, score.fold));
+    Ok(results)
+}
+
+// Averages the scores of every shard of a search like tune::search does,
+// the best candidate first. Fails unless the reports are of the same search
+// and every shard of it is there once.
+pub fn merge(reports: &[ShardReport]) -> Result<Vec<TuneResult>, String> {
+    let first = reports.first().ok_or("There are no shards to merge")?;
+    for report in reports {
+        if report.data != first.data
+            || report.rows != first.rows
+            || report.search != first.search
+            || report.folds != first.folds
+            || report.seed != first.seed
+            || report.candidates != first.candidates
+            || report.shard.count != first.shard.count
+        {
+            return Err(format!(
+                "Shard {} is of a different search than shard {}",
+                report.shard, first.shard
+            ));
+        }
+    }
+
+    let count = first.shard.count;
+    for index in 1..=count {
+        match reports
+            .iter()
+            .filter(|report| report.shard.index == index)
+            .count()
+        {
+            0 => return Err(format!("Shard {}/{} is missing", index, count)),
+            1 => {}
+            _ => return Err(format!("Shard {}/{} is there more than once", index, count)),
+        }
+    }
+
+    // By candidate and then fold, like they'd come from tune::search
+    let mut scores: Vec<&FoldScore> = reports.iter().flat_map(|report| &report.scores).collect();
+    scores.sort_by_key(|score| (score.candidate, score.fold));
+    let folds = first.folds;
+    let complete = folds > 0
+        && scores.len() == first.candidates.len() * folds
+        && scores
+            .iter()
+            .enumerate()
+            .all(|(i, score)| (score.candidate, score.fold) == (i / folds, i % folds));
+    if !complete {
+        return Err("The shards don't score every fold of every candidate once".to_string());
+    }
+
+    let results = first
+        .candidates
+        .iter()
+        .zip(scores.chunks(folds))
+        .enumerate()
+        .map(|(i, (&candidate, folds))| {
+            (
+                i,
+                TuneResult {
+                    candidate,
+                    accuracy: summation::mean(folds.iter().map(|fold| fold.accuracy)),
+                    log_likelihood: summation::mean(folds.iter().map(|fold| fold.log_likelihood)),
+                },
+            )
+        })
+        .collect();
+
+    Ok(tune::rank(results))
+}
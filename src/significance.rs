@@ -0,0 +1,279 @@
+use serde::Serialize;
+
+use crate::summation;
+
+// Outcome of comparing two sets of paired scores
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PairedTest {
+    // Average of second minus first
+    pub mean_difference: f64,
+    pub t: f64,
+    pub degrees_of_freedom: usize,
+    // Two-sided
+    pub p_value: f64,
+}
+
+// Nadeau and Bengio's corrected resampled t-test on per-fold differences
+// between two models, e.g. their accuracies. Folds of cross-validation
+// share most of their training rows, so their scores aren't independent
+// and a plain paired t-test finds differences far too often. The variance
+// is inflated by the ratio of testing to training rows to make up for it.
+pub fn corrected_resampled_t_test(differences: &[f64], test_train_ratio: f64) -> PairedTest {
+    let count = differences.len() as f64;
+    let mean = summation::mean(differences.iter().copied());
+    let variance = summation::sum(
+        differences
+            .iter()
+            .map(|difference| (difference - mean).powi(2)),
+    ) / (count - 1.0);
+    let degrees_of_freedom = differences.len() - 1;
+
+    let (t, p_value) = if variance > 0.0 {
+        let t = mean / ((1.0 / count + test_train_ratio) * variance).sqrt();
+        (t, student_t_two_sided(t, degrees_of_freedom as f64))
+    } else if mean == 0.0 {
+        (0.0, 1.0)
+    } else {
+        // Every fold moved the same way by the same amount
+        (mean.signum() * f64::INFINITY, 0.0)
+    };
+
+    PairedTest {
+        mean_difference: mean,
+        t,
+        degrees_of_freedom,
+        p_value,
+    }
+}
+
+// Outcome of comparing two models' predictions of the same rows
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct McNemarTest {
+    // Rows only the first or only the second model got right
+    pub first_only: usize,
+    pub second_only: usize,
+    pub statistic: f64,
+    // Two-sided
+    pub p_value: f64,
+}
+
+// McNemar's test with Edwards' continuity correction, on whether each model
+// got every row right. Rows both got right or wrong say nothing about which
+// one is better, so only the ones they disagree on count.
+pub fn mcnemar_test(first: &[bool], second: &[bool]) -> McNemarTest {
+    let first_only = first.iter().zip(second).filter(|&(&a, &b)| a && !b).count();
+    let second_only = first.iter().zip(second).filter(|&(&a, &b)| !a && b).count();
+    let disagreements = (first_only + second_only) as f64;
+
+    let statistic = if disagreements > 0.0 {
+        ((first_only as f64 - second_only as f64).abs() - 1.0)
+            .max(0.0)
+            .powi(2)
+            / disagreements
+    } else {
+        0.0
+    };
+
+    McNemarTest {
+        first_only,
+        second_only,
+        statistic,
+        p_value: chi_squared_p_value(statistic, 1.0),
+    }
+}
+
+// P(|T| >= |t|) for Student's t distribution
+fn student_t_two_sided(t: f64, degrees_of_freedom: f64) -> f64 {
+    let x = degrees_of_freedom / (degrees_of_freedom + t * t);
+    regularized_incomplete_beta(x, degrees_of_freedom / 2.0, 0.5)
+}
+
+// P(X >= statistic) for a chi-squared distribution
+pub fn chi_squared_p_value(statistic: f64, degrees_of_freedom: f64) -> f64 {
+    if statistic <= 0.0 {
+        return 1.0;
+    }
+
+    regularized_upper_gamma(degrees_of_freedom / 2.0, statistic / 2.0)
+}
+
+// Lanczos approximation
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941677,
+        24.01409824083091,
+        -1.231739572450155,
+        0.1208650973866179e-2,
+        -0.5395239384953e-5,
+    ];
+
+    let tmp = x + 5.5;
+    let series = COEFFICIENTS
+        .iter()
+        .enumerate()
+        .fold(1.000000000190015, |acc, (i, coefficient)| {
+            acc + coefficient / (x + 1.0 + i as f64)
+        });
+
+    -tmp + (x + 0.5) * tmp.ln() + (2.5066282746310005 * series / x).ln()
+}
+
+// I_x(a, b), from the continued fraction in Numerical Recipes
+fn regularized_incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+
+    // The fraction converges quickly on this side, use the symmetry
+    // I_x(a, b) = 1 - I_(1-x)(b, a) on the other
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(x, a, b) / a
+    } else {
+        1.0 - front * beta_continued_fraction(1.0 - x, b, a) / b
+    }
+}
+
+// Q(a, x), from the series and continued fraction in Numerical Recipes
+fn regularized_upper_gamma(a: f64, x: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 200;
+    const EPSILON: f64 = 3e-16;
+    const TINY: f64 = 1e-300;
+
+    let front = (-x + a * x.ln() - ln_gamma(a)).exp();
+
+    // The series converges quickly below a + 1 and gives P(a, x) = 1 - Q(a, x)
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+
+        for n in 1..=MAX_ITERATIONS {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+
+        return 1.0 - front * sum;
+    }
+
+    let clamp = |value: f64| if value.abs() < TINY { TINY } else { value };
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / TINY;
+    let mut d = 1.0 / b;
+    let mut res = d;
+
+    for i in 1..=MAX_ITERATIONS {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = 1.0 / clamp(an * d + b);
+        c = clamp(b + an / c);
+        let delta = d * c;
+        res *= delta;
+
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+
+    front * res
+}
+
+fn beta_continued_fraction(x: f64, a: f64, b: f64) -> f64 {
+    const MAX_ITERATIONS: usize = 200;
+    const EPSILON: f64 = 3e-16;
+    const TINY: f64 = 1e-300;
+
+    let clamp = |value: f64| if value.abs() < TINY { TINY } else { value };
+    let mut c = 1.0;
+    let mut d = 1.0 / clamp(1.0 - (a + b) * x / (a + 1.0));
+    let mut res = d;
+
+    for m in 1..=MAX_ITERATIONS {
+        let m = m as f64;
+        let even = m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m));
+        d = 1.0 / clamp(1.0 + even * d);
+        c = clamp(1.0 + even / c);
+        res *= d * c;
+
+        let odd = -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0));
+        d = 1.0 / clamp(1.0 + odd * d);
+        c = clamp(1.0 + odd / c);
+        let delta = d * c;
+        res *= delta;
+
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_a_paired_t_test_without_the_correction() {
+        let test = corrected_resampled_t_test(&[1.0, 2.0, 3.0], 0.0);
+
+        assert_eq!(test.mean_difference, 2.0);
+        assert_eq!(test.degrees_of_freedom, 2);
+        assert!((test.t - 12f64.sqrt()).abs() < 1e-12);
+        // With 2 degrees of freedom P(|T| >= t) = 1 - t / sqrt(t^2 + 2)
+        assert!((test.p_value - (1.0 - 12f64.sqrt() / 14f64.sqrt())).abs() < 1e-9);
+
+        // Overlapping training rows make the difference less significant
+        let corrected = corrected_resampled_t_test(&[1.0, 2.0, 3.0], 0.25);
+        assert!(corrected.t < test.t);
+        assert!(corrected.p_value > test.p_value);
+    }
+
+    #[test]
+    fn is_certain_when_every_fold_moves_the_same() {
+        let same = corrected_resampled_t_test(&[-0.5; 4], 0.25);
+        assert_eq!((same.t, same.p_value), (f64::NEG_INFINITY, 0.0));
+
+        let none = corrected_resampled_t_test(&[0.0; 4], 0.25);
+        assert_eq!((none.t, none.p_value), (0.0, 1.0));
+    }
+
+    #[test]
+    fn only_counts_the_rows_the_models_disagree_on() {
+        // Only the first right on 10 rows, both right on 5, both wrong on 3
+        // and only the second right on 2
+        let first = [vec![true; 15], vec![false; 5]].concat();
+        let second = [
+            vec![false; 10],
+            vec![true; 5],
+            vec![false; 3],
+            vec![true; 2],
+        ]
+        .concat();
+
+        let test = mcnemar_test(&first, &second);
+        assert_eq!((test.first_only, test.second_only), (10, 2));
+        // (|10 - 2| - 1)^2 / 12
+        assert!((test.statistic - 49.0 / 12.0).abs() < 1e-12);
+        assert!((test.p_value - 0.04330814281079198).abs() < 1e-9);
+
+        let same = mcnemar_test(&first, &first);
+        assert_eq!((same.statistic, same.p_value), (0.0, 1.0));
+    }
+
+    #[test]
+    fn matches_the_chi_squared_distribution() {
+        // With 2 degrees of freedom P(X >= x) = e^(-x / 2)
+        assert!((chi_squared_p_value(6.0, 2.0) - (-3f64).exp()).abs() < 1e-9);
+        assert!((chi_squared_p_value(0.5, 2.0) - (-0.25f64).exp()).abs() < 1e-9);
+        assert_eq!(chi_squared_p_value(0.0, 1.0), 1.0);
+    }
+}
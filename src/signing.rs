@@ -0,0 +1,152 @@
+use std::convert::TryInto;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use rand::{thread_rng, RngCore};
+
+// Detached ed25519 signatures of model files, stored next to them as
+// <file>.sig. Keys and signatures are written as hex, so they can be pasted
+// into configs and secrets managers.
+
+pub fn signature_path(filename: &str) -> PathBuf {
+    PathBuf::from(format!("{}.sig", filename))
+}
+
+fn invalid_data(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn read_hex<const N: usize>(filename: &Path) -> io::Result<[u8; N]> {
+    let contents = fs::read_to_string(filename)?;
+    let hex = contents.trim();
+    let invalid = || invalid_data(format!("{} isn't {} bytes of hex", filename.display(), N));
+
+    if hex.len() != N * 2 || !hex.is_ascii() {
+        return Err(invalid());
+    }
+
+    let bytes: Vec<u8> = (0..N)
+        .map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16))
+        .collect::<Result<_, _>>()
+        .map_err(|_| invalid())?;
+    Ok(bytes.try_into().unwrap())
+}
+
+// Only the owner can read the signing key where permissions allow it
+fn write_private(filename: &str, contents: &str) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options.open(filename)?.write_all(contents.as_bytes())
+}
+
+pub fn generate_signing_key() -> SigningKey {
+    let mut bytes = [0; 32];
+    thread_rng().fill_bytes(&mut bytes);
+    SigningKey::from_bytes(&bytes)
+}
+
+// Writes the signing key to the file and its verifying key to <file>.pub
+pub fn save_keypair(key: &SigningKey, filename: &str) -> io::Result<PathBuf> {
+    let public_path = PathBuf::from(format!("{}.pub", filename));
+    write_private(filename, &(to_hex(&key.to_bytes()) + "\n"))?;
+    fs::write(&public_path, to_hex(key.verifying_key().as_bytes()) + "\n")?;
+    Ok(public_path)
+}
+
+pub fn load_signing_key(filename: &str) -> io::Result<SigningKey> {
+    Ok(SigningKey::from_bytes(&read_hex(Path::new(filename))?))
+}
+
+pub fn load_verifying_key(filename: &str) -> io::Result<VerifyingKey> {
+    VerifyingKey::from_bytes(&read_hex(Path::new(filename))?)
+        .map_err(|_| invalid_data(format!("{} isn't an ed25519 public key", filename)))
+}
+
+// Signs the file as it is on disk, so encrypted models can be checked
+// without decrypting them
+pub fn sign_file(filename: &str, key: &SigningKey) -> io::Result<PathBuf> {
+    let signature = key.sign(&fs::read(filename)?);
+    let path = signature_path(filename);
+    fs::write(&path, to_hex(&signature.to_bytes()) + "\n")?;
+    Ok(path)
+}
+
+// Checks the contents of the file against its signature
+pub fn verify(filename: &str, contents: &[u8], key: &VerifyingKey) -> io::Result<()> {
+    let path = signature_path(filename);
+    let signature = match read_hex(&path) {
+        Ok(bytes) => Signature::from_bytes(&bytes),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(invalid_data(format!(
+                "{} isn't signed, {} is missing",
+                filename,
+                path.display()
+            )))
+        }
+        Err(e) => return Err(e),
+    };
+
+    key.verify(contents, &signature)
+        .map_err(|_| invalid_data(format!("{} doesn't match its signature", filename)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Removed again when dropped
+    struct TempFile(String);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("{}-{}", std::process::id(), name));
+            TempFile(path.to_str().unwrap().to_string())
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            for path in [
+                self.0.clone(),
+                format!("{}.pub", self.0),
+                format!("{}.sig", self.0),
+            ] {
+                let _ = fs::remove_file(path);
+            }
+        }
+    }
+
+    #[test]
+    fn verifies_what_it_signs() {
+        let key_file = TempFile::new("signing.key");
+        let model = TempFile::new("signed-model.json");
+        fs::write(&model.0, b"counts").unwrap();
+
+        let public_path = save_keypair(&generate_signing_key(), &key_file.0).unwrap();
+        let key = load_signing_key(&key_file.0).unwrap();
+        let verifying_key = load_verifying_key(public_path.to_str().unwrap()).unwrap();
+        sign_file(&model.0, &key).unwrap();
+
+        assert!(verify(&model.0, b"counts", &verifying_key).is_ok());
+        assert!(verify(&model.0, b"counts!", &verifying_key).is_err());
+        assert!(verify(&model.0, b"counts", &generate_signing_key().verifying_key()).is_err());
+    }
+
+    #[test]
+    fn fails_without_a_signature() {
+        let model = TempFile::new("unsigned-model.json");
+        fs::write(&model.0, b"counts").unwrap();
+
+        let e = verify(&model.0, b"counts", &generate_signing_key().verifying_key()).unwrap_err();
+        assert!(e.to_string().contains("isn't signed"), "{}", e);
+    }
+}
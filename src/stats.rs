@@ -0,0 +1,122 @@
+use std::iter::FromIterator;
+
+use crate::data::{Choice, Class, Row, ATTRIBUTES_COUNT, CHOICES, CLASSES, CLASSES_COUNT};
+
+// Counts for sanity-checking a dataset before training on it. Unlike a
+// model, nothing is smoothed.
+#[derive(Debug, Clone, Default)]
+pub struct DatasetStats {
+    rows_count: u32,
+    class_counts: [u32; CLASSES_COUNT],
+    // [class][attribute][choice]
+    choice_counts: [[[u32; CHOICES.len()]; ATTRIBUTES_COUNT]; CLASSES_COUNT],
+}
+
+impl DatasetStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, row: &Row) {
+        self.rows_count += 1;
+        self.class_counts[row.class.index()] += 1;
+
+        for (attribute, choice) in row.attributes.iter().enumerate() {
+            self.choice_counts[row.class.index()][attribute][choice.index()] += 1;
+        }
+    }
+
+    pub fn rows_count(&self) -> u32 {
+        self.rows_count
+    }
+
+    pub fn class_count(&self, class: Class) -> u32 {
+        self.class_counts[class.index()]
+    }
+
+    // Rows of the class that voted the choice on the attribute
+    pub fn count(&self, class: Class, attribute: usize, choice: Choice) -> u32 {
+        self.choice_counts[class.index()][attribute][choice.index()]
+    }
+
+    // Rows of any class that voted the choice on the attribute
+    pub fn total_count(&self, attribute: usize, choice: Choice) -> u32 {
+        CLASSES
+            .iter()
+            .map(|&class| self.count(class, attribute, choice))
+            .sum()
+    }
+
+    // Share of the rows that didn't vote on the attribute
+    pub fn missing_rate(&self, attribute: usize) -> f64 {
+        self.total_count(attribute, Choice::Unknown) as f64 / self.rows_count as f64
+    }
+
+    pub fn class_missing_rate(&self, class: Class, attribute: usize) -> f64 {
+        self.count(class, attribute, Choice::Unknown) as f64 / self.class_count(class) as f64
+    }
+}
+
+impl<'a> FromIterator<&'a Row> for DatasetStats {
+    fn from_iter<I: IntoIterator<Item = &'a Row>>(rows: I) -> Self {
+        let mut res = Self::new();
+        rows.into_iter().for_each(|row| res.add(row));
+        res
+    }
+}
+
+impl FromIterator<Row> for DatasetStats {
+    fn from_iter<I: IntoIterator<Item = Row>>(rows: I) -> Self {
+        let mut res = Self::new();
+        rows.into_iter().for_each(|row| res.add(&row));
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::try_parse_row;
+
+    #[test]
+    fn counts_the_votes_of_every_class() {
+        let stats: DatasetStats = [
+            "republican,y,n,?,y,n,?,y,n,?,y,n,?,y,n,?,y",
+            "republican,n,n,?,y,n,?,y,n,?,y,n,?,y,n,?,y",
+            "democrat,?,y,y,y,n,?,y,n,?,y,n,?,y,n,?,y",
+        ]
+        .iter()
+        .map(|line| try_parse_row(line).unwrap())
+        .collect();
+
+        assert_eq!(stats.rows_count(), 3);
+        assert_eq!(stats.class_count(Class::Republican), 2);
+        assert_eq!(stats.class_count(Class::Democrat), 1);
+        assert_eq!(stats.count(Class::Republican, 0, Choice::Yes), 1);
+        assert_eq!(stats.count(Class::Republican, 0, Choice::No), 1);
+        assert_eq!(stats.total_count(1, Choice::No), 2);
+        assert_eq!(stats.missing_rate(0), 1.0 / 3.0);
+        assert_eq!(stats.missing_rate(2), 2.0 / 3.0);
+        assert_eq!(stats.class_missing_rate(Class::Republican, 2), 1.0);
+        assert_eq!(stats.class_missing_rate(Class::Democrat, 2), 0.0);
+    }
+
+    #[test]
+    fn counts_the_house_votes() {
+        let rows: Vec<Row> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let stats: DatasetStats = rows.iter().collect();
+
+        assert_eq!(stats.rows_count(), 435);
+        assert_eq!(stats.class_count(Class::Democrat), 267);
+        for attribute in 0..ATTRIBUTES_COUNT {
+            let total: u32 = CHOICES
+                .iter()
+                .map(|&choice| stats.total_count(attribute, choice))
+                .sum();
+            assert_eq!(total, 435);
+        }
+    }
+}
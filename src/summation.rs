@@ -0,0 +1,78 @@
+// Compensated summation for metrics aggregated over many values, e.g. the
+// log-likelihood of millions of rows, so the result doesn't drift with the
+// number or order of the values like a plain running total does. It's
+// Neumaier's variant of Kahan summation, which also holds up when a value
+// is larger than the total so far.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct KahanSum {
+    sum: f64,
+    // What adding to sum has rounded away
+    compensation: f64,
+}
+
+impl KahanSum {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add(&mut self, value: f64) {
+        let sum = self.sum + value;
+        self.compensation += if self.sum.abs() >= value.abs() {
+            (self.sum - sum) + value
+        } else {
+            (value - sum) + self.sum
+        };
+        self.sum = sum;
+    }
+
+    pub fn total(&self) -> f64 {
+        self.sum + self.compensation
+    }
+}
+
+impl Extend<f64> for KahanSum {
+    fn extend<I: IntoIterator<Item = f64>>(&mut self, values: I) {
+        for value in values {
+            self.add(value);
+        }
+    }
+}
+
+pub fn sum<I: IntoIterator<Item = f64>>(values: I) -> f64 {
+    let mut res = KahanSum::new();
+    res.extend(values);
+    res.total()
+}
+
+// NaN without values, like dividing an empty sum by 0
+pub fn mean<I: IntoIterator<Item = f64>>(values: I) -> f64 {
+    let mut res = KahanSum::new();
+    let mut count = 0;
+
+    for value in values {
+        res.add(value);
+        count += 1;
+    }
+
+    res.total() / count as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_what_a_running_total_rounds_away() {
+        assert_eq!(vec![0.1; 10].into_iter().sum::<f64>(), 0.9999999999999999);
+        assert_eq!(sum(vec![0.1; 10]), 1.0);
+        // Even when a value dwarfs the total so far
+        assert_eq!(sum(vec![1.0, 1e100, 1.0, -1e100]), 2.0);
+    }
+
+    #[test]
+    fn averages_over_the_values() {
+        assert_eq!(mean(vec![0.1; 10]), 0.1);
+        assert_eq!(mean(vec![1.0, 2.0, 6.0]), 3.0);
+        assert!(mean(vec![]).is_nan());
+    }
+}
@@ -0,0 +1,182 @@
+use crate::classifier::{normalize_log_probabilities, Classifier};
+use crate::counts::JointCounts;
+use crate::data::{Choice, ATTRIBUTES_COUNT, CHOICES, CLASSES, CLASSES_COUNT};
+
+// Tree-augmented naive Bayes (Friedman, Geiger and Goldszmidt, 1997). Every
+// attribute but the first depends on one other attribute as well as on the
+// class, along the maximum spanning tree of their conditional mutual
+// information, so the pairs that break the independence assumption most
+// are modelled together.
+#[derive(Debug, Clone)]
+pub struct TanModel {
+    // Parent of every attribute in the tree, None for the root
+    parents: Vec<Option<usize>>,
+    // Natural log of P(class)
+    log_priors: [f64; CLASSES_COUNT],
+    // Natural log of P(choice | class, choice of the parent), packed
+    // [class][attribute][parent choice][choice]. The root only uses the
+    // first parent choice, for P(choice | class).
+    log_conditionals: Vec<f64>,
+}
+
+impl TanModel {
+    // Every choice starts with the smoothing count, like in Trainer
+    pub fn new(counts: &JointCounts, smoothing: f64) -> Self {
+        let parents = spanning_tree(counts);
+        let total = counts.rows_count() as f64;
+        let log_priors = CLASSES.map(|class| (counts.class_count(class) as f64 / total).ln());
+        let mut log_conditionals =
+            vec![0.0; CLASSES_COUNT * ATTRIBUTES_COUNT * CHOICES.len() * CHOICES.len()];
+
+        for &class in CLASSES.iter() {
+            for (attribute, &parent) in parents.iter().enumerate() {
+                for &parent_choice in CHOICES.iter() {
+                    for &choice in CHOICES.iter() {
+                        let (count, parent_count) = match parent {
+                            Some(parent) => (
+                                counts.pair_count(class, attribute, choice, parent, parent_choice),
+                                counts.count(class, parent, parent_choice),
+                            ),
+                            None => (
+                                counts.count(class, attribute, choice),
+                                counts.class_count(class),
+                            ),
+                        };
+
+                        log_conditionals
+                            [Self::idx(class.index(), attribute, parent_choice, choice)] =
+                            ((count as f64 + smoothing)
+                                / (parent_count as f64 + smoothing * CHOICES.len() as f64))
+                                .ln();
+                    }
+                }
+            }
+        }
+
+        TanModel {
+            parents,
+            log_priors,
+            log_conditionals,
+        }
+    }
+
+    fn idx(class: usize, attribute: usize, parent_choice: Choice, choice: Choice) -> usize {
+        ((class * ATTRIBUTES_COUNT + attribute) * CHOICES.len() + parent_choice.index())
+            * CHOICES.len()
+            + choice.index()
+    }
+
+    pub fn parents(&self) -> &[Option<usize>] {
+        &self.parents
+    }
+}
+
+impl Classifier for TanModel {
+    fn log_probabilities(&self, attributes: &[Choice]) -> [f64; CLASSES_COUNT] {
+        let mut res = self.log_priors;
+
+        for (class, log_probability) in res.iter_mut().enumerate() {
+            for (attribute, &choice) in attributes.iter().enumerate() {
+                let parent_choice = match self.parents[attribute] {
+                    Some(parent) => attributes[parent],
+                    None => CHOICES[0],
+                };
+                *log_probability +=
+                    self.log_conditionals[Self::idx(class, attribute, parent_choice, choice)];
+            }
+        }
+
+        normalize_log_probabilities(res)
+    }
+}
+
+// Prim's algorithm from the first attribute, with the conditional mutual
+// information of every pair as its weight. Ties go to the earlier attribute.
+fn spanning_tree(counts: &JointCounts) -> Vec<Option<usize>> {
+    let mut parents = vec![None; ATTRIBUTES_COUNT];
+    let mut in_tree = [false; ATTRIBUTES_COUNT];
+    // The heaviest edge from the tree to every attribute outside of it
+    let mut best: Vec<(f64, usize)> = (0..ATTRIBUTES_COUNT)
+        .map(|attribute| (counts.conditional_mutual_information(0, attribute), 0))
+        .collect();
+    in_tree[0] = true;
+
+    for _ in 1..ATTRIBUTES_COUNT {
+        let next = (0..ATTRIBUTES_COUNT)
+            .filter(|&attribute| !in_tree[attribute])
+            .fold(None, |acc: Option<usize>, attribute| match acc {
+                Some(other) if best[other].0 >= best[attribute].0 => Some(other),
+                _ => Some(attribute),
+            })
+            .unwrap();
+
+        in_tree[next] = true;
+        parents[next] = Some(best[next].1);
+
+        for attribute in (0..ATTRIBUTES_COUNT).filter(|&attribute| !in_tree[attribute]) {
+            let weight = counts.conditional_mutual_information(next, attribute);
+            if weight > best[attribute].0 {
+                best[attribute] = (weight, next);
+            }
+        }
+    }
+
+    parents
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::{try_parse_row, Row};
+    use crate::model::{Model, DEFAULT_SMOOTHING};
+
+    #[test]
+    fn spans_every_attribute_from_the_first() {
+        let rows: Vec<Row> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let model = TanModel::new(&rows.iter().collect(), DEFAULT_SMOOTHING);
+        let parents = model.parents();
+
+        assert_eq!(parents[0], None);
+        for attribute in 1..ATTRIBUTES_COUNT {
+            // Following the parents reaches the root without a cycle
+            let mut current = attribute;
+            for _ in 0..ATTRIBUTES_COUNT {
+                match parents[current] {
+                    Some(parent) => current = parent,
+                    None => break,
+                }
+            }
+            assert_eq!(current, 0);
+        }
+
+        let probabilities = model.probabilities(&rows[0].attributes);
+        assert!((probabilities.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn learns_classes_naive_bayes_cant() {
+        // Democrats voted the same on the first two bills and republicans
+        // didn't, so neither vote says anything about the class alone
+        let rest = ",n".repeat(ATTRIBUTES_COUNT - 2);
+        let rows: Vec<Row> = [
+            "democrat,y,y",
+            "democrat,n,n",
+            "republican,y,n",
+            "republican,n,y",
+        ]
+        .iter()
+        .map(|votes| try_parse_row(&format!("{}{}", votes, rest)).unwrap())
+        .collect();
+        let tan = TanModel::new(&rows.iter().collect(), DEFAULT_SMOOTHING);
+        let naive_bayes = Model::from_rows(rows.iter().cloned());
+
+        assert_eq!(tan.parents()[1], Some(0));
+        for row in &rows {
+            assert_eq!(tan.classify(&row.attributes), row.class);
+            assert_eq!(naive_bayes.classify(&row.attributes), CLASSES[0]);
+        }
+    }
+}
@@ -0,0 +1,168 @@
+use serde::Serialize;
+
+use crate::data::Class;
+use crate::evaluation::FoldResult;
+
+// What tune-threshold picks the threshold for. F1 is of the positive class.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Objective {
+    F1,
+    Accuracy,
+    // Average of the recalls of both classes, for when one of them is rare
+    BalancedAccuracy,
+}
+
+// Metrics of predicting the positive class when its probability is at least
+// the threshold
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct ThresholdPoint {
+    pub threshold: f64,
+    pub precision: f64,
+    pub recall: f64,
+    pub f1: f64,
+    pub accuracy: f64,
+    pub balanced_accuracy: f64,
+}
+
+impl ThresholdPoint {
+    pub fn get(&self, objective: Objective) -> f64 {
+        match objective {
+            Objective::F1 => self.f1,
+            Objective::Accuracy => self.accuracy,
+            Objective::BalancedAccuracy => self.balanced_accuracy,
+        }
+    }
+}
+
+// Ratio that is 0 instead of NaN when nothing was counted
+fn ratio(count: u32, total: u32) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+// Whether every row is of the positive class and the probability it was
+// given of being so, each from the model of the fold the row was tested in
+pub fn out_of_fold_probabilities(folds: &[FoldResult], positive: Class) -> Vec<(bool, f64)> {
+    folds
+        .iter()
+        .flat_map(|fold| {
+            fold.testing_set
+                .iter()
+                .zip(&fold.probabilities)
+                .map(move |(row, probabilities)| {
+                    (row.class == positive, probabilities[positive.index()])
+                })
+        })
+        .collect()
+}
+
+// The probabilities are of the positive class, the one the threshold is for
+pub fn evaluate(probabilities: &[(bool, f64)], threshold: f64) -> ThresholdPoint {
+    let (mut true_positives, mut false_positives) = (0, 0);
+    let (mut true_negatives, mut false_negatives) = (0, 0);
+
+    for &(positive, probability) in probabilities {
+        match (positive, probability >= threshold) {
+            (true, true) => true_positives += 1,
+            (false, true) => false_positives += 1,
+            (false, false) => true_negatives += 1,
+            (true, false) => false_negatives += 1,
+        }
+    }
+
+    let precision = ratio(true_positives, true_positives + false_positives);
+    let recall = ratio(true_positives, true_positives + false_negatives);
+    let specificity = ratio(true_negatives, true_negatives + false_positives);
+
+    ThresholdPoint {
+        threshold,
+        precision,
+        recall,
+        f1: if precision + recall > 0.0 {
+            2.0 * precision * recall / (precision + recall)
+        } else {
+            0.0
+        },
+        accuracy: ratio(true_positives + true_negatives, probabilities.len() as u32),
+        balanced_accuracy: (recall + specificity) / 2.0,
+    }
+}
+
+// Evaluates evenly spaced thresholds from 0 to 1, about step apart
+pub fn sweep(probabilities: &[(bool, f64)], step: f64) -> Vec<ThresholdPoint> {
+    let steps = ((1.0 / step).round() as usize).max(1);
+
+    (0..=steps)
+        .map(|i| evaluate(probabilities, i as f64 / steps as f64))
+        .collect()
+}
+
+// The point with the highest objective. Of equally good ones, the threshold
+// closest to 0.5 is picked, so ties don't push predictions to an extreme.
+pub fn best(points: &[ThresholdPoint], objective: Objective) -> ThresholdPoint {
+    *points
+        .iter()
+        .max_by(|a, b| {
+            a.get(objective).total_cmp(&b.get(objective)).then_with(|| {
+                (b.threshold - 0.5)
+                    .abs()
+                    .total_cmp(&(a.threshold - 0.5).abs())
+            })
+        })
+        .expect("No thresholds to pick from")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROBABILITIES: [(bool, f64); 6] = [
+        (true, 0.9),
+        (true, 0.7),
+        (false, 0.6),
+        (true, 0.4),
+        (false, 0.2),
+        (false, 0.1),
+    ];
+
+    #[test]
+    fn counts_the_predictions_at_the_threshold() {
+        let point = evaluate(&PROBABILITIES, 0.5);
+
+        assert_eq!(point.precision, 2.0 / 3.0);
+        assert_eq!(point.recall, 2.0 / 3.0);
+        assert_eq!(point.f1, 2.0 / 3.0);
+        assert_eq!(point.accuracy, 4.0 / 6.0);
+        assert_eq!(point.balanced_accuracy, 2.0 / 3.0);
+        // The probability itself counts as reaching the threshold
+        assert_eq!(evaluate(&PROBABILITIES, 0.4).recall, 1.0);
+
+        let nothing = evaluate(&PROBABILITIES, 1.0);
+        assert_eq!((nothing.precision, nothing.f1), (0.0, 0.0));
+        assert_eq!(nothing.balanced_accuracy, 0.5);
+    }
+
+    #[test]
+    fn picks_the_best_threshold_closest_to_a_half() {
+        let points = sweep(&PROBABILITIES, 0.1);
+        assert_eq!(points.len(), 11);
+        assert_eq!(points[0].threshold, 0.0);
+        assert_eq!(points[10].threshold, 1.0);
+
+        // Everything above 0.2 up to 0.4 finds every positive with
+        // a single false alarm
+        let chosen = best(&points, Objective::F1);
+        assert_eq!(chosen.threshold, 0.4);
+        assert_eq!(chosen.get(Objective::F1), 6.0 / 7.0);
+
+        let points = [0.3, 0.7, 0.55].map(|threshold| ThresholdPoint {
+            threshold,
+            ..evaluate(&PROBABILITIES, 0.5)
+        });
+        assert_eq!(best(&points, Objective::Accuracy).threshold, 0.55);
+    }
+}
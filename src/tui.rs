@@ -0,0 +1,297 @@
+use std::io;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Style};
+use ratatui::symbols::Marker;
+use ratatui::text::Line;
+use ratatui::widgets::{Axis, Block, Cell, Chart, Dataset, Gauge, GraphType, List, Table};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::data::{Class, Row, ATTRIBUTE_NAMES, CLASSES};
+use crate::evaluation::{ConfusionMatrix, CrossValidation, FoldResult};
+
+// How many of the most discriminative attributes are listed
+const TOP_ATTRIBUTES: usize = 8;
+// How often the dashboard checks for key presses and finished folds
+const TICK: Duration = Duration::from_millis(100);
+
+// (fold, accuracy) points of the accuracy chart
+type Points = Vec<(f64, f64)>;
+
+struct Dashboard {
+    splits: usize,
+    folds: Vec<FoldResult>,
+    confusion: ConfusionMatrix,
+}
+
+// Runs cross-validation in the background while showing its progress, until
+// the user quits with q or Esc. Returns the folds that finished by then.
+pub fn run(data: Vec<Row>, crossvalidation: &CrossValidation) -> io::Result<Vec<FoldResult>> {
+    let (sender, receiver) = mpsc::channel();
+
+    let folds = crossvalidation
+        .run(data)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    thread::spawn(move || {
+        for fold in folds {
+            // The dashboard was closed early
+            if sender.send(fold).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut dashboard = Dashboard {
+        splits: crossvalidation.splits,
+        folds: vec![],
+        confusion: ConfusionMatrix::new(),
+    };
+
+    let res = ratatui::run(|terminal| dashboard.run(terminal, &receiver));
+    res.map(|()| dashboard.folds)
+}
+
+impl Dashboard {
+    fn run(
+        &mut self,
+        terminal: &mut DefaultTerminal,
+        receiver: &Receiver<FoldResult>,
+    ) -> io::Result<()> {
+        loop {
+            for fold in receiver.try_iter() {
+                self.confusion.merge(&fold.confusion);
+                self.folds.push(fold);
+            }
+
+            terminal.draw(|frame| self.draw(frame))?;
+
+            if event::poll(TICK)? {
+                if let Event::Key(key) = event::read()? {
+                    if key.kind == KeyEventKind::Press
+                        && matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                    {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [progress, middle, bottom] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(12),
+            Constraint::Length(TOP_ATTRIBUTES as u16 + 2),
+        ])
+        .areas(frame.area());
+        let [chart, confusion] =
+            Layout::horizontal([Constraint::Percentage(65), Constraint::Percentage(35)])
+                .areas(middle);
+
+        frame.render_widget(self.progress(), progress);
+        let (accuracies, running_average) = self.accuracy_points();
+        frame.render_widget(self.accuracy_chart(&accuracies, &running_average), chart);
+        frame.render_widget(self.confusion_table(), confusion);
+        frame.render_widget(self.top_attributes(), bottom);
+    }
+
+    fn progress(&self) -> Gauge<'_> {
+        let done = self.folds.len();
+        let title = if done == self.splits {
+            "Cross-validation done, press q to quit"
+        } else {
+            "Cross-validation, press q to quit"
+        };
+
+        Gauge::default()
+            .block(Block::bordered().title(title))
+            .gauge_style(Style::default().fg(Color::Green))
+            .ratio(done as f64 / self.splits as f64)
+            .label(format!("Fold {}/{}", done, self.splits))
+    }
+
+    // Accuracy of each fold, and the average of the folds up to it
+    fn accuracy_points(&self) -> (Points, Points) {
+        let accuracies: Points = self
+            .folds
+            .iter()
+            .map(|fold| (fold.fold as f64, fold.accuracy))
+            .collect();
+        let running_average: Points = accuracies
+            .iter()
+            .scan(0.0, |sum, &(x, accuracy)| {
+                *sum += accuracy;
+                Some((x, *sum / (x + 1.0)))
+            })
+            .collect();
+
+        (accuracies, running_average)
+    }
+
+    fn accuracy_chart<'a>(
+        &self,
+        accuracies: &'a [(f64, f64)],
+        running_average: &'a [(f64, f64)],
+    ) -> Chart<'a> {
+        // Leave some room below the worst fold, so it isn't drawn on the axis
+        let lowest = accuracies.iter().map(|x| x.1).fold(1.0, f64::min);
+        let y_min = ((lowest - 0.05) * 20.0).floor() / 20.0;
+        let x_max = (self.splits - 1) as f64;
+
+        let datasets = vec![
+            Dataset::default()
+                .name("Fold accuracy")
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Scatter)
+                .style(Style::default().fg(Color::Cyan))
+                .data(accuracies),
+            Dataset::default()
+                .name("Running average")
+                .marker(Marker::Braille)
+                .graph_type(GraphType::Line)
+                .style(Style::default().fg(Color::Yellow))
+                .data(running_average),
+        ];
+
+        Chart::new(datasets)
+            .block(Block::bordered().title("Accuracy"))
+            .x_axis(
+                Axis::default()
+                    .title("Fold")
+                    .bounds([0.0, x_max])
+                    .labels(["0".to_string(), format!("{}", self.splits - 1)]),
+            )
+            .y_axis(
+                Axis::default()
+                    .bounds([y_min, 1.0])
+                    .labels([format!("{:.2}", y_min), "1.00".to_string()]),
+            )
+    }
+
+    fn confusion_table(&self) -> Table<'_> {
+        let header = std::iter::once(Cell::from("actual \\ predicted"))
+            .chain(CLASSES.iter().map(|class| Cell::from(class.name())));
+        let rows = CLASSES.iter().map(|&actual| {
+            let counts = CLASSES.iter().map(move |&predicted| {
+                Cell::from(self.confusion.get(actual, predicted).to_string())
+            });
+            ratatui::widgets::Row::new(std::iter::once(Cell::from(actual.name())).chain(counts))
+        });
+        let widths = std::iter::once(Constraint::Length(19))
+            .chain(CLASSES.iter().map(|_| Constraint::Length(11)));
+
+        Table::new(rows, widths)
+            .header(ratatui::widgets::Row::new(header).style(Style::default().fg(Color::Yellow)))
+            .block(Block::bordered().title("Confusion matrix (all folds)"))
+    }
+
+    fn top_attributes(&self) -> List<'_> {
+        let items: Vec<Line> = match self.folds.last() {
+            Some(fold) => fold
+                .model
+                .discriminative_attributes()
+                .into_iter()
+                .take(TOP_ATTRIBUTES)
+                .map(|(i, log_odds)| {
+                    let class = if log_odds > 0.0 {
+                        Class::Republican
+                    } else {
+                        Class::Democrat
+                    };
+                    Line::from(format!(
+                        "{:<40} yes points to {:<10} (log odds {:+.2})",
+                        ATTRIBUTE_NAMES[i],
+                        class.name(),
+                        log_odds
+                    ))
+                })
+                .collect(),
+            None => vec![Line::from("Waiting for the first fold...")],
+        };
+
+        List::new(items)
+            .block(Block::bordered().title("Most discriminative attributes (latest fold)"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    use super::*;
+    use crate::data::try_parse_row;
+
+    fn dashboard(finished: usize) -> Dashboard {
+        let rows: Vec<Row> = include_str!("../house-votes-84.data")
+            .lines()
+            .map(|line| try_parse_row(line).unwrap())
+            .collect();
+        let crossvalidation = CrossValidation {
+            seed: Some(1),
+            ..CrossValidation::new(3)
+        };
+        let folds: Vec<_> = crossvalidation.run(rows).unwrap().take(finished).collect();
+        let mut confusion = ConfusionMatrix::new();
+        for fold in &folds {
+            confusion.merge(&fold.confusion);
+        }
+
+        Dashboard {
+            splits: 3,
+            folds,
+            confusion,
+        }
+    }
+
+    fn screen(dashboard: &Dashboard) -> String {
+        let mut terminal = Terminal::new(TestBackend::new(100, 30)).unwrap();
+        terminal.draw(|frame| dashboard.draw(frame)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        buffer
+            .content
+            .chunks(buffer.area.width as usize)
+            .map(|line| line.iter().map(|cell| cell.symbol()).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn shows_the_finished_folds() {
+        let dashboard = dashboard(2);
+        let screen = screen(&dashboard);
+
+        assert!(screen.contains("Fold 2/3"));
+        assert!(!screen.contains("Cross-validation done"));
+        let republicans = dashboard
+            .confusion
+            .get(Class::Republican, Class::Republican);
+        assert!(screen.contains(&republicans.to_string()));
+        let (i, _) = dashboard.folds[1].model.discriminative_attributes()[0];
+        assert!(screen.contains(ATTRIBUTE_NAMES[i]));
+
+        let (accuracies, running_average) = dashboard.accuracy_points();
+        assert_eq!(accuracies.len(), 2);
+        assert_eq!(running_average[0], accuracies[0]);
+        assert_eq!(
+            running_average[1].1,
+            (accuracies[0].1 + accuracies[1].1) / 2.0
+        );
+    }
+
+    #[test]
+    fn waits_for_the_first_fold_and_tells_when_done() {
+        let waiting = screen(&dashboard(0));
+        assert!(waiting.contains("Fold 0/3"));
+        assert!(waiting.contains("Waiting for the first fold..."));
+
+        let done = screen(&dashboard(3));
+        assert!(done.contains("Fold 3/3"));
+        assert!(done.contains("Cross-validation done"));
+    }
+}
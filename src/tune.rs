@@ -0,0 +1,220 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::classifier::ModelKind;
+use crate::data::Row;
+use crate::evaluation::CrossValidation;
+use crate::model::{MissingVotes, PriorMode};
+use crate::summation;
+
+// How candidates are picked from a SearchSpace
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Search {
+    // Every combination, see SearchSpace::grid
+    Grid,
+    // Random combinations, see SearchSpace::random
+    Random,
+}
+
+// One combination of the settings tune searches over
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub smoothing: f64,
+    pub missing_votes: MissingVotes,
+    // Both missing from shard reports of searches before they were searched
+    #[serde(default)]
+    pub prior_mode: PriorMode,
+    // None for all of the attributes
+    #[serde(default)]
+    pub feature_selection: Option<usize>,
+    pub model: ModelKind,
+}
+
+impl Candidate {
+    // Only naive Bayes can ignore unknown votes, change the priors or
+    // select attributes
+    pub fn is_valid(&self) -> bool {
+        self.model == ModelKind::NaiveBayes
+            || (self.missing_votes == MissingVotes::Category
+                && self.prior_mode == PriorMode::Learned
+                && self.feature_selection.is_none())
+    }
+}
+
+// The values to search of every setting
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchSpace {
+    pub smoothing: Vec<f64>,
+    pub missing_votes: Vec<MissingVotes>,
+    pub prior_modes: Vec<PriorMode>,
+    // None for all of the attributes
+    pub feature_selection: Vec<Option<usize>>,
+    pub models: Vec<ModelKind>,
+}
+
+impl SearchSpace {
+    // Every valid combination
+    pub fn grid(&self) -> Vec<Candidate> {
+        let mut res = vec![];
+
+        for (missing_votes, prior_mode, feature_selection, model) in self.categories() {
+            for &smoothing in &self.smoothing {
+                res.push(Candidate {
+                    smoothing,
+                    missing_votes,
+                    prior_mode,
+                    feature_selection,
+                    model,
+                });
+            }
+        }
+
+        res
+    }
+
+    // Every valid combination of the settings other than the smoothing
+    fn categories(&self) -> Vec<(MissingVotes, PriorMode, Option<usize>, ModelKind)> {
+        let mut res = vec![];
+
+        for &model in &self.models {
+            for &missing_votes in &self.missing_votes {
+                for &prior_mode in &self.prior_modes {
+                    for &feature_selection in &self.feature_selection {
+                        let candidate = Candidate {
+                            smoothing: 1.0,
+                            missing_votes,
+                            prior_mode,
+                            feature_selection,
+                            model,
+                        };
+                        if candidate.is_valid() {
+                            res.push((missing_votes, prior_mode, feature_selection, model));
+                        }
+                    }
+                }
+            }
+        }
+
+        res
+    }
+
+    // Up to trials valid combinations, with the smoothing drawn log-uniformly
+    // between the smallest and largest value of the space instead of only
+    // from its values
+    pub fn random<R: Rng>(&self, trials: usize, rng: &mut R) -> Vec<Candidate> {
+        let low = self.smoothing.iter().copied().fold(f64::INFINITY, f64::min);
+        let high = self
+            .smoothing
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        let categories = self.categories();
+        if categories.is_empty() {
+            return vec![];
+        }
+
+        (0..trials)
+            .map(|_| {
+                let &(missing_votes, prior_mode, feature_selection, model) =
+                    categories.choose(rng).unwrap();
+                let smoothing = if low < high {
+                    rng.gen_range(low.ln()..=high.ln()).exp()
+                } else {
+                    low
+                };
+                Candidate {
+                    smoothing,
+                    missing_votes,
+                    prior_mode,
+                    feature_selection,
+                    model,
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct TuneResult {
+    #[serde(flatten)]
+    pub candidate: Candidate,
+    // Averages over the folds
+    pub accuracy: f64,
+    pub log_likelihood: f64,
+}
+
+// Cross-validates every candidate on the same folds, spread over the
+// threads, and returns them the best first: by accuracy, and then by
+// log-likelihood. on_done is called as each one finishes. Fails if the data
+// can't be split into the folds.
+pub fn search(
+    data: &[Row],
+    candidates: &[Candidate],
+    crossvalidation: CrossValidation,
+    threads: usize,
+    on_done: impl Fn() + Sync,
+) -> Result<Vec<TuneResult>, String> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(Vec::with_capacity(candidates.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, candidates.len().max(1)) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&candidate) = candidates.get(i) else {
+                    break;
+                };
+
+                let result = CrossValidation {
+                    smoothing: candidate.smoothing,
+                    missing_votes: candidate.missing_votes,
+                    prior_mode: candidate.prior_mode,
+                    feature_selection: candidate.feature_selection,
+                    model: candidate.model,
+                    ..crossvalidation
+                }
+                .run(data.to_vec())
+                .map(|folds| {
+                    let folds: Vec<(f64, f64)> = folds
+                        .map(|fold| (fold.accuracy, fold.log_likelihood))
+                        .collect();
+                    TuneResult {
+                        candidate,
+                        accuracy: summation::mean(folds.iter().map(|fold| fold.0)),
+                        log_likelihood: summation::mean(folds.iter().map(|fold| fold.1)),
+                    }
+                });
+
+                results.lock().unwrap().push((i, result));
+                on_done();
+            });
+        }
+    });
+
+    // Threads finish in any order
+    let results = results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|(i, result)| result.map(|result| (i, result)))
+        .collect::<Result<_, _>>()?;
+    Ok(rank(results))
+}
+
+// Sorts the results, each paired with the index of its candidate, the best
+// first like search does. Ties go to the earlier candidate.
+pub fn rank(mut results: Vec<(usize, TuneResult)>) -> Vec<TuneResult> {
+    results.sort_by(|(i, a), (j, b)| {
+        b.accuracy
+            .total_cmp(&a.accuracy)
+            .then(b.log_likelihood.total_cmp(&a.log_likelihood))
+            .then(i.cmp(j))
+    });
+    results.into_iter().map(|(_, result)| result).collect()
+}
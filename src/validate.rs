@@ -0,0 +1,215 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::data::{parse_vote, Class, Row, ATTRIBUTES_COUNT, ATTRIBUTE_NAMES, CLASSES};
+use crate::duplicates::find_duplicates;
+
+// Something wrong with a dataset, found before training on it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Problem {
+    EmptyLine,
+    // A class and ATTRIBUTES_COUNT votes are expected
+    FieldCount { found: usize },
+    UnknownClass { class: String },
+    // The dataset parser silently reads these as ?
+    UnknownVote { attribute: usize, vote: String },
+    // Rows with the same votes but different classes, by 1-based line
+    ConflictingLabels { lines: Vec<usize> },
+    MissingClass { class: &'static str },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::EmptyLine => write!(f, "Empty line"),
+            Problem::FieldCount { found } => write!(
+                f,
+                "Expected {} fields, found {}",
+                ATTRIBUTES_COUNT + 1,
+                found
+            ),
+            Problem::UnknownClass { class } => write!(f, "Unknown class '{}'", class),
+            Problem::UnknownVote { attribute, vote } => write!(
+                f,
+                "Unknown vote '{}' on {}",
+                vote, ATTRIBUTE_NAMES[*attribute]
+            ),
+            Problem::ConflictingLabels { lines } => {
+                let lines: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+                write!(
+                    f,
+                    "Same votes with different classes on lines {}",
+                    lines.join(", ")
+                )
+            }
+            Problem::MissingClass { class } => write!(f, "No rows of class {}", class),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Finding {
+    // 1-based, none for problems with the dataset as a whole
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    #[serde(flatten)]
+    pub problem: Problem,
+}
+
+// Every problem of a single line, in the order of its fields. The row is
+// only returned when there are none.
+pub fn check_line(line: &str) -> Result<Row, Vec<Problem>> {
+    if line.trim().is_empty() {
+        return Err(vec![Problem::EmptyLine]);
+    }
+
+    let fields: Vec<&str> = line.split(',').collect();
+    let mut problems = vec![];
+
+    if fields.len() != ATTRIBUTES_COUNT + 1 {
+        problems.push(Problem::FieldCount {
+            found: fields.len(),
+        });
+    }
+
+    let class = CLASSES
+        .iter()
+        .copied()
+        .find(|class| class.name() == fields[0]);
+    if class.is_none() {
+        problems.push(Problem::UnknownClass {
+            class: fields[0].to_string(),
+        });
+    }
+
+    let mut attributes = vec![];
+    for (attribute, &vote) in fields[1..].iter().take(ATTRIBUTES_COUNT).enumerate() {
+        match parse_vote(vote) {
+            Ok(choice) if vote == vote.trim() => attributes.push(choice),
+            _ => problems.push(Problem::UnknownVote {
+                attribute,
+                vote: vote.to_string(),
+            }),
+        }
+    }
+
+    match class {
+        Some(class) if problems.is_empty() => Ok(Row { class, attributes }),
+        _ => Err(problems),
+    }
+}
+
+// Checks every line of the dataset, and then the rows that could be parsed
+// for conflicting labels and classes without rows
+pub fn validate(contents: impl Iterator<Item = String>) -> (usize, Vec<Finding>) {
+    let mut findings = vec![];
+    let mut rows = vec![];
+    let mut lines = vec![];
+
+    for (i, line) in contents.enumerate() {
+        match check_line(&line) {
+            Ok(row) => {
+                rows.push(row);
+                lines.push(i + 1);
+            }
+            Err(problems) => findings.extend(problems.into_iter().map(|problem| Finding {
+                line: Some(i + 1),
+                problem,
+            })),
+        }
+    }
+
+    let duplicates = find_duplicates(&rows);
+    findings.extend(duplicates.conflicts(&rows).map(|group| Finding {
+        line: None,
+        problem: Problem::ConflictingLabels {
+            lines: group.iter().map(|&i| lines[i]).collect(),
+        },
+    }));
+
+    findings.extend(
+        CLASSES
+            .iter()
+            .filter(|&&class| rows.iter().all(|row: &Row| row.class != class))
+            .map(|&class: &Class| Finding {
+                line: None,
+                problem: Problem::MissingClass {
+                    class: class.name(),
+                },
+            }),
+    );
+
+    (rows.len(), findings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VOTES: &str = "y,n,?,y,n,?,y,n,?,y,n,?,y,n,?,y";
+
+    #[test]
+    fn finds_every_problem_of_a_line() {
+        assert_eq!(
+            check_line("green,y, n,x"),
+            Err(vec![
+                Problem::FieldCount { found: 4 },
+                Problem::UnknownClass {
+                    class: "green".to_string()
+                },
+                Problem::UnknownVote {
+                    attribute: 1,
+                    vote: " n".to_string()
+                },
+                Problem::UnknownVote {
+                    attribute: 2,
+                    vote: "x".to_string()
+                },
+            ])
+        );
+        assert_eq!(check_line(" "), Err(vec![Problem::EmptyLine]));
+        assert!(check_line(&format!("democrat,{}", VOTES)).is_ok());
+    }
+
+    #[test]
+    fn finds_conflicting_labels_and_missing_classes() {
+        let lines = vec![
+            format!("democrat,{}", VOTES),
+            String::new(),
+            format!("democrat,{}", VOTES),
+            format!("republican,{}", VOTES),
+        ];
+        let (rows, findings) = validate(lines.into_iter());
+
+        assert_eq!(rows, 3);
+        assert_eq!(
+            findings,
+            [
+                Finding {
+                    line: Some(2),
+                    problem: Problem::EmptyLine
+                },
+                Finding {
+                    line: None,
+                    problem: Problem::ConflictingLabels {
+                        lines: vec![1, 3, 4]
+                    }
+                },
+            ]
+        );
+        assert_eq!(
+            findings[1].problem.to_string(),
+            "Same votes with different classes on lines 1, 3, 4"
+        );
+
+        let (_, findings) = validate(std::iter::once(format!("democrat,{}", VOTES)));
+        assert_eq!(
+            findings[0].problem,
+            Problem::MissingClass {
+                class: "republican"
+            }
+        );
+    }
+}
@@ -0,0 +1,80 @@
+use wasm_bindgen::prelude::*;
+
+use crate::data::{parse_attributes, try_parse_row, Row, ATTRIBUTE_NAMES, CLASSES};
+use crate::model::Model;
+
+// Exposes training and prediction to JavaScript. Build with
+// `wasm-pack build --target web --out-dir web/pkg -- --no-default-features
+// --features wasm` and see web/index.html for an example.
+#[wasm_bindgen]
+pub struct WasmModel {
+    model: Model,
+}
+
+#[wasm_bindgen]
+impl WasmModel {
+    // Trains on the contents of a dataset file in the house-votes format,
+    // throwing with the line of the first row that can't be parsed
+    #[wasm_bindgen(constructor)]
+    pub fn train(data: &str) -> Result<WasmModel, JsError> {
+        let rows = data
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.is_empty())
+            .map(|(i, line)| {
+                try_parse_row(line).map_err(|e| JsError::new(&format!("Line {}: {}", i + 1, e)))
+            })
+            .collect::<Result<Vec<Row>, JsError>>()?;
+
+        Ok(WasmModel {
+            model: Model::from_rows(rows),
+        })
+    }
+
+    // Returns the class name for comma-separated votes, e.g. "y,n,?,..."
+    pub fn predict(&self, votes: &str) -> Result<String, JsError> {
+        let attributes = parse_attributes(votes).map_err(|e| JsError::new(&e))?;
+        Ok(self.model.classify(&attributes).name().to_string())
+    }
+
+    // Posterior probabilities in the order of `classes()`
+    pub fn probabilities(&self, votes: &str) -> Result<Vec<f64>, JsError> {
+        let attributes = parse_attributes(votes).map_err(|e| JsError::new(&e))?;
+        Ok(self.model.probabilities(&attributes).to_vec())
+    }
+
+    pub fn classes() -> Vec<String> {
+        CLASSES.iter().map(|x| x.name().to_string()).collect()
+    }
+
+    pub fn attribute_names() -> Vec<String> {
+        ATTRIBUTE_NAMES.iter().map(|x| x.to_string()).collect()
+    }
+}
+
+// Only the paths that don't throw, JsError needs a JavaScript host
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATA: &str = include_str!("../house-votes-84.data");
+    const VOTES: &str = "n,y,n,y,y,y,n,n,n,y,?,y,y,y,n,y";
+
+    #[test]
+    fn predicts_like_the_model() {
+        let model = WasmModel::train(&format!("{}\n\n", DATA)).unwrap();
+        let expected = Model::from_rows(DATA.lines().map(|line| try_parse_row(line).unwrap()));
+        let attributes = parse_attributes(VOTES).unwrap();
+
+        assert_eq!(
+            model.predict(VOTES).unwrap(),
+            expected.classify(&attributes).name()
+        );
+        assert_eq!(
+            model.probabilities(VOTES).unwrap(),
+            expected.probabilities(&attributes).to_vec()
+        );
+        assert_eq!(WasmModel::classes(), ["republican", "democrat"]);
+        assert_eq!(WasmModel::attribute_names().len(), ATTRIBUTE_NAMES.len());
+    }
+}
@@ -0,0 +1,142 @@
+use calamine::{open_workbook, Data, Reader, Xlsx};
+
+// The rows of a sheet as lines of the dataset, with the class and the votes
+// in the cells of every row from the left. Lines are counted from the first
+// row that isn't empty. The first sheet is read unless one is named.
+pub fn read_lines(filename: &str, sheet: Option<&str>) -> Result<Vec<String>, String> {
+    let mut workbook: Xlsx<_> =
+        open_workbook(filename).map_err(|e| format!("Couldn't open {}: {}", filename, e))?;
+    let name = match sheet {
+        Some(name) => name.to_string(),
+        None => workbook
+            .sheet_names()
+            .first()
+            .cloned()
+            .ok_or_else(|| format!("{} has no sheets", filename))?,
+    };
+    let range = workbook
+        .worksheet_range(&name)
+        .map_err(|e| format!("Couldn't read sheet {}: {}", name, e))?;
+
+    Ok(range
+        .rows()
+        .map(|row| {
+            // Cells past the votes are part of the range when any row uses
+            // them, e.g. for notes
+            let end = row
+                .iter()
+                .rposition(|cell| *cell != Data::Empty)
+                .map_or(0, |i| i + 1);
+            let cells: Vec<String> = row[..end].iter().map(cell_text).collect();
+            cells.join(",")
+        })
+        .collect())
+}
+
+fn cell_text(cell: &Data) -> String {
+    match cell {
+        Data::String(text) => text.trim().to_string(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::io::Write;
+
+    use zip::write::SimpleFileOptions;
+    use zip::{CompressionMethod, ZipWriter};
+
+    use super::*;
+
+    const VOTES: &str = "y,n,?,y,n,?,y,n,?,y,n,?,y,n,?,y";
+    const NAMESPACE: &str = "http://schemas.openxmlformats.org";
+
+    // A workbook with a sheet of these rows of cells, starting at B2
+    fn write_workbook(filename: &str, sheet: &str, rows: &[Vec<&str>]) {
+        let cell = |row: usize, column: usize, text: &str| {
+            format!(
+                "<c r=\"{}{}\" t=\"inlineStr\"><is><t xml:space=\"preserve\">{}</t></is></c>",
+                (b'B' + column as u8) as char,
+                row + 2,
+                text
+            )
+        };
+        let rows: String = rows
+            .iter()
+            .enumerate()
+            .map(|(i, cells)| {
+                let cells: String = cells
+                    .iter()
+                    .enumerate()
+                    .map(|(j, text)| cell(i, j, text))
+                    .collect();
+                format!("<row r=\"{}\">{}</row>", i + 2, cells)
+            })
+            .collect();
+
+        // Only the parts calamine reads
+        let relationship = |kind: &str, target: &str| {
+            format!(
+                "<Relationships xmlns=\"{}/package/2006/relationships\"><Relationship Id=\"rId1\" Type=\"{}/officeDocument/2006/relationships/{}\" Target=\"{}\"/></Relationships>",
+                NAMESPACE, NAMESPACE, kind, target
+            )
+        };
+        let files = [
+            ("_rels/.rels", relationship("officeDocument", "xl/workbook.xml")),
+            (
+                "xl/_rels/workbook.xml.rels",
+                relationship("worksheet", "worksheets/sheet1.xml"),
+            ),
+            (
+                "xl/workbook.xml",
+                format!(
+                    "<workbook xmlns=\"{}/spreadsheetml/2006/main\" xmlns:r=\"{}/officeDocument/2006/relationships\"><sheets><sheet name=\"{}\" sheetId=\"1\" r:id=\"rId1\"/></sheets></workbook>",
+                    NAMESPACE, NAMESPACE, sheet
+                ),
+            ),
+            (
+                "xl/worksheets/sheet1.xml",
+                format!(
+                    "<worksheet xmlns=\"{}/spreadsheetml/2006/main\"><sheetData>{}</sheetData></worksheet>",
+                    NAMESPACE, rows
+                ),
+            ),
+        ];
+
+        let mut zip = ZipWriter::new(File::create(filename).unwrap());
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, contents) in &files {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(contents.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn reads_the_rows_of_a_sheet_as_lines() {
+        let filename = std::env::temp_dir().join(format!("votes-{}.xlsx", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        let democrat: Vec<&str> = std::iter::once(" democrat ")
+            .chain(VOTES.split(','))
+            .collect();
+        let mut republican: Vec<&str> = std::iter::once("republican")
+            .chain(VOTES.split(','))
+            .collect();
+        republican.push("a note");
+        write_workbook(filename, "Votes", &[democrat, republican]);
+
+        let lines = read_lines(filename, None).unwrap();
+        assert_eq!(
+            lines,
+            [
+                format!("democrat,{}", VOTES),
+                format!("republican,{},a note", VOTES)
+            ]
+        );
+        assert_eq!(read_lines(filename, Some("Votes")).unwrap(), lines);
+        assert!(read_lines(filename, Some("Other")).is_err());
+        fs::remove_file(filename).unwrap();
+    }
+}